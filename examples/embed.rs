@@ -0,0 +1,56 @@
+// A minimal front end that embeds agstash the way a tray app would: the export runs on a worker thread, progress
+// comes back over a channel to the "UI" thread, and the UI can cancel it at any point.
+//
+//   cargo run --example embed -- /tmp/stashes.tar.gz
+
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+
+use agstash::embed::{self, CancelToken, Progress};
+
+enum Event {
+    Progress(Progress),
+    Done(embed::EmbedResult<usize>),
+}
+
+fn main() {
+    let output = std::env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join("agstash-embed-example.tar.gz"));
+
+    let stashes = embed::list_stashes().unwrap_or_default();
+    println!("{} stash(es) in the store", stashes.len());
+
+    let cancel = CancelToken::new();
+    let (sender, receiver) = mpsc::channel();
+    let worker = {
+        let cancel = cancel.clone();
+        let output = output.clone();
+        thread::spawn(move || {
+            let progress_sender = sender.clone();
+            let result = embed::export(
+                &output,
+                true,
+                true,
+                |progress| {
+                    let _ = progress_sender.send(Event::Progress(progress.clone()));
+                },
+                &cancel,
+            );
+            let _ = sender.send(Event::Done(result));
+        })
+    };
+
+    // The UI thread stays free to redraw; a "Cancel" button would call cancel.cancel()
+    for event in receiver {
+        match event {
+            Event::Progress(progress) => println!("[{}/{}] {}", progress.done, progress.total, progress.item),
+            Event::Done(Ok(0)) => println!("No stashes to export"),
+            Event::Done(Ok(count)) => println!("Exported {} file(s) to {}", count, output.display()),
+            Event::Done(Err(error)) => println!("Export stopped: {}", error),
+        }
+    }
+    worker.join().expect("worker thread panicked");
+}
@@ -0,0 +1,28 @@
+use super::{strip_front_matter, with_title, without_title, Adapter, CANONICAL_TITLE};
+use crate::markdown::Document;
+
+// Front matter of a converted rule: a description Cursor shows in its rule list, applied to every request like AGENTS.md
+const FRONT_MATTER: &str = "---\ndescription: Project conventions from AGENTS.md\nalwaysApply: true\n---\n\n";
+
+// CursorRule is one rule in Cursor's .cursor/rules layout: an .mdc file whose front matter says when it applies
+pub struct CursorRule;
+
+impl Adapter for CursorRule {
+    fn name(&self) -> &'static str {
+        "cursor"
+    }
+
+    fn path(&self) -> &'static str {
+        ".cursor/rules/agents.mdc"
+    }
+
+    fn parse(&self, content: &str) -> Document {
+        // description, globs and alwaysApply only tell Cursor when to use the rule, so they have no AGENTS.md equivalent
+        let body = strip_front_matter(content);
+        with_title(&Document::parse(body), CANONICAL_TITLE)
+    }
+
+    fn render(&self, document: &Document) -> String {
+        format!("{}{}", FRONT_MATTER, without_title(document).render())
+    }
+}
@@ -0,0 +1,133 @@
+use crate::markdown::{Document, Heading, Section};
+
+mod cursor;
+mod plain;
+
+// Title every AGENTS.md starts with, the canonical dialect's only fixed structure
+const CANONICAL_TITLE: &str = "AGENTS";
+
+// An adapter translates between AGENTS.md, the canonical dialect, and the format one tool expects. Every conversion goes
+// through the canonical document, so a new tool needs one adapter rather than one per pair it converts to and from.
+pub trait Adapter {
+    // name is how the dialect is given to convert --from and --to, e.g. "cursorrules"
+    fn name(&self) -> &'static str;
+
+    // path is where the tool reads its instructions, relative to the project root
+    fn path(&self) -> &'static str;
+
+    // parse reads the tool's file into an AGENTS.md document, '# AGENTS' title included
+    fn parse(&self, content: &str) -> Document;
+
+    // render writes an AGENTS.md document out in the tool's format
+    fn render(&self, document: &Document) -> String;
+}
+
+// adapters lists every dialect convert knows, AGENTS.md first
+fn adapters() -> Vec<Box<dyn Adapter>> {
+    vec![
+        Box::new(plain::Plain::agents()),
+        Box::new(plain::Plain::claude()),
+        Box::new(plain::Plain::gemini()),
+        Box::new(plain::Plain::copilot()),
+        Box::new(plain::Plain::cursorrules()),
+        Box::new(cursor::CursorRule),
+    ]
+}
+
+// AdapterNames returns the names of every dialect, for help and error messages
+pub fn adapter_names() -> Vec<&'static str> {
+    adapters().iter().map(|adapter| adapter.name()).collect()
+}
+
+// FindAdapter returns the adapter for a dialect name
+pub fn find_adapter(name: &str) -> Result<Box<dyn Adapter>, Box<dyn std::error::Error>> {
+    adapters()
+        .into_iter()
+        .find(|adapter| adapter.name() == name.to_lowercase())
+        .ok_or_else(|| format!("Unknown format '{}': use one of {}", name, adapter_names().join(", ")).into())
+}
+
+// Convert rewrites content from one dialect into another by way of the canonical AGENTS.md document
+pub fn convert(from: &dyn Adapter, to: &dyn Adapter, content: &str) -> String {
+    to.render(&from.parse(content))
+}
+
+// strip_front_matter returns the markdown below a leading '---' block, or all of content when it has none
+fn strip_front_matter(content: &str) -> &str {
+    let Some(rest) = content.strip_prefix("---\n").or_else(|| content.strip_prefix("---\r\n")) else {
+        return content;
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+        if line.trim_end() == "---" {
+            return &rest[offset..];
+        }
+    }
+    // An unterminated block is ordinary text rather than front matter
+    content
+}
+
+// without_title returns the document minus a leading level-1 heading, whose blocks stay as the preamble
+fn without_title(document: &Document) -> Document {
+    let mut document = document.clone();
+    if let Some(first) = document.sections.first_mut() {
+        if first.heading.as_ref().is_some_and(|heading| heading.level == 1) {
+            first.heading = None;
+            if first.blocks.is_empty() {
+                document.sections.remove(0);
+            }
+        }
+    }
+    document
+}
+
+// with_title returns the document under a level-1 title, replacing the one it has, e.g. "# CLAUDE.md" with "# AGENTS"
+fn with_title(document: &Document, text: &str) -> Document {
+    let mut document = without_title(document);
+    let title = Heading {
+        level: 1,
+        text: text.to_string(),
+    };
+    match document.sections.first_mut() {
+        Some(first) if first.heading.is_none() => first.heading = Some(title),
+        _ => document.sections.insert(
+            0,
+            Section {
+                heading: Some(title),
+                blocks: Vec::new(),
+            },
+        ),
+    }
+    document
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::adapters;
+
+    const AGENTS: &str = "# AGENTS\n\nShared conventions.\n\n## Style\n\n- Use tabs\n- Keep functions short\n";
+
+    #[test]
+    fn test_convert_between_dialects() {
+        let agents = adapters::find_adapter("agents").unwrap();
+        let claude = adapters::find_adapter("claude").unwrap();
+        let cursor = adapters::find_adapter("cursor").unwrap();
+        assert!(adapters::find_adapter("windsurf").is_err());
+
+        let claude_md = adapters::convert(agents.as_ref(), claude.as_ref(), AGENTS);
+        assert_eq!(claude_md, "# CLAUDE.md\n\nShared conventions.\n\n## Style\n\n- Use tabs\n- Keep functions short\n");
+        assert_eq!(adapters::convert(claude.as_ref(), agents.as_ref(), &claude_md), AGENTS);
+
+        let rule = adapters::convert(agents.as_ref(), cursor.as_ref(), AGENTS);
+        assert!(rule.starts_with("---\ndescription: Project conventions from AGENTS.md\nalwaysApply: true\n---\n\n"));
+        assert!(!rule.contains("# AGENTS"));
+        assert_eq!(adapters::convert(cursor.as_ref(), agents.as_ref(), &rule), AGENTS);
+
+        // Copilot's file takes no title, and converting it back restores the canonical one
+        let copilot = adapters::find_adapter("copilot").unwrap();
+        let instructions = adapters::convert(agents.as_ref(), copilot.as_ref(), AGENTS);
+        assert!(instructions.starts_with("Shared conventions.\n"));
+        assert_eq!(adapters::convert(copilot.as_ref(), agents.as_ref(), &instructions), AGENTS);
+    }
+}
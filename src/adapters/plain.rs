@@ -0,0 +1,79 @@
+use super::{strip_front_matter, with_title, without_title, Adapter, CANONICAL_TITLE};
+use crate::markdown::Document;
+
+// Plain is a tool whose instructions are a single markdown file with no front matter, optionally under a title of
+// its own; AGENTS.md itself is one with the '# AGENTS' title
+pub struct Plain {
+    name: &'static str,
+    path: &'static str,
+    title: Option<&'static str>,
+}
+
+impl Plain {
+    // agents is the canonical AGENTS.md
+    pub fn agents() -> Plain {
+        Plain {
+            name: "agents",
+            path: "AGENTS.md",
+            title: Some(CANONICAL_TITLE),
+        }
+    }
+
+    // claude is Claude Code's CLAUDE.md
+    pub fn claude() -> Plain {
+        Plain {
+            name: "claude",
+            path: "CLAUDE.md",
+            title: Some("CLAUDE.md"),
+        }
+    }
+
+    // gemini is Gemini CLI's GEMINI.md
+    pub fn gemini() -> Plain {
+        Plain {
+            name: "gemini",
+            path: "GEMINI.md",
+            title: Some("GEMINI.md"),
+        }
+    }
+
+    // copilot is GitHub Copilot's repository-wide instructions, which are untitled
+    pub fn copilot() -> Plain {
+        Plain {
+            name: "copilot",
+            path: ".github/copilot-instructions.md",
+            title: None,
+        }
+    }
+
+    // cursorrules is Cursor's older single-file layout, which is untitled
+    pub fn cursorrules() -> Plain {
+        Plain {
+            name: "cursorrules",
+            path: ".cursorrules",
+            title: None,
+        }
+    }
+}
+
+impl Adapter for Plain {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn path(&self) -> &'static str {
+        self.path
+    }
+
+    fn parse(&self, content: &str) -> Document {
+        let body = strip_front_matter(content);
+        with_title(&Document::parse(body), CANONICAL_TITLE)
+    }
+
+    fn render(&self, document: &Document) -> String {
+        match self.title {
+            Some(title) => with_title(document, title).render(),
+            None => without_title(document).render(),
+        }
+    }
+}
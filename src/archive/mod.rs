@@ -0,0 +1,838 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+use crate::error::AgStashError;
+use crate::history::{self, Version};
+use crate::metadata::{self, StashMetadata};
+use crate::utils;
+
+// GetArchivesDir returns the directory holding archived projects
+pub fn get_archives_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(utils::get_agstash_dir()?.join("archives"))
+}
+
+// GetArchivePath returns where a project's archive is written
+pub fn get_archive_path(project_name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    utils::validate_project_name(project_name)?;
+    Ok(get_archives_dir()?.join(format!("{}.tar.gz", project_name)))
+}
+
+// ProjectFiles lists everything the store keeps for a project, relative to the .agstash directory
+pub fn project_files(project_name: &str) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let agstash_dir = utils::get_agstash_dir()?;
+    let mut files = Vec::new();
+
+    // Stashes for the project, its nested directories and its slots, with their metadata sidecars
+    for (stash_key, path) in utils::list_stashes(utils::get_stashes_dir()?)? {
+        if !utils::stash_belongs_to(&stash_key, project_name) {
+            continue;
+        }
+        for path in [path.clone(), path.with_extension("json")] {
+            if utils::file_exists(&path) {
+                files.push(path.strip_prefix(&agstash_dir)?.to_path_buf());
+            }
+        }
+    }
+
+    // Undo backups and history kept for the same stash keys, even once the stash itself is gone
+    for area in ["undo", "history"] {
+        let area_dir = agstash_dir.join(area);
+        if !area_dir.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&area_dir)? {
+            let dir = entry?.path();
+            let owned = dir
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| utils::stash_belongs_to(name, project_name));
+            if !dir.is_dir() || !owned {
+                continue;
+            }
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.is_file() {
+                    files.push(path.strip_prefix(&agstash_dir)?.to_path_buf());
+                }
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+// ExportFiles lists what export packs, relative to the .agstash directory: every stash and the store format, plus the
+// metadata sidecars and history when asked for
+pub fn export_files(metadata: bool, history: bool) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let agstash_dir = utils::get_agstash_dir()?;
+    let mut files = Vec::new();
+
+    let format_path = crate::store::get_format_path()?;
+    if utils::file_exists(&format_path) {
+        files.push(format_path.strip_prefix(&agstash_dir)?.to_path_buf());
+    }
+    let stashes_dir = utils::get_stashes_dir()?;
+    if stashes_dir.is_dir() {
+        for (_, path) in utils::list_stashes(&stashes_dir)? {
+            files.push(path.strip_prefix(&agstash_dir)?.to_path_buf());
+            let sidecar = path.with_extension("json");
+            if metadata && utils::file_exists(&sidecar) {
+                files.push(sidecar.strip_prefix(&agstash_dir)?.to_path_buf());
+            }
+        }
+    }
+
+    let history_dir = agstash_dir.join("history");
+    if history && history_dir.is_dir() {
+        for entry in fs::read_dir(&history_dir)? {
+            let dir = entry?.path();
+            if !dir.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.is_file() {
+                    files.push(path.strip_prefix(&agstash_dir)?.to_path_buf());
+                }
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+// ExportStore packs every stash, and optionally metadata and history, into one archive at output, laid out as in the
+// .agstash directory; returns how many files were packed, writing nothing when there are no stashes
+pub fn export_store(output: &Path, metadata: bool, history: bool) -> Result<usize, Box<dyn std::error::Error>> {
+    if utils::file_exists(output) {
+        return Err(AgStashError::FileExists(format!("{} already exists; choose another --output", output.display())).into());
+    }
+
+    let files = export_files(metadata, history)?;
+    if !files.iter().any(|relative| relative.starts_with("stashes")) {
+        return Ok(0);
+    }
+
+    let agstash_dir = utils::get_agstash_dir()?;
+    if let Some(parent) = output.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let encoder = GzEncoder::new(File::create(output)?, Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    for relative in &files {
+        builder.append_path_with_name(agstash_dir.join(relative), relative)?;
+    }
+    builder.into_inner()?.finish()?;
+    Ok(files.len())
+}
+
+// GetBackupDir returns where backups are written: the configured directory, or backups in the data directory, e.g.
+// ~/.local/share/agstash/backups; a ~/.agstash-backups from before that default keeps being used
+pub fn get_backup_dir(configured: Option<&str>) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if let Some(configured) = configured {
+        return utils::expand_home(configured);
+    }
+    let legacy_dir = utils::home_dir()?.join(".agstash-backups");
+    if legacy_dir.is_dir() {
+        return Ok(legacy_dir);
+    }
+    Ok(utils::get_platform_data_dir()?.join("backups"))
+}
+
+// BackupStore packs the whole .agstash directory into a new timestamped archive in backup_dir, laid out as export
+// lays out its archives; returns the archive, or None when there is no store
+pub fn backup_store(backup_dir: &Path) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let agstash_dir = utils::get_agstash_dir()?;
+    if !agstash_dir.is_dir() {
+        return Ok(None);
+    }
+
+    let mut files = Vec::new();
+    store_files(&agstash_dir, backup_dir, &mut files)?;
+    files.sort();
+
+    fs::create_dir_all(backup_dir)?;
+    let stamp = utils::format_timestamp(SystemTime::now()).replace(['-', ':'], "");
+    let mut output = backup_dir.join(format!("agstash-{}.tar.gz", stamp));
+    let mut counter = 1;
+    while utils::file_exists(&output) {
+        output = backup_dir.join(format!("agstash-{}-{}.tar.gz", stamp, counter));
+        counter += 1;
+    }
+
+    let encoder = GzEncoder::new(File::create(&output)?, Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    for path in &files {
+        builder.append_path_with_name(path, path.strip_prefix(&agstash_dir)?)?;
+    }
+    builder.into_inner()?.finish()?;
+    Ok(Some(output))
+}
+
+// store_files collects every file under dir, leaving out the backup directory when it sits inside the store
+fn store_files(dir: &Path, backup_dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path == backup_dir {
+            continue;
+        }
+        if path.is_dir() {
+            store_files(&path, backup_dir, files)?;
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+// ListBackups returns the backups in backup_dir, oldest first
+pub fn list_backups(backup_dir: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    if !backup_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(backup_dir)? {
+        let path = entry?.path();
+        let stamp = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("agstash-"))
+            .and_then(|name| name.strip_suffix(".tar.gz"))
+            .map(String::from);
+        if let (Some(stamp), true) = (stamp, path.is_file()) {
+            backups.push((stamp, path));
+        }
+    }
+    // Compare stamps without the extension so "...Z" sorts before "...Z-1" taken in the same second
+    backups.sort();
+    Ok(backups.into_iter().map(|(_, path)| path).collect())
+}
+
+// RotateBackups removes the oldest backups in backup_dir until at most keep remain, returning what was removed
+pub fn rotate_backups(backup_dir: &Path, keep: usize) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let backups = list_backups(backup_dir)?;
+    let excess = backups.len().saturating_sub(keep.max(1));
+    let removed: Vec<PathBuf> = backups.into_iter().take(excess).collect();
+    for path in &removed {
+        utils::remove_file(path)?;
+    }
+    Ok(removed)
+}
+
+// RestoreChange is one file a restore from backup adds, changes or removes, relative to the .agstash directory
+#[derive(Debug, Clone, PartialEq)]
+pub enum RestoreChange {
+    Added(PathBuf),
+    Changed(PathBuf),
+    Removed(PathBuf),
+}
+
+// RestorePlan is what restoring a backup would do: the files it writes and every change, in path order
+#[derive(Debug, Default)]
+pub struct RestorePlan {
+    pub format: Option<u32>,
+    pub files: ImportFiles,
+    pub changes: Vec<RestoreChange>,
+}
+
+// PlanRestore compares a backup archive with the store without touching either; merge keeps files the backup does not
+// have, otherwise the store is made to match the backup exactly, apart from backup_dir should it sit inside the store
+pub fn plan_restore(archive_path: &Path, merge: bool, backup_dir: &Path) -> Result<RestorePlan, Box<dyn std::error::Error>> {
+    let agstash_dir = utils::get_agstash_dir()?;
+    let mut plan = RestorePlan::default();
+    for (relative, content) in archive_files(archive_path)? {
+        if !relative.components().all(|component| matches!(component, std::path::Component::Normal(_))) {
+            return Err(AgStashError::InvalidArchive(format!("{} contains an unsafe path: {}", archive_path.display(), relative.display())).into());
+        }
+        if relative == Path::new("format") {
+            plan.format = String::from_utf8_lossy(&content).trim().parse().ok();
+        }
+        match fs::read(agstash_dir.join(&relative)) {
+            Ok(existing) if existing == content => {}
+            Ok(_) => plan.changes.push(RestoreChange::Changed(relative.clone())),
+            Err(_) => plan.changes.push(RestoreChange::Added(relative.clone())),
+        }
+        plan.files.push((relative, content));
+    }
+    if plan.files.is_empty() {
+        return Err(AgStashError::InvalidArchive(format!("{} is empty", archive_path.display())).into());
+    }
+
+    if !merge && agstash_dir.is_dir() {
+        let mut existing = Vec::new();
+        store_files(&agstash_dir, backup_dir, &mut existing)?;
+        for path in existing {
+            let relative = path.strip_prefix(&agstash_dir)?.to_path_buf();
+            if !plan.files.iter().any(|(file, _)| *file == relative) {
+                plan.changes.push(RestoreChange::Removed(relative));
+            }
+        }
+    }
+    plan.changes.sort_by(|a, b| change_path(a).cmp(change_path(b)));
+    Ok(plan)
+}
+
+// change_path returns the file a restore change is about
+fn change_path(change: &RestoreChange) -> &Path {
+    match change {
+        RestoreChange::Added(path) | RestoreChange::Changed(path) | RestoreChange::Removed(path) => path,
+    }
+}
+
+// ApplyRestore carries out a restore plan, relinking restored stashes to their content objects
+pub fn apply_restore(plan: &RestorePlan) -> Result<(), Box<dyn std::error::Error>> {
+    let agstash_dir = utils::get_agstash_dir()?;
+    for change in &plan.changes {
+        let relative = change_path(change);
+        let path = agstash_dir.join(relative);
+        if let RestoreChange::Removed(_) = change {
+            utils::remove_file(&path)?;
+            remove_empty_parents(&path, &agstash_dir)?;
+            continue;
+        }
+        let Some((_, content)) = plan.files.iter().find(|(file, _)| file == relative) else {
+            continue;
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Replace rather than write through, so a stash never changes its content object in place
+        if utils::file_exists(&path) {
+            utils::remove_file(&path)?;
+        }
+        fs::write(&path, content)?;
+    }
+
+    for (stash_key, path) in utils::list_stashes(utils::get_stashes_dir()?)? {
+        if plan.changes.iter().any(|change| agstash_dir.join(change_path(change)) == path) {
+            crate::store::share_stash(&path)?;
+            utils::log_info(&format!("Restored stash: {}", stash_key));
+        }
+    }
+    Ok(())
+}
+
+// Manifest is the JSON form of an export, for tools that generate or consume stashes programmatically
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: u32,
+    pub stashes: Vec<ManifestStash>,
+}
+
+// ManifestStash is one stash in a manifest; import checks content against sha256 when it is given
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestStash {
+    pub project: String,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<StashMetadata>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<ManifestVersion>,
+}
+
+// ManifestVersion is one recorded version of a stash in a manifest
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestVersion {
+    #[serde(flatten)]
+    pub version: Version,
+    pub content: String,
+}
+
+// ExportManifest describes every stash, and optionally its metadata and history, as a manifest
+pub fn export_manifest(metadata: bool, history: bool) -> Result<Manifest, Box<dyn std::error::Error>> {
+    let mut stashes = Vec::new();
+    for (project, path) in utils::list_stashes(utils::get_stashes_dir()?)? {
+        let (err, content) = utils::read_file(&path);
+        if let Some(error) = err {
+            return Err(error);
+        }
+        let mut stash = ManifestStash {
+            sha256: Some(crate::store::content_hash(content.as_bytes())),
+            metadata: if metadata { metadata::load_metadata(&project)? } else { None },
+            history: Vec::new(),
+            project,
+            content,
+        };
+        if history {
+            for version in history::list_versions(&stash.project)? {
+                let content = history::read_version(&stash.project, version.id)?;
+                stash.history.push(ManifestVersion { version, content });
+            }
+        }
+        stashes.push(stash);
+    }
+    Ok(Manifest {
+        format: crate::store::STORE_FORMAT,
+        stashes,
+    })
+}
+
+// read_manifest turns a JSON manifest into import entries, with history laid out as it is in the store
+fn read_manifest(path: &Path) -> Result<(Option<u32>, Vec<ImportEntry>), Box<dyn std::error::Error>> {
+    let (err, content) = utils::read_file(path);
+    if let Some(error) = err {
+        return Err(error);
+    }
+    let manifest: Manifest = serde_json::from_str(&content)
+        .map_err(|e| format!("Invalid manifest in {}: {}", path.display(), e))?;
+
+    let mut entries = Vec::new();
+    for stash in manifest.stashes {
+        let mut history = Vec::new();
+        for entry in &stash.history {
+            history.push((format!("{}.json", entry.version.id), serde_json::to_vec_pretty(&entry.version)?));
+            history.push((format!("{}.md", entry.version.id), entry.content.clone().into_bytes()));
+        }
+        entries.push(ImportEntry {
+            source: stash.project.clone(),
+            stash_key: stash.project,
+            content: stash.content.into_bytes(),
+            metadata: stash.metadata.map(|stash_metadata| serde_json::to_vec_pretty(&stash_metadata)).transpose()?,
+            history,
+            sha256: stash.sha256,
+        });
+    }
+    Ok((Some(manifest.format), entries))
+}
+
+// ImportEntry is one stash found by import, with the metadata and history exported alongside it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportEntry {
+    // Key the stash had where it came from
+    pub stash_key: String,
+    // Path of the file within the archive or directory, for messages
+    pub source: String,
+    pub content: Vec<u8>,
+    pub metadata: Option<Vec<u8>>,
+    // History files by name, e.g. "3.json" and "3.md.gz"
+    pub history: Vec<(String, Vec<u8>)>,
+    // Checksum the content must match, when the source recorded one
+    pub sha256: Option<String>,
+}
+
+// ReadImport collects the stashes in an archive or manifest written by export, an extracted store, or a plain directory
+// of markdown files, along with the store format recorded with them, if any
+pub fn read_import(path: &Path) -> Result<(Option<u32>, Vec<ImportEntry>), Box<dyn std::error::Error>> {
+    if path.is_file() && path.extension().and_then(|ext| ext.to_str()) == Some("json") {
+        return read_manifest(path);
+    }
+    let files = if path.is_dir() { directory_files(path)? } else { archive_files(path)? };
+
+    let mut format = None;
+    let mut entries: BTreeMap<PathBuf, ImportEntry> = BTreeMap::new();
+    let mut metadata: BTreeMap<PathBuf, Vec<u8>> = BTreeMap::new();
+    let mut history: BTreeMap<String, Vec<(String, Vec<u8>)>> = BTreeMap::new();
+    for (relative, content) in files {
+        let parts: Vec<String> = relative.iter().map(|part| part.to_string_lossy().into_owned()).collect();
+        // Stashes sit at the top level or in stashes/; a backup's quarantine, undo and archives are left alone
+        let stash_area = parts.len() == 1 || (parts.len() == 2 && parts[0] == "stashes");
+        match (parts.first().map(String::as_str), parts.len(), relative.extension().and_then(|ext| ext.to_str())) {
+            (Some("format"), 1, _) => {
+                format = Some(
+                    String::from_utf8_lossy(&content)
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("Invalid store format in {}", path.display()))?,
+                );
+            }
+            (Some("history"), 3, _) => history.entry(parts[1].clone()).or_default().push((parts[2].clone(), content)),
+            (_, _, Some("md")) if stash_area => {
+                let stem = relative.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+                let entry = ImportEntry {
+                    stash_key: stem.strip_prefix("stash-").unwrap_or(stem).to_string(),
+                    source: relative.display().to_string(),
+                    content,
+                    ..Default::default()
+                };
+                entries.insert(relative.with_extension(""), entry);
+            }
+            (_, _, Some("json")) if stash_area => {
+                metadata.insert(relative.with_extension(""), content);
+            }
+            _ => {}
+        }
+    }
+
+    let entries = entries
+        .into_iter()
+        .map(|(stem, mut entry)| {
+            entry.metadata = metadata.remove(&stem);
+            entry.history = history.remove(&entry.stash_key).unwrap_or_default();
+            entry
+        })
+        .collect();
+    Ok((format, entries))
+}
+
+// ImportFiles are the files found by an import, as paths relative to its root with their contents
+type ImportFiles = Vec<(PathBuf, Vec<u8>)>;
+
+// archive_files reads every file in a .tar.gz archive, keyed by its path inside it
+fn archive_files(path: &Path) -> Result<ImportFiles, Box<dyn std::error::Error>> {
+    let mut files = Vec::new();
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(path)?));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let relative = entry.path()?.into_owned();
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        files.push((relative, content));
+    }
+    Ok(files)
+}
+
+// directory_files reads the files directly in dir, and in the stashes and history areas when dir is an extracted store
+fn directory_files(dir: &Path) -> Result<ImportFiles, Box<dyn std::error::Error>> {
+    let mut dirs = vec![dir.to_path_buf(), dir.join("stashes")];
+    let history_dir = dir.join("history");
+    if history_dir.is_dir() {
+        for entry in fs::read_dir(&history_dir)? {
+            dirs.push(entry?.path());
+        }
+    }
+
+    let mut files = Vec::new();
+    for current in dirs.into_iter().filter(|current| current.is_dir()) {
+        for entry in fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.is_file() {
+                files.push((path.strip_prefix(dir)?.to_path_buf(), fs::read(&path)?));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// ArchiveProject bundles a project's files into one archive and removes them from the active store, returning the archive path
+pub fn archive_project(project_name: &str) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let archive_path = get_archive_path(project_name)?;
+    if utils::file_exists(&archive_path) {
+        return Err(AgStashError::FileExists(format!("An archive already exists for {}: {}", project_name, archive_path.display())).into());
+    }
+
+    let files = project_files(project_name)?;
+    if files.is_empty() {
+        return Ok(None);
+    }
+
+    let agstash_dir = utils::get_agstash_dir()?;
+    fs::create_dir_all(get_archives_dir()?)?;
+
+    let encoder = GzEncoder::new(File::create(&archive_path)?, Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    for relative in &files {
+        builder.append_path_with_name(agstash_dir.join(relative), relative)?;
+    }
+    builder.into_inner()?.finish()?;
+
+    // Only remove files once the archive is safely written
+    for relative in &files {
+        let path = agstash_dir.join(relative);
+        utils::remove_file(&path)?;
+        remove_empty_parents(&path, &agstash_dir)?;
+    }
+
+    Ok(Some(archive_path))
+}
+
+// RestoreProject unpacks a project's archive back into the active store and deletes it, refusing to overwrite unless force
+pub fn restore_project(project_name: &str, force: bool) -> Result<Option<Vec<PathBuf>>, Box<dyn std::error::Error>> {
+    let archive_path = get_archive_path(project_name)?;
+    if !utils::file_exists(&archive_path) {
+        return Ok(None);
+    }
+    let agstash_dir = utils::get_agstash_dir()?;
+
+    // Read the file list first so nothing is unpacked when any of it would clobber the active store
+    let mut files = Vec::new();
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(&archive_path)?));
+    for entry in archive.entries()? {
+        files.push(entry?.path()?.into_owned());
+    }
+    if !force {
+        if let Some(existing) = files.iter().find(|relative| agstash_dir.join(relative).exists()) {
+            return Err(format!(
+                "Restoring {} would overwrite {}; use --force to replace it",
+                project_name,
+                agstash_dir.join(existing).display()
+            )
+            .into());
+        }
+    }
+
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(&archive_path)?));
+    for entry in archive.entries()? {
+        // unpack_in refuses entries that would land outside the store
+        entry?.unpack_in(&agstash_dir)?;
+    }
+    utils::remove_file(&archive_path)?;
+
+    Ok(Some(files))
+}
+
+// remove_empty_parents deletes directories left empty by a removed file, stopping at root
+fn remove_empty_parents(path: &Path, root: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut current = path.parent();
+    while let Some(dir) = current {
+        if dir == root || !dir.starts_with(root) || fs::read_dir(dir)?.next().is_some() {
+            break;
+        }
+        fs::remove_dir(dir)?;
+        current = dir.parent();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use flate2::read::GzDecoder;
+
+    use crate::archive::{self, RestoreChange};
+    use crate::history;
+    use crate::metadata;
+    use crate::store;
+    use crate::undo;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_export_store() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+        store::create_store().unwrap();
+
+        let output = temp_dir.path().join("backup").join("stashes.tar.gz");
+        assert_eq!(archive::export_store(&output, true, true).unwrap(), 0);
+        assert!(!output.exists());
+
+        let stash_path = utils::get_stash_path("api").unwrap();
+        fs::write(&stash_path, "# AGENTS\n").unwrap();
+        metadata::save_metadata("api", &metadata::StashMetadata::new(Some("kept"), None)).unwrap();
+        history::record_version("api", &stash_path, None).unwrap();
+
+        let stashes_only = temp_dir.path().join("stashes-only.tar.gz");
+        assert_eq!(archive::export_store(&stashes_only, false, false).unwrap(), 2);
+        assert_eq!(archive::export_store(&output, true, true).unwrap(), 5);
+        assert!(archive::export_store(&output, true, true).is_err());
+
+        let mut archive = tar::Archive::new(GzDecoder::new(fs::File::open(&output).unwrap()));
+        let entries: Vec<String> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+            .collect();
+        assert_eq!(
+            entries,
+            vec!["format", "history/api/1.json", "history/api/1.md", "stashes/stash-api.json", "stashes/stash-api.md"]
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_backup_store() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        let backup_dir = archive::get_backup_dir(None).unwrap();
+        assert_eq!(backup_dir, utils::get_platform_data_dir().unwrap().join("backups"));
+        assert_eq!(archive::backup_store(&backup_dir).unwrap(), None);
+
+        store::create_store().unwrap();
+        fs::write(utils::get_stash_path("api").unwrap(), "# AGENTS\n").unwrap();
+        fs::write(utils::get_agstash_dir().unwrap().join("journal.jsonl"), "").unwrap();
+        let first = archive::backup_store(&backup_dir).unwrap().unwrap();
+        let second = archive::backup_store(&backup_dir).unwrap().unwrap();
+        assert_ne!(first, second);
+        assert_eq!(archive::list_backups(&backup_dir).unwrap(), vec![first.clone(), second.clone()]);
+
+        let mut archive = tar::Archive::new(GzDecoder::new(fs::File::open(&second).unwrap()));
+        let entries: Vec<String> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+            .collect();
+        assert_eq!(entries, vec!["format", "journal.jsonl", "stashes/stash-api.md"]);
+
+        // Rotation drops the oldest first
+        assert_eq!(archive::rotate_backups(&backup_dir, 1).unwrap(), vec![first]);
+        assert_eq!(archive::list_backups(&backup_dir).unwrap(), vec![second]);
+
+        // Backups made before the data directory default stay where they are
+        let legacy_dir = temp_dir.path().join(".agstash-backups");
+        fs::create_dir_all(&legacy_dir).unwrap();
+        assert_eq!(archive::get_backup_dir(None).unwrap(), legacy_dir);
+    }
+
+    #[test]
+    #[serial]
+    fn test_restore() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+        store::create_store().unwrap();
+
+        let backup_dir = archive::get_backup_dir(None).unwrap();
+        let api_path = utils::get_stash_path("api").unwrap();
+        fs::write(&api_path, "# AGENTS\n\n- backed up\n").unwrap();
+        let backup = archive::backup_store(&backup_dir).unwrap().unwrap();
+
+        fs::write(&api_path, "# AGENTS\n\n- changed since\n").unwrap();
+        let web_path = utils::get_stash_path("web").unwrap();
+        fs::write(&web_path, "# AGENTS\n").unwrap();
+
+        // Planning changes nothing
+        let plan = archive::plan_restore(&backup, false, &backup_dir).unwrap();
+        assert_eq!(
+            plan.changes,
+            vec![
+                RestoreChange::Changed("stashes/stash-api.md".into()),
+                RestoreChange::Removed("stashes/stash-web.md".into()),
+            ]
+        );
+        assert_eq!(plan.format, Some(crate::store::STORE_FORMAT));
+        assert!(web_path.exists());
+
+        let merge = archive::plan_restore(&backup, true, &backup_dir).unwrap();
+        assert_eq!(merge.changes, vec![RestoreChange::Changed("stashes/stash-api.md".into())]);
+        archive::apply_restore(&merge).unwrap();
+        assert_eq!(fs::read_to_string(&api_path).unwrap(), "# AGENTS\n\n- backed up\n");
+        assert!(web_path.exists());
+
+        archive::apply_restore(&archive::plan_restore(&backup, false, &backup_dir).unwrap()).unwrap();
+        assert!(!web_path.exists());
+        assert!(archive::plan_restore(&backup, false, &backup_dir).unwrap().changes.is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn test_export_manifest() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+        store::create_store().unwrap();
+
+        let stash_path = utils::get_stash_path("api").unwrap();
+        fs::write(&stash_path, "# AGENTS\n\n- api\n").unwrap();
+        metadata::save_metadata("api", &metadata::StashMetadata::new(Some("kept"), None)).unwrap();
+        history::record_version("api", &stash_path, None).unwrap();
+
+        let manifest = archive::export_manifest(true, true).unwrap();
+        assert_eq!(manifest.stashes.len(), 1);
+        assert_eq!(manifest.stashes[0].project, "api");
+        assert_eq!(manifest.stashes[0].sha256, Some(crate::store::content_hash(b"# AGENTS\n\n- api\n")));
+        assert_eq!(manifest.stashes[0].history[0].content, "# AGENTS\n\n- api\n");
+
+        // A manifest reads back as import entries, history included
+        let path = temp_dir.path().join("stashes.json");
+        fs::write(&path, serde_json::to_string(&manifest).unwrap()).unwrap();
+        let (format, entries) = archive::read_import(&path).unwrap();
+        assert_eq!(format, Some(crate::store::STORE_FORMAT));
+        assert_eq!(entries[0].stash_key, "api");
+        assert_eq!(entries[0].content, b"# AGENTS\n\n- api\n".to_vec());
+        let history_names: Vec<&str> = entries[0].history.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(history_names, vec!["1.json", "1.md"]);
+
+        // Hand-written manifests only need a project and its content
+        fs::write(&path, r##"{"format": 3, "stashes": [{"project": "web", "content": "# AGENTS\n"}]}"##).unwrap();
+        let (_, entries) = archive::read_import(&path).unwrap();
+        assert_eq!(entries[0].stash_key, "web");
+        assert_eq!(entries[0].sha256, None);
+    }
+
+    #[test]
+    fn test_read_import() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        fs::write(dir.join("notes.md"), "# AGENTS\n\n- loose\n").unwrap();
+        fs::create_dir_all(dir.join("stashes")).unwrap();
+        fs::write(dir.join("stashes").join("stash-api.md"), "# AGENTS\n").unwrap();
+        fs::write(dir.join("stashes").join("stash-api.json"), "{}").unwrap();
+        fs::create_dir_all(dir.join("history").join("api")).unwrap();
+        fs::write(dir.join("history").join("api").join("1.md"), "# AGENTS\n").unwrap();
+        fs::write(dir.join("format"), "3\n").unwrap();
+
+        let (format, entries) = archive::read_import(dir).unwrap();
+        assert_eq!(format, Some(3));
+        let keys: Vec<&str> = entries.iter().map(|entry| entry.stash_key.as_str()).collect();
+        assert_eq!(keys, vec!["notes", "api"]);
+        assert_eq!(entries[0].metadata, None);
+        assert_eq!(entries[1].metadata, Some(b"{}".to_vec()));
+        assert_eq!(entries[1].history, vec![("1.md".to_string(), b"# AGENTS\n".to_vec())]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_archive_and_restore_project() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+        store::create_store().unwrap();
+
+        let stash = utils::get_stash_path("retired").unwrap();
+        let nested = utils::get_stash_path("retired@docs").unwrap();
+        let other = utils::get_stash_path("retired-too").unwrap();
+        fs::write(&stash, "# AGENTS\n\n- root\n").unwrap();
+        fs::write(&nested, "# AGENTS\n\n- docs\n").unwrap();
+        fs::write(&other, "# AGENTS\n\n- other\n").unwrap();
+        metadata::save_metadata("retired", &metadata::StashMetadata::new(Some("kept"), None)).unwrap();
+        let agents = temp_dir.path().join("AGENTS.md");
+        fs::write(&agents, "# AGENTS\n\n- before clean\n").unwrap();
+        undo::save_backup("retired", "clean", &agents).unwrap();
+        history::record_version("retired", &stash, None).unwrap();
+
+        // Nothing to archive for an unknown project
+        assert!(archive::archive_project("unknown").unwrap().is_none());
+
+        let archive_path = archive::archive_project("retired").unwrap().unwrap();
+        assert!(archive_path.exists());
+        assert!(!stash.exists());
+        assert!(!nested.exists());
+        assert!(!undo::get_undo_dir("retired").unwrap().exists());
+        assert!(!history::get_history_dir("retired").unwrap().exists());
+        assert!(other.exists());
+
+        // A second archive is refused rather than overwriting the first
+        fs::write(&stash, "# AGENTS\n\n- new\n").unwrap();
+        assert!(archive::archive_project("retired").is_err());
+
+        // Restoring refuses to clobber the new stash without force
+        assert!(archive::restore_project("retired", false).is_err());
+        fs::remove_file(&stash).unwrap();
+
+        let restored = archive::restore_project("retired", false).unwrap().unwrap();
+        assert_eq!(restored.len(), 7);
+        assert_eq!(fs::read_to_string(&stash).unwrap(), "# AGENTS\n\n- root\n");
+        assert_eq!(fs::read_to_string(&nested).unwrap(), "# AGENTS\n\n- docs\n");
+        assert_eq!(
+            metadata::load_metadata("retired").unwrap().unwrap().message.as_deref(),
+            Some("kept")
+        );
+        assert_eq!(undo::load_backup("retired").unwrap().unwrap().action, "clean");
+        assert_eq!(history::list_versions("retired").unwrap().len(), 1);
+        assert!(!archive_path.exists());
+    }
+}
@@ -0,0 +1,175 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::utils;
+
+// A bundle keeps a project's whole agent configuration, every instruction file the tools it uses read, as one unit in
+// the store, so the files are stashed and applied together rather than one at a time
+
+// Files a bundle captures when the bundle setting is not configured
+pub const DEFAULT_BUNDLE: &[&str] = &["AGENTS.md", "CLAUDE.md", "GEMINI.md", ".cursorrules", ".github/copilot-instructions.md"];
+
+// BundleFiles is the content of a bundle: each file's path relative to the project root and its bytes
+pub type BundleFiles = Vec<(String, Vec<u8>)>;
+
+// Suffix of the files apply writes next to each target before moving them all into place
+const STAGED_SUFFIX: &str = ".agstash-new";
+
+// GetBundleDir returns the directory holding a project's bundle, laid out like the project itself
+pub fn get_bundle_dir(stash_key: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    utils::validate_project_name(stash_key)?;
+    Ok(utils::get_agstash_dir()?.join("bundles").join(stash_key))
+}
+
+// check_relative refuses a bundle entry that would point outside the project
+fn check_relative(relative: &str) -> Result<&Path, Box<dyn std::error::Error>> {
+    let path = Path::new(relative);
+    if relative.is_empty() || !path.components().all(|component| matches!(component, Component::Normal(_))) {
+        return Err(format!("Invalid bundle file '{}': use a path inside the project", relative).into());
+    }
+    Ok(path)
+}
+
+// SaveBundle replaces a project's bundle with those of files present in root, returning the ones captured
+pub fn save_bundle(root: &Path, stash_key: &str, files: &[String]) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut captured = Vec::new();
+    for relative in files {
+        let path = root.join(check_relative(relative)?);
+        if path.is_file() {
+            captured.push((relative.clone(), fs::read(&path)?));
+        }
+    }
+    if captured.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Write the new bundle beside the old one and swap them, so a failure leaves the old bundle whole
+    let bundle_dir = get_bundle_dir(stash_key)?;
+    let staging_dir = bundle_dir.with_file_name(format!("{}.tmp", stash_key));
+    if staging_dir.exists() {
+        fs::remove_dir_all(&staging_dir)?;
+    }
+    for (relative, content) in &captured {
+        let path = staging_dir.join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content)?;
+    }
+    if bundle_dir.exists() {
+        fs::remove_dir_all(&bundle_dir)?;
+    }
+    fs::rename(&staging_dir, &bundle_dir)?;
+    Ok(captured.into_iter().map(|(relative, _)| relative).collect())
+}
+
+// ListBundle returns the files in a project's bundle as paths relative to the project root, sorted
+pub fn list_bundle(stash_key: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let bundle_dir = get_bundle_dir(stash_key)?;
+    let mut files = Vec::new();
+    let mut pending = vec![bundle_dir.clone()];
+    while let Some(dir) = pending.pop() {
+        if !dir.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if let Ok(relative) = path.strip_prefix(&bundle_dir) {
+                let components: Vec<String> = relative
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy().into_owned())
+                    .collect();
+                files.push(components.join("/"));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// ReadBundle returns every file in a project's bundle with its content
+pub fn read_bundle(stash_key: &str) -> Result<BundleFiles, Box<dyn std::error::Error>> {
+    let bundle_dir = get_bundle_dir(stash_key)?;
+    let mut files = Vec::new();
+    for relative in list_bundle(stash_key)? {
+        let content = fs::read(bundle_dir.join(&relative))?;
+        files.push((relative, content));
+    }
+    Ok(files)
+}
+
+// ApplyBundle writes every file in a bundle into root as one step: each is staged next to its target first, and only
+// when all are staged are they moved into place, so a failure part way leaves the project as it was
+pub fn apply_bundle(root: &Path, files: &BundleFiles) -> Result<(), Box<dyn std::error::Error>> {
+    let mut staged = Vec::new();
+    let staging = (|| -> Result<(), Box<dyn std::error::Error>> {
+        for (relative, content) in files {
+            let target = root.join(check_relative(relative)?);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut staged_name = target.clone().into_os_string();
+            staged_name.push(STAGED_SUFFIX);
+            let staged_path = PathBuf::from(staged_name);
+            fs::write(&staged_path, content)?;
+            staged.push((staged_path, target));
+        }
+        Ok(())
+    })();
+    if let Err(error) = staging {
+        for (staged_path, _) in &staged {
+            let _ = fs::remove_file(staged_path);
+        }
+        return Err(error);
+    }
+
+    for (staged_path, target) in staged {
+        fs::rename(staged_path, target)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::bundle;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_bundle_roundtrip() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        let root = temp_dir.path().join("api");
+        fs::create_dir_all(root.join(".github")).unwrap();
+        fs::write(root.join("AGENTS.md"), "# AGENTS\n").unwrap();
+        fs::write(root.join("CLAUDE.md"), "# CLAUDE\n").unwrap();
+        fs::write(root.join(".github").join("copilot-instructions.md"), "Be brief.\n").unwrap();
+        let files: Vec<String> = bundle::DEFAULT_BUNDLE.iter().map(|file| file.to_string()).collect();
+
+        // Files the project doesn't have are left out
+        let captured = bundle::save_bundle(&root, "api", &files).unwrap();
+        assert_eq!(captured, vec!["AGENTS.md", "CLAUDE.md", ".github/copilot-instructions.md"]);
+        assert_eq!(bundle::list_bundle("api").unwrap(), vec![".github/copilot-instructions.md", "AGENTS.md", "CLAUDE.md"]);
+        assert!(bundle::save_bundle(&root, "api", &["../escape".to_string()]).is_err());
+
+        let fresh = temp_dir.path().join("fresh");
+        fs::create_dir_all(&fresh).unwrap();
+        bundle::apply_bundle(&fresh, &bundle::read_bundle("api").unwrap()).unwrap();
+        assert_eq!(fs::read_to_string(fresh.join("CLAUDE.md")).unwrap(), "# CLAUDE\n");
+        assert_eq!(fs::read_to_string(fresh.join(".github").join("copilot-instructions.md")).unwrap(), "Be brief.\n");
+        assert_eq!(fs::read_dir(&fresh).unwrap().count(), 3);
+
+        // Re-stashing replaces the bundle rather than adding to it
+        fs::remove_file(root.join("CLAUDE.md")).unwrap();
+        bundle::save_bundle(&root, "api", &files).unwrap();
+        assert!(!bundle::list_bundle("api").unwrap().contains(&"CLAUDE.md".to_string()));
+    }
+}
@@ -0,0 +1,130 @@
+use super::{color_string, rekey_project, root_context, BOLD, GREEN, YELLOW};
+use crate::registry;
+use crate::utils;
+
+// HandleAlias registers a human-friendly name the current project's stashes are keyed by instead of its directory
+// name and path hash, moving the stashes it already has; with no name it shows the key in use
+pub fn handle_alias(name: Option<&str>, remove: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let (root, project_name) = root_context()?;
+
+    if remove {
+        let Some(alias) = registry::remove_alias(&root)? else {
+            println!("{} has no alias", color_string(&root.display().to_string(), BOLD));
+            return Ok(());
+        };
+        let stash_key = registry::project_key(&root)?;
+        let kept = rekey_project(&alias, &stash_key)?;
+        utils::log_info(&format!("Removed alias {} for {}", alias, root.display()));
+        println!(
+            "{} alias {}; stashes are keyed by {} again",
+            color_string("Removed", GREEN),
+            color_string(&alias, BOLD),
+            color_string(&stash_key, BOLD)
+        );
+        print_kept(&kept, &stash_key);
+        return Ok(());
+    }
+
+    let Some(alias) = name else {
+        match registry::alias_for(&root)? {
+            Some(alias) => println!("{} is aliased as {}", root.display(), color_string(&alias, BOLD)),
+            None => println!("{} has no alias; stashes are keyed by {}", root.display(), color_string(&project_name, BOLD)),
+        }
+        return Ok(());
+    };
+
+    utils::validate_project_name(alias)?;
+    if alias.contains(['@', '+']) {
+        return Err(format!("Invalid alias '{}': '@' and '+' are reserved for nested and slot stashes", alias).into());
+    }
+    if alias == project_name {
+        println!("{} is already keyed by {}", root.display(), color_string(alias, BOLD));
+        return Ok(());
+    }
+    if let Some(other_root) = registry::root_for(alias)? {
+        return Err(format!("The alias '{}' is already used by {}", alias, other_root.display()).into());
+    }
+
+    // Stashes the alias already has, e.g. synced from another machine, win over this project's
+    let kept = rekey_project(&project_name, alias)?;
+    registry::set_alias(&root, alias)?;
+    utils::log_info(&format!("Aliased {} as {}", root.display(), alias));
+    println!(
+        "{} {} as {}",
+        color_string("Aliased", GREEN),
+        root.display(),
+        color_string(alias, BOLD)
+    );
+    print_kept(&kept, alias);
+    Ok(())
+}
+
+// print_kept names the stashes that stayed under their old key because the new key already had one
+fn print_kept(kept: &[String], new_name: &str) {
+    for stash_key in kept {
+        println!(
+            "{} {} was left as is because {} already has that stash",
+            color_string("Note:", YELLOW),
+            color_string(stash_key, BOLD),
+            new_name
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::registry;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_alias() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir(".git").unwrap();
+
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(move || {
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        let stash_key = utils::project_stash_key(temp_dir.path()).unwrap();
+        fs::write("AGENTS.md", "# AGENTS\n\n- aliased\n").unwrap();
+        commands::handle_stash(None).unwrap();
+        commands::handle_stash(Some("before the alias")).unwrap();
+
+        assert!(commands::handle_alias(Some("api+web"), false).is_err());
+        commands::handle_alias(Some("api"), false).unwrap();
+        assert_eq!(registry::alias_for(temp_dir.path()).unwrap().as_deref(), Some("api"));
+        assert!(utils::get_stash_path("api").unwrap().exists());
+        assert!(!utils::get_stash_path(&stash_key).unwrap().exists());
+
+        // Stash and apply now use the alias
+        fs::write("AGENTS.md", "# AGENTS\n\n- after the alias\n").unwrap();
+        commands::handle_stash(None).unwrap();
+        assert_eq!(fs::read_to_string(utils::get_stash_path("api").unwrap()).unwrap(), "# AGENTS\n\n- after the alias\n");
+
+        // Another project can't take the same alias
+        let other = TempDir::new().unwrap();
+        registry::set_alias(other.path(), "web").unwrap();
+        assert!(commands::handle_alias(Some("web"), false).is_err());
+
+        commands::handle_alias(None, true).unwrap();
+        assert_eq!(registry::alias_for(temp_dir.path()).unwrap(), None);
+        assert!(utils::get_stash_path(&stash_key).unwrap().exists());
+        assert!(!utils::get_stash_path("api").unwrap().exists());
+    }
+}
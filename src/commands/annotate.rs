@@ -0,0 +1,109 @@
+use super::{color_string, fail_if_strict, named_stash, project_context, BOLD, GREEN, YELLOW};
+use crate::error::AgStashError;
+use crate::metadata::{self, StashMetadata};
+use crate::utils;
+
+// HandleAnnotate adds a warning to a project's stash that apply shows before writing it, or clears them all;
+// with neither it lists the current warnings
+pub fn handle_annotate(project: Option<&str>, warning: Option<&str>, clear: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let project_name = match project {
+        Some(name) => named_stash(name)?,
+        None => project_context()?.1,
+    };
+
+    let stash_path = utils::get_stash_path(&project_name)?;
+    if !utils::file_exists(&stash_path) {
+        fail_if_strict(AgStashError::StashNotFound(format!("No stash found for project {}", project_name)))?;
+        utils::log_info(&format!("No stash found for project: {}", project_name));
+        println!("No stash found for project {}", color_string(&project_name, BOLD));
+        return Ok(());
+    }
+
+    let mut stash_metadata = match metadata::load_metadata(&project_name)? {
+        Some(stash_metadata) => stash_metadata,
+        None => StashMetadata::from_file(&stash_path, "metadata added by annotate")?,
+    };
+
+    if clear {
+        let count = stash_metadata.warnings.len();
+        stash_metadata.warnings.clear();
+        metadata::save_metadata(&project_name, &stash_metadata)?;
+        println!(
+            "{} {} warning(s) from {}",
+            color_string("Cleared", GREEN),
+            count,
+            color_string(&project_name, BOLD)
+        );
+    } else if let Some(warning) = warning.map(str::trim).filter(|warning| !warning.is_empty()) {
+        if !stash_metadata.warnings.iter().any(|existing| existing == warning) {
+            stash_metadata.warnings.push(warning.to_string());
+            metadata::save_metadata(&project_name, &stash_metadata)?;
+        }
+        utils::log_info(&format!("Annotated stash {}: {}", project_name, warning));
+        println!(
+            "{} {}: {}",
+            color_string("Annotated", GREEN),
+            color_string(&project_name, BOLD),
+            warning
+        );
+    } else if stash_metadata.warnings.is_empty() {
+        println!("No warnings for {}", color_string(&project_name, BOLD));
+    } else {
+        for warning in &stash_metadata.warnings {
+            println!("{} {}", color_string("Warning:", YELLOW), warning);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::metadata;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_annotate() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir(".git").unwrap();
+
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(move || {
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        let project_name = utils::project_stash_key(temp_dir.path()).unwrap();
+        fs::write("AGENTS.md", "# AGENTS\n\n- never write tests\n").unwrap();
+        commands::handle_stash(None).unwrap();
+
+        commands::handle_annotate(None, Some("contains experimental strict rules"), false).unwrap();
+        commands::handle_annotate(None, Some("contains experimental strict rules"), false).unwrap();
+        let stash_metadata = metadata::load_metadata(&project_name).unwrap().unwrap();
+        assert_eq!(stash_metadata.warnings, vec!["contains experimental strict rules"]);
+
+        // Stashing again keeps the warning, and apply still goes ahead
+        commands::handle_stash(Some("again")).unwrap();
+        assert_eq!(metadata::load_metadata(&project_name).unwrap().unwrap().warnings.len(), 1);
+        fs::write("AGENTS.md", "# AGENTS\n").unwrap();
+        commands::handle_apply(&commands::ApplyOptions { force: true, ..Default::default() }).unwrap();
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- never write tests\n");
+
+        commands::handle_annotate(Some(&project_name), None, true).unwrap();
+        assert!(metadata::load_metadata(&project_name).unwrap().unwrap().warnings.is_empty());
+    }
+}
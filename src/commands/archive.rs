@@ -0,0 +1,175 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::{color_string, get_user_confirmation, BOLD, GREEN, RED, YELLOW};
+use crate::archive::{self, RestoreChange};
+use crate::error::AgStashError;
+use crate::journal;
+use crate::store;
+use crate::utils;
+
+// Default number of backups kept in the backup directory
+pub const DEFAULT_KEEP_BACKUPS: usize = 10;
+
+// HandleArchiveProject moves everything kept for a retired project into a single archive file
+pub fn handle_archive_project(project_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    utils::validate_project_name(project_name)?;
+
+    match archive::archive_project(project_name)? {
+        Some(archive_path) => {
+            journal::record_event(project_name, "archive", Some(&archive_path.display().to_string()))?;
+            utils::log_info(&format!("Archived project to: {}", archive_path.display()));
+            println!(
+                "{} {} to {}",
+                color_string("Archived", GREEN),
+                color_string(project_name, BOLD),
+                archive_path.display()
+            );
+        }
+        None => {
+            utils::log_info(&format!("Nothing stored for project: {}", project_name));
+            println!("Nothing stored for project {}", color_string(project_name, BOLD));
+        }
+    }
+
+    Ok(())
+}
+
+// HandleExport packs every stash, and optionally metadata and history, into one archive to move to another machine,
+// or with the json format describes them in a manifest for other tools; a json output of "-" goes to stdout
+pub fn handle_export(output: &Path, format: &str, metadata: bool, history: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let count = match format {
+        "tar" => archive::export_store(output, metadata, history)?,
+        "json" => return export_manifest(output, metadata, history),
+        other => return Err(AgStashError::Usage(format!("Unknown export format: {} (expected tar or json)", other)).into()),
+    };
+    if count == 0 {
+        println!("{}", color_string("No stashes to export.", YELLOW));
+        return Ok(());
+    }
+
+    utils::log_info(&format!("Exported {} file(s) to: {}", count, output.display()));
+    println!("{} {} file(s) to {}", color_string("Exported", GREEN), count, output.display());
+    Ok(())
+}
+
+// export_manifest writes the JSON manifest of the store to output, or to stdout when output is "-"
+fn export_manifest(output: &Path, metadata: bool, history: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = archive::export_manifest(metadata, history)?;
+    let content = serde_json::to_string_pretty(&manifest)?;
+    if output == Path::new("-") {
+        println!("{}", content);
+        return Ok(());
+    }
+    if utils::file_exists(output) {
+        return Err(AgStashError::FileExists(format!("{} already exists; choose another --output", output.display())).into());
+    }
+    if let Some(error) = utils::write_file(output, &format!("{}\n", content)) {
+        return Err(error);
+    }
+    utils::log_info(&format!("Exported {} stash(es) to: {}", manifest.stashes.len(), output.display()));
+    println!("{} {} stash(es) to {}", color_string("Exported", GREEN), manifest.stashes.len(), output.display());
+    Ok(())
+}
+
+// HandleBackup writes a timestamped archive of the whole store to the backup directory and rotates out old backups
+pub fn handle_backup(backup_dir: Option<&str>, keep: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let backup_dir = archive::get_backup_dir(backup_dir)?;
+    let Some(backup_path) = archive::backup_store(&backup_dir)? else {
+        println!("{}", color_string("No store to back up.", YELLOW));
+        return Ok(());
+    };
+    utils::log_info(&format!("Backed up the store to: {}", backup_path.display()));
+    println!("{} the store to {}", color_string("Backed up", GREEN), backup_path.display());
+
+    let removed = archive::rotate_backups(&backup_dir, keep)?;
+    if !removed.is_empty() {
+        utils::log_info(&format!("Removed {} old backup(s) from: {}", removed.len(), backup_dir.display()));
+        println!("Removed {} old backup(s), keeping the newest {}", removed.len(), keep.max(1));
+    }
+    Ok(())
+}
+
+// HandleRestore brings the store back to the state saved in a backup archive, or with merge adds the backup's files
+// over the current store, after previewing every change; the current store is backed up first
+pub fn handle_restore(
+    archive_path: &Path,
+    merge: bool,
+    force: bool,
+    backup_dir: Option<&str>,
+    keep_backups: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !utils::file_exists(archive_path) {
+        return Err(AgStashError::FileNotFound(format!("{} does not exist", archive_path.display())).into());
+    }
+    let plan = archive::plan_restore(archive_path, merge, &archive::get_backup_dir(backup_dir)?)?;
+    if let Some(format) = plan.format.filter(|format| *format > store::STORE_FORMAT) {
+        return Err(AgStashError::UnsupportedStoreFormat(format!(
+            "{} was written by a newer agstash (store format {}, this one understands up to {}); upgrade agstash first",
+            archive_path.display(),
+            format,
+            store::STORE_FORMAT
+        ))
+        .into());
+    }
+    if plan.changes.is_empty() {
+        println!("The store already matches {}", archive_path.display());
+        return Ok(());
+    }
+
+    for change in &plan.changes {
+        match change {
+            RestoreChange::Added(path) => println!("{}", color_string(&format!("+ {}", path.display()), GREEN)),
+            RestoreChange::Changed(path) => println!("{}", color_string(&format!("~ {}", path.display()), YELLOW)),
+            RestoreChange::Removed(path) => println!("{}", color_string(&format!("- {}", path.display()), RED)),
+        }
+    }
+    if !force {
+        print!(
+            "\nApply these {} change(s) from {}? [y/N]: ",
+            plan.changes.len(),
+            color_string(&archive_path.display().to_string(), BOLD)
+        );
+        io::stdout().flush()?; // Ensure the prompt is displayed
+        if !get_user_confirmation()? {
+            println!("\nOperation cancelled. The store was not modified.");
+            return Ok(());
+        }
+    }
+
+    // The backup being restored is already in memory, so rotation may safely remove it
+    handle_backup(backup_dir, keep_backups)?;
+    archive::apply_restore(&plan)?;
+    utils::log_info(&format!("Restored {} change(s) from: {}", plan.changes.len(), archive_path.display()));
+    println!(
+        "{} {} change(s) from {}",
+        color_string("Restored", GREEN),
+        plan.changes.len(),
+        archive_path.display()
+    );
+    Ok(())
+}
+
+// HandleRestoreProject unpacks an archived project back into the active store
+pub fn handle_restore_project(project_name: &str, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    utils::validate_project_name(project_name)?;
+
+    match archive::restore_project(project_name, force)? {
+        Some(files) => {
+            journal::record_event(project_name, "restore", None)?;
+            utils::log_info(&format!("Restored {} file(s) for project: {}", files.len(), project_name));
+            println!(
+                "{} {} ({} file(s))",
+                color_string("Restored", GREEN),
+                color_string(project_name, BOLD),
+                files.len()
+            );
+        }
+        None => {
+            utils::log_info(&format!("No archive found for project: {}", project_name));
+            println!("No archive found for project {}", color_string(project_name, BOLD));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::json;
+
+use super::{color_string, indicator, project_context, Indicator, BOLD, RED, YELLOW};
+use crate::managed::ManagedFile;
+use crate::{registry, utils};
+
+// AuditEntry is the audit result for a single stashed project
+struct AuditEntry {
+    project: String,
+    path: PathBuf,
+    size: u64,
+    modified: Option<String>,
+    drifted: Option<bool>,
+    failures: Vec<String>,
+}
+
+// HandleAudit reports validation and drift for stashed projects without modifying anything, returning whether any failed
+pub fn handle_audit(all: bool, json: bool, store: Option<&Path>) -> Result<bool, Box<dyn std::error::Error>> {
+    let stashes_dir = match store {
+        Some(store) => store.to_path_buf(),
+        None => utils::get_stashes_dir()?,
+    };
+    utils::log_info(&format!("Auditing stashes in: {}", stashes_dir.display()));
+
+    let entries = if all {
+        // Only projects registered on this machine have a known location to judge drift against, and a shared store
+        // describes other machines' checkouts
+        let roots = match store {
+            Some(_) => HashMap::new(),
+            None => registered_roots()?,
+        };
+        let mut entries = Vec::new();
+        for (project, path) in utils::list_stashes(&stashes_dir)? {
+            let agents_path = roots.get(&project).map(|root| root.join("AGENTS.md"));
+            entries.push(audit_stash(&project, &path, agents_path.as_deref()));
+        }
+        entries
+    } else {
+        let (root, project_name) = project_context()?;
+        let stash_path = stashes_dir.join(format!("stash-{}.md", project_name));
+        if !utils::file_exists(&stash_path) {
+            println!("No stash found for project {}", color_string(&project_name, BOLD));
+            return Ok(false);
+        }
+        vec![audit_stash(&project_name, &stash_path, Some(&root.join("AGENTS.md")))]
+    };
+
+    let failed = entries.iter().any(|entry| !entry.failures.is_empty());
+
+    if json {
+        let report: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                json!({
+                    "project": entry.project,
+                    "path": entry.path.display().to_string(),
+                    "size": entry.size,
+                    "modified": entry.modified,
+                    "drifted": entry.drifted,
+                    "valid": entry.failures.is_empty(),
+                    "failures": entry.failures,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(failed);
+    }
+
+    if entries.is_empty() {
+        println!("{}", color_string("No stashes found.", YELLOW));
+        return Ok(false);
+    }
+
+    for entry in &entries {
+        let verdict = if entry.failures.is_empty() {
+            indicator(Indicator::Ok, "ok")
+        } else {
+            indicator(Indicator::Error, "failed")
+        };
+        println!("{} {}", color_string(&entry.project, BOLD), verdict);
+        println!("  modified: {}", entry.modified.as_deref().unwrap_or("unknown"));
+        println!("  size: {} bytes", entry.size);
+        match entry.drifted {
+            Some(true) => println!("  drift: {}", indicator(Indicator::Drift, "AGENTS.md differs from stash")),
+            Some(false) => println!("  drift: none"),
+            None => {}
+        }
+        for failure in &entry.failures {
+            println!("  {} {}", color_string("-", RED), failure);
+        }
+    }
+
+    let failures = entries.iter().filter(|entry| !entry.failures.is_empty()).count();
+    println!("\n{} project(s) audited, {} with failures", entries.len(), failures);
+
+    Ok(failed)
+}
+
+// registered_roots maps the stash key of every registered project to its root
+fn registered_roots() -> Result<HashMap<String, PathBuf>, Box<dyn std::error::Error>> {
+    let mut roots = HashMap::new();
+    for (root, project) in registry::load_projects()? {
+        let root = PathBuf::from(root);
+        let stash_key = match project.alias {
+            Some(alias) => alias,
+            None => utils::project_stash_key(&root)?,
+        };
+        roots.insert(stash_key, root);
+    }
+    Ok(roots)
+}
+
+// audit_stash validates a single stash file and, when given the working AGENTS.md, checks it for drift
+fn audit_stash(project: &str, path: &Path, agents_path: Option<&Path>) -> AuditEntry {
+    let metadata = fs::metadata(path).ok();
+    let mut entry = AuditEntry {
+        project: project.to_string(),
+        path: path.to_path_buf(),
+        size: metadata.as_ref().map(|m| m.len()).unwrap_or(0),
+        modified: metadata
+            .and_then(|m| m.modified().ok())
+            .map(utils::format_timestamp),
+        drifted: None,
+        failures: Vec::new(),
+    };
+
+    let (err, content) = utils::read_file(path);
+    if let Some(error) = err {
+        entry.failures.push(format!("unreadable: {}", error));
+        return entry;
+    }
+    let file = ManagedFile::for_stash_key(project);
+    if content.trim().is_empty() {
+        entry.failures.push("stash is empty".to_string());
+    } else if !file.is_valid(&content) {
+        entry.failures.push(file.requirement().to_string());
+    }
+
+    if let Some(agents_path) = agents_path {
+        let (err, agents_content) = utils::read_file(agents_path);
+        entry.drifted = Some(err.is_some() || agents_content != content);
+    }
+
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use serial_test::serial;
+    use tempfile::TempDir;
+
+    use crate::commands;
+    use crate::registry;
+    use crate::testing::TestEnv;
+    use crate::utils;
+
+    #[test]
+    fn test_handle_audit_all() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("stash-good.md"), "# AGENTS\n\n- rule\n").unwrap();
+
+        // A store with only valid stashes passes
+        let failed = commands::handle_audit(true, false, Some(temp_dir.path())).unwrap();
+        assert!(!failed);
+
+        // An invalid stash is reported as a failure
+        fs::write(temp_dir.path().join("stash-bad.md"), "no header").unwrap();
+        let failed = commands::handle_audit(true, true, Some(temp_dir.path())).unwrap();
+        assert!(failed);
+    }
+
+    #[test]
+    fn test_audit_stash() {
+        let temp_dir = TempDir::new().unwrap();
+        let stash = temp_dir.path().join("stash-demo.md");
+        let agents = temp_dir.path().join("AGENTS.md");
+        fs::write(&stash, "# AGENTS\n").unwrap();
+        fs::write(&agents, "# AGENTS\n\n- local\n").unwrap();
+
+        let entry = super::audit_stash("demo", &stash, Some(&agents));
+        assert!(entry.failures.is_empty());
+        assert_eq!(entry.drifted, Some(true));
+        assert_eq!(entry.size, 9);
+    }
+
+    #[test]
+    #[serial]
+    fn test_registered_roots() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        let api = temp_dir.path().join("api");
+        let web = temp_dir.path().join("web");
+        for root in [&api, &web] {
+            fs::create_dir_all(root).unwrap();
+            registry::register_project(root).unwrap();
+        }
+        registry::set_alias(&web, "web-app").unwrap();
+
+        // --all judges drift for registered projects by their stash key
+        let roots = super::registered_roots().unwrap();
+        let api_key = utils::project_stash_key(&api.canonicalize().unwrap()).unwrap();
+        assert_eq!(roots.get(&api_key), Some(&api.canonicalize().unwrap()));
+        assert_eq!(roots.get("web-app"), Some(&web.canonicalize().unwrap()));
+        assert_eq!(roots.len(), 2);
+    }
+}
@@ -0,0 +1,161 @@
+use std::fs;
+use std::io::{self, Write};
+
+use super::{announce, apply_allowed, color_string, get_user_confirmation, root_context, BOLD, GREEN, YELLOW};
+use crate::bundle;
+use crate::config;
+use crate::journal;
+use crate::managed::ManagedFile;
+use crate::registry;
+use crate::undo;
+use crate::utils;
+
+// HandleStashBundle stashes every configured agent file the project has, e.g. AGENTS.md, CLAUDE.md and .cursorrules,
+// as one bundle that replaces the project's previous one
+pub fn handle_stash_bundle(message: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let (root, project_name) = root_context()?;
+    let files = config::load_settings()?.bundle.unwrap_or_default();
+
+    // Each file is held to the same standard as a plain stash of it, e.g. AGENTS.md needs its header
+    for name in &files {
+        let file = ManagedFile::new(name)?;
+        let path = root.join(&file.name);
+        if !path.is_file() {
+            continue;
+        }
+        let (err, content) = utils::read_file(&path);
+        if let Some(error) = err {
+            return Err(error);
+        }
+        if !file.is_valid(&content) {
+            println!(
+                "{} {}",
+                color_string(&format!("{} content is invalid ({}).", file.name, file.requirement()), YELLOW),
+                color_string("Stash aborted.", YELLOW)
+            );
+            return Ok(());
+        }
+    }
+
+    let captured = bundle::save_bundle(&root, &project_name, &files)?;
+    if captured.is_empty() {
+        println!("{} ({})", color_string("None of the bundle files exist in the project root", YELLOW), files.join(", "));
+        return Ok(());
+    }
+    journal::record_event(&project_name, "bundle", Some(message.unwrap_or(&format!("stashed {}", captured.join(", ")))))?;
+    registry::register_project(&root)?;
+
+    utils::log_info(&format!("Stashed bundle for project {}: {}", project_name, captured.join(", ")));
+    for file in &captured {
+        println!("{} {}", color_string("Bundled", GREEN), file);
+    }
+    println!("\n{} file(s) stashed as the bundle for {}", captured.len(), color_string(&project_name, BOLD));
+    Ok(())
+}
+
+// HandleApplyBundle writes the project's bundle back into the project root all at once, asking before it replaces
+// files that differ unless forced
+pub fn handle_apply_bundle(force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let (root, project_name) = root_context()?;
+    let files = bundle::read_bundle(&project_name)?;
+    if files.is_empty() {
+        println!("No bundle found for project {}", color_string(&project_name, BOLD));
+        return Ok(());
+    }
+    apply_allowed(&root, force)?;
+
+    // Files that already match are left alone so their mtimes survive
+    let changed: bundle::BundleFiles = files
+        .into_iter()
+        .filter(|(relative, content)| fs::read(root.join(relative)).ok().as_ref() != Some(content))
+        .collect();
+    if changed.is_empty() {
+        println!("The bundle is already up to date for {}", color_string(&project_name, BOLD));
+        return Ok(());
+    }
+
+    let replaced: Vec<&str> = changed
+        .iter()
+        .filter(|(relative, _)| root.join(relative).exists())
+        .map(|(relative, _)| relative.as_str())
+        .collect();
+    if !replaced.is_empty() && !force {
+        println!(
+            "\n{} applying the bundle replaces {}",
+            color_string("WARNING:", &format!("{}{}", YELLOW, BOLD)),
+            replaced.join(", ")
+        );
+        print!("Type 'yes' to confirm or 'no' to cancel [y/N]: ");
+        io::stdout().flush()?; // Ensure the prompt is displayed
+        if !get_user_confirmation()? {
+            println!("\nOperation cancelled. No files were modified.");
+            return Ok(());
+        }
+    }
+
+    // Only AGENTS.md has an undo slot; the rest are overwritten after the prompt above
+    if replaced.contains(&"AGENTS.md") {
+        undo::save_backup(&project_name, "apply", &root.join("AGENTS.md"))?;
+    }
+    bundle::apply_bundle(&root, &changed)?;
+    let applied: Vec<&str> = changed.iter().map(|(relative, _)| relative.as_str()).collect();
+    journal::record_event(&project_name, "bundle", Some(&format!("applied {}", applied.join(", "))))?;
+
+    utils::log_info(&format!("Applied bundle for project {}: {}", project_name, applied.join(", ")));
+    for file in &applied {
+        announce(&format!("{} {}", color_string("Applied", GREEN), file));
+    }
+    println!("\n{} file(s) applied from the bundle for {}", applied.len(), color_string(&project_name, BOLD));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::bundle;
+    use crate::commands;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_stash_and_apply_bundle() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir(".git").unwrap();
+
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(move || {
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        let project_name = utils::project_stash_key(temp_dir.path()).unwrap();
+        fs::write("AGENTS.md", "# AGENTS\n\n- bundled\n").unwrap();
+        fs::write("CLAUDE.md", "# CLAUDE\n").unwrap();
+        fs::write(".cursorrules", "Use tabs.\n").unwrap();
+        commands::handle_stash_bundle(None).unwrap();
+        assert_eq!(bundle::list_bundle(&project_name).unwrap(), vec![".cursorrules", "AGENTS.md", "CLAUDE.md"]);
+
+        for file in ["AGENTS.md", "CLAUDE.md", ".cursorrules"] {
+            fs::remove_file(file).unwrap();
+        }
+        commands::handle_apply_bundle(false).unwrap();
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- bundled\n");
+        assert_eq!(fs::read_to_string(".cursorrules").unwrap(), "Use tabs.\n");
+
+        // Forced apply replaces files that drifted
+        fs::write("CLAUDE.md", "# CLAUDE\n\n- drifted\n").unwrap();
+        commands::handle_apply_bundle(true).unwrap();
+        assert_eq!(fs::read_to_string("CLAUDE.md").unwrap(), "# CLAUDE\n");
+    }
+}
@@ -0,0 +1,136 @@
+use std::fs;
+use std::io::{self, IsTerminal};
+
+use serde_json::json;
+
+use super::{annotation, in_github_actions, indicator, json_output, print_json, project_context, set_color, target_file, Indicator};
+use crate::daemon::State;
+use crate::error::AgStashError;
+use crate::utils;
+
+// Exit codes check reports, so CI can tell the failures apart; 1 stays the code for any other error
+pub const EXIT_MISSING: i32 = 2;
+pub const EXIT_INVALID: i32 = 3;
+pub const EXIT_DRIFTED: i32 = 4;
+
+// HandleCheck verifies for CI that AGENTS.md exists, is valid and matches the project's stash; with no stash, as on a
+// CI runner, a valid file passes. Returns the exit code: 0 when it passes, else EXIT_MISSING, EXIT_INVALID or
+// EXIT_DRIFTED. A pass is reported on stdout and a failure on stderr, without color unless stdout is a terminal. In a
+// GitHub Actions workflow a failure is also printed as an annotation on the file. With --output json the state is
+// printed as JSON instead
+pub fn handle_check() -> Result<i32, Box<dyn std::error::Error>> {
+    if !io::stdout().is_terminal() {
+        set_color(false);
+    }
+    let file = target_file();
+    if file.is_rules_dir() {
+        return Err(AgStashError::Usage(format!("check works on single files, not on {}", file.name)).into());
+    }
+    let (dir, project_name) = project_context()?;
+    let path = dir.join(&file.name);
+    let stash_path = utils::get_stash_path(&file.stash_key(&project_name))?;
+
+    // Annotations name the file relative to the repository, as GitHub shows it
+    let relative = utils::get_project_root()
+        .ok()
+        .and_then(|root| path.strip_prefix(root).ok().map(|relative| relative.display().to_string()))
+        .unwrap_or_else(|| file.name.clone());
+    let content = fs::read(&path).ok();
+    let (state, line) = match &content {
+        None => (State::Missing, None),
+        Some(content) if !file.is_valid(&String::from_utf8_lossy(content)) => (State::Invalid, Some(1)),
+        Some(content) => match fs::read(&stash_path) {
+            Ok(stash_content) if stash_content != *content => {
+                let line = first_difference(&String::from_utf8_lossy(content), &String::from_utf8_lossy(&stash_content));
+                (State::Drift, Some(line))
+            }
+            Ok(_) => (State::InSync, None),
+            Err(_) => (State::Unstashed, None),
+        },
+    };
+    let code = match state {
+        State::Missing => EXIT_MISSING,
+        State::Invalid => EXIT_INVALID,
+        State::Drift => EXIT_DRIFTED,
+        State::InSync | State::Unstashed => 0,
+    };
+
+    if json_output() {
+        print_json(&json!({
+            "file": relative,
+            "project": project_name,
+            "state": state,
+            "line": line,
+            "exit_code": code,
+        }))?;
+        return Ok(code);
+    }
+
+    let message = match state {
+        State::Missing => format!("{} is missing", file.name),
+        State::Invalid => format!("{} is invalid ({})", file.name, file.requirement()),
+        State::Drift => format!("{} differs from the stash for {}", file.name, project_name),
+        State::InSync => format!("{} matches the stash", file.name),
+        State::Unstashed => format!("{} is valid", file.name),
+    };
+    if code == 0 {
+        println!("{}", indicator(Indicator::Ok, &message));
+        return Ok(0);
+    }
+    let marker = if state == State::Drift { Indicator::Drift } else { Indicator::Error };
+    eprintln!("{}", indicator(marker, &message));
+    if in_github_actions() {
+        println!("{}", annotation("error", &relative, line, &message));
+    }
+    Ok(code)
+}
+
+// first_difference returns the 1-based line where content first differs from stash_content
+fn first_difference(content: &str, stash_content: &str) -> usize {
+    let mut stash_lines = stash_content.lines();
+    content.lines().take_while(|line| stash_lines.next() == Some(line)).count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_check() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir(".git").unwrap();
+
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(move || {
+            env::set_current_dir(original_dir).unwrap();
+            commands::set_color(true);
+        });
+
+        assert_eq!(commands::handle_check().unwrap(), super::EXIT_MISSING);
+        fs::write("AGENTS.md", "Use tabs.\n").unwrap();
+        assert_eq!(commands::handle_check().unwrap(), super::EXIT_INVALID);
+
+        // Without a stash a valid file passes
+        fs::write("AGENTS.md", "# AGENTS\n\n- Use tabs\n").unwrap();
+        assert_eq!(commands::handle_check().unwrap(), 0);
+        commands::handle_stash(None).unwrap();
+        assert_eq!(commands::handle_check().unwrap(), 0);
+        fs::write("AGENTS.md", "# AGENTS\n\n- Use spaces\n").unwrap();
+        assert_eq!(commands::handle_check().unwrap(), super::EXIT_DRIFTED);
+        assert_eq!(super::first_difference("# AGENTS\n\n- Use spaces\n", "# AGENTS\n\n- Use tabs\n"), 3);
+    }
+}
@@ -0,0 +1,93 @@
+use super::{announce, color_string, CYAN, GREEN};
+use crate::config::{self, ConfigLayers};
+use crate::error::AgStashError;
+use crate::utils;
+
+// HandleConfigList prints the effective value of every setting, optionally with the layer it comes from
+pub fn handle_config_list(layers: &ConfigLayers, origin: bool) -> Result<(), Box<dyn std::error::Error>> {
+    for (key, value) in layers.effective().entries() {
+        print_setting(layers, key, value.as_deref(), origin);
+    }
+    Ok(())
+}
+
+// HandleConfigGet prints the effective value of one setting, optionally with the layer it comes from
+pub fn handle_config_get(layers: &ConfigLayers, key: &str, origin: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let Some((key, value)) = layers.effective().entries().into_iter().find(|(name, _)| *name == key) else {
+        return Err(AgStashError::Usage(format!("Unknown setting '{}'; run `agstash config list` to see them", key)).into());
+    };
+    print_setting(layers, key, value.as_deref(), origin);
+    Ok(())
+}
+
+// HandleConfigSet stores a setting in the user's configuration file, ~/.agstash/config.toml
+pub fn handle_config_set(key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+    config::set_user_value(key, value)?;
+    let path = config::get_user_config_path()?;
+    utils::log_info(&format!("Set {} in {}", key, path.display()));
+    announce(&format!("{} {} = {} in {}", color_string("Set", GREEN), key, value, path.display()));
+    Ok(())
+}
+
+// print_setting prints key = value, with the layer that sets it when origin is given
+fn print_setting(layers: &ConfigLayers, key: &str, value: Option<&str>, origin: bool) {
+    let line = format!("{} = {}", key, value.unwrap_or("(unset)"));
+    match (origin, layers.origin_of(key)) {
+        (true, Some(source)) => println!("{}  {}", line, color_string(&format!("# {}", source.describe(key)), CYAN)),
+        _ => println!("{}", line),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::config::{self, ConfigLayers, Origin, Settings};
+    use crate::testing::TestEnv;
+
+    #[test]
+    fn test_handle_config_list() {
+        let layers = ConfigLayers {
+            layers: vec![(Origin::Default, Settings::defaults())],
+        };
+        assert!(commands::handle_config_list(&layers, false).is_ok());
+        assert!(commands::handle_config_list(&layers, true).is_ok());
+        assert!(commands::handle_config_get(&layers, "trash", true).is_ok());
+        assert!(commands::handle_config_get(&layers, "colour", false).is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_handle_config_set() {
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(move || {
+        });
+
+        let config_path = config::get_user_config_path().unwrap();
+        fs::create_dir_all(config_path.parent().unwrap()).unwrap();
+        fs::write(&config_path, "# Mine\ntrash = true\n").unwrap();
+
+        // Each value is stored as the type its setting takes, keeping the comments already there
+        commands::handle_config_set("keep_last", "20").unwrap();
+        commands::handle_config_set("strict", "yes").unwrap();
+        commands::handle_config_set("mirrors", "CLAUDE.md, .cursorrules").unwrap();
+        commands::handle_config_set("template", "~/agents-template.md").unwrap();
+        assert_eq!(
+            fs::read_to_string(&config_path).unwrap(),
+            "# Mine\ntrash = true\nkeep_last = 20\nstrict = true\nmirrors = [\"CLAUDE.md\", \".cursorrules\"]\ntemplate = \"~/agents-template.md\"\n"
+        );
+        let settings = config::load_user_config().unwrap();
+        assert_eq!(settings.keep_last, Some(20));
+        assert_eq!(settings.strict, Some(true));
+
+        assert!(commands::handle_config_set("keep_last", "many").is_err());
+        assert!(commands::handle_config_set("colour", "false").is_err());
+    }
+}
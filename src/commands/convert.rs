@@ -0,0 +1,99 @@
+use std::fs;
+
+use super::{color_string, project_context, BOLD, GREEN, YELLOW};
+use crate::adapters;
+use crate::utils;
+
+// HandleConvert rewrites one tool's instruction file in the project root into another tool's format and location, e.g.
+// AGENTS.md into .cursorrules, printing the result instead when to_stdout is set
+pub fn handle_convert(from: &str, to: &str, to_stdout: bool, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let from = adapters::find_adapter(from)?;
+    let to = adapters::find_adapter(to)?;
+    let (root, _) = project_context()?;
+
+    let source_path = root.join(from.path());
+    if !utils::file_exists(&source_path) {
+        println!("{} {}", color_string(from.path(), BOLD), color_string("does not exist in project root.", YELLOW));
+        return Ok(());
+    }
+    let (err, content) = utils::read_file(&source_path);
+    if let Some(error) = err {
+        return Err(error);
+    }
+    let converted = adapters::convert(from.as_ref(), to.as_ref(), &content);
+
+    if to_stdout {
+        print!("{}", converted);
+        return Ok(());
+    }
+
+    let target_path = root.join(to.path());
+    if utils::file_exists(&target_path) && !force {
+        utils::log_warn(&format!("{} already exists, convert aborted", target_path.display()));
+        println!(
+            "{} {}",
+            color_string(&format!("{} already exists.", to.path()), YELLOW),
+            color_string("Use --force to overwrite it.", YELLOW)
+        );
+        return Ok(());
+    }
+    if let Some(parent) = target_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if let Some(error) = utils::write_file(&target_path, &converted) {
+        return Err(error);
+    }
+    utils::log_info(&format!("Converted {} to {}", source_path.display(), target_path.display()));
+    println!(
+        "{} {} to {}",
+        color_string("Converted", GREEN),
+        color_string(from.path(), BOLD),
+        color_string(to.path(), BOLD)
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_convert() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir(".git").unwrap();
+
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(move || {
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        fs::write("AGENTS.md", "# AGENTS\n\n## Style\n\n- Use tabs\n").unwrap();
+        commands::handle_convert("agents", "cursor", false, false).unwrap();
+        let rule = fs::read_to_string(".cursor/rules/agents.mdc").unwrap();
+        assert!(rule.starts_with("---\n"));
+        assert!(rule.ends_with("## Style\n\n- Use tabs\n"));
+
+        // An existing file is only replaced when forced
+        fs::write(".cursorrules", "Old rules.\n").unwrap();
+        commands::handle_convert("agents", "cursorrules", false, false).unwrap();
+        assert_eq!(fs::read_to_string(".cursorrules").unwrap(), "Old rules.\n");
+        commands::handle_convert("agents", "cursorrules", false, true).unwrap();
+        assert_eq!(fs::read_to_string(".cursorrules").unwrap(), "## Style\n\n- Use tabs\n");
+
+        assert!(commands::handle_convert("agents", "windsurf", false, false).is_err());
+    }
+}
@@ -0,0 +1,84 @@
+use super::{color_string, BOLD, GREEN, YELLOW};
+use crate::journal;
+use crate::metadata;
+use crate::store;
+use crate::utils;
+
+// HandleCopy duplicates the stash of src_name so that dst_name starts from the same AGENTS.md
+pub fn handle_copy(src_name: &str, dst_name: &str, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    utils::validate_project_name(src_name)?;
+    utils::validate_project_name(dst_name)?;
+
+    let src_path = utils::get_stash_path(src_name)?;
+    let dst_path = utils::get_stash_path(dst_name)?;
+
+    if !utils::file_exists(&src_path) {
+        utils::log_info(&format!("No stash found for project: {}", src_name));
+        println!("No stash found for project {}", color_string(src_name, BOLD));
+        return Ok(());
+    }
+
+    if utils::file_exists(&dst_path) && !force {
+        utils::log_warn(&format!("A stash already exists for project: {}", dst_name));
+        println!(
+            "{} {}",
+            color_string(&format!("A stash already exists for {}.", dst_name), YELLOW),
+            color_string("Use --force to overwrite it.", YELLOW)
+        );
+        return Ok(());
+    }
+
+    if let Some(error) = store::copy_to_stash(&src_path, &dst_path) {
+        return Err(error);
+    }
+    store::share_stash(&dst_path)?;
+    metadata::copy_metadata(src_name, dst_name)?;
+    journal::record_event(dst_name, "copy", Some(&format!("copied from {}", src_name)))?;
+    utils::log_info(&format!("Copied stash {} to {}", src_path.display(), dst_path.display()));
+    println!(
+        "{} stash {} to {}",
+        color_string("Copied", GREEN),
+        color_string(src_name, BOLD),
+        color_string(dst_name, BOLD)
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::store;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_copy() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+        store::create_store().unwrap();
+
+        let src_path = utils::get_stash_path("source").unwrap();
+        fs::write(&src_path, "# AGENTS\n\n- shared\n").unwrap();
+
+        commands::handle_copy("source", "target", false).unwrap();
+
+        let dst_path = utils::get_stash_path("target").unwrap();
+        assert!(src_path.exists());
+        assert_eq!(fs::read_to_string(&dst_path).unwrap(), "# AGENTS\n\n- shared\n");
+
+        // An existing destination is only replaced with force
+        fs::write(&src_path, "# AGENTS\n\n- updated\n").unwrap();
+        commands::handle_copy("source", "target", false).unwrap();
+        assert_eq!(fs::read_to_string(&dst_path).unwrap(), "# AGENTS\n\n- shared\n");
+
+        commands::handle_copy("source", "target", true).unwrap();
+        assert_eq!(fs::read_to_string(&dst_path).unwrap(), "# AGENTS\n\n- updated\n");
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use super::{color_string, indicator, stash_agents, Indicator, BOLD, GREEN};
+use crate::daemon::{self, ProjectState, State, States};
+use crate::metadata;
+use crate::registry;
+use crate::store;
+use crate::utils;
+
+// Message recorded on stashes the daemon makes
+const AUTO_STASH_MESSAGE: &str = "auto-stashed by the daemon";
+
+// HandleDaemon watches every registered project until stopped: each AGENTS.md edited while it runs is stashed when
+// auto_stash is set, drift is reported as it appears, and status gets its answers from the daemon over a local socket
+pub fn handle_daemon(interval: u64, auto_stash: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let states = States::default();
+    daemon::listen(states.clone())?;
+    println!(
+        "{} {} project(s) every {}s; listening on {}",
+        color_string("Watching", GREEN),
+        registry::load_projects()?.len(),
+        interval,
+        daemon::get_socket_path()?.display()
+    );
+
+    let mut seen = HashMap::new();
+    loop {
+        // The registry is re-read each round so projects stashed for the first time are picked up
+        for root in registry::load_projects()?.into_keys() {
+            let root = Path::new(&root);
+            if !root.is_dir() {
+                continue;
+            }
+            let previous = seen.get(root).cloned();
+            match check_project(root, previous.as_ref().map(|(_, hash)| hash), auto_stash) {
+                Ok((project_state, hash)) => {
+                    report_change(root, previous.as_ref().map(|(state, _)| *state), &project_state);
+                    seen.insert(root.to_path_buf(), (project_state.state, hash));
+                    daemon::record(&states, root, project_state);
+                }
+                Err(error) => utils::log_warn(&format!("Could not check {}: {}", root.display(), error)),
+            }
+        }
+        thread::sleep(Duration::from_secs(interval.max(1)));
+    }
+}
+
+// check_project finds a project's state, stashing its AGENTS.md first when auto_stash is set and the file changed
+// since previous_hash; returns the state with the hash of the AGENTS.md it saw. A file seen for the first time is not
+// a change, so starting the daemon never stashes anything
+fn check_project(
+    root: &Path,
+    previous_hash: Option<&Option<String>>,
+    auto_stash: bool,
+) -> Result<(ProjectState, Option<String>), Box<dyn std::error::Error>> {
+    let stash_key = registry::project_key(root)?;
+    let agents_path = root.join("AGENTS.md");
+    let stash_path = utils::get_stash_path(&stash_key)?;
+    let content = fs::read(&agents_path).ok();
+    let hash = content.as_deref().map(store::content_hash);
+    let valid = content.as_deref().is_some_and(|content| utils::is_valid_agents(&String::from_utf8_lossy(content)));
+
+    let changed = previous_hash.is_some_and(|previous| *previous != hash);
+    if changed && auto_stash && valid && fs::read(&stash_path).ok() != content {
+        stash_agents(&agents_path, &stash_key, Some(AUTO_STASH_MESSAGE))?;
+        utils::log_info(&format!("Auto-stashed {}", agents_path.display()));
+        println!("{} AGENTS.md for {}", color_string("Stashed", GREEN), color_string(&stash_key, BOLD));
+    }
+
+    let state = match &content {
+        None => State::Missing,
+        Some(_) if !valid => State::Invalid,
+        Some(content) => match fs::read(&stash_path) {
+            Err(_) => State::Unstashed,
+            Ok(stash_content) if stash_content == *content => State::InSync,
+            Ok(_) => State::Drift,
+        },
+    };
+    let stashed_at = metadata::load_metadata(&stash_key)
+        .ok()
+        .flatten()
+        .map(|stash_metadata| stash_metadata.created_at);
+    let project_state = ProjectState {
+        stash_key,
+        state,
+        checked_at: utils::format_timestamp(SystemTime::now()),
+        stashed_at,
+    };
+    Ok((project_state, hash))
+}
+
+// report_change prints a project's state when it first appears or changes, so drift shows up as it happens
+fn report_change(root: &Path, previous: Option<State>, project_state: &ProjectState) {
+    if previous == Some(project_state.state) {
+        return;
+    }
+    println!("{} {}", indicator(state_indicator(project_state.state), project_state.state.label()), root.display());
+}
+
+// StateIndicator picks the marker status and the daemon show for a state
+pub fn state_indicator(state: State) -> Indicator {
+    match state {
+        State::InSync => Indicator::Ok,
+        State::Drift => Indicator::Drift,
+        State::Unstashed | State::Missing => Indicator::Warning,
+        State::Invalid => Indicator::Error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::daemon::State;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_check_project() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir(".git").unwrap();
+
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(move || {
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        let root = temp_dir.path();
+        fs::write("AGENTS.md", "# AGENTS\n\n- first\n").unwrap();
+        commands::handle_stash(None).unwrap();
+        let (state, hash) = super::check_project(root, None, true).unwrap();
+        assert_eq!(state.state, State::InSync);
+
+        // An edit made while the daemon runs is stashed on the next round
+        fs::write("AGENTS.md", "# AGENTS\n\n- edited\n").unwrap();
+        let (state, hash) = super::check_project(root, Some(&hash), true).unwrap();
+        assert_eq!(state.state, State::InSync);
+        let stash_path = utils::get_stash_path(&state.stash_key).unwrap();
+        assert_eq!(fs::read_to_string(&stash_path).unwrap(), "# AGENTS\n\n- edited\n");
+
+        // A stash changed elsewhere, e.g. by a synced store, shows up as drift rather than being overwritten
+        fs::write(&stash_path, "# AGENTS\n\n- from another machine\n").unwrap();
+        let (state, _) = super::check_project(root, Some(&hash), true).unwrap();
+        assert_eq!(state.state, State::Drift);
+        assert_eq!(fs::read_to_string(&stash_path).unwrap(), "# AGENTS\n\n- from another machine\n");
+    }
+}
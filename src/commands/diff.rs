@@ -0,0 +1,175 @@
+use serde_json::json;
+use similar::TextDiff;
+
+use super::{color_string, json_output, print_json, project_context, target_file, BOLD, CYAN, GREEN, RED, YELLOW};
+use crate::error::AgStashError;
+use crate::utils;
+
+// HandleDiff prints a unified diff from the stashed AGENTS.md, or the file chosen with --file, to the working copy and
+// reports whether they differ
+pub fn handle_diff() -> Result<bool, Box<dyn std::error::Error>> {
+    let file = target_file();
+    if file.is_rules_dir() {
+        return Err(AgStashError::Usage(format!("diff works on single files, not on {}", file.name)).into());
+    }
+    let (root, project_name) = project_context()?;
+    let project_name = file.stash_key(&project_name);
+
+    let stash_path = utils::get_stash_path(&project_name)?;
+    let agents_path = root.join(&file.name);
+
+    let stash_exists = utils::file_exists(&stash_path);
+    let agents_exists = utils::file_exists(&agents_path);
+
+    if !stash_exists && !agents_exists && !json_output() {
+        utils::log_info(&format!("Neither {} nor a stash exists, nothing to compare", file.name));
+        println!(
+            "{} {}",
+            color_string(&file.name, BOLD),
+            color_string("and its stash do not exist.", YELLOW)
+        );
+        return Ok(false);
+    }
+
+    // A missing side is compared as an empty file
+    let stash_content = if stash_exists {
+        let (err, content) = utils::read_file(&stash_path);
+        if let Some(error) = err {
+            return Err(error);
+        }
+        content
+    } else {
+        String::new()
+    };
+    let agents_content = if agents_exists {
+        let (err, content) = utils::read_file(&agents_path);
+        if let Some(error) = err {
+            return Err(error);
+        }
+        content
+    } else {
+        String::new()
+    };
+
+    let old_header = if stash_exists {
+        format!("stash/{}", project_name)
+    } else {
+        "/dev/null".to_string()
+    };
+    let new_header = if agents_exists { file.name.as_str() } else { "/dev/null" };
+
+    let diff = TextDiff::from_lines(&stash_content, &agents_content);
+    let unified = diff
+        .unified_diff()
+        .context_radius(3)
+        .header(&old_header, new_header)
+        .to_string();
+    let identical = stash_content == agents_content;
+
+    if json_output() {
+        print_json(&json!({
+            "project": project_name,
+            "agents_exists": agents_exists,
+            "stash_exists": stash_exists,
+            "identical": identical,
+            "diff": (!identical).then_some(unified),
+        }))?;
+        return Ok(!identical);
+    }
+
+    if identical {
+        utils::log_info(&format!("{} matches the stash", file.name));
+        println!(
+            "{} is identical to the stash for {}",
+            color_string(&file.name, BOLD),
+            color_string(&project_name, BOLD)
+        );
+        return Ok(false);
+    }
+
+    for line in unified.lines() {
+        println!("{}", colorize_diff_line(line));
+    }
+
+    Ok(true)
+}
+
+// colorize_diff_line applies ANSI colors to a single line of unified diff output
+fn colorize_diff_line(line: &str) -> String {
+    if line.starts_with("---") || line.starts_with("+++") {
+        color_string(line, BOLD)
+    } else if line.starts_with("@@") {
+        color_string(line, CYAN)
+    } else if line.starts_with('+') {
+        color_string(line, GREEN)
+    } else if line.starts_with('-') {
+        color_string(line, RED)
+    } else {
+        line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::managed::ManagedFile;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_diff() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(|| {
+            let _ = env::set_current_dir(&original_dir);
+        });
+
+        // Create a .git directory to establish project root
+        fs::create_dir(".git").unwrap();
+
+        // Set up HOME environment variable to temp directory
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        // Stash an AGENTS.md so both sides exist
+        fs::write("AGENTS.md", "# AGENTS\n\n- one\n").unwrap();
+        commands::handle_stash(None).unwrap();
+
+        // Identical content should not be reported as a difference
+        assert!(!commands::handle_diff().unwrap());
+
+        // Local edits should be reported as a difference
+        fs::write("AGENTS.md", "# AGENTS\n\n- one\n- two\n").unwrap();
+        assert!(commands::handle_diff().unwrap());
+
+        // A missing working copy is compared against an empty file
+        fs::remove_file("AGENTS.md").unwrap();
+        assert!(commands::handle_diff().unwrap());
+
+        // --file compares that file with its own stash
+        commands::set_target_file(ManagedFile::new("CLAUDE.md").unwrap());
+        let _reset_file = defer::defer(|| commands::set_target_file(ManagedFile::agents()));
+        fs::write("CLAUDE.md", "Prefer small commits.\n").unwrap();
+        commands::handle_stash(None).unwrap();
+        assert!(!commands::handle_diff().unwrap());
+        commands::handle_status().unwrap();
+        fs::write("CLAUDE.md", "Prefer large commits.\n").unwrap();
+        assert!(commands::handle_diff().unwrap());
+    }
+
+    #[test]
+    fn test_colorize_diff_line() {
+        assert_eq!(super::colorize_diff_line(" context"), " context");
+        assert!(super::colorize_diff_line("+added").starts_with(super::GREEN));
+        assert!(super::colorize_diff_line("-removed").starts_with(super::RED));
+        assert!(super::colorize_diff_line("@@ -1 +1 @@").starts_with(super::CYAN));
+    }
+}
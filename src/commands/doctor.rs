@@ -0,0 +1,418 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use serde_json::json;
+
+use super::{color_string, indicator, json_output, print_json, Indicator, BOLD};
+use crate::config;
+use crate::journal;
+use crate::managed::ManagedFile;
+use crate::metadata;
+use crate::store;
+use crate::utils;
+
+// Finding is one problem doctor found, with what the user can do about it
+#[derive(Debug, Clone, PartialEq)]
+struct Finding {
+    problem: String,
+    fix: String,
+}
+
+impl Finding {
+    // new builds a finding from its problem and suggested fix
+    fn new(problem: String, fix: String) -> Finding {
+        Finding { problem, fix }
+    }
+}
+
+// HandleDoctor checks the installation for problems and suggests a fix for each, returning whether any were found;
+// roundtrip adds a check that stash then apply reproduces files byte for byte
+pub fn handle_doctor(roundtrip: bool) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut findings = diagnose()?;
+    if roundtrip {
+        check_roundtrip(&mut findings)?;
+    }
+
+    if json_output() {
+        let findings: Vec<_> = findings
+            .iter()
+            .map(|finding| json!({ "problem": finding.problem, "fix": finding.fix }))
+            .collect();
+        print_json(&json!({ "findings": findings }))?;
+        return Ok(!findings.is_empty());
+    }
+
+    if findings.is_empty() {
+        println!("{} no problems found", indicator(Indicator::Ok, "ok"));
+        return Ok(false);
+    }
+
+    for finding in &findings {
+        println!("{}", indicator(Indicator::Error, &finding.problem));
+        println!("  fix: {}", finding.fix);
+    }
+    println!("\n{} problem(s) found", color_string(&findings.len().to_string(), BOLD));
+    Ok(true)
+}
+
+// diagnose runs every check in turn
+fn diagnose() -> Result<Vec<Finding>, Box<dyn std::error::Error>> {
+    let mut findings = Vec::new();
+    check_config(&mut findings);
+
+    let agstash_dir = utils::get_agstash_dir()?;
+    if !agstash_dir.is_dir() {
+        utils::log_info(&format!("No store at {}, nothing else to check", agstash_dir.display()));
+        return Ok(findings);
+    }
+    check_format(&mut findings);
+    check_permissions(&agstash_dir, &mut findings)?;
+    let stashes_dir = utils::get_stashes_dir()?;
+    if stashes_dir.is_dir() {
+        check_permissions(&stashes_dir, &mut findings)?;
+        check_stashes(&mut findings)?;
+        check_sync_conflicts(&stashes_dir, &mut findings)?;
+        check_metadata(&stashes_dir, &mut findings)?;
+    }
+    check_journal(&mut findings)?;
+    Ok(findings)
+}
+
+// check_config reports configuration files that cannot be parsed
+fn check_config(findings: &mut Vec<Finding>) {
+    if let Err(error) = config::load_system_config() {
+        findings.push(Finding::new(
+            error.to_string(),
+            format!("correct or remove {}", config::system_config_path().display()),
+        ));
+    }
+    if let Err(error) = config::ConfigLayers::load() {
+        findings.push(Finding::new(
+            error.to_string(),
+            "correct the file named above, or unset the AGSTASH_* variable it mentions".to_string(),
+        ));
+    }
+}
+
+// check_format reports a store layout this agstash cannot use as it is
+fn check_format(findings: &mut Vec<Finding>) {
+    match store::read_format() {
+        Ok(Some(format)) if format > store::STORE_FORMAT => findings.push(Finding::new(
+            format!("the store uses format {}, newer than the {} this agstash understands", format, store::STORE_FORMAT),
+            "upgrade agstash".to_string(),
+        )),
+        Ok(Some(format)) if format < store::STORE_FORMAT => findings.push(Finding::new(
+            format!("the store uses format {}, older than the current {}", format, store::STORE_FORMAT),
+            "agstash migrate".to_string(),
+        )),
+        Ok(_) => {}
+        Err(error) => findings.push(Finding::new(
+            error.to_string(),
+            format!("agstash migrate after correcting or removing {}", store::get_format_path().map(|path| path.display().to_string()).unwrap_or_default()),
+        )),
+    }
+}
+
+// check_permissions reports a store directory agstash cannot write to, or that other users can
+fn check_permissions(dir: &Path, findings: &mut Vec<Finding>) -> Result<(), Box<dyn std::error::Error>> {
+    let permissions = fs::metadata(dir)?.permissions();
+    if permissions.readonly() {
+        findings.push(Finding::new(
+            format!("{} is not writable", dir.display()),
+            format!("chmod u+w {}", dir.display()),
+        ));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if permissions.mode() & 0o022 != 0 {
+            findings.push(Finding::new(
+                format!("{} is writable by other users", dir.display()),
+                format!("chmod go-w {}", dir.display()),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// check_stashes reports unreadable or invalid stashes, and stashes whose project directory is gone
+fn check_stashes(findings: &mut Vec<Finding>) -> Result<(), Box<dyn std::error::Error>> {
+    for (stash_key, path) in utils::list_stashes(utils::get_stashes_dir()?)? {
+        let (err, content) = utils::read_file(&path);
+        if let Some(error) = err {
+            findings.push(Finding::new(
+                format!("stash {} cannot be read: {}", stash_key, error),
+                format!("inspect {} and remove it with `agstash drop` if it is damaged", path.display()),
+            ));
+            continue;
+        }
+        let file = ManagedFile::for_stash_key(&stash_key);
+        if content.len() >= utils::MAX_STASH_SIZE {
+            findings.push(Finding::new(
+                format!("stash {} is too large: {}", stash_key, utils::format_size(content.len() as u64)),
+                format!("trim it with `agstash edit {}` or remove it with `agstash drop`", stash_key),
+            ));
+        } else if !file.is_valid(&content) {
+            findings.push(Finding::new(
+                format!("stash {} of {} is invalid: {}", stash_key, file.name, file.requirement()),
+                format!("agstash edit {}", stash_key),
+            ));
+        }
+
+        // Slots outlive the file they were taken from on purpose
+        if stash_key.contains('+') {
+            continue;
+        }
+        let source_dir = metadata::load_metadata(&stash_key)
+            .ok()
+            .flatten()
+            .and_then(|stash_metadata| stash_metadata.source_path)
+            .and_then(|source_path| Path::new(&source_path).parent().map(Path::to_path_buf));
+        if let Some(source_dir) = source_dir {
+            if !source_dir.is_dir() {
+                findings.push(Finding::new(
+                    format!("stash {} was taken from {}, which no longer exists", stash_key, source_dir.display()),
+                    format!(
+                        "agstash rename {} <new-name> if the project moved, otherwise agstash archive-project {}",
+                        stash_key,
+                        utils::project_of(&stash_key)
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// check_sync_conflicts reports conflict copies a file sync client left beside stashes
+fn check_sync_conflicts(stashes_dir: &Path, findings: &mut Vec<Finding>) -> Result<(), Box<dyn std::error::Error>> {
+    for (stash_key, path) in utils::list_sync_conflicts(stashes_dir)? {
+        findings.push(Finding::new(
+            format!("stash {} has a conflicting copy from file sync: {}", stash_key, path.display()),
+            "agstash resolve".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// check_metadata reports metadata sidecars that cannot be parsed or have lost their stash
+fn check_metadata(stashes_dir: &Path, findings: &mut Vec<Finding>) -> Result<(), Box<dyn std::error::Error>> {
+    for entry in fs::read_dir(stashes_dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        if !path.with_extension("md").exists() {
+            findings.push(Finding::new(
+                format!("metadata {} has no stash", path.display()),
+                "agstash gc".to_string(),
+            ));
+            continue;
+        }
+        let (err, content) = utils::read_file(&path);
+        let parsed = err.is_none() && serde_json::from_str::<metadata::StashMetadata>(&content).is_ok();
+        if !parsed {
+            findings.push(Finding::new(
+                format!("metadata {} is corrupt", path.display()),
+                format!("rm {} (the stash itself is kept)", path.display()),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// check_journal reports journal lines that cannot be parsed
+fn check_journal(findings: &mut Vec<Finding>) -> Result<(), Box<dyn std::error::Error>> {
+    let journal_path = journal::get_journal_path()?;
+    if !utils::file_exists(&journal_path) {
+        return Ok(());
+    }
+    let (err, content) = utils::read_file(&journal_path);
+    if let Some(error) = err {
+        findings.push(Finding::new(
+            format!("journal {} cannot be read: {}", journal_path.display(), error),
+            format!("move {} aside; a new journal is started automatically", journal_path.display()),
+        ));
+        return Ok(());
+    }
+
+    let malformed: Vec<String> = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && serde_json::from_str::<journal::JournalEntry>(line).is_err())
+        .map(|(index, _)| (index + 1).to_string())
+        .collect();
+    if !malformed.is_empty() {
+        findings.push(Finding::new(
+            format!("journal has malformed line(s): {}", malformed.join(", ")),
+            format!("delete those lines from {}; `agstash log` skips them meanwhile", journal_path.display()),
+        ));
+    }
+    Ok(())
+}
+
+// roundtrip_samples lists the kinds of file stash and apply must reproduce exactly, by description
+fn roundtrip_samples() -> Vec<(&'static str, Vec<u8>)> {
+    let mut large = b"# AGENTS\n\n".to_vec();
+    while large.len() < 4_000_000 {
+        large.extend_from_slice("- A long rule with some non-ASCII text: naïve café ✓\r\n".as_bytes());
+    }
+    vec![
+        ("LF line endings", b"# AGENTS\n\n- Run cargo test\n".to_vec()),
+        ("CRLF line endings", b"# AGENTS\r\n\r\n- Run cargo test\r\n".to_vec()),
+        ("mixed line endings and no final newline", b"# AGENTS\r\n\n- one\r- two".to_vec()),
+        ("a UTF-8 byte order mark", "\u{feff}# AGENTS\n\n- rule\n".as_bytes().to_vec()),
+        ("non-ASCII text", "# AGENTS\n\n- Überprüfe 日本語 ✓ 🚀\n".as_bytes().to_vec()),
+        ("a size of several megabytes", large),
+    ]
+}
+
+// check_roundtrip pushes sample files through the same file operations stash and apply use, in a scratch directory
+// outside the store, and reports any that do not come back byte for byte
+fn check_roundtrip(findings: &mut Vec<Finding>) -> Result<(), Box<dyn std::error::Error>> {
+    let scratch = env::temp_dir().join(format!("agstash-roundtrip-{}", process::id()));
+    let project_dir = scratch.join("project");
+    fs::create_dir_all(&project_dir)?;
+    let agents_path = project_dir.join("AGENTS.md");
+    let stash_path = scratch.join("stash.md");
+
+    let mut checks = Vec::new();
+    for (description, content) in roundtrip_samples() {
+        fs::write(&agents_path, &content)?;
+        checks.push((description, content.clone(), roundtrip(&agents_path, &stash_path, true)));
+    }
+
+    // A symlinked AGENTS.md, e.g. into a shared rules repository, is stashed and applied through the link
+    #[cfg(unix)]
+    {
+        let content = b"# AGENTS\n\n- shared\n".to_vec();
+        let target = scratch.join("shared.md");
+        fs::write(&target, &content)?;
+        fs::remove_file(&agents_path)?;
+        std::os::unix::fs::symlink(&target, &agents_path)?;
+        let result = roundtrip(&agents_path, &stash_path, false).and_then(|applied| {
+            match fs::symlink_metadata(&agents_path)?.file_type().is_symlink() {
+                true => Ok(applied),
+                false => Err("apply replaced the symlink with a regular file".into()),
+            }
+        });
+        checks.push(("a symlinked AGENTS.md", content, result));
+    }
+    fs::remove_dir_all(&scratch)?;
+
+    utils::log_info(&format!("Checked stash and apply round trips for {} kinds of file", checks.len()));
+    for (description, content, result) in checks {
+        match result {
+            Ok(applied) if applied == content => {}
+            Ok(_) => findings.push(Finding::new(
+                format!("stash then apply changes files with {}", description),
+                "report this as a bug, with the output of `agstash doctor --roundtrip`".to_string(),
+            )),
+            Err(error) => findings.push(Finding::new(
+                format!("stash then apply fails for files with {}: {}", description, error),
+                "report this as a bug, with the output of `agstash doctor --roundtrip`".to_string(),
+            )),
+        }
+    }
+    Ok(())
+}
+
+// roundtrip stashes agents_path to stash_path, optionally removes it as clean does, applies the stash back and returns
+// what ends up in agents_path
+fn roundtrip(agents_path: &Path, stash_path: &Path, clean: bool) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let (err, agents_content) = utils::read_file(agents_path);
+    if let Some(error) = err {
+        return Err(error);
+    }
+    if !utils::is_valid_agents(&agents_content) {
+        return Err("stash rejects it as missing the '# AGENTS' header".into());
+    }
+    if let Some(error) = store::copy_to_stash(agents_path, stash_path) {
+        return Err(error);
+    }
+    if clean {
+        utils::remove_file(agents_path)?;
+    }
+
+    let (err, stash_content) = utils::read_file(stash_path);
+    if let Some(error) = err {
+        return Err(error);
+    }
+    if let Some(error) = utils::write_file(agents_path, &stash_content) {
+        return Err(error);
+    }
+    Ok(fs::read(agents_path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::journal;
+    use crate::metadata;
+    use crate::store;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_diagnose() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        // A missing store is healthy
+        assert!(!commands::handle_doctor(false).unwrap());
+
+        let project_dir = temp_dir.path().join("project");
+        fs::create_dir_all(&project_dir).unwrap();
+        fs::create_dir_all(utils::get_stashes_dir().unwrap()).unwrap();
+        store::write_format(store::STORE_FORMAT).unwrap();
+        fs::write(utils::get_stash_path("healthy").unwrap(), "# AGENTS\n").unwrap();
+        let source = Some(project_dir.join("AGENTS.md").display().to_string());
+        metadata::save_metadata("healthy", &metadata::StashMetadata::new(None, source)).unwrap();
+        journal::record_event("healthy", "stash", None).unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for dir in [utils::get_agstash_dir().unwrap(), utils::get_stashes_dir().unwrap()] {
+                fs::set_permissions(dir, fs::Permissions::from_mode(0o700)).unwrap();
+            }
+        }
+        assert_eq!(super::diagnose().unwrap(), vec![]);
+
+        fs::write(utils::get_stash_path("broken").unwrap(), "no header\n").unwrap();
+        fs::write(utils::get_stash_path("huge").unwrap(), "# AGENTS\n".repeat(utils::MAX_STASH_SIZE / 9 + 1)).unwrap();
+        fs::write(utils::get_stash_path("gone").unwrap().with_extension("json"), "{}").unwrap();
+        let moved = Some(temp_dir.path().join("moved").join("AGENTS.md").display().to_string());
+        fs::write(utils::get_stash_path("moved").unwrap(), "# AGENTS\n").unwrap();
+        metadata::save_metadata("moved", &metadata::StashMetadata::new(None, moved)).unwrap();
+        let mut journal_content = fs::read_to_string(journal::get_journal_path().unwrap()).unwrap();
+        journal_content.push_str("not json\n");
+        fs::write(journal::get_journal_path().unwrap(), journal_content).unwrap();
+
+        let problems: Vec<String> = super::diagnose().unwrap().into_iter().map(|finding| finding.problem).collect();
+        assert_eq!(problems.len(), 5, "{:?}", problems);
+        assert!(problems.iter().any(|problem| problem.contains("broken")));
+        assert!(problems.iter().any(|problem| problem.contains("stash huge is too large")));
+        assert!(problems.iter().any(|problem| problem.contains("stash-gone.json")));
+        assert!(problems.iter().any(|problem| problem.contains("moved")));
+        assert!(problems.iter().any(|problem| problem.contains("line(s): 2")));
+        assert!(commands::handle_doctor(false).unwrap());
+    }
+
+    #[test]
+    fn test_check_roundtrip() {
+        let mut findings = Vec::new();
+        super::check_roundtrip(&mut findings).unwrap();
+        assert_eq!(findings, vec![]);
+    }
+}
@@ -0,0 +1,185 @@
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::Command;
+
+use super::{color_string, fail_if_strict, get_user_confirmation, named_stash, project_context, BOLD, GREEN, YELLOW};
+use crate::error::AgStashError;
+use crate::history;
+use crate::journal;
+use crate::metadata;
+use crate::store;
+use crate::utils;
+
+// HandleEdit opens a project's stash in the user's editor and only saves it back when it is still a valid AGENTS.md
+pub fn handle_edit(project: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let project_name = match project {
+        Some(name) => named_stash(name)?,
+        None => project_context()?.1,
+    };
+
+    let stash_path = utils::get_stash_path(&project_name)?;
+    if !utils::file_exists(&stash_path) {
+        fail_if_strict(AgStashError::StashNotFound(format!("No stash found for project {}", project_name)))?;
+        utils::log_info(&format!("No stash found for project: {}", project_name));
+        println!("No stash found for project {}", color_string(&project_name, BOLD));
+        return Ok(());
+    }
+
+    let editor = get_editor();
+    utils::log_info(&format!("Editing stash {} with {}", stash_path.display(), editor));
+
+    let saved = edit_until_valid(&editor, &stash_path, || {
+        print!("Re-open the editor to fix it? [y/N]: ");
+        io::stdout().flush()?; // Ensure the prompt is displayed
+        get_user_confirmation()
+    })?;
+
+    if saved {
+        store::share_stash(&stash_path)?;
+        metadata::refresh_checksum(&project_name)?;
+        history::record_version(&project_name, &stash_path, Some("edited"))?;
+        journal::record_event(&project_name, "edit", None)?;
+        println!(
+            "{} stash for {}",
+            color_string("Saved", GREEN),
+            color_string(&project_name, BOLD)
+        );
+    } else {
+        println!("\nOperation cancelled. The stash was not modified.");
+    }
+
+    Ok(())
+}
+
+// get_editor picks the editor from $VISUAL, then $EDITOR, falling back to vi
+fn get_editor() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+// edit_until_valid edits a scratch copy of the stash, writing it back only once it validates; retry decides whether to re-edit invalid content
+fn edit_until_valid<F>(editor: &str, stash_path: &Path, mut retry: F) -> Result<bool, Box<dyn std::error::Error>>
+where
+    F: FnMut() -> Result<bool, Box<dyn std::error::Error>>,
+{
+    let (err, original) = utils::read_file(stash_path);
+    if let Some(error) = err {
+        return Err(error);
+    }
+
+    // Edit a scratch copy so an invalid save never touches the store
+    let stash_file_name = stash_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("AGENTS.md");
+    let scratch_path = env::temp_dir().join(format!("agstash-edit-{}-{}", std::process::id(), stash_file_name));
+    if let Some(error) = utils::write_file(&scratch_path, &original) {
+        return Err(error);
+    }
+
+    let result = edit_scratch(editor, stash_path, &scratch_path, &original, &mut retry);
+    let _ = fs::remove_file(&scratch_path);
+    result
+}
+
+// edit_scratch runs the editor on the scratch file until its content validates or the user gives up
+fn edit_scratch<F>(
+    editor: &str,
+    stash_path: &Path,
+    scratch_path: &Path,
+    original: &str,
+    retry: &mut F,
+) -> Result<bool, Box<dyn std::error::Error>>
+where
+    F: FnMut() -> Result<bool, Box<dyn std::error::Error>>,
+{
+    loop {
+        run_editor(editor, scratch_path)?;
+
+        let (err, edited) = utils::read_file(scratch_path);
+        if let Some(error) = err {
+            return Err(error);
+        }
+
+        if utils::is_valid_agents(&edited) {
+            if edited != original {
+                if let Some(error) = store::write_stash(stash_path, &edited) {
+                    return Err(error);
+                }
+                utils::log_info("Saved edited stash");
+            } else {
+                utils::log_info("Stash unchanged after editing");
+            }
+            return Ok(true);
+        }
+
+        utils::log_warn("Edited content is invalid, refusing to save");
+        println!(
+            "{}",
+            color_string("Edited content is invalid (missing '# AGENTS' header).", YELLOW)
+        );
+        if !retry()? {
+            return Ok(false);
+        }
+    }
+}
+
+// run_editor launches the editor on path and waits for it to exit
+fn run_editor(editor: &str, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    // Editors are often configured with arguments, e.g. "code --wait"
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().ok_or("No editor configured")?;
+
+    let status = Command::new(program).args(parts).arg(path).status()?;
+    if !status.success() {
+        return Err(format!("Editor '{}' exited with {}", editor, status).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_edit_until_valid() {
+        let temp_dir = TempDir::new().unwrap();
+        let stash_path = temp_dir.path().join("stash-demo.md");
+        fs::write(&stash_path, "# AGENTS\n").unwrap();
+
+        // An editor that leaves the file as-is keeps a valid stash
+        let saved = super::edit_until_valid("true", &stash_path, || Ok(false)).unwrap();
+        assert!(saved);
+        assert_eq!(fs::read_to_string(&stash_path).unwrap(), "# AGENTS\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_edit_until_valid_rejects_invalid_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let stash_path = temp_dir.path().join("stash-invalid.md");
+        fs::write(&stash_path, "# AGENTS\n").unwrap();
+
+        // An editor that strips the header produces content that must not be saved
+        let editor_script = temp_dir.path().join("editor.sh");
+        fs::write(&editor_script, "#!/bin/sh\necho 'no header' > \"$1\"\n").unwrap();
+        let editor = format!("sh {}", editor_script.display());
+
+        let mut attempts = 0;
+        let saved = super::edit_until_valid(&editor, &stash_path, || {
+            attempts += 1;
+            Ok(attempts < 2)
+        })
+        .unwrap();
+
+        assert!(!saved);
+        assert_eq!(attempts, 2);
+        assert_eq!(fs::read_to_string(&stash_path).unwrap(), "# AGENTS\n");
+    }
+}
@@ -0,0 +1,161 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{color_string, BOLD, GREEN, YELLOW};
+use crate::history::{self, Retention};
+use crate::store;
+use crate::utils;
+
+// Default number of days history versions are kept for
+pub const DEFAULT_RETENTION_DAYS: u64 = 90;
+
+// HandleGc prunes history versions outside the retention policy and removes orphaned files and empty directories
+pub fn handle_gc(retention: &Retention, dry_run: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let agstash_dir = utils::get_agstash_dir()?;
+    utils::log_info(&format!("Collecting history outside retention: {:?}", retention));
+
+    let mut doomed = Vec::new();
+
+    // Expired history versions; the newest one of each stash is always kept
+    for stash_key in history::list_histories()? {
+        let expired = history::expired_versions(&stash_key, retention)?;
+        doomed.extend(history::version_files(&stash_key, &expired)?);
+    }
+
+    // Metadata sidecars whose stash is gone
+    let stashes_dir = utils::get_stashes_dir()?;
+    if stashes_dir.is_dir() {
+        for entry in fs::read_dir(&stashes_dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) == Some("json") && !path.with_extension("md").exists() {
+                doomed.push(path);
+            }
+        }
+    }
+
+    // Stashes written before content addressing get linked to their objects, then unreferenced objects go
+    if !dry_run {
+        for (_, path) in utils::list_stashes(&stashes_dir)? {
+            store::share_stash(&path)?;
+        }
+    }
+    doomed.extend(store::orphaned_objects()?);
+
+    doomed.retain(|path| path.is_file());
+    let reclaimed: u64 = doomed.iter().filter_map(|path| fs::metadata(path).ok()).map(|m| m.len()).sum();
+
+    if !dry_run {
+        for path in &doomed {
+            utils::remove_file(path)?;
+        }
+    }
+
+    // Directories left empty, including ones emptied just now
+    let mut empty_dirs = Vec::new();
+    for area in ["history", "undo"] {
+        let area_dir = agstash_dir.join(area);
+        if !area_dir.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&area_dir)? {
+            let dir = entry?.path();
+            if dir.is_dir() && is_effectively_empty(&dir, &doomed)? {
+                empty_dirs.push(dir);
+            }
+        }
+    }
+    if !dry_run {
+        for dir in &empty_dirs {
+            fs::remove_dir(dir)?;
+        }
+    }
+
+    let verb = if dry_run { "Would remove" } else { "Removed" };
+    for path in doomed.iter().chain(empty_dirs.iter()) {
+        utils::log_info(&format!("{} {}", verb, path.display()));
+    }
+    if doomed.is_empty() && empty_dirs.is_empty() {
+        println!("{}", color_string("Nothing to collect.", YELLOW));
+        return Ok(());
+    }
+    println!(
+        "{} {} file(s) and {} empty director(ies), {} {}",
+        color_string(verb, GREEN),
+        doomed.len(),
+        empty_dirs.len(),
+        if dry_run { "reclaiming" } else { "reclaimed" },
+        color_string(&utils::format_size(reclaimed), BOLD)
+    );
+
+    Ok(())
+}
+
+// is_effectively_empty checks whether dir holds nothing besides files that are about to be removed
+fn is_effectively_empty(dir: &Path, removed: &[PathBuf]) -> Result<bool, Box<dyn std::error::Error>> {
+    for entry in fs::read_dir(dir)? {
+        if !removed.contains(&entry?.path()) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::history::{self, Retention};
+    use crate::store;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_gc() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+        store::create_store().unwrap();
+
+        let retention = Retention {
+            keep_last: None,
+            keep_days: Some(90),
+        };
+
+        // Nothing to collect in an empty store
+        commands::handle_gc(&retention, false).unwrap();
+
+        let source = temp_dir.path().join("AGENTS.md");
+        for rule in ["one", "two", "three"] {
+            fs::write(&source, format!("# AGENTS\n\n- {}\n", rule)).unwrap();
+            history::record_version("demo", &source, None).unwrap();
+        }
+
+        // Age the first two versions past the retention window
+        let history_dir = history::get_history_dir("demo").unwrap();
+        for id in [1, 2] {
+            let record = history_dir.join(format!("{}.json", id));
+            let mut version: history::Version = serde_json::from_str(&fs::read_to_string(&record).unwrap()).unwrap();
+            version.created_at = "2000-01-01T00:00:00Z".to_string();
+            fs::write(&record, serde_json::to_string(&version).unwrap()).unwrap();
+        }
+
+        let orphan = utils::get_stash_path("gone").unwrap().with_extension("json");
+        fs::write(&orphan, "{}").unwrap();
+        fs::create_dir_all(utils::get_agstash_dir().unwrap().join("undo").join("empty")).unwrap();
+
+        // A dry run changes nothing
+        commands::handle_gc(&retention, true).unwrap();
+        assert_eq!(history::list_versions("demo").unwrap().len(), 3);
+        assert!(orphan.exists());
+
+        commands::handle_gc(&retention, false).unwrap();
+        let ids: Vec<u64> = history::list_versions("demo").unwrap().iter().map(|v| v.id).collect();
+        assert_eq!(ids, vec![3]);
+        assert!(!orphan.exists());
+        assert!(!utils::get_agstash_dir().unwrap().join("undo").join("empty").exists());
+    }
+}
@@ -0,0 +1,177 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use super::{announce, color_string, stash_agents, BOLD, GREEN, RED, YELLOW};
+use crate::git;
+use crate::registry;
+use crate::utils;
+
+// Git hooks install-hooks writes
+const HOOKS: [&str; 2] = ["pre-commit", "post-checkout"];
+
+// Line that marks a hook as written by agstash
+const HOOK_MARKER: &str = "# Installed by agstash install-hooks";
+
+// Suffix a hook that was already there is kept under; the agstash hook runs it first
+const CHAINED_SUFFIX: &str = ".pre-agstash";
+
+// HandleInstallHooks writes pre-commit and post-checkout hooks into the repository that warn when AGENTS.md drifted
+// from its stash, or with auto_stash stash it on every commit. Hooks that were already there are kept and run first.
+// With uninstall the agstash hooks are removed and the earlier hooks put back
+pub fn handle_install_hooks(uninstall: bool, auto_stash: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let root = utils::get_project_root()?;
+    let hooks_dir = git::hooks_dir(&root).ok_or("Git hooks need a git repository")?;
+    fs::create_dir_all(&hooks_dir)?;
+
+    for hook in HOOKS {
+        let path = hooks_dir.join(hook);
+        let chained = hooks_dir.join(format!("{}{}", hook, CHAINED_SUFFIX));
+        let installed = is_agstash_hook(&path);
+        if uninstall {
+            if !installed {
+                if path.exists() {
+                    println!("{} {}", color_string(hook, BOLD), color_string("was not installed by agstash, left as is.", YELLOW));
+                }
+                continue;
+            }
+            fs::remove_file(&path)?;
+            if chained.exists() {
+                fs::rename(&chained, &path)?;
+            }
+            utils::log_info(&format!("Removed hook {}", path.display()));
+            announce(&format!("{} {} hook", color_string("Removed", RED), hook));
+            continue;
+        }
+
+        // A hook of the repository's own keeps running, ahead of the agstash one
+        if path.exists() && !installed {
+            if chained.exists() {
+                return Err(format!("Both {} and {} exist; remove one before installing", path.display(), chained.display()).into());
+            }
+            fs::rename(&path, &chained)?;
+            println!("Kept the existing {} hook; it runs before the agstash one", color_string(hook, BOLD));
+        }
+        fs::write(&path, hook_script(hook, auto_stash)?)?;
+        make_executable(&path)?;
+        utils::log_info(&format!("Wrote hook {}", path.display()));
+        announce(&format!("{} {} hook", color_string("Installed", GREEN), hook));
+    }
+    Ok(())
+}
+
+// HandleGitHook is what the installed hooks run. It never fails the git command: problems are reported and git
+// carries on. pre-commit stashes AGENTS.md when auto_stash is set and it changed, and otherwise warns about drift
+pub fn handle_git_hook(hook: &str, auto_stash: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let root = utils::get_project_root()?;
+    let stash_key = registry::project_key(&root)?;
+    let agents_path = root.join("AGENTS.md");
+    let stash_path = utils::get_stash_path(&stash_key)?;
+    let content = fs::read(&agents_path).ok();
+    let stash_content = fs::read(&stash_path).ok();
+
+    match (content, stash_content) {
+        (Some(content), stash_content) if Some(&content) != stash_content.as_ref() => {
+            let valid = utils::is_valid_agents(&String::from_utf8_lossy(&content));
+            if hook == "pre-commit" && auto_stash && valid {
+                stash_agents(&agents_path, &stash_key, Some("auto-stashed on commit"))?;
+                registry::register_project(&root)?;
+                println!("agstash: stashed AGENTS.md for {}", stash_key);
+            } else if stash_content.is_some() {
+                println!("agstash: AGENTS.md differs from its stash; run `agstash diff` to review or `agstash stash` to keep it");
+            }
+        }
+        (None, Some(_)) if hook == "post-checkout" => {
+            println!("agstash: this checkout has no AGENTS.md; run `agstash apply` to restore it from the stash");
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// hook_script returns the shell script installed as hook, calling this binary by its full path
+fn hook_script(hook: &str, auto_stash: bool) -> Result<String, Box<dyn std::error::Error>> {
+    let exe = utils::shell_path(&env::current_exe()?).replace('\'', r"'\''");
+    let flags = if auto_stash { " --auto-stash" } else { "" };
+    Ok(format!(
+        "#!/bin/sh\n{marker}; `agstash install-hooks --uninstall` removes it\n\
+         if [ -x \"$0{chained}\" ]; then\n  \"$0{chained}\" \"$@\" || exit $?\nfi\n\
+         '{exe}' git-hook {hook}{flags} 2>/dev/null || true\n",
+        marker = HOOK_MARKER,
+        chained = CHAINED_SUFFIX,
+    ))
+}
+
+// is_agstash_hook reports whether the hook at path was written by install-hooks
+fn is_agstash_hook(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|content| content.contains(HOOK_MARKER))
+}
+
+// make_executable sets the execute bits git requires of a hook
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+// make_executable has nothing to do where git doesn't check execute bits
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_install_hooks() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir_all(".git/hooks").unwrap();
+
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(move || {
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        fs::write(".git/hooks/pre-commit", "#!/bin/sh\ncargo fmt --check\n").unwrap();
+        commands::handle_install_hooks(false, true).unwrap();
+        let pre_commit = fs::read_to_string(".git/hooks/pre-commit").unwrap();
+        assert!(pre_commit.contains("git-hook pre-commit --auto-stash"));
+        assert_eq!(fs::read_to_string(".git/hooks/pre-commit.pre-agstash").unwrap(), "#!/bin/sh\ncargo fmt --check\n");
+        assert!(fs::read_to_string(".git/hooks/post-checkout").unwrap().contains("git-hook post-checkout"));
+
+        // Reinstalling replaces the agstash hooks without chaining them to themselves
+        commands::handle_install_hooks(false, false).unwrap();
+        assert!(!fs::read_to_string(".git/hooks/pre-commit").unwrap().contains("--auto-stash"));
+
+        // The commit hook stashes a changed AGENTS.md only when asked to
+        fs::write("AGENTS.md", "# AGENTS\n\n- hooked\n").unwrap();
+        commands::handle_git_hook("pre-commit", false).unwrap();
+        let stash_path = utils::get_stash_path(&utils::project_stash_key(temp_dir.path()).unwrap()).unwrap();
+        assert!(!stash_path.exists());
+        commands::handle_git_hook("pre-commit", true).unwrap();
+        assert_eq!(fs::read_to_string(&stash_path).unwrap(), "# AGENTS\n\n- hooked\n");
+
+        commands::handle_install_hooks(true, false).unwrap();
+        assert_eq!(fs::read_to_string(".git/hooks/pre-commit").unwrap(), "#!/bin/sh\ncargo fmt --check\n");
+        assert!(!temp_dir.path().join(".git/hooks/pre-commit.pre-agstash").exists());
+        assert!(!temp_dir.path().join(".git/hooks/post-checkout").exists());
+    }
+}
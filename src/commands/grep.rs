@@ -0,0 +1,133 @@
+use regex::{Regex, RegexBuilder};
+
+use super::{color_string, BOLD, CYAN, RED};
+use crate::utils;
+
+// GrepMatch is a single matching line found in a stash
+#[derive(Debug, PartialEq)]
+struct GrepMatch {
+    project: String,
+    line_number: usize,
+    line: String,
+}
+
+// HandleGrep searches every stash for pattern and prints the matching lines, reporting whether anything matched
+pub fn handle_grep(pattern: &str, regex: bool, ignore_case: bool) -> Result<bool, Box<dyn std::error::Error>> {
+    let matcher = build_matcher(pattern, regex, ignore_case)?;
+
+    let stashes_dir = utils::get_stashes_dir()?;
+    utils::log_info(&format!("Searching stashes in: {}", stashes_dir.display()));
+
+    let mut found = false;
+    for (project, path) in utils::list_stashes(&stashes_dir)? {
+        let (err, content) = utils::read_file(&path);
+        if let Some(error) = err {
+            utils::log_warn(&format!("Skipping unreadable stash {}: {}", path.display(), error));
+            continue;
+        }
+
+        for grep_match in search_content(&matcher, &project, &content) {
+            found = true;
+            println!(
+                "{}:{}: {}",
+                color_string(&grep_match.project, BOLD),
+                color_string(&grep_match.line_number.to_string(), CYAN),
+                highlight_matches(&matcher, &grep_match.line)
+            );
+        }
+    }
+
+    if !found {
+        utils::log_info("No stash matched the pattern");
+    }
+
+    Ok(found)
+}
+
+// build_matcher compiles the search pattern, escaping it unless regex syntax was requested
+fn build_matcher(pattern: &str, regex: bool, ignore_case: bool) -> Result<Regex, Box<dyn std::error::Error>> {
+    let source = if regex {
+        pattern.to_string()
+    } else {
+        regex::escape(pattern)
+    };
+
+    RegexBuilder::new(&source)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|e| format!("Invalid pattern '{}': {}", pattern, e).into())
+}
+
+// search_content returns every line of content that matches
+fn search_content(matcher: &Regex, project: &str, content: &str) -> Vec<GrepMatch> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| matcher.is_match(line))
+        .map(|(index, line)| GrepMatch {
+            project: project.to_string(),
+            line_number: index + 1,
+            line: line.to_string(),
+        })
+        .collect()
+}
+
+// highlight_matches colors every match within a line
+fn highlight_matches(matcher: &Regex, line: &str) -> String {
+    matcher
+        .replace_all(line, |caps: &regex::Captures| {
+            color_string(&caps[0], &format!("{}{}", RED, BOLD))
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::store;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    fn test_search_content() {
+        let content = "# AGENTS\n\n- Always rebase\n- never force push\n";
+
+        let matcher = super::build_matcher("rebase", false, false).unwrap();
+        let matches = super::search_content(&matcher, "demo", content);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line_number, 3);
+        assert_eq!(matches[0].line, "- Always rebase");
+
+        // Literal patterns do not interpret regex syntax
+        let matcher = super::build_matcher("- .*", false, false).unwrap();
+        assert!(super::search_content(&matcher, "demo", content).is_empty());
+
+        let matcher = super::build_matcher("^- (always|never)", true, true).unwrap();
+        assert_eq!(super::search_content(&matcher, "demo", content).len(), 2);
+    }
+
+    #[test]
+    fn test_build_matcher_invalid_regex() {
+        assert!(super::build_matcher("(unclosed", true, false).is_err());
+        assert!(super::build_matcher("(unclosed", false, false).is_ok());
+    }
+
+    #[test]
+    #[serial]
+    fn test_handle_grep() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+        store::create_store().unwrap();
+
+        fs::write(utils::get_stash_path("alpha").unwrap(), "# AGENTS\n\n- Use tabs\n").unwrap();
+        fs::write(utils::get_stash_path("beta").unwrap(), "# AGENTS\n\n- Use spaces\n").unwrap();
+
+        assert!(commands::handle_grep("TABS", false, true).unwrap());
+        assert!(!commands::handle_grep("TABS", false, false).unwrap());
+    }
+}
@@ -0,0 +1,103 @@
+use serde_json::json;
+
+use super::{color_string, json_output, named_stash, print_json, project_context, BOLD, CYAN, YELLOW};
+use crate::history;
+use crate::utils;
+
+// HandleHistory lists every recorded version of a project's stash, newest first
+pub fn handle_history(project: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let project_name = match project {
+        Some(name) => named_stash(name)?,
+        None => project_context()?.1,
+    };
+
+    let versions = history::list_versions(&project_name)?;
+    if json_output() {
+        return print_json(&json!({ "project": project_name, "versions": versions }));
+    }
+    if versions.is_empty() {
+        utils::log_info(&format!("No history for project: {}", project_name));
+        println!("{} {}", color_string("No history recorded for", YELLOW), color_string(&project_name, BOLD));
+        return Ok(());
+    }
+
+    // The copies of the project's file taken before apply are listed apart, since they were never the stash's content
+    let (stash_versions, snapshots): (Vec<_>, Vec<_>) = versions.iter().partition(|version| version.is_stash());
+    println!("History of {}", color_string(&project_name, BOLD));
+    print_versions(&stash_versions);
+    if !snapshots.is_empty() {
+        println!("\nSnapshots taken before apply");
+        print_versions(&snapshots);
+    }
+
+    Ok(())
+}
+
+// print_versions prints one line per version, newest first
+fn print_versions(versions: &[&history::Version]) {
+    for version in versions.iter().rev() {
+        let line = format!("{:>4}  {}", version.id, color_string(&version.created_at, CYAN));
+        match &version.message {
+            Some(message) => println!("{}  {}", line, message),
+            None => println!("{}", line),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_history_and_apply_version() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(|| {
+            let _ = env::set_current_dir(&original_dir);
+        });
+
+        // Create a .git directory to establish project root
+        fs::create_dir(".git").unwrap();
+
+        // Set up HOME environment variable to temp directory
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        // No history before anything is stashed
+        assert!(commands::handle_history(None).is_ok());
+
+        fs::write("AGENTS.md", "# AGENTS\n\n- first\n").unwrap();
+        commands::handle_stash(Some("first")).unwrap();
+        fs::write("AGENTS.md", "# AGENTS\n\n- second\n").unwrap();
+        commands::handle_stash(Some("second")).unwrap();
+        assert!(commands::handle_history(None).is_ok());
+
+        // Older versions can be applied even after the stash moved on
+        commands::handle_apply(&commands::ApplyOptions {
+            force: true,
+            version: Some("1".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- first\n");
+
+        // Unknown versions leave AGENTS.md alone
+        commands::handle_apply(&commands::ApplyOptions {
+            force: true,
+            version: Some("9".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- first\n");
+    }
+}
@@ -0,0 +1,135 @@
+use std::env;
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::{apply_stash_to, color_string, get_user_confirmation, root_context, target_file, ApplyOptions, BOLD};
+use crate::error::AgStashError;
+use crate::registry;
+use crate::utils;
+
+// Each snippet runs `agstash hook-apply` when the shell changes directory, passing the directory it came from.
+// Its INFO lines go to /dev/null so only the offer itself reaches the terminal
+const BASH_HOOK: &str = r#"_agstash_hook() {
+  if [ "$PWD" != "${_AGSTASH_LAST_DIR-}" ]; then
+    {agstash} hook-apply --from "${_AGSTASH_LAST_DIR-}" 2>/dev/null
+    _AGSTASH_LAST_DIR="$PWD"
+  fi
+}
+if [[ ";${PROMPT_COMMAND:-};" != *";_agstash_hook;"* ]]; then
+  PROMPT_COMMAND="_agstash_hook${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+fi
+"#;
+
+const ZSH_HOOK: &str = r#"_agstash_hook() {
+  {agstash} hook-apply --from "$OLDPWD" 2>/dev/null
+}
+typeset -ag chpwd_functions
+if (( ! ${chpwd_functions[(I)_agstash_hook]} )); then
+  chpwd_functions+=(_agstash_hook)
+fi
+"#;
+
+const FISH_HOOK: &str = r#"function _agstash_hook --on-variable PWD
+    {agstash} hook-apply --from "$_agstash_last_dir" 2>/dev/null
+    set -g _agstash_last_dir $PWD
+end
+set -g _agstash_last_dir $PWD
+"#;
+
+// HandleHook prints the snippet that hooks agstash into a shell's directory changes, meant to be evaluated from the
+// shell's startup file, e.g. eval "$(agstash hook bash)"
+pub fn handle_hook(shell: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let snippet = match shell {
+        "bash" => BASH_HOOK,
+        "zsh" => ZSH_HOOK,
+        "fish" => FISH_HOOK,
+        _ => return Err(AgStashError::Usage(format!("Unsupported shell: {} (expected bash, zsh or fish)", shell)).into()),
+    };
+    // The snippet calls this binary by its full path so it works before PATH is set up
+    let exe = env::current_exe()?;
+    print!("{}", snippet.replace("{agstash}", &shell_quote(&utils::shell_path(&exe))));
+    Ok(())
+}
+
+// HandleHookApply is what the shell hook runs after each directory change. On entering a registered project from
+// outside it, when the project's AGENTS.md is missing but its stash exists, it offers to apply the stash; anywhere
+// else it prints nothing
+pub fn handle_hook_apply(from: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    let Ok(root) = utils::get_project_root() else {
+        return Ok(());
+    };
+    // Moving between directories of the same project only offers once, on the way in
+    let from = from.map(|dir| dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf()));
+    if from.is_some_and(|dir| dir.starts_with(&root)) || !registry::is_registered(&root)? {
+        return Ok(());
+    }
+
+    let file = target_file();
+    let (root, project_name) = root_context()?;
+    let stash_key = file.stash_key(&project_name);
+    if file.is_rules_dir() || root.join(&file.name).exists() || !utils::file_exists(utils::get_stash_path(&stash_key)?) {
+        return Ok(());
+    }
+
+    print!(
+        "agstash: {} has a stash but no {}. Apply it? [y/N]: ",
+        color_string(&stash_key, BOLD),
+        file.name
+    );
+    io::stdout().flush()?; // Ensure the prompt is displayed
+    if get_user_confirmation()? {
+        apply_stash_to(&root, &project_name, &file, &ApplyOptions::default())?;
+    }
+    Ok(())
+}
+
+// shell_quote wraps value in single quotes for bash, zsh and fish alike
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_hook_apply_stays_quiet() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir(".git").unwrap();
+
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(move || {
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        // An unregistered project is left alone
+        let outside = temp_home.path();
+        commands::handle_hook_apply(Some(outside)).unwrap();
+        assert!(!Path::new("AGENTS.md").exists());
+
+        // Once stashed, moving around inside the project doesn't offer the stash again
+        fs::write("AGENTS.md", "# AGENTS\n\n- hooked\n").unwrap();
+        commands::handle_stash(None).unwrap();
+        fs::remove_file("AGENTS.md").unwrap();
+        fs::create_dir("src").unwrap();
+        commands::handle_hook_apply(Some(&temp_dir.path().join("src"))).unwrap();
+        assert!(!Path::new("AGENTS.md").exists());
+
+        assert!(commands::handle_hook("tcsh").is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use std::fs;
+use std::path::Path;
+
+use super::{announce, color_string, target_file, GREEN};
+use crate::config;
+use crate::git;
+use crate::utils;
+
+// Comment written above the entries agstash adds
+const IGNORE_HEADER: &str = "# Agent files managed by agstash";
+
+// IgnoreAgentFiles adds the files init and apply write, the whole bundle when bundle is set, to the project's
+// .gitignore, or with local to .git/info/exclude so the shared .gitignore is untouched; entries already listed are
+// left as they are
+pub fn ignore_agent_files(bundle: bool, local: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let root = utils::get_project_root()?;
+    let names = if bundle {
+        config::load_settings()?.bundle.unwrap_or_default()
+    } else {
+        let file = target_file();
+        // A directory entry needs its trailing slash so it only matches the directory
+        vec![if file.is_rules_dir() { format!("{}/", file.name) } else { file.name }]
+    };
+    let (ignore_path, label) = if local {
+        (git::exclude_path(&root).ok_or("--exclude-local needs a git repository")?, ".git/info/exclude")
+    } else {
+        (root.join(".gitignore"), ".gitignore")
+    };
+    if let Some(parent) = ignore_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let added = add_entries(&ignore_path, &names)?;
+    if !added.is_empty() {
+        utils::log_info(&format!("Added {} to {}", added.join(", "), ignore_path.display()));
+        announce(&format!("{} {} to {}", color_string("Added", GREEN), added.join(", "), label));
+    }
+    Ok(())
+}
+
+// add_entries appends each of names that path doesn't list yet, returning the ones it added
+fn add_entries(path: &Path, names: &[String]) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let existing = fs::read_to_string(path).unwrap_or_default();
+    let listed: Vec<&str> = existing.lines().map(|line| line.trim().trim_start_matches('/')).collect();
+    let added: Vec<String> = names.iter().filter(|name| !listed.contains(&name.as_str())).cloned().collect();
+    if added.is_empty() {
+        return Ok(added);
+    }
+
+    let mut content = existing.clone();
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    if !existing.contains(IGNORE_HEADER) {
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        content.push_str(IGNORE_HEADER);
+        content.push('\n');
+    }
+    for name in &added {
+        content.push_str(name);
+        content.push('\n');
+    }
+    if let Some(error) = utils::write_file(path, &content) {
+        return Err(error);
+    }
+    Ok(added)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    #[test]
+    fn test_add_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let gitignore_path = temp_dir.path().join(".gitignore");
+        fs::write(&gitignore_path, "target\n/CLAUDE.md").unwrap();
+
+        let names = vec!["AGENTS.md".to_string(), "CLAUDE.md".to_string()];
+        assert_eq!(super::add_entries(&gitignore_path, &names).unwrap(), vec!["AGENTS.md".to_string()]);
+        assert!(super::add_entries(&gitignore_path, &names).unwrap().is_empty());
+        assert_eq!(
+            fs::read_to_string(&gitignore_path).unwrap(),
+            "target\n/CLAUDE.md\n\n# Agent files managed by agstash\nAGENTS.md\n"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_ignore_locally() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir(".git").unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(move || {
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        super::ignore_agent_files(false, true).unwrap();
+        assert_eq!(fs::read_to_string(".git/info/exclude").unwrap(), "# Agent files managed by agstash\nAGENTS.md\n");
+        assert!(!temp_dir.path().join(".gitignore").exists());
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use super::{color_string, BOLD, GREEN, YELLOW};
+use crate::archive::{self, ImportEntry};
+use crate::error::AgStashError;
+use crate::history;
+use crate::journal;
+use crate::managed::ManagedFile;
+use crate::metadata::{self, StashMetadata};
+use crate::store;
+use crate::utils;
+
+// ImportConflict is what import does with a stash whose key is already taken
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportConflict {
+    // Import nothing and name the stashes in the way
+    Refuse,
+    Overwrite,
+    Skip,
+    // Import under a free key such as "api-imported"
+    Rename,
+}
+
+// HandleImport brings the stashes in an archive written by export, or in a directory of markdown files, into the store
+pub fn handle_import(path: &Path, conflict: ImportConflict) -> Result<(), Box<dyn std::error::Error>> {
+    if !path.exists() {
+        return Err(AgStashError::FileNotFound(format!("{} does not exist", path.display())).into());
+    }
+    let (format, entries) = archive::read_import(path)?;
+    if let Some(format) = format.filter(|format| *format > store::STORE_FORMAT) {
+        return Err(AgStashError::UnsupportedStoreFormat(format!(
+            "{} was exported by a newer agstash (store format {}, this one understands up to {}); upgrade agstash first",
+            path.display(),
+            format,
+            store::STORE_FORMAT
+        ))
+        .into());
+    }
+
+    // Validate everything before writing anything
+    let mut valid = Vec::new();
+    for entry in entries {
+        match validate(&entry) {
+            Ok(content) => valid.push((entry, content)),
+            Err(problem) => println!("{} {} ({})", color_string("Skipped", YELLOW), entry.source, problem),
+        }
+    }
+    if valid.is_empty() {
+        println!("No valid stashes found in {}", path.display());
+        return Ok(());
+    }
+
+    let mut seen = HashSet::new();
+    let mut taken = Vec::new();
+    for (entry, _) in &valid {
+        if !seen.insert(entry.stash_key.clone()) || utils::file_exists(utils::get_stash_path(&entry.stash_key)?) {
+            taken.push(entry.stash_key.clone());
+        }
+    }
+    if conflict == ImportConflict::Refuse && !taken.is_empty() {
+        return Err(format!(
+            "Already in the store: {}; choose --overwrite, --skip or --rename",
+            taken.join(", ")
+        )
+        .into());
+    }
+
+    let mut imported = 0;
+    for (entry, content) in valid {
+        let exists = utils::file_exists(utils::get_stash_path(&entry.stash_key)?);
+        let stash_key = match (exists, conflict) {
+            (false, _) | (true, ImportConflict::Overwrite) | (true, ImportConflict::Refuse) => entry.stash_key.clone(),
+            (true, ImportConflict::Skip) => {
+                println!("{} {} (already in the store)", color_string("Skipped", YELLOW), entry.source);
+                continue;
+            }
+            (true, ImportConflict::Rename) => free_key(&entry.stash_key)?,
+        };
+        import_entry(path, &entry, &stash_key, &content)?;
+        imported += 1;
+        println!(
+            "{} {} as {}",
+            color_string("Imported", GREEN),
+            entry.source,
+            color_string(&stash_key, BOLD)
+        );
+    }
+    println!("\n{} stash(es) imported from {}", imported, path.display());
+    Ok(())
+}
+
+// validate checks that an entry can become a stash, returning its text
+fn validate(entry: &ImportEntry) -> Result<String, String> {
+    utils::validate_project_name(&entry.stash_key).map_err(|e| e.to_string())?;
+    let content = String::from_utf8(entry.content.clone()).map_err(|_| "not UTF-8 text".to_string())?;
+    let file = ManagedFile::for_stash_key(&entry.stash_key);
+    if !file.is_valid(&content) {
+        return Err(format!("not a valid {}: {}", file.name, file.requirement()));
+    }
+    if entry.sha256.as_ref().is_some_and(|sha256| *sha256 != store::content_hash(content.as_bytes())) {
+        return Err("content does not match its sha256".to_string());
+    }
+    Ok(content)
+}
+
+// free_key finds an unused key for an imported stash, e.g. "api-imported" or "api-imported-2"
+fn free_key(stash_key: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let mut candidate = format!("{}-imported", stash_key);
+    let mut counter = 2;
+    while utils::file_exists(utils::get_stash_path(&candidate)?) {
+        candidate = format!("{}-imported-{}", stash_key, counter);
+        counter += 1;
+    }
+    Ok(candidate)
+}
+
+// import_entry writes one stash under stash_key with its metadata, and its history when the key has none yet
+fn import_entry(source: &Path, entry: &ImportEntry, stash_key: &str, content: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let history_dir = history::get_history_dir(stash_key)?;
+    if !entry.history.is_empty() && !history_dir.exists() {
+        fs::create_dir_all(&history_dir)?;
+        for (file_name, history_content) in &entry.history {
+            fs::write(history_dir.join(file_name), history_content)?;
+        }
+    }
+
+    let stash_path = utils::get_stash_path(stash_key)?;
+    history::record_snapshot(stash_key, &stash_path, "import")?;
+    if let Some(error) = store::write_stash(&stash_path, content) {
+        return Err(error);
+    }
+    store::share_stash(&stash_path)?;
+
+    let stash_metadata = entry
+        .metadata
+        .as_deref()
+        .and_then(|raw| serde_json::from_slice::<StashMetadata>(raw).ok())
+        .unwrap_or_else(|| StashMetadata::new(Some(&format!("imported from {}", source.display())), None));
+    metadata::save_metadata(stash_key, &stash_metadata)?;
+    history::record_version(stash_key, &stash_path, Some("imported"))?;
+    journal::record_event(stash_key, "import", Some(&source.display().to_string()))?;
+    utils::log_info(&format!("Imported {} as {}", entry.source, stash_key));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use super::ImportConflict;
+    use crate::archive;
+    use crate::commands;
+    use crate::history;
+    use crate::metadata;
+    use crate::store;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_import() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+        store::create_store().unwrap();
+
+        // Export a store, then start a new one that already has a stash named like one of the exported ones
+        let api_path = utils::get_stash_path("api").unwrap();
+        fs::write(&api_path, "# AGENTS\n\n- exported api\n").unwrap();
+        metadata::save_metadata("api", &metadata::StashMetadata::new(Some("from the old laptop"), None)).unwrap();
+        history::record_version("api", &api_path, None).unwrap();
+        fs::write(utils::get_stash_path("web").unwrap(), "# AGENTS\n\n- exported web\n").unwrap();
+        fs::write(utils::get_stash_path("web+CLAUDE.md").unwrap(), "Prefer small commits.\n").unwrap();
+        let export = temp_dir.path().join("stashes.tar.gz");
+        archive::export_store(&export, true, true).unwrap();
+        fs::remove_dir_all(utils::get_agstash_dir().unwrap()).unwrap();
+        store::create_store().unwrap();
+        fs::write(utils::get_stash_path("api").unwrap(), "# AGENTS\n\n- local api\n").unwrap();
+
+        // Refused outright until a strategy is chosen
+        assert!(commands::handle_import(&export, ImportConflict::Refuse).is_err());
+        assert!(!utils::get_stash_path("web").unwrap().exists());
+
+        commands::handle_import(&export, ImportConflict::Skip).unwrap();
+        assert_eq!(fs::read_to_string(utils::get_stash_path("api").unwrap()).unwrap(), "# AGENTS\n\n- local api\n");
+        assert_eq!(fs::read_to_string(utils::get_stash_path("web").unwrap()).unwrap(), "# AGENTS\n\n- exported web\n");
+        assert_eq!(fs::read_to_string(utils::get_stash_path("web+CLAUDE.md").unwrap()).unwrap(), "Prefer small commits.\n");
+
+        commands::handle_import(&export, ImportConflict::Rename).unwrap();
+        let renamed = metadata::load_metadata("api-imported").unwrap().unwrap();
+        assert_eq!(renamed.message.as_deref(), Some("from the old laptop"));
+        assert!(utils::get_stash_path("web-imported").unwrap().exists());
+
+        commands::handle_import(&export, ImportConflict::Overwrite).unwrap();
+        assert_eq!(fs::read_to_string(utils::get_stash_path("api").unwrap()).unwrap(), "# AGENTS\n\n- exported api\n");
+        assert!(!utils::get_stash_path("api-imported-2").unwrap().exists());
+
+        // A plain directory of markdown files, skipping what isn't an AGENTS.md
+        let dir = temp_dir.path().join("rules");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("cli.md"), "# AGENTS\n\n- cli\n").unwrap();
+        fs::write(dir.join("README.md"), "# Rules\n").unwrap();
+        commands::handle_import(&dir, ImportConflict::Refuse).unwrap();
+        assert!(utils::get_stash_path("cli").unwrap().exists());
+        assert!(!utils::get_stash_path("README").unwrap().exists());
+    }
+}
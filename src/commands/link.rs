@@ -0,0 +1,203 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::{announce, apply_allowed, color_string, get_user_confirmation, project_context, target_file, BOLD, GREEN, YELLOW};
+use crate::error::AgStashError;
+use crate::journal;
+use crate::metadata;
+use crate::store;
+use crate::undo;
+use crate::utils;
+
+// HandleApplyLink replaces the project's AGENTS.md with a symlink to its stash, so an edit in either place is the same
+// edit; the stash keeps a file of its own from then on rather than sharing one with identical stashes
+pub fn handle_apply_link(force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let file = target_file();
+    if file.is_rules_dir() {
+        return Err(AgStashError::Usage(format!("apply --link works on single files, not on {}", file.name)).into());
+    }
+    let (dir, project_name) = project_context()?;
+    let stash_key = file.stash_key(&project_name);
+    let stash_path = utils::get_stash_path(&stash_key)?;
+    if !utils::file_exists(&stash_path) {
+        utils::log_info(&format!("No stash found for project: {}", stash_key));
+        println!("No stash found for project {}", color_string(&stash_key, BOLD));
+        return Ok(());
+    }
+    let agents_path = dir.join(&file.name);
+    if is_linked(&agents_path, &stash_key)? {
+        println!("{} is already linked to the stash for {}", color_string(&file.name, BOLD), color_string(&stash_key, BOLD));
+        return Ok(());
+    }
+    apply_allowed(&dir, force)?;
+
+    if fs::symlink_metadata(&agents_path).is_ok() {
+        let differs = fs::read(&agents_path).ok() != Some(fs::read(&stash_path)?);
+        if differs && !force {
+            println!(
+                "\n{} {} differs from the stash and linking replaces it with the stash.",
+                color_string("WARNING:", &format!("{}{}", YELLOW, BOLD)),
+                color_string(&file.name, BOLD)
+            );
+            print!("Type 'yes' to confirm or 'no' to cancel [y/N]: ");
+            io::stdout().flush()?; // Ensure the prompt is displayed
+            if !get_user_confirmation()? {
+                println!("\nOperation cancelled. {} was not modified.", color_string(&file.name, BOLD));
+                return Ok(());
+            }
+        }
+        if differs {
+            undo::save_backup(&stash_key, "apply", &agents_path)?;
+        }
+    }
+
+    // Link beside the file and rename over it later, so a platform that refuses symlinks leaves it as it was
+    if let Some(parent) = agents_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut staging_name = agents_path.file_name().unwrap_or_default().to_os_string();
+    staging_name.push(".link");
+    let staging = agents_path.with_file_name(staging_name);
+    if fs::symlink_metadata(&staging).is_ok() {
+        fs::remove_file(&staging)?;
+    }
+    symlink(&stash_path, &staging)?;
+
+    // Mark the stash first so it isn't shared again, then give it a file of its own before anything writes through
+    let mut stash_metadata = metadata::load_metadata(&stash_key)?
+        .unwrap_or_else(|| metadata::StashMetadata::new(None, Some(agents_path.display().to_string())));
+    stash_metadata.linked_to = Some(agents_path.display().to_string());
+    metadata::save_metadata(&stash_key, &stash_metadata)?;
+    let (err, stash_content) = utils::read_file(&stash_path);
+    if let Some(error) = err {
+        return Err(error);
+    }
+    if let Some(error) = store::write_stash(&stash_path, &stash_content) {
+        return Err(error);
+    }
+
+    fs::rename(&staging, &agents_path)?;
+    journal::record_event(&stash_key, "apply", Some("linked"))?;
+    utils::log_info(&format!("Linked {} to {}", agents_path.display(), stash_path.display()));
+    announce(&format!(
+        "{} {} to the stash for {}",
+        color_string("Linked", GREEN),
+        file.name,
+        color_string(&stash_key, BOLD)
+    ));
+    Ok(())
+}
+
+// IsLinked reports whether path is a link made by apply --link to the stash kept under stash_key
+pub fn is_linked(path: &Path, stash_key: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let Ok(target) = fs::read_link(path) else {
+        return Ok(false);
+    };
+    Ok(target == utils::get_stash_path(stash_key)?)
+}
+
+// Unlink removes path if it is a symlink, returning whether it was; a stash it was linked to is shared again
+pub fn unlink(path: &Path, stash_key: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    if !fs::symlink_metadata(path).is_ok_and(|link| link.file_type().is_symlink()) {
+        return Ok(false);
+    }
+    let linked = is_linked(path, stash_key)?;
+    fs::remove_file(path)?;
+    if linked {
+        if let Some(mut stash_metadata) = metadata::load_metadata(stash_key)? {
+            stash_metadata.linked_to = None;
+            metadata::save_metadata(stash_key, &stash_metadata)?;
+        }
+        let stash_path = utils::get_stash_path(stash_key)?;
+        if utils::file_exists(&stash_path) {
+            store::share_stash(&stash_path)?;
+        }
+    }
+    Ok(true)
+}
+
+// Symlink points link at target
+#[cfg(unix)]
+pub fn symlink(target: &Path, link: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    std::os::unix::fs::symlink(target, link)?;
+    Ok(())
+}
+
+// Symlink points link at target; Windows tells file and directory links apart and only lets administrators or
+// Developer Mode create them, so callers that can should fall back to a copy
+#[cfg(windows)]
+pub fn symlink(target: &Path, link: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let resolved = link.parent().map(|parent| parent.join(target)).unwrap_or_else(|| target.to_path_buf());
+    match resolved.is_dir() {
+        true => std::os::windows::fs::symlink_dir(target, link)?,
+        false => std::os::windows::fs::symlink_file(target, link)?,
+    }
+    Ok(())
+}
+
+// Symlink refuses where agstash does not create symlinks
+#[cfg(not(any(unix, windows)))]
+pub fn symlink(_target: &Path, _link: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    Err("Symlinks are not supported on this platform".into())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::metadata;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_apply_link() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir(".git").unwrap();
+
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(move || {
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        let project_name = utils::project_stash_key(temp_dir.path()).unwrap();
+        let stash_path = utils::get_stash_path(&project_name).unwrap();
+        fs::write("AGENTS.md", "# AGENTS\n\n- linked\n").unwrap();
+        commands::handle_stash(None).unwrap();
+        commands::handle_apply_link(false).unwrap();
+        assert_eq!(fs::read_link("AGENTS.md").unwrap(), stash_path);
+
+        // Edits through the link are the stash's, and stashing again leaves them intact
+        fs::write("AGENTS.md", "# AGENTS\n\n- edited through the link\n").unwrap();
+        assert_eq!(fs::read_to_string(&stash_path).unwrap(), "# AGENTS\n\n- edited through the link\n");
+        commands::handle_stash(None).unwrap();
+        assert_eq!(fs::read_to_string(&stash_path).unwrap(), "# AGENTS\n\n- edited through the link\n");
+        assert!(metadata::load_metadata(&project_name).unwrap().unwrap().linked_to.is_some());
+
+        // Clean removes only the link
+        commands::handle_clean(true, false).unwrap();
+        assert!(fs::symlink_metadata("AGENTS.md").is_err());
+        assert!(stash_path.exists());
+        assert!(metadata::load_metadata(&project_name).unwrap().unwrap().linked_to.is_none());
+        assert!(!utils::get_stash_path(&utils::slot_stash_key(&project_name, "pre-clean")).unwrap().exists());
+
+        // Linking over a file that differs replaces it once forced
+        fs::write("AGENTS.md", "# AGENTS\n\n- local copy\n").unwrap();
+        commands::handle_apply_link(true).unwrap();
+        assert!(Path::new("AGENTS.md").is_symlink());
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- edited through the link\n");
+    }
+}
@@ -0,0 +1,126 @@
+use std::path::Path;
+
+use serde_json::json;
+
+use super::{
+    annotation, color_string, in_github_actions, indicator, json_output, named_stash, print_json, project_context, Indicator,
+    BOLD, CYAN,
+};
+use crate::config;
+use crate::lint::{self, Contradiction, Rule};
+use crate::utils;
+
+// HandleLint flags rules that contradict each other within a project's stash, or between its root stash and a
+// nested one that agents read together with it, returning whether any were found. In a GitHub Actions workflow each
+// rule involved is also annotated with the rule it contradicts
+pub fn handle_lint(project: Option<&str>) -> Result<bool, Box<dyn std::error::Error>> {
+    let project_name = match project {
+        Some(name) => named_stash(name)?,
+        None => utils::project_of(&project_context()?.1).to_string(),
+    };
+
+    // Slots are snapshots kept aside, so they are never read alongside the other stashes
+    let mut root_rules = Vec::new();
+    let mut nested = Vec::new();
+    for (stash_key, path) in utils::list_stashes(utils::get_stashes_dir()?)? {
+        if !utils::stash_belongs_to(&stash_key, &project_name) || !utils::split_stash_key(&stash_key).slots.is_empty() {
+            continue;
+        }
+        let (err, content) = utils::read_file(&path);
+        if let Some(error) = err {
+            return Err(error);
+        }
+        let rules = lint::extract_rules(&stash_key, &content);
+        if stash_key == project_name {
+            root_rules = rules;
+        } else {
+            nested.push(rules);
+        }
+    }
+    if root_rules.is_empty() && nested.is_empty() && !json_output() {
+        utils::log_info(&format!("No stash found for project: {}", project_name));
+        println!("No stash found for project {}", color_string(&project_name, BOLD));
+        return Ok(false);
+    }
+
+    let mut contradictions = lint::find_contradictions(&root_rules);
+    for rules in nested {
+        let composed: Vec<Rule> = root_rules.iter().cloned().chain(rules).collect();
+        for contradiction in lint::find_contradictions(&composed) {
+            if !contradictions.contains(&contradiction) {
+                contradictions.push(contradiction);
+            }
+        }
+    }
+
+    // Subjects the project's config accepts in both forms are not reported
+    let ignored = config::load_settings()?.lint_ignore.unwrap_or_default();
+    contradictions.retain(|contradiction| !lint::is_ignored(&contradiction.first, &ignored));
+    if json_output() {
+        let rule_json = |rule: &Rule| json!({ "source": rule.source, "line": rule.line, "text": rule.text });
+        let contradictions: Vec<_> = contradictions
+            .iter()
+            .map(|Contradiction { first, second }| json!([rule_json(first), rule_json(second)]))
+            .collect();
+        print_json(&json!({ "project": project_name, "contradictions": contradictions }))?;
+        return Ok(!contradictions.is_empty());
+    }
+
+    if contradictions.is_empty() {
+        println!("{} no contradictions in {}", indicator(Indicator::Ok, "ok"), color_string(&project_name, BOLD));
+        return Ok(false);
+    }
+
+    for Contradiction { first, second } in &contradictions {
+        println!("{}", indicator(Indicator::Warning, "Possible contradiction"));
+        for rule in [first, second] {
+            println!("  {} {}", color_string(&format!("{}:{}", rule.source, rule.line), CYAN), rule.text);
+        }
+        if in_github_actions() {
+            for (rule, other) in [(first, second), (second, first)] {
+                let message = format!("Possible contradiction with {}:{}: {}", rule_file(other, &project_name), other.line, other.text);
+                println!("{}", annotation("warning", &rule_file(rule, &project_name), Some(rule.line), &message));
+            }
+        }
+    }
+    println!("\n{} possible contradiction(s) in {}", contradictions.len(), color_string(&project_name, BOLD));
+    Ok(true)
+}
+
+// rule_file names the AGENTS.md a rule's stash is applied to, relative to the project root, e.g. "docs/AGENTS.md"
+fn rule_file(rule: &Rule, project_name: &str) -> String {
+    let dir = utils::nested_relative_path(&rule.source, project_name).unwrap_or_default();
+    Path::new(&dir).join("AGENTS.md").display().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_lint() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        let stashes_dir = utils::get_stashes_dir().unwrap();
+        fs::create_dir_all(&stashes_dir).unwrap();
+        fs::write(utils::get_stash_path("repo").unwrap(), "# AGENTS\n\n- Always rebase\n").unwrap();
+        fs::write(utils::get_stash_path("repo+pre-clean").unwrap(), "# AGENTS\n\n- Never rebase\n").unwrap();
+        fs::write(utils::get_stash_path("other").unwrap(), "# AGENTS\n\n- Never rebase\n").unwrap();
+
+        // Slots and other projects are not read together with the stash
+        assert!(!commands::handle_lint(Some("repo")).unwrap());
+        assert!(!commands::handle_lint(Some("missing")).unwrap());
+
+        fs::write(utils::get_stash_path("repo@docs").unwrap(), "# AGENTS\n\n- Never rebase\n").unwrap();
+        assert!(commands::handle_lint(Some("repo")).unwrap());
+    }
+}
@@ -0,0 +1,357 @@
+use std::path::PathBuf;
+
+use serde_json::{json, Value};
+
+use super::{color_string, fail_if_strict, json_output, print_json, named_stash, project_context, BOLD, CYAN, YELLOW};
+use crate::error::AgStashError;
+use crate::history;
+use crate::markdown::{self, Block, Document};
+use crate::metadata::{self, StashMetadata};
+use crate::store;
+use crate::utils;
+
+// HandleList prints every stashed project along with its recorded metadata
+pub fn handle_list() -> Result<(), Box<dyn std::error::Error>> {
+    let stashes = utils::list_stashes(utils::get_stashes_dir()?)?;
+    let conflicts = utils::list_sync_conflicts(utils::get_stashes_dir()?)?;
+    if json_output() {
+        return list_json(&stashes, &conflicts);
+    }
+
+    if stashes.is_empty() && conflicts.is_empty() {
+        utils::log_info("No stashes found");
+        println!("{}", color_string("No stashes found.", YELLOW));
+        return Ok(());
+    }
+
+    let shared = store::shared_stashes()?;
+    for (project_name, _) in stashes {
+        let stash_metadata = metadata::load_metadata(&project_name)?;
+        match stash_metadata {
+            Some(stash_metadata) => {
+                println!(
+                    "{} {}",
+                    color_string(&project_name, BOLD),
+                    color_string(&format!("({})", stash_metadata.created_at), CYAN)
+                );
+                if let Some(message) = &stash_metadata.message {
+                    println!("  {}", message);
+                }
+                if let Some(git) = &stash_metadata.git {
+                    println!("  {}", color_string(&format!("stashed from {}", git.describe()), CYAN));
+                }
+            }
+            None => println!("{}", color_string(&project_name, BOLD)),
+        }
+
+        // Projects with identical rules share one stored copy
+        if let Some(group) = shared.iter().find(|group| group.contains(&project_name)) {
+            let others: Vec<&str> = group.iter().filter(|key| **key != project_name).map(String::as_str).collect();
+            println!("  {}", color_string(&format!("Same rules as: {}", others.join(", ")), CYAN));
+        }
+    }
+
+    // Copies a sync client could not merge, including any whose stash has since gone
+    for (stash_key, path) in &conflicts {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        println!(
+            "{} {}",
+            color_string(&format!("Sync conflict for {}:", stash_key), YELLOW),
+            file_name
+        );
+    }
+    if !conflicts.is_empty() {
+        println!("Run {} to choose which copy to keep", color_string("agstash resolve", BOLD));
+    }
+
+    Ok(())
+}
+
+// list_json prints every stash with its metadata and the projects sharing its rules, and any sync conflicts, as JSON
+fn list_json(stashes: &[(String, PathBuf)], conflicts: &[(String, PathBuf)]) -> Result<(), Box<dyn std::error::Error>> {
+    let shared = store::shared_stashes()?;
+    let mut entries = Vec::new();
+    for (project_name, path) in stashes {
+        let same_rules: Vec<&String> = shared
+            .iter()
+            .find(|group| group.contains(project_name))
+            .map(|group| group.iter().filter(|key| *key != project_name).collect())
+            .unwrap_or_default();
+        entries.push(json!({
+            "project": project_name,
+            "path": path.display().to_string(),
+            "metadata": metadata::load_metadata(project_name)?,
+            "same_rules_as": same_rules,
+        }));
+    }
+    let conflicts: Vec<Value> = conflicts
+        .iter()
+        .map(|(stash_key, path)| json!({ "project": stash_key, "path": path.display().to_string() }))
+        .collect();
+    print_json(&json!({ "stashes": entries, "sync_conflicts": conflicts }))
+}
+
+// HandleShow prints a project's stash metadata followed by the stashed AGENTS.md, or its structure as JSON with ast
+pub fn handle_show(project: Option<&str>, version: Option<&str>, ast: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let project_name = match project {
+        Some(name) => named_stash(name)?,
+        None => project_context()?.1,
+    };
+
+    if ast {
+        return show_ast(&project_name, version);
+    }
+    if let Some(spec) = version {
+        return show_version(&project_name, spec);
+    }
+
+    let stash_path = utils::get_stash_path(&project_name)?;
+    if !utils::file_exists(&stash_path) {
+        missing(AgStashError::StashNotFound(format!("No stash found for project {}", project_name)))?;
+        utils::log_info(&format!("No stash found for project: {}", project_name));
+        println!("No stash found for project {}", color_string(&project_name, BOLD));
+        return Ok(());
+    }
+
+    let (err, content) = utils::read_file(&stash_path);
+    if let Some(error) = err {
+        return Err(error);
+    }
+    if json_output() {
+        return print_json(&json!({
+            "project": project_name,
+            "metadata": metadata::load_metadata(&project_name)?,
+            "content": content,
+        }));
+    }
+
+    println!("Project: {}", color_string(&project_name, BOLD));
+    if let Some(stash_metadata) = metadata::load_metadata(&project_name)? {
+        print_metadata(&stash_metadata);
+    }
+    println!();
+    print!("{}", content);
+    if !content.ends_with('\n') {
+        println!();
+    }
+
+    Ok(())
+}
+
+// missing fails with error when strict, or when the answer is JSON that a message would break
+fn missing(error: AgStashError) -> Result<(), Box<dyn std::error::Error>> {
+    if json_output() {
+        return Err(error.into());
+    }
+    fail_if_strict(error)
+}
+
+// show_version prints one recorded version of a project's stash
+fn show_version(project_name: &str, spec: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let version = match history::resolve_version(project_name, spec)? {
+        Some(version) => version,
+        None => {
+            missing(AgStashError::VersionNotFound(format!("No version {} found for project {}", spec, project_name)))?;
+            utils::log_info(&format!("No version {} for project: {}", spec, project_name));
+            println!("No version {} found for project {}", spec, color_string(project_name, BOLD));
+            return Ok(());
+        }
+    };
+
+    let content = history::read_version(project_name, version.id)?;
+    if json_output() {
+        return print_json(&json!({
+            "project": project_name,
+            "version": version,
+            "content": content,
+        }));
+    }
+
+    println!("Project: {}", color_string(project_name, BOLD));
+    println!("Version: {}", version.id);
+    println!("Stashed: {}", version.created_at);
+    if let Some(message) = &version.message {
+        println!("Message: {}", message);
+    }
+    println!();
+    print!("{}", content);
+    if !content.ends_with('\n') {
+        println!();
+    }
+
+    Ok(())
+}
+
+// show_ast prints a stash, or one of its versions, as JSON: where it came from plus its sections and blocks
+fn show_ast(project_name: &str, spec: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let (content, mut provenance) = match spec {
+        Some(spec) => match history::resolve_version(project_name, spec)? {
+            Some(version) => (
+                history::read_version(project_name, version.id)?,
+                json!({
+                    "version": version.id,
+                    "stashed_at": version.created_at,
+                    "message": version.message,
+                }),
+            ),
+            None => {
+                fail_if_strict(AgStashError::VersionNotFound(format!("No version {} found for project {}", spec, project_name)))?;
+                utils::log_info(&format!("No version {} for project: {}", spec, project_name));
+                println!("No version {} found for project {}", spec, color_string(project_name, BOLD));
+                return Ok(());
+            }
+        },
+        None => {
+            let stash_path = utils::get_stash_path(project_name)?;
+            if !utils::file_exists(&stash_path) {
+                fail_if_strict(AgStashError::StashNotFound(format!("No stash found for project {}", project_name)))?;
+                utils::log_info(&format!("No stash found for project: {}", project_name));
+                println!("No stash found for project {}", color_string(project_name, BOLD));
+                return Ok(());
+            }
+            let (err, content) = utils::read_file(&stash_path);
+            if let Some(error) = err {
+                return Err(error);
+            }
+
+            let stash_metadata = metadata::load_metadata(project_name)?;
+            let stash_metadata = stash_metadata.as_ref();
+            (
+                content,
+                json!({
+                    "version": null,
+                    "path": stash_path.display().to_string(),
+                    "stashed_at": stash_metadata.map(|m| &m.created_at),
+                    "message": stash_metadata.and_then(|m| m.message.as_ref()),
+                    "source_path": stash_metadata.and_then(|m| m.source_path.as_ref()),
+                }),
+            )
+        }
+    };
+
+    provenance["project"] = json!(project_name);
+    let report = json!({
+        "provenance": provenance,
+        "sections": document_ast(&Document::parse(&content)),
+    });
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
+
+// document_ast describes each section of a document, with list items stripped of their markers
+fn document_ast(document: &Document) -> Vec<Value> {
+    document
+        .sections
+        .iter()
+        .map(|section| {
+            let blocks: Vec<Value> = section
+                .blocks
+                .iter()
+                .map(|block| match block {
+                    Block::List(items) => json!({
+                        "type": "list",
+                        "items": items.iter().map(|item| markdown::item_text(item)).collect::<Vec<_>>(),
+                    }),
+                    Block::Text(lines) => json!({
+                        "type": "text",
+                        "text": lines.join("\n"),
+                    }),
+                })
+                .collect();
+            json!({
+                "heading": section.heading.as_ref().map(|heading| json!({
+                    "level": heading.level,
+                    "text": heading.text,
+                })),
+                "blocks": blocks,
+            })
+        })
+        .collect()
+}
+
+// print_metadata prints the recorded fields of a stash's metadata
+fn print_metadata(stash_metadata: &StashMetadata) {
+    println!("Stashed: {}", stash_metadata.created_at);
+    if let Some(source_path) = &stash_metadata.source_path {
+        println!("Source:  {}", source_path);
+    }
+    if let Some(git) = &stash_metadata.git {
+        println!("Git:     {}", git.describe());
+    }
+    if let Some(message) = &stash_metadata.message {
+        println!("Message: {}", message);
+    }
+    for warning in &stash_metadata.warnings {
+        println!("Warning: {}", color_string(warning, YELLOW));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use serde_json::json;
+
+    use crate::commands;
+    use crate::markdown::Document;
+    use crate::metadata;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_list_and_show() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(|| {
+            let _ = env::set_current_dir(&original_dir);
+        });
+
+        // Create a .git directory to establish project root
+        fs::create_dir(".git").unwrap();
+
+        // Set up HOME environment variable to temp directory
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        // Listing an empty store should not error
+        assert!(commands::handle_list().is_ok());
+
+        fs::write("AGENTS.md", "# AGENTS\n").unwrap();
+        commands::handle_stash(Some("rules for the refactor sprint")).unwrap();
+
+        let project_name = &utils::project_stash_key(temp_dir.path()).unwrap();
+        let stash_metadata = metadata::load_metadata(project_name).unwrap().unwrap();
+        assert_eq!(stash_metadata.message.as_deref(), Some("rules for the refactor sprint"));
+        assert!(stash_metadata.source_path.unwrap().ends_with("AGENTS.md"));
+
+        assert!(commands::handle_list().is_ok());
+        assert!(commands::handle_show(None, None, false).is_ok());
+        assert!(commands::handle_show(None, Some("1"), false).is_ok());
+        assert!(commands::handle_show(None, Some("2"), false).is_ok());
+        assert!(commands::handle_show(Some("missing"), None, false).is_ok());
+        assert!(commands::handle_show(None, None, true).is_ok());
+        assert!(commands::handle_show(None, Some("1"), true).is_ok());
+    }
+
+    #[test]
+    fn test_document_ast() {
+        let document = Document::parse("# AGENTS\n\n- Use tabs\n- Run tests\n  with --all\n\n## Notes\n\nKeep it short.\n");
+        let ast = super::document_ast(&document);
+
+        assert_eq!(ast.len(), 2);
+        assert_eq!(ast[0]["heading"], json!({"level": 1, "text": "AGENTS"}));
+        assert_eq!(
+            ast[0]["blocks"],
+            json!([{"type": "list", "items": ["Use tabs", "Run tests\nwith --all"]}])
+        );
+        assert_eq!(ast[1]["heading"]["text"], "Notes");
+        assert_eq!(ast[1]["blocks"], json!([{"type": "text", "text": "Keep it short."}]));
+    }
+}
@@ -0,0 +1,98 @@
+use serde_json::json;
+
+use super::{color_string, json_output, print_json, project_context, BOLD, CYAN, GREEN, RED, YELLOW};
+use crate::journal;
+use crate::utils;
+
+// HandleLog prints the journal of stash operations for the current project, or for every project
+pub fn handle_log(all: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let project_name = if all { None } else { Some(project_context()?.1) };
+
+    let entries: Vec<_> = journal::read_events()?
+        .into_iter()
+        .filter(|entry| project_name.as_ref().is_none_or(|name| entry.project == *name))
+        .collect();
+    if json_output() {
+        return print_json(&json!(entries));
+    }
+
+    if entries.is_empty() {
+        utils::log_info("No journal entries found");
+        println!("{}", color_string("No stash history recorded.", YELLOW));
+        return Ok(());
+    }
+
+    for entry in entries {
+        let mut line = format!(
+            "{} {} {}",
+            color_string(&entry.timestamp, CYAN),
+            color_action(&entry.action),
+            color_string(&entry.project, BOLD)
+        );
+        if let Some(message) = &entry.message {
+            line.push_str(&format!(": {}", message));
+        }
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+// color_action colors an action name by whether it adds to or removes from the store
+fn color_action(action: &str) -> String {
+    let padded = format!("{:<6}", action);
+    match action {
+        "drop" => color_string(&padded, RED),
+        "stash" | "copy" | "import" => color_string(&padded, GREEN),
+        _ => padded,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::journal;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_log() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(|| {
+            let _ = env::set_current_dir(&original_dir);
+        });
+
+        // Create a .git directory to establish project root
+        fs::create_dir(".git").unwrap();
+
+        // Set up HOME environment variable to temp directory
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        assert!(commands::handle_log(false).is_ok());
+
+        fs::write("AGENTS.md", "# AGENTS\n").unwrap();
+        commands::handle_stash(Some("baseline")).unwrap();
+        fs::write("AGENTS.md", "# AGENTS\n\n- edited\n").unwrap();
+        commands::handle_pop(true).unwrap();
+
+        let actions: Vec<String> = journal::read_events()
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.action)
+            .collect();
+        assert_eq!(actions, vec!["stash", "apply", "drop"]);
+
+        assert!(commands::handle_log(false).is_ok());
+        assert!(commands::handle_log(true).is_ok());
+    }
+}
@@ -0,0 +1,130 @@
+use super::{color_string, fail_if_strict, BOLD, GREEN, YELLOW};
+use crate::error::AgStashError;
+use crate::journal;
+use crate::markdown;
+use crate::metadata;
+use crate::store;
+use crate::utils;
+
+// HandleMerge combines two projects' stashes, printing the result or storing it as the stash of into
+pub fn handle_merge(first: &str, second: &str, into: Option<&str>, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut contents = Vec::new();
+    for project_name in [first, second] {
+        utils::validate_project_name(project_name)?;
+        let stash_path = utils::get_stash_path(project_name)?;
+        if !utils::file_exists(&stash_path) {
+            fail_if_strict(AgStashError::StashNotFound(format!("No stash found for project {}", project_name)))?;
+            utils::log_info(&format!("No stash found for project: {}", project_name));
+            println!("No stash found for project {}", color_string(project_name, BOLD));
+            return Ok(());
+        }
+        let (err, content) = utils::read_file(&stash_path);
+        if let Some(error) = err {
+            return Err(error);
+        }
+        contents.push(content);
+    }
+
+    let merged = merge_documents(&contents[0], &contents[1]);
+
+    let target = match into {
+        Some(target) => target,
+        None => {
+            print!("{}", merged);
+            return Ok(());
+        }
+    };
+
+    utils::validate_project_name(target)?;
+    let target_path = utils::get_stash_path(target)?;
+    if utils::file_exists(&target_path) && !force {
+        utils::log_warn(&format!("A stash already exists for project: {}", target));
+        println!(
+            "{} {}",
+            color_string(&format!("A stash already exists for {}.", target), YELLOW),
+            color_string("Use --force to overwrite it.", YELLOW)
+        );
+        return Ok(());
+    }
+
+    if let Some(error) = store::write_stash(&target_path, &merged) {
+        return Err(error);
+    }
+    store::share_stash(&target_path)?;
+    let message = format!("merged from {} and {}", first, second);
+    metadata::save_metadata(target, &metadata::StashMetadata::new(Some(&message), None))?;
+    journal::record_event(target, "merge", Some(&message))?;
+    utils::log_info(&format!("Merged stashes into: {}", target_path.display()));
+    println!(
+        "{} {} and {} into {}",
+        color_string("Merged", GREEN),
+        color_string(first, BOLD),
+        color_string(second, BOLD),
+        color_string(target, BOLD)
+    );
+
+    Ok(())
+}
+
+// merge_documents folds second into first: sections with the same heading are combined and only unseen content is added
+fn merge_documents(first: &str, second: &str) -> String {
+    let mut document = markdown::Document::parse(first);
+    document.merge(markdown::Document::parse(second));
+    document.render()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::store;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    fn test_merge_documents() {
+        let first = "# AGENTS\n\n- Run cargo test\n\n## Style\n\n- Use tabs\n";
+        let second = "# AGENTS\n\n- Run cargo test\n- Keep commits small\n\n## Release\n\n- Tag every release\n\n## style\n\n- Use tabs\n- Wrap at 100 columns\n";
+
+        let merged = super::merge_documents(first, second);
+        assert_eq!(
+            merged,
+            "# AGENTS\n\n- Run cargo test\n- Keep commits small\n\n## Style\n\n- Use tabs\n- Wrap at 100 columns\n\n## Release\n\n- Tag every release\n"
+        );
+
+        // Merging a document with itself changes nothing
+        assert_eq!(super::merge_documents(first, first), first);
+    }
+
+    #[test]
+    #[serial]
+    fn test_handle_merge() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+        store::create_store().unwrap();
+
+        fs::write(utils::get_stash_path("api").unwrap(), "# AGENTS\n\n- Run cargo test\n").unwrap();
+        fs::write(utils::get_stash_path("web").unwrap(), "# AGENTS\n\n- Run npm test\n").unwrap();
+
+        commands::handle_merge("api", "web", Some("baseline"), false).unwrap();
+        let baseline = utils::get_stash_path("baseline").unwrap();
+        assert_eq!(
+            fs::read_to_string(&baseline).unwrap(),
+            "# AGENTS\n\n- Run cargo test\n- Run npm test\n"
+        );
+
+        // An existing target is only replaced with force
+        fs::write(&baseline, "# AGENTS\n\n- custom\n").unwrap();
+        commands::handle_merge("api", "web", Some("baseline"), false).unwrap();
+        assert_eq!(fs::read_to_string(&baseline).unwrap(), "# AGENTS\n\n- custom\n");
+        commands::handle_merge("api", "web", Some("baseline"), true).unwrap();
+        assert_eq!(
+            fs::read_to_string(&baseline).unwrap(),
+            "# AGENTS\n\n- Run cargo test\n- Run npm test\n"
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use super::{adopt_legacy_stashes, color_string, BOLD, GREEN, YELLOW};
+use crate::history;
+use crate::metadata::{self, StashMetadata};
+use crate::registry;
+use crate::store;
+use crate::utils;
+
+// HandleMigrate upgrades the store in place to the layout this agstash uses, one format at a time, recording each
+// step as it completes so an interrupted migration resumes where it stopped
+pub fn handle_migrate() -> Result<(), Box<dyn std::error::Error>> {
+    move_legacy_store()?;
+    let Some(mut format) = store::read_format()? else {
+        println!("{}", color_string("No store to migrate.", YELLOW));
+        return Ok(());
+    };
+    if format > store::STORE_FORMAT {
+        return store::check_format();
+    }
+    if format == store::STORE_FORMAT {
+        println!("Store is already at format {}", color_string(&format.to_string(), BOLD));
+        return Ok(());
+    }
+
+    while format < store::STORE_FORMAT {
+        let (description, changed) = match format {
+            1 => ("metadata for every stash", add_metadata()?),
+            2 => ("content objects and checksums", add_checksums()?),
+            _ => ("project keys with a path hash", rekey_legacy_projects()?),
+        };
+        format += 1;
+        store::write_format(format)?;
+        utils::log_info(&format!("Migrated store to format {}", format));
+        println!(
+            "{} to format {}: {} ({} file(s) updated)",
+            color_string("Migrated", GREEN),
+            color_string(&format.to_string(), BOLD),
+            description,
+            changed
+        );
+    }
+    Ok(())
+}
+
+// MoveLegacyStore moves ~/.agstash to the XDG base directories on Linux, reporting where the store and its config
+// went; it returns whether anything moved
+pub fn move_legacy_store() -> Result<bool, Box<dyn std::error::Error>> {
+    let Some(data_dir) = store::migrate_legacy_dir()? else {
+        return Ok(false);
+    };
+    utils::log_warn(&format!(
+        "Moved ~/.agstash to {} and its config to {}, following the XDG base directories",
+        data_dir.display(),
+        utils::get_platform_config_dir()?.display()
+    ));
+    Ok(true)
+}
+
+// add_metadata gives every stash without a metadata sidecar one dated by the stash file
+fn add_metadata() -> Result<usize, Box<dyn std::error::Error>> {
+    let mut changed = 0;
+    for (stash_key, path) in utils::list_stashes(utils::get_stashes_dir()?)? {
+        if utils::file_exists(metadata::get_metadata_path(&stash_key)?) {
+            continue;
+        }
+        metadata::save_metadata(&stash_key, &StashMetadata::from_file(&path, "metadata added by migrate")?)?;
+        changed += 1;
+    }
+    Ok(changed)
+}
+
+// add_checksums links every stash to its content object and records checksums for stashes and history versions
+fn add_checksums() -> Result<usize, Box<dyn std::error::Error>> {
+    let mut changed = 0;
+    for (stash_key, path) in utils::list_stashes(utils::get_stashes_dir()?)? {
+        store::share_stash(&path)?;
+        if let Some(stash_metadata) = metadata::load_metadata(&stash_key)? {
+            if stash_metadata.sha256.is_none() {
+                metadata::save_metadata(&stash_key, &stash_metadata)?;
+                changed += 1;
+            }
+        }
+    }
+
+    for stash_key in history::list_histories()? {
+        let record_dir = history::get_history_dir(&stash_key)?;
+        for mut version in history::list_versions(&stash_key)? {
+            if version.sha256.is_some() {
+                continue;
+            }
+            let content = history::read_version(&stash_key, version.id)?;
+            version.sha256 = Some(store::content_hash(content.as_bytes()));
+            let record_path = record_dir.join(format!("{}.json", version.id));
+            if let Some(error) = utils::write_file(&record_path, &serde_json::to_string_pretty(&version)?) {
+                return Err(error);
+            }
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+// rekey_legacy_projects moves the stashes of every project the registry or a stash's source path names to the key with
+// a path hash, e.g. "api" to "api-3f2a91c0"; stashes whose project can't be found keep their key
+fn rekey_legacy_projects() -> Result<usize, Box<dyn std::error::Error>> {
+    let mut roots: BTreeSet<PathBuf> = registry::load_projects()?.into_keys().map(PathBuf::from).collect();
+    for (stash_key, _) in utils::list_stashes(utils::get_stashes_dir()?)? {
+        let Some(source_path) = metadata::load_metadata(&stash_key)?.and_then(|stash_metadata| stash_metadata.source_path) else {
+            continue;
+        };
+        let project = utils::project_of(&stash_key);
+        if let Some(root) = Path::new(&source_path).ancestors().find(|dir| dir.file_name().is_some_and(|name| name == project)) {
+            roots.insert(root.to_path_buf());
+        }
+    }
+
+    let mut changed = 0;
+    for root in roots {
+        let Some(legacy_name) = root.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let project_name = registry::project_key(&root)?;
+        if project_name != legacy_name {
+            changed += adopt_legacy_stashes(&root, legacy_name, &project_name)?;
+        }
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::history;
+    use crate::metadata;
+    use crate::store;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_migrate() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        // A store from before versioning: a bare stash and a version without a checksum
+        let stashes_dir = utils::get_stashes_dir().unwrap();
+        fs::create_dir_all(&stashes_dir).unwrap();
+        let stash_path = stashes_dir.join("stash-old.md");
+        fs::write(&stash_path, "# AGENTS\n\n- old\n").unwrap();
+        let history_dir = history::get_history_dir("old").unwrap();
+        fs::create_dir_all(&history_dir).unwrap();
+        fs::write(history_dir.join("1.md"), "# AGENTS\n\n- older\n").unwrap();
+        fs::write(history_dir.join("1.json"), "{\"id\": 1, \"created_at\": \"2025-01-01T00:00:00Z\"}").unwrap();
+        assert_eq!(store::read_format().unwrap(), Some(1));
+
+        commands::handle_migrate().unwrap();
+        assert_eq!(store::read_format().unwrap(), Some(store::STORE_FORMAT));
+        let stash_metadata = metadata::load_metadata("old").unwrap().unwrap();
+        assert_eq!(stash_metadata.sha256, Some(store::content_hash(b"# AGENTS\n\n- old\n")));
+        let versions = history::list_versions("old").unwrap();
+        assert_eq!(versions[0].sha256, Some(store::content_hash(b"# AGENTS\n\n- older\n")));
+
+        // Migrating again changes nothing, and a newer store is left alone
+        commands::handle_migrate().unwrap();
+        store::write_format(store::STORE_FORMAT + 1).unwrap();
+        assert!(commands::handle_migrate().is_err());
+    }
+
+    #[test]
+    #[serial]
+    #[cfg(target_os = "linux")]
+    fn test_handle_migrate_moves_legacy_store() {
+        // Create a temporary directory to use as HOME, finding the store as a real install does
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+        std::env::remove_var(utils::HOME_ENV);
+
+        let legacy_dir = utils::get_legacy_dir().unwrap();
+        fs::create_dir_all(legacy_dir.join("stashes")).unwrap();
+        fs::write(legacy_dir.join("stashes").join("stash-old.md"), "# AGENTS\n").unwrap();
+
+        commands::handle_migrate().unwrap();
+        assert!(!legacy_dir.exists());
+        assert_eq!(utils::get_agstash_dir().unwrap(), utils::get_platform_data_dir().unwrap());
+        assert_eq!(store::read_format().unwrap(), Some(store::STORE_FORMAT));
+        assert!(!commands::move_legacy_store().unwrap());
+    }
+}
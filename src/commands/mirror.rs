@@ -0,0 +1,138 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::link::symlink;
+use super::{color_string, project_context, BOLD, GREEN, RED, YELLOW};
+use crate::config;
+use crate::managed::{ManagedFile, AGENTS_FILE};
+use crate::utils;
+
+// HandleMirror points every configured mirror, e.g. CLAUDE.md and .cursorrules, at the project's AGENTS.md with a
+// relative symlink, copying it instead where symlinks can't be made; with remove it takes the mirrors away again
+pub fn handle_mirror(remove: bool, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let (dir, _) = project_context()?;
+    let agents_path = dir.join(AGENTS_FILE);
+    if !remove && !utils::file_exists(&agents_path) {
+        println!("{} {}", color_string(AGENTS_FILE, BOLD), color_string("does not exist, nothing to mirror.", YELLOW));
+        return Ok(());
+    }
+
+    for name in config::load_settings()?.mirrors.unwrap_or_default() {
+        let mirror = ManagedFile::new(&name)?;
+        if mirror.is_agents() {
+            continue;
+        }
+        let path = dir.join(&mirror.name);
+        if remove {
+            remove_mirror(&path, &agents_path, &mirror.name)?;
+        } else {
+            add_mirror(&path, &agents_path, &mirror.name, force)?;
+        }
+    }
+    Ok(())
+}
+
+// add_mirror links path to AGENTS.md, leaving a file with content of its own alone unless forced
+fn add_mirror(path: &Path, agents_path: &Path, name: &str, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let target = relative_target(name);
+    if fs::read_link(path).ok().as_ref() == Some(&target) {
+        println!("{} already mirrors {}", color_string(name, BOLD), AGENTS_FILE);
+        return Ok(());
+    }
+    if fs::symlink_metadata(path).is_ok() {
+        if fs::read(path).ok() != Some(fs::read(agents_path)?) && !force {
+            println!(
+                "{} {}",
+                color_string(&format!("{} has content of its own.", name), YELLOW),
+                color_string("Use --force to replace it with a mirror.", YELLOW)
+            );
+            return Ok(());
+        }
+        fs::remove_file(path)?;
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if let Err(error) = symlink(&target, path) {
+        utils::log_warn(&format!("Could not link {}, copying instead: {}", path.display(), error));
+        fs::copy(agents_path, path)?;
+        println!("{} {} as a copy of {}", color_string("Mirrored", GREEN), name, AGENTS_FILE);
+        return Ok(());
+    }
+    utils::log_info(&format!("Linked {} to {}", path.display(), target.display()));
+    println!("{} {} to {}", color_string("Mirrored", GREEN), name, AGENTS_FILE);
+    Ok(())
+}
+
+// remove_mirror deletes path when it is a link to AGENTS.md or a copy with nothing AGENTS.md doesn't have
+fn remove_mirror(path: &Path, agents_path: &Path, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let is_link = fs::read_link(path).ok().as_ref() == Some(&relative_target(name));
+    let is_copy = !is_link && path.is_file() && fs::read(path).ok() == fs::read(agents_path).ok();
+    if !is_link && !is_copy {
+        if fs::symlink_metadata(path).is_ok() {
+            println!("{} {}", color_string(name, BOLD), color_string("is not a mirror, left as is.", YELLOW));
+        }
+        return Ok(());
+    }
+    fs::remove_file(path)?;
+    utils::log_info(&format!("Removed mirror {}", path.display()));
+    println!("{} mirror {}", color_string("Removed", RED), name);
+    Ok(())
+}
+
+// relative_target returns the path from a mirror to AGENTS.md, e.g. "../AGENTS.md" for .github/copilot-instructions.md,
+// so the link survives the project being moved or cloned
+fn relative_target(name: &str) -> PathBuf {
+    let depth = Path::new(name).components().count().saturating_sub(1);
+    let mut target = PathBuf::new();
+    for _ in 0..depth {
+        target.push("..");
+    }
+    target.join(AGENTS_FILE)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_mirror() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir(".git").unwrap();
+
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(move || {
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        fs::write("AGENTS.md", "# AGENTS\n\n- Use tabs\n").unwrap();
+        fs::write(".cursorrules", "Use spaces.\n").unwrap();
+        commands::handle_mirror(false, false).unwrap();
+        assert_eq!(fs::read_link("CLAUDE.md").unwrap(), PathBuf::from("AGENTS.md"));
+        assert_eq!(fs::read_to_string(".cursorrules").unwrap(), "Use spaces.\n");
+
+        commands::handle_mirror(false, true).unwrap();
+        assert_eq!(fs::read_to_string(".cursorrules").unwrap(), "# AGENTS\n\n- Use tabs\n");
+
+        commands::handle_mirror(true, false).unwrap();
+        assert!(fs::symlink_metadata("CLAUDE.md").is_err());
+        assert!(fs::symlink_metadata(".cursorrules").is_err());
+        assert!(Path::new("AGENTS.md").exists());
+    }
+}
@@ -1,24 +1,488 @@
+use std::env;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::io::{self, Write};
-
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::error::AgStashError;
+use crate::git;
+use crate::journal;
+use crate::managed::ManagedFile;
+use crate::manifest;
+use crate::markdown;
+use crate::metadata;
+use crate::registry;
+use crate::store;
 use crate::utils;
 
+mod alias;
+mod annotate;
+mod archive;
+mod audit;
+mod bundle;
+mod check;
+mod config;
+mod convert;
+mod copy;
+mod daemon;
+mod diff;
+mod doctor;
+mod edit;
+mod gc;
+mod githooks;
+mod grep;
+mod history;
+mod hook;
+mod ignore;
+mod import;
+mod link;
+mod lint;
+mod list;
+mod log;
+mod merge;
+mod migrate;
+mod mirror;
+mod patch;
+mod pop;
+mod projects;
+mod prompt;
+mod recursive;
+mod rename;
+mod repair;
+mod replay;
+mod resolve;
+mod rules;
+mod stats;
+mod status;
+mod sync;
+mod tidy;
+mod undo;
+mod verify;
+
+pub use alias::handle_alias;
+pub use annotate::handle_annotate;
+pub use archive::{
+    handle_archive_project, handle_backup, handle_export, handle_restore, handle_restore_project, DEFAULT_KEEP_BACKUPS,
+};
+pub use audit::handle_audit;
+pub use bundle::{handle_apply_bundle, handle_stash_bundle};
+pub use check::{handle_check, EXIT_DRIFTED, EXIT_INVALID, EXIT_MISSING};
+pub use config::{handle_config_get, handle_config_list, handle_config_set};
+pub use convert::handle_convert;
+pub use copy::handle_copy;
+pub use daemon::handle_daemon;
+pub use diff::handle_diff;
+pub use doctor::handle_doctor;
+pub use edit::handle_edit;
+pub use gc::{handle_gc, DEFAULT_RETENTION_DAYS};
+pub use githooks::{handle_git_hook, handle_install_hooks};
+pub use grep::handle_grep;
+pub use history::handle_history;
+pub use hook::{handle_hook, handle_hook_apply};
+pub use ignore::ignore_agent_files;
+pub use import::{handle_import, ImportConflict};
+pub use link::handle_apply_link;
+pub use lint::handle_lint;
+pub use list::{handle_list, handle_show};
+pub use log::handle_log;
+pub use merge::handle_merge;
+pub use migrate::{handle_migrate, move_legacy_store};
+pub use mirror::handle_mirror;
+pub use patch::handle_stash_patch;
+pub use pop::{handle_drop, handle_pop};
+pub use projects::handle_projects;
+pub use prompt::handle_prompt_status;
+pub use recursive::{handle_apply_recursive, handle_stash_recursive};
+pub use rename::handle_rename;
+pub use repair::handle_repair;
+pub use replay::handle_replay;
+pub use resolve::{handle_resolve, ConflictResolution};
+pub use stats::handle_stats;
+pub use status::handle_status;
+pub use sync::handle_sync;
+pub use tidy::handle_tidy;
+pub use undo::handle_undo;
+pub use verify::handle_verify;
+
 // ANSI color codes
 const RESET: &str = "\x1b[0m";
 const RED: &str = "\x1b[31m";
 const GREEN: &str = "\x1b[32m";
 const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
 const BOLD: &str = "\x1b[1m";
 
-// color_string applies ANSI color codes to a string
+// Stash slot that clean snapshots AGENTS.md into before removing it
+const PRE_CLEAN_SLOT: &str = "pre-clean";
+
+// Whether status indicators are drawn with glyphs; plain ASCII is used for fonts lacking them
+static USE_ICONS: AtomicBool = AtomicBool::new(true);
+
+// Whether output is colored with ANSI escape codes
+static USE_COLOR: AtomicBool = AtomicBool::new(true);
+
+// Whether confirmations of what a command did are left out, leaving only errors, prompts and requested data
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+// Whether soft failures, such as stash with no AGENTS.md or apply with no stash, are errors
+static STRICT: AtomicBool = AtomicBool::new(false);
+
+// Whether questions before overwriting or removing files are asked; when off they are answered yes
+static CONFIRM: AtomicBool = AtomicBool::new(true);
+
+// Whether commands that support it print JSON instead of text
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+// Whether nested AGENTS.md files are ignored in favour of the one at the repository root
+static TOP_LEVEL: AtomicBool = AtomicBool::new(false);
+
+// File init, clean, stash and apply work on; AGENTS.md unless --file or target_file chose another
+static TARGET_FILE: Mutex<Option<ManagedFile>> = Mutex::new(None);
+
+// Indicator is a status state that must stay distinguishable without relying on color
+#[derive(Clone, Copy)]
+enum Indicator {
+    Ok,
+    Error,
+    Drift,
+    Warning,
+}
+
+// Scaffold written by init when no project manifest is found
+const DEFAULT_TEMPLATE: &str = "# AGENTS\n\n\n";
+
+// Scaffold written by init when a project manifest provides facts to fill in
+const PROJECT_TEMPLATE: &str = "# AGENTS\n\n## Project\n\n- Package: {{package_name}}\n- Test command: `{{test_command}}`\n- Toolchain: {{toolchain}}\n\n\n";
+
+// color_string applies ANSI color codes to a string, unless color is turned off
 fn color_string(s: &str, color_code: &str) -> String {
+    if !USE_COLOR.load(Ordering::Relaxed) {
+        return s.to_string();
+    }
     format!("{}{}{}", color_code, s, RESET)
 }
 
-// HandleInit creates a default AGENTS.md file in the current directory if one doesn't exist
+// SetJsonOutput makes list, projects, status, diff, check and audit print JSON instead of text
+pub fn set_json_output(enabled: bool) {
+    JSON_OUTPUT.store(enabled, Ordering::Relaxed);
+}
+
+// json_output reports whether --output json was given
+fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+// print_json prints a command's JSON report
+fn print_json(report: &serde_json::Value) -> Result<(), Box<dyn std::error::Error>> {
+    println!("{}", serde_json::to_string_pretty(report)?);
+    Ok(())
+}
+
+// in_github_actions reports whether agstash runs in a GitHub Actions workflow, which sets GITHUB_ACTIONS=true
+fn in_github_actions() -> bool {
+    env::var("GITHUB_ACTIONS").is_ok_and(|value| value == "true")
+}
+
+// annotation formats a GitHub Actions workflow command that shows message inline on file in a pull request, e.g.
+// "::error file=AGENTS.md,line=3::AGENTS.md differs from the stash"
+fn annotation(level: &str, file: &str, line: Option<usize>, message: &str) -> String {
+    // Workflow commands reserve these characters, and properties also reserve ':' and ','
+    let escape = |value: &str| value.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A");
+    let mut properties = format!("file={}", escape(file).replace(':', "%3A").replace(',', "%2C"));
+    if let Some(line) = line {
+        properties.push_str(&format!(",line={}", line));
+    }
+    format!("::{} {}::{}", level, properties, escape(message))
+}
+
+// SetColor turns ANSI color codes in output on or off
+pub fn set_color(enabled: bool) {
+    USE_COLOR.store(enabled, Ordering::Relaxed);
+}
+
+// SetQuiet leaves out confirmations such as "Stashed AGENTS.md for project"
+pub fn set_quiet(enabled: bool) {
+    QUIET.store(enabled, Ordering::Relaxed);
+}
+
+// announce prints a confirmation of what a command did, unless --quiet was given
+fn announce(message: &str) {
+    if !QUIET.load(Ordering::Relaxed) {
+        println!("{}", message);
+    }
+}
+
+// SetStrict makes soft failures, which otherwise print a message and succeed, fail with their error code
+pub fn set_strict(enabled: bool) {
+    STRICT.store(enabled, Ordering::Relaxed);
+}
+
+// fail_if_strict returns error when --strict is in force, so the soft failure it describes stops the command
+fn fail_if_strict(error: AgStashError) -> Result<(), Box<dyn std::error::Error>> {
+    if STRICT.load(Ordering::Relaxed) {
+        return Err(error.into());
+    }
+    Ok(())
+}
+
+// SetConfirm turns the questions asked before overwriting or removing files on, or off so they are answered yes
+pub fn set_confirm(enabled: bool) {
+    CONFIRM.store(enabled, Ordering::Relaxed);
+}
+
+// SetIcons chooses between glyph and ASCII status indicators
+pub fn set_icons(enabled: bool) {
+    USE_ICONS.store(enabled, Ordering::Relaxed);
+}
+
+// indicator renders a colored status symbol followed by its label
+fn indicator(state: Indicator, label: &str) -> String {
+    let color = match state {
+        Indicator::Ok => GREEN,
+        Indicator::Error => RED,
+        Indicator::Drift | Indicator::Warning => YELLOW,
+    };
+    color_string(&format!("{} {}", symbol(state), label), color)
+}
+
+// symbol returns the glyph, or its ASCII stand-in, that marks a status state
+fn symbol(state: Indicator) -> &'static str {
+    let icons = USE_ICONS.load(Ordering::Relaxed);
+    match state {
+        Indicator::Ok => if icons { "✓" } else { "[ok]" },
+        Indicator::Error => if icons { "✗" } else { "[x]" },
+        Indicator::Drift => if icons { "~" } else { "[~]" },
+        Indicator::Warning => if icons { "!" } else { "[!]" },
+    }
+}
+
+// SetTopLevel makes commands target the repository root AGENTS.md even from inside a nested package
+pub fn set_top_level(enabled: bool) {
+    TOP_LEVEL.store(enabled, Ordering::Relaxed);
+}
+
+// SetTargetFile chooses the file init, clean, stash and apply work on, e.g. CLAUDE.md
+pub fn set_target_file(file: ManagedFile) {
+    if let Ok(mut target) = TARGET_FILE.lock() {
+        *target = Some(file);
+    }
+}
+
+// target_file returns the file init, clean, stash and apply work on
+fn target_file() -> ManagedFile {
+    TARGET_FILE
+        .lock()
+        .ok()
+        .and_then(|target| target.clone())
+        .unwrap_or_else(ManagedFile::agents)
+}
+
+// project_context finds the directory holding the AGENTS.md to operate on and the name its stash is keyed by
+fn project_context() -> Result<(PathBuf, String), Box<dyn std::error::Error>> {
+    let (root, project_name) = root_context()?;
+
+    if !TOP_LEVEL.load(Ordering::Relaxed) {
+        if let Some((dir, stash_key)) = nested_context(&root, &project_name)? {
+            utils::log_info(&format!("Using nested AGENTS.md in: {}", dir.display()));
+            return Ok((dir, stash_key));
+        }
+    }
+
+    Ok((root, project_name))
+}
+
+// root_context finds the project root and the key the project's stashes are stored under
+fn root_context() -> Result<(PathBuf, String), Box<dyn std::error::Error>> {
+    let root = utils::get_project_root()?;
+
+    utils::log_info(&format!("Found project root at: {}", root.display()));
+
+    let project_name = registry::project_key(&root)?;
+    let legacy_name = root
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or("Could not extract project name")?
+        .to_string();
+    let unmigrated = store::read_format()?.is_some_and(|format| format < LEGACY_KEYS_FORMAT);
+    if unmigrated || !utils::file_exists(utils::get_stash_path(&project_name)?) {
+        if legacy_stashes(&root, &legacy_name)?.is_empty() {
+            return Ok((root, project_name));
+        }
+        // Until migrate rekeys them, a store from before path hashes keeps its stashes under the directory name, as
+        // does a read-only store
+        if unmigrated || crate::config::load_settings()?.readonly.unwrap_or(false) {
+            return Ok((root, legacy_name));
+        }
+        // A legacy stash migrate could not place, with no registry entry or recorded source, is claimed by the first
+        // project of that name to use it
+        let adopted = adopt_legacy_stashes(&root, &legacy_name, &project_name)?;
+        utils::log_info(&format!("Adopted {} legacy stash(es) of {} as {}", adopted, legacy_name, project_name));
+    }
+
+    Ok((root, project_name))
+}
+
+// Store format from which project keys carry a path hash
+const LEGACY_KEYS_FORMAT: u32 = 4;
+
+// legacy_stashes lists the stashes a project had before keys included a path hash, e.g. "api" and "api+pre-clean";
+// stashes taken from another directory of the same name are left out
+fn legacy_stashes(root: &Path, legacy_name: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let source_of = |stash_key: &str| {
+        metadata::load_metadata(stash_key)
+            .ok()
+            .flatten()
+            .and_then(|stash_metadata| stash_metadata.source_path)
+    };
+    // Slots and nested stashes that do not record a source go wherever the project's own stash goes
+    let project_source = source_of(legacy_name);
+    let canonical_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let mut legacy = Vec::new();
+    for (stash_key, _) in utils::list_stashes(utils::get_stashes_dir()?)? {
+        if !utils::stash_belongs_to(&stash_key, legacy_name) {
+            continue;
+        }
+        let source_path = source_of(&stash_key).or_else(|| project_source.clone());
+        let taken_here = |source_path: String| {
+            Path::new(&source_path).starts_with(root) || Path::new(&source_path).starts_with(&canonical_root)
+        };
+        if source_path.is_none_or(taken_here) {
+            legacy.push(stash_key);
+        }
+    }
+    Ok(legacy)
+}
+
+// adopt_legacy_stashes rekeys a project's legacy stashes under project_name, e.g. "api" and "api+pre-clean" become
+// "api-3f2a91c0" and "api-3f2a91c0+pre-clean", returning how many moved
+fn adopt_legacy_stashes(root: &Path, legacy_name: &str, project_name: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut adopted = 0;
+    for old_key in legacy_stashes(root, legacy_name)? {
+        let new_key = format!("{}{}", project_name, &old_key[legacy_name.len()..]);
+        if rekey_stash(&old_key, &new_key)? {
+            adopted += 1;
+        }
+    }
+    Ok(adopted)
+}
+
+// rekey_project moves a project's stashes, slots and nested stashes from old_name to new_name, returning the keys left
+// behind because new_name already had a stash there
+fn rekey_project(old_name: &str, new_name: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut kept = Vec::new();
+    for (old_key, _) in utils::list_stashes(utils::get_stashes_dir()?)? {
+        if !utils::stash_belongs_to(&old_key, old_name) {
+            continue;
+        }
+        let new_key = format!("{}{}", new_name, &old_key[old_name.len()..]);
+        if !rekey_stash(&old_key, &new_key)? {
+            kept.push(old_key);
+        }
+    }
+    Ok(kept)
+}
+
+// rekey_stash moves a stash with its metadata, history and undo backup from old_key to new_key, returning false and
+// changing nothing when new_key is already taken
+fn rekey_stash(old_key: &str, new_key: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let new_path = utils::get_stash_path(new_key)?;
+    if utils::file_exists(&new_path) {
+        return Ok(false);
+    }
+    fs::rename(utils::get_stash_path(old_key)?, &new_path)?;
+    metadata::rename_metadata(old_key, new_key)?;
+    crate::history::rename_history(old_key, new_key)?;
+    let old_undo_dir = crate::undo::get_undo_dir(old_key)?;
+    let new_undo_dir = crate::undo::get_undo_dir(new_key)?;
+    if old_undo_dir.is_dir() && !new_undo_dir.exists() {
+        fs::rename(old_undo_dir, new_undo_dir)?;
+    }
+    journal::record_event(new_key, "rename", Some(&format!("renamed from {}", old_key)))?;
+    utils::log_info(&format!("Rekeyed stash {} as {}", old_key, new_key));
+    Ok(true)
+}
+
+// nested_context finds the nearest directory below the root, starting from the working directory, that has an AGENTS.md or a stash of its own
+fn nested_context(root: &Path, project_name: &str) -> Result<Option<(PathBuf, String)>, Box<dyn std::error::Error>> {
+    let current_dir = env::current_dir()?;
+
+    for dir in current_dir.ancestors() {
+        if dir == root {
+            break;
+        }
+        let relative = match dir.strip_prefix(root) {
+            Ok(relative) => relative,
+            Err(_) => break,
+        };
+
+        let stash_key = utils::nested_stash_key(project_name, relative);
+        if utils::file_exists(dir.join("AGENTS.md")) || utils::file_exists(utils::get_stash_path(&stash_key)?) {
+            return Ok(Some((dir.to_path_buf(), stash_key)));
+        }
+    }
+
+    Ok(None)
+}
+
+// named_stash resolves a stash named on the command line: the key itself when the store has it, otherwise the same
+// stash of the project known by that name, so "api" finds "api-3f2a91c0" and "api+pre-clean" finds
+// "api-3f2a91c0+pre-clean"
+fn named_stash(name: &str) -> Result<String, Box<dyn std::error::Error>> {
+    utils::validate_project_name(name)?;
+    if utils::file_exists(utils::get_stash_path(name)?) || crate::history::get_history_dir(name)?.is_dir() {
+        return Ok(name.to_string());
+    }
+
+    let project = utils::project_of(name);
+    let suffix = &name[project.len()..];
+    let project_name = match registry::find_project(project) {
+        Ok(root) => registry::project_key(&root)?,
+        Err(error) => match error.downcast_ref::<AgStashError>() {
+            Some(AgStashError::UnknownProject(_)) => match hashed_keys(project)?.as_slice() {
+                [project_name] => project_name.clone(),
+                _ => return Ok(name.to_string()),
+            },
+            _ => return Err(error),
+        },
+    };
+    Ok(format!("{}{}", project_name, suffix))
+}
+
+// hashed_keys lists the project keys in the store made from a directory name and a path hash, e.g. "api-3f2a91c0"
+// for "api"
+fn hashed_keys(dir_name: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut keys: Vec<String> = utils::list_stashes(utils::get_stashes_dir()?)?
+        .into_iter()
+        .map(|(stash_key, _)| utils::project_of(&stash_key).to_string())
+        .filter(|project| {
+            project
+                .strip_prefix(dir_name)
+                .and_then(|rest| rest.strip_prefix('-'))
+                .is_some_and(|hash| hash.len() == 8 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+        })
+        .collect();
+    keys.sort();
+    keys.dedup();
+    Ok(keys)
+}
+
+// current_project_name names the project for the working directory, falling back to the directory itself outside a project
+fn current_project_name() -> Result<String, Box<dyn std::error::Error>> {
+    if let Ok((_, project_name)) = project_context() {
+        return Ok(project_name);
+    }
+
+    registry::project_key(&env::current_dir()?)
+}
+
+// HandleInit creates a default AGENTS.md, or the file chosen with --file, in the current directory if one doesn't exist
 pub fn handle_init(force: bool) -> Result<(), Box<dyn std::error::Error>> {
-    let agents_file_path = Path::new("AGENTS.md");
+    let file = target_file();
+    if file.is_rules_dir() {
+        return rules::init_rules(&env::current_dir()?, &file);
+    }
+    let agents_file_path = Path::new(&file.name);
 
     // Check if we need user confirmation
     let needs_confirmation = utils::file_exists(agents_file_path) && !force;
@@ -27,7 +491,7 @@ pub fn handle_init(force: bool) -> Result<(), Box<dyn std::error::Error>> {
         println!(
             "\n{} {} already exists in the current directory.",
             color_string("WARNING:", &format!("{}{}", YELLOW, BOLD)),
-            color_string("AGENTS.md", BOLD)
+            color_string(&file.name, BOLD)
         );
         println!("Do you want to replace it with a default version?");
         println!("This action will permanently overwrite the current file.\n");
@@ -37,41 +501,83 @@ pub fn handle_init(force: bool) -> Result<(), Box<dyn std::error::Error>> {
         let user_confirmed = get_user_confirmation()?;
         if !user_confirmed {
             utils::log_info("User declined to overwrite, aborting init");
-            println!("\nOperation cancelled. {} was not modified.", color_string("AGENTS.md", BOLD));
+            println!("\nOperation cancelled. {} was not modified.", color_string(&file.name, BOLD));
             return Ok(());
         } else {
             utils::log_info("User confirmed overwrite");
-            println!("\nConfirmed. Creating default {}...", color_string("AGENTS.md", BOLD));
+            println!("\nConfirmed. Creating default {}...", color_string(&file.name, BOLD));
         }
     } else if utils::file_exists(agents_file_path) {
-        utils::log_info("No existing AGENTS.md or force is true, proceeding with init");
+        utils::log_info(&format!("No existing {} or force is true, proceeding with init", file.name));
     }
 
-    // Content to write to the AGENTS.md file - initialize with just the header for an empty template
-    let agents_content = "# AGENTS\n\n\n";
-
-    if let Some(error) = utils::write_file(agents_file_path, agents_content) {
+    // Fill the scaffold with facts from the project manifest, falling back to just the header
+    let facts = manifest::detect_facts(Path::new("."));
+    let agents_content = match configured_template()? {
+        Some(template) => utils::render_template(&template, &facts.to_vars()),
+        None if facts.is_empty() => DEFAULT_TEMPLATE.to_string(),
+        None => {
+            utils::log_info(&format!("Rendering {} with facts from project manifest", file.name));
+            utils::render_template(PROJECT_TEMPLATE, &facts.to_vars())
+        }
+    };
+    // Other files get the same scaffold under their own heading
+    let agents_content = match agents_content.strip_prefix("# AGENTS") {
+        Some(rest) if !file.is_agents() => format!("{}{}", file.header(), rest),
+        _ => agents_content,
+    };
+
+    if let Some(parent) = agents_file_path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    if let Some(error) = utils::write_file(agents_file_path, &agents_content) {
         return Err(error);
     }
-    utils::log_info("Created AGENTS.md file");
-    println!("{} AGENTS.md", color_string("Created", GREEN));
+    utils::log_info(&format!("Created {} file", file.name));
+    announce(&format!("{} {}", color_string("Created", GREEN), file.name));
 
     Ok(())
 }
 
-// HandleClean removes the AGENTS.md file from the current directory if it exists
-pub fn handle_clean() -> Result<(), Box<dyn std::error::Error>> {
-    let agents_file_path = Path::new("AGENTS.md");
+// HandleClean removes the AGENTS.md file, or the file chosen with --file, from the current directory if it exists
+pub fn handle_clean(auto_stash: bool, to_trash: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let file = target_file();
+    let agents_file_path = Path::new(&file.name);
+    if file.is_rules_dir() {
+        let slot = utils::slot_stash_key(&file.stash_key(&current_project_name()?), PRE_CLEAN_SLOT);
+        return rules::clean_rules(&env::current_dir()?, &file, &slot, auto_stash, to_trash);
+    }
+
+    // A link made by apply --link only points at the stash, so removing it loses nothing and needs no snapshot
+    let link_path = env::current_dir()?.join(agents_file_path);
+    let stash_key = file.stash_key(&current_project_name()?);
+    if link::is_linked(&link_path, &stash_key)? {
+        link::unlink(&link_path, &stash_key)?;
+        utils::log_info(&format!("Removed the {} link", file.name));
+        announce(&format!("{} {} link; the stash is kept", color_string("Removed", RED), file.name));
+        return Ok(());
+    }
 
     if utils::file_exists(agents_file_path) {
-        fs::remove_file(agents_file_path)?;
-        utils::log_info("Removed AGENTS.md file");
-        println!("{} AGENTS.md", color_string("Removed", RED));
+        let project_name = file.stash_key(&current_project_name()?);
+        let backup_target = env::current_dir()?.join(agents_file_path);
+        if auto_stash {
+            snapshot_before_clean(&project_name, &backup_target)?;
+        }
+        crate::undo::save_backup(&project_name, "clean", &backup_target)?;
+        utils::discard(&backup_target, to_trash)?;
+        if to_trash {
+            utils::log_info(&format!("Moved {} file to the trash", file.name));
+            announce(&format!("{} {}", color_string("Trashed", RED), file.name));
+        } else {
+            utils::log_info(&format!("Removed {} file", file.name));
+            announce(&format!("{} {}", color_string("Removed", RED), file.name));
+        }
     } else {
-        utils::log_info("AGENTS.md does not exist, nothing to remove");
+        utils::log_info(&format!("{} does not exist, nothing to remove", file.name));
         println!(
             "{} {}",
-            color_string("AGENTS.md", BOLD),
+            color_string(&file.name, BOLD),
             color_string("does not exist.", YELLOW)
         );
     }
@@ -79,24 +585,47 @@ pub fn handle_clean() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-// HandleStash reads the AGENTS.md file from the project root and copies it to a global stash location
-pub fn handle_stash() -> Result<(), Box<dyn std::error::Error>> {
-    let root = utils::get_project_root()?;
+// snapshot_before_clean copies the file into its stash's pre-clean slot so content that was never stashed survives a clean
+fn snapshot_before_clean(project_name: &str, agents_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let slot = utils::slot_stash_key(project_name, PRE_CLEAN_SLOT);
+    let stash_path = utils::get_stash_path(&slot)?;
 
-    utils::log_info(&format!("Found project root at: {}", root.display()));
+    if let Some(error) = store::copy_to_stash(agents_path, &stash_path) {
+        return Err(error);
+    }
+    store::share_stash(&stash_path)?;
+    let message = "automatic snapshot before clean";
+    let stash_metadata = metadata::StashMetadata::new(Some(message), Some(agents_path.display().to_string()));
+    metadata::save_metadata(&slot, &stash_metadata)?;
+    journal::record_event(&slot, "stash", Some(message))?;
+    let file_name = agents_path.file_name().unwrap_or_default().to_string_lossy();
+    utils::log_info(&format!("Snapshotted {} to: {}", file_name, stash_path.display()));
+    announce(&format!("{} {} to {}", color_string("Stashed", GREEN), file_name, color_string(&slot, BOLD)));
+    Ok(())
+}
 
-    let project_name = root
-        .file_name()
-        .and_then(|name| name.to_str())
-        .ok_or("Could not extract project name")?;
+// HandleStash reads the AGENTS.md file, or the file chosen with --file, from the project root and copies it to a global
+// stash location, recording an optional message
+pub fn handle_stash(message: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let file = target_file();
+    let (root, project_name) = project_context()?;
+    let project_name = file.stash_key(&project_name);
+    let project_name = project_name.as_str();
+    if file.is_rules_dir() {
+        if rules::stash_rules(&root, &file, project_name, message)? {
+            registry::register_project(&root)?;
+        }
+        return Ok(());
+    }
 
-    let agents_path = root.join("AGENTS.md");
+    let agents_path = root.join(&file.name);
 
     if !utils::file_exists(&agents_path) {
-        utils::log_info(&format!("AGENTS.md does not exist in project root: {}", agents_path.display()));
+        fail_if_strict(AgStashError::FileNotFound(format!("{} does not exist in project root", file.name)))?;
+        utils::log_info(&format!("{} does not exist in project root: {}", file.name, agents_path.display()));
         println!(
             "{} {}",
-            color_string("AGENTS.md", BOLD),
+            color_string(&file.name, BOLD),
             color_string("does not exist in project root.", YELLOW)
         );
         return Ok(());
@@ -107,62 +636,181 @@ pub fn handle_stash() -> Result<(), Box<dyn std::error::Error>> {
         return Err(error);
     }
 
-    if !utils::is_valid_agents(&agents_content) {
-        utils::log_warn("AGENTS.md content is invalid, stash aborted");
+    if !file.is_valid(&agents_content) {
+        fail_if_strict(AgStashError::InvalidFile(format!("{} content is invalid ({})", file.name, file.requirement())))?;
+        utils::log_warn(&format!("{} content is invalid, stash aborted", file.name));
         println!(
             "{} {}",
-            color_string("AGENTS.md content is invalid (missing '# AGENTS' header).", YELLOW),
+            color_string(&format!("{} content is invalid ({}).", file.name, file.requirement()), YELLOW),
             color_string("Stash aborted.", YELLOW)
         );
         return Ok(());
     }
 
+    stash_agents(&agents_path, project_name, message)?;
+    registry::register_project(&utils::get_project_root()?)?;
+    utils::log_info(&format!("{} stashed for project: {}", file.name, project_name));
+    announce(&format!(
+        "{} {} for {}",
+        color_string("Stashed", GREEN),
+        file.name,
+        color_string(project_name, BOLD)
+    ));
+
+    Ok(())
+}
+
+// stash_agents copies an AGENTS.md into the stash kept under project_name, recording its metadata and history;
+// stash and the embedding API both stash through it
+pub(crate) fn stash_agents(agents_path: &Path, project_name: &str, message: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
     let stash_path = utils::get_stash_path(project_name)?;
 
     utils::log_info(&format!("Stashing to path: {}", stash_path.display()));
-    if let Some(error) = utils::copy_file(&agents_path, &stash_path) {
-        return Err(error);
+    // An AGENTS.md applied with --link already is the stash, and copying a file onto itself would empty it
+    let linked = fs::canonicalize(agents_path).ok().is_some_and(|target| fs::canonicalize(&stash_path).ok() == Some(target));
+    if !linked {
+        crate::history::record_snapshot(project_name, &stash_path, "stash")?;
+        if let Some(error) = store::copy_to_stash(agents_path, &stash_path) {
+            return Err(error);
+        }
     }
-    utils::log_info(&format!("AGENTS.md stashed for project: {}", project_name));
-    println!(
-        "{} AGENTS.md for {}",
-        color_string("Stashed", GREEN),
-        color_string(project_name, BOLD)
-    );
-
+    store::share_stash(&stash_path)?;
+    let mut stash_metadata = metadata::StashMetadata::new(message, Some(agents_path.display().to_string()));
+    // Warnings describe the project's rules rather than one snapshot of them, so they carry over, as does the link
+    if let Some(previous) = metadata::load_metadata(project_name).ok().flatten() {
+        stash_metadata.warnings = previous.warnings;
+        stash_metadata.linked_to = previous.linked_to;
+    }
+    metadata::save_metadata(project_name, &stash_metadata)?;
+    crate::history::record_version(project_name, &stash_path, message)?;
+    journal::record_event(project_name, "stash", message)?;
     Ok(())
 }
 
+// ApplyOptions controls how a stash is written over the project's AGENTS.md
+#[derive(Debug, Clone, Default)]
+pub struct ApplyOptions {
+    // Overwrite an existing AGENTS.md without prompting
+    pub force: bool,
+    // Copy an existing AGENTS.md to AGENTS.md.bak before overwriting it
+    pub backup: bool,
+    // Add the stashed rules below the existing AGENTS.md instead of replacing it
+    pub append: bool,
+    // Apply a recorded version, by id or date, instead of the current stash
+    pub version: Option<String>,
+    // Write the configured global_fallback where there is no project or no stash
+    pub fallback_global: bool,
+}
+
 // HandleApply copies the stashed AGENTS.md file back to the project root
-pub fn handle_apply(force: bool) -> Result<(), Box<dyn std::error::Error>> {
-    let root = utils::get_project_root()?;
+pub fn handle_apply(options: &ApplyOptions) -> Result<(), Box<dyn std::error::Error>> {
+    apply_stash(options)?;
+    Ok(())
+}
 
-    utils::log_info(&format!("Found project root at: {}", root.display()));
-    let project_name = root
-        .file_name()
-        .and_then(|name| name.to_str())
-        .ok_or("Could not extract project name")?;
+// apply_stash copies the project's stash over AGENTS.md and reports whether the file was actually applied
+fn apply_stash(options: &ApplyOptions) -> Result<bool, Box<dyn std::error::Error>> {
+    let context = project_context();
+    let file = target_file();
+    if options.fallback_global && options.version.is_none() {
+        let fallback_to = match &context {
+            Err(_) => {
+                let dir = env::current_dir()?;
+                Some((file.stash_key(&registry::project_key(&dir)?), dir))
+            }
+            Ok((root, project_name)) => {
+                let stash_key = file.stash_key(project_name);
+                (!utils::file_exists(utils::get_stash_path(&stash_key)?)).then(|| (stash_key, root.clone()))
+            }
+        };
+        if let Some((stash_key, dir)) = fallback_to {
+            return apply_global_fallback(&dir, &stash_key, &file, options);
+        }
+    }
+    let (root, project_name) = context?;
+    apply_stash_to(&root, &file.stash_key(&project_name), &file, options)
+}
 
-    let stash_file_path = utils::get_stash_path(project_name)?;
-    let agents_md_file_path = root.join("AGENTS.md");
+// apply_stash_to copies the stash kept under project_name over the file in root
+fn apply_stash_to(
+    root: &Path,
+    project_name: &str,
+    file: &ManagedFile,
+    options: &ApplyOptions,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    if file.is_rules_dir() {
+        return rules::apply_rules(root, file, project_name, options);
+    }
+    let stash_content = match &options.version {
+        Some(spec) => match crate::history::resolve_version(project_name, spec)? {
+            Some(version) => crate::history::read_version(project_name, version.id)?,
+            None => {
+                fail_if_strict(AgStashError::VersionNotFound(format!("No version {} found for project {}", spec, project_name)))?;
+                utils::log_info(&format!("No version {} for project: {}", spec, project_name));
+                println!("No version {} found for project {}", spec, color_string(project_name, BOLD));
+                return Ok(false);
+            }
+        },
+        None => {
+            let stash_file_path = utils::get_stash_path(project_name)?;
+            utils::log_info(&format!("Looking for stash at: {}", stash_file_path.display()));
+
+            // Check if stash exists first
+            if !utils::file_exists(&stash_file_path) {
+                fail_if_strict(AgStashError::StashNotFound(format!("No stash found for project {}", project_name)))?;
+                utils::log_info(&format!("No stash found for project: {}", project_name));
+                println!("No stash found for project {}", color_string(project_name, BOLD));
+                return Ok(false);
+            }
+            let (err, stash_content) = utils::read_file(&stash_file_path);
+            if let Some(error) = err {
+                return Err(error);
+            }
+            stash_content
+        }
+    };
+    apply_content(root, project_name, file, &stash_content, options)
+}
 
-    utils::log_info(&format!("Looking for stash at: {}", stash_file_path.display()));
+// apply_content writes stash_content over the file in root once the apply guard and the user allow it, keeping an
+// undo backup and a history snapshot of what it replaces
+fn apply_content(
+    root: &Path,
+    project_name: &str,
+    file: &ManagedFile,
+    stash_content: &str,
+    options: &ApplyOptions,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let agents_md_file_path = root.join(&file.name);
 
-    // Check if stash exists first
-    if !utils::file_exists(&stash_file_path) {
-        utils::log_info(&format!("No stash found for project: {}", project_name));
-        println!("No stash found for project {}", color_string(project_name, BOLD));
-        return Ok(());
+    apply_allowed(root, options.force)?;
+
+    // Leave an identical AGENTS.md untouched so its mtime survives and file watchers stay quiet
+    if utils::file_exists(&agents_md_file_path) && fs::read(&agents_md_file_path)? == stash_content.as_bytes() {
+        utils::log_info(&format!("{} already matches the stash, nothing to apply", file.name));
+        println!(
+            "{} is already up to date for {}",
+            color_string(&file.name, BOLD),
+            color_string(project_name, BOLD)
+        );
+        return Ok(true);
     }
 
-    // Check if we need user confirmation
-    let needs_confirmation = utils::file_exists(&agents_md_file_path) && !force;
+    // Warnings added with annotate are shown before anything is written, prompt or not
+    if let Some(stash_metadata) = metadata::load_metadata(project_name).ok().flatten() {
+        for warning in &stash_metadata.warnings {
+            println!("{} {}", indicator(Indicator::Warning, "Stash warning:"), warning);
+        }
+    }
+
+    // Check if we need user confirmation; appending keeps the current rules so it never needs one
+    let needs_confirmation = utils::file_exists(&agents_md_file_path) && !options.force && !options.append;
     if needs_confirmation {
-        utils::log_info("AGENTS.md exists and force is false, prompting user");
+        utils::log_info(&format!("{} exists and force is false, prompting user", file.name));
         println!(
             "\n{} {} already exists in the current directory.",
             color_string("WARNING:", &format!("{}{}", YELLOW, BOLD)),
-            color_string("AGENTS.md", BOLD)
+            color_string(&file.name, BOLD)
         );
         println!("Do you want to replace it with the stashed version?");
         println!("This action will permanently overwrite the current file.\n");
@@ -172,21 +820,105 @@ pub fn handle_apply(force: bool) -> Result<(), Box<dyn std::error::Error>> {
         let user_confirmed = get_user_confirmation()?;
         if !user_confirmed {
             utils::log_info("User declined to overwrite, aborting apply");
-            println!("\nOperation cancelled. {} was not modified.", color_string("AGENTS.md", BOLD));
-            return Ok(());
+            println!("\nOperation cancelled. {} was not modified.", color_string(&file.name, BOLD));
+            return Ok(false);
         } else {
             utils::log_info("User confirmed overwrite");
-            println!("\nConfirmed. Applying stashed {}...", color_string("AGENTS.md", BOLD));
+            println!("\nConfirmed. Applying stashed {}...", color_string(&file.name, BOLD));
         }
     } else {
-        utils::log_info("No existing AGENTS.md or force is true, proceeding with apply");
+        utils::log_info(&format!("No existing {} or force is true, proceeding with apply", file.name));
     }
 
     // Validate and apply the stash
-    apply_stash_content(&stash_file_path, &agents_md_file_path, project_name)
+    apply_stash_content(stash_content, &agents_md_file_path, project_name, file, options)
+}
+
+// configured_template reads the file the template setting names, if it is set
+fn configured_template() -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let Some(configured) = crate::config::load_settings()?.template else {
+        return Ok(None);
+    };
+    let path = utils::expand_home(&configured)?;
+    utils::log_info(&format!("Rendering the template at {}", path.display()));
+    let (err, content) = utils::read_file(&path);
+    if let Some(error) = err {
+        return Err(AgStashError::InvalidConfig(format!("Could not read template {}: {}", path.display(), error)).into());
+    }
+    Ok(Some(content))
+}
+
+// global_fallback reads the configured machine-wide AGENTS.md, returning its path and content, or None when unset
+fn global_fallback() -> Result<Option<(PathBuf, String)>, Box<dyn std::error::Error>> {
+    let Some(configured) = crate::config::load_settings()?.global_fallback else {
+        return Ok(None);
+    };
+    let path = utils::expand_home(&configured)?;
+    let (err, content) = utils::read_file(&path);
+    if let Some(error) = err {
+        return Err(format!("Could not read global_fallback {}: {}", path.display(), error).into());
+    }
+    Ok(Some((path, content)))
+}
+
+// apply_global_fallback writes the machine-wide AGENTS.md into dir, which has no project or no stash of its own, the
+// way apply writes a stash kept under stash_key
+fn apply_global_fallback(
+    dir: &Path,
+    stash_key: &str,
+    file: &ManagedFile,
+    options: &ApplyOptions,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let Some((fallback_path, content)) = global_fallback()? else {
+        println!(
+            "{} set global_fallback to the path of a machine-wide AGENTS.md to use --fallback-global",
+            indicator(Indicator::Error, "No global fallback configured:")
+        );
+        return Ok(false);
+    };
+    if file.is_rules_dir() {
+        return Err(AgStashError::Usage(format!("The global fallback is a single file and can't be applied to {}", file.name)).into());
+    }
+
+    utils::log_info(&format!("Applying global fallback {} to: {}", fallback_path.display(), dir.join(&file.name).display()));
+    apply_content(dir, stash_key, file, &content, options)
+}
+
+// apply_allowed enforces the project's apply_only_on rule: outside the listed branches, or while a rebase or
+// merge is underway, apply fails with ApplyRefused unless forced, in which case it only warns
+fn apply_allowed(root: &Path, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(branches) = crate::config::load_settings()?.apply_only_on else {
+        return Ok(());
+    };
+    if git::get_git_dir(root).is_none() {
+        return Ok(());
+    }
+
+    let problem = match (git::operation_in_progress(root), git::current_branch(root)) {
+        (Some(operation), _) => format!("a {} is in progress", operation),
+        (None, Some(branch)) if branches.iter().any(|pattern| git::branch_matches(&branch, pattern)) => return Ok(()),
+        (None, Some(branch)) => format!("branch {} is not in apply_only_on ({})", branch, branches.join(", ")),
+        (None, None) => "HEAD is detached".to_string(),
+    };
+
+    utils::log_info(&format!("Apply guard tripped: {}", problem));
+    if force {
+        println!("{} {}, applying anyway", indicator(Indicator::Warning, "Apply guard:"), problem);
+        return Ok(());
+    }
+    Err(AgStashError::ApplyRefused(format!(
+        "Refusing to apply: {}. Nothing was modified; use --force to apply anyway",
+        problem
+    ))
+    .into())
 }
 
 fn get_user_confirmation() -> Result<bool, Box<dyn std::error::Error>> {
+    // With confirm = false the question is answered on the user's behalf
+    if !CONFIRM.load(Ordering::Relaxed) {
+        println!("yes");
+        return Ok(true);
+    }
     let mut input = String::new();
     io::stdin().read_line(&mut input)?;
 
@@ -203,53 +935,140 @@ fn get_user_confirmation() -> Result<bool, Box<dyn std::error::Error>> {
     Ok(false)
 }
 
-// apply_stash_content validates the stashed content and copies it to the project's AGENTS.md file
+// apply_stash_content validates the stashed content and writes it to the project's AGENTS.md file, reporting whether it was applied
 fn apply_stash_content(
-    stash_file_path: &Path,
+    stash_content: &str,
     agents_md_file_path: &Path,
     project_name: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    utils::log_info(&format!("Reading stash content from: {}", stash_file_path.display()));
-    let (err, stash_content) = utils::read_file(stash_file_path);
-    if let Some(error) = err {
-        return Err(error);
-    }
-
-    if !utils::is_valid_agents(&stash_content) {
+    file: &ManagedFile,
+    options: &ApplyOptions,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    if !file.is_valid(stash_content) {
         utils::log_warn("Stash content is invalid, apply aborted");
         println!(
             "{} {}",
-            color_string("Stash content is invalid (missing '# AGENTS' header).", YELLOW),
+            color_string(&format!("Stash content is invalid ({}).", file.requirement()), YELLOW),
             color_string("Apply aborted.", YELLOW)
         );
-        return Ok(());
+        return Ok(false);
     }
 
     utils::log_info(&format!("Applying stash to: {}", agents_md_file_path.display()));
-    if let Some(error) = utils::copy_file(stash_file_path, agents_md_file_path) {
-        return Err(error);
+    if options.backup && utils::file_exists(agents_md_file_path) {
+        let backup_path = utils::backup_path_for(agents_md_file_path);
+        if let Some(error) = utils::copy_file(agents_md_file_path, &backup_path) {
+            return Err(error);
+        }
+        utils::log_info(&format!("Backed up {} to: {}", file.name, backup_path.display()));
+        announce(&format!("{} {} to {}", color_string("Backed up", GREEN), file.name, backup_path.display()));
     }
-    utils::log_info(&format!("AGENTS.md applied for project: {}", project_name));
-    println!(
-        "{} AGENTS.md for {}",
+    if options.append && utils::file_exists(agents_md_file_path) {
+        let (err, existing_content) = utils::read_file(agents_md_file_path);
+        if let Some(error) = err {
+            return Err(error);
+        }
+        let merged = append_rules(&existing_content, stash_content);
+        if merged == existing_content {
+            utils::log_info(&format!("{} already contains every stashed rule, nothing to append", file.name));
+            println!(
+                "{} is already up to date for {}",
+                color_string(&file.name, BOLD),
+                color_string(project_name, BOLD)
+            );
+            return Ok(true);
+        }
+        write_applied(project_name, agents_md_file_path, &merged, Some("appended"))?;
+        utils::log_info(&format!("Stash appended to {} for project: {}", file.name, project_name));
+        announce(&format!(
+            "{} stash to {} for {}",
+            color_string("Appended", GREEN),
+            file.name,
+            color_string(project_name, BOLD)
+        ));
+        return Ok(true);
+    }
+
+    write_applied(project_name, agents_md_file_path, stash_content, None)?;
+    utils::log_info(&format!("{} applied for project: {}", file.name, project_name));
+    announce(&format!(
+        "{} {} for {}",
         color_string("Applied", GREEN),
+        file.name,
         color_string(project_name, BOLD)
-    );
+    ));
+
+    Ok(true)
+}
 
+// write_applied writes content over path for the stash kept under stash_key without printing anything, saving what it
+// replaces for undo and as an apply snapshot; apply and the embedding API both write through it
+pub(crate) fn write_applied(
+    stash_key: &str,
+    path: &Path,
+    content: &str,
+    detail: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    crate::undo::save_backup(stash_key, "apply", path)?;
+    crate::history::record_apply_snapshot(stash_key, path)?;
+    // Files such as .github/copilot-instructions.md live in a directory a fresh checkout may not have yet
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    // Writing through a link made by apply --link would change the stash too, so the file becomes a copy again
+    link::unlink(path, stash_key)?;
+    if let Some(error) = utils::write_file(path, content) {
+        return Err(error);
+    }
+    journal::record_event(stash_key, "apply", detail)?;
     Ok(())
 }
 
-// HandleUninstall completely removes the .agstash directory and all its contents from the user's home directory
-pub fn handle_uninstall() -> Result<(), Box<dyn std::error::Error>> {
+// append_rules puts the stashed rules below the existing ones, dropping the stash's '# AGENTS' header and bullets already present
+fn append_rules(existing: &str, stash: &str) -> String {
+    let mut document = markdown::Document::parse(existing);
+    let kept = document.sections.len();
+    document.append(markdown::Document::parse(stash));
+
+    // Only the added part is rendered so the existing file keeps its exact formatting
+    let added = markdown::Document {
+        sections: document.sections.split_off(kept),
+    };
+    if added.sections.is_empty() {
+        return existing.to_string();
+    }
+    if existing.trim().is_empty() {
+        return added.render();
+    }
+    format!("{}\n\n{}", existing.trim_end(), added.render())
+}
+
+// HandleUninstall completely removes the .agstash directory and all its contents from the user's home directory, apart
+// from backups kept inside it, which outlive the store
+pub fn handle_uninstall(to_trash: bool, backup_dir: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
     let agstash_dir = utils::get_agstash_dir()?;
 
     utils::log_info(&format!("Located agstash directory at: {}", agstash_dir.display()));
 
     if utils::file_exists(&agstash_dir) {
         utils::log_info(&format!("Removing agstash directory: {}", agstash_dir.display()));
-        fs::remove_dir_all(&agstash_dir)?;
+        let backup_dir = crate::archive::get_backup_dir(backup_dir)?;
+        let kept = backup_dir.starts_with(&agstash_dir) && backup_dir.is_dir() && backup_dir != agstash_dir;
+        if kept {
+            for entry in fs::read_dir(&agstash_dir)? {
+                let path = entry?.path();
+                if !backup_dir.starts_with(&path) {
+                    utils::discard(&path, to_trash)?;
+                }
+            }
+        } else {
+            utils::discard(&agstash_dir, to_trash)?;
+        }
         utils::log_info("Successfully removed agstash directory");
-        println!("{} {}", color_string("Removed", RED), agstash_dir.display());
+        let verb = if to_trash { "Trashed" } else { "Removed" };
+        println!("{} {}", color_string(verb, RED), agstash_dir.display());
+        if kept {
+            println!("Kept backups in {}", backup_dir.display());
+        }
     } else {
         utils::log_info(&format!("agstash directory does not exist: {}", agstash_dir.display()));
         println!(
@@ -266,12 +1085,16 @@ pub fn handle_uninstall() -> Result<(), Box<dyn std::error::Error>> {
 mod tests {
     use std::fs;
     use std::env;
-    use std::path::{Path, PathBuf};
+    use std::path::Path;
     use tempfile::TempDir;
     use serial_test::serial;
 
     use crate::commands;
+    use crate::error;
+    use crate::managed::ManagedFile;
+    use crate::store;
     use crate::utils;
+    use crate::testing::TestEnv;
 
     #[test]
     #[serial]
@@ -307,6 +1130,469 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    #[serial]
+    fn test_handle_init_with_manifest() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(|| {
+            let _ = env::set_current_dir(&original_dir);
+        });
+
+        // Create a Cargo.toml so init can pick up project facts
+        fs::write("Cargo.toml", "[package]\nname = \"demo\"\n").unwrap();
+
+        let result = commands::handle_init(true);
+        assert!(result.is_ok());
+
+        let content = fs::read_to_string(temp_dir.path().join("AGENTS.md")).unwrap();
+        assert!(content.starts_with("# AGENTS\n"));
+        assert!(content.contains("- Package: demo\n"));
+        assert!(content.contains("- Test command: `cargo test`\n"));
+        assert!(!content.contains("Toolchain"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_handle_init_with_template() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::write("Cargo.toml", "[package]\nname = \"demo\"\n").unwrap();
+        fs::write("template.md", "# AGENTS\n\nPackage {{package_name}}, built with {{toolchain}}\n- Be brief\n").unwrap();
+        env::set_var("AGSTASH_TEMPLATE", temp_dir.path().join("template.md"));
+
+        // Ensure cleanup happens, asking before overwriting again
+        let _cleanup = defer::defer(|| {
+            env::remove_var("AGSTASH_TEMPLATE");
+            commands::set_confirm(true);
+            let _ = env::set_current_dir(&original_dir);
+        });
+
+        commands::handle_init(true).unwrap();
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- Be brief\n");
+
+        // With confirm off the overwrite question is answered without reading stdin
+        fs::write("AGENTS.md", "# AGENTS\n\n- Mine\n").unwrap();
+        commands::set_confirm(false);
+        commands::handle_init(false).unwrap();
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- Be brief\n");
+    }
+
+    #[test]
+    #[serial]
+    fn test_indicator_without_icons() {
+        commands::set_icons(false);
+        let _cleanup = defer::defer(|| commands::set_icons(true));
+
+        assert!(commands::indicator(commands::Indicator::Ok, "in sync").contains("[ok] in sync"));
+        assert!(commands::indicator(commands::Indicator::Drift, "drift").contains("[~] drift"));
+
+        commands::set_icons(true);
+        assert!(commands::indicator(commands::Indicator::Error, "missing").contains("✗ missing"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_project_stash_keys() {
+        // Two projects with the same directory name
+        let temp_dir = TempDir::new().unwrap();
+        let first = temp_dir.path().join("work").join("api");
+        let second = temp_dir.path().join("oss").join("api");
+        for project in [&first, &second] {
+            fs::create_dir_all(project.join(".git")).unwrap();
+        }
+        let original_dir = env::current_dir().unwrap();
+
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+        store::create_store().unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(move || {
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        let first_key = utils::project_stash_key(&first).unwrap();
+        let second_key = utils::project_stash_key(&second).unwrap();
+        assert_ne!(first_key, second_key);
+        assert!(first_key.starts_with("api-"));
+
+        // A store from before path hashes keeps working under the directory name until migrate rekeys it
+        let legacy_path = utils::get_stash_path("api").unwrap();
+        crate::store::write_format(3).unwrap();
+        fs::write(&legacy_path, "# AGENTS\n\n- work\n").unwrap();
+        let source_path = first.canonicalize().unwrap().join("AGENTS.md").display().to_string();
+        crate::metadata::save_metadata("api", &crate::metadata::StashMetadata::new(None, Some(source_path))).unwrap();
+        fs::write(utils::get_stash_path("api+pre-clean").unwrap(), "# AGENTS\n").unwrap();
+
+        env::set_current_dir(&second).unwrap();
+        fs::write("AGENTS.md", "# AGENTS\n\n- oss\n").unwrap();
+        commands::handle_stash(None).unwrap();
+        assert!(utils::get_stash_path(&second_key).unwrap().exists());
+
+        env::set_current_dir(&first).unwrap();
+        commands::handle_apply(&commands::ApplyOptions::default()).unwrap();
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- work\n");
+        assert!(legacy_path.exists());
+
+        // Migrate moves them to the key of the project they were taken from
+        commands::handle_migrate().unwrap();
+        assert!(!legacy_path.exists());
+        assert!(utils::get_stash_path(&first_key).unwrap().exists());
+        assert!(utils::get_stash_path(&utils::slot_stash_key(&first_key, "pre-clean")).unwrap().exists());
+        assert_eq!(
+            fs::read_to_string(utils::get_stash_path(&second_key).unwrap()).unwrap(),
+            "# AGENTS\n\n- oss\n"
+        );
+
+        // A directory name finds the hashed key of the registered project, or of the only stash made from it
+        assert_eq!(super::named_stash(&first_key).unwrap(), first_key);
+        assert_eq!(super::named_stash("api").unwrap(), second_key);
+        fs::remove_file(crate::registry::get_registry_path().unwrap()).unwrap();
+        assert_eq!(super::named_stash("api").unwrap(), "api");
+        fs::remove_file(utils::get_stash_path(&second_key).unwrap()).unwrap();
+        assert_eq!(super::named_stash("api+pre-clean").unwrap(), utils::slot_stash_key(&first_key, "pre-clean"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_unplaced_legacy_stash_after_migrate() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path().join("api");
+        fs::create_dir_all(project.join(".git")).unwrap();
+        let original_dir = env::current_dir().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path().join("home")).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(move || {
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        // A store from before metadata has nothing that says which project a stash came from
+        let legacy_path = utils::get_stash_path("api").unwrap();
+        fs::create_dir_all(legacy_path.parent().unwrap()).unwrap();
+        fs::write(&legacy_path, "# AGENTS\n\n- legacy\n").unwrap();
+        commands::handle_migrate().unwrap();
+        assert_eq!(store::read_format().unwrap(), Some(store::STORE_FORMAT));
+
+        // The project of that name claims it the first time it looks for its stash
+        env::set_current_dir(&project).unwrap();
+        commands::handle_apply(&commands::ApplyOptions::default()).unwrap();
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- legacy\n");
+        assert!(!legacy_path.exists());
+        assert!(utils::get_stash_path(&utils::project_stash_key(&project).unwrap()).unwrap().exists());
+    }
+
+    #[test]
+    #[serial]
+    fn test_nested_stash_resolution() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(|| {
+            let _ = env::set_current_dir(&original_dir);
+            commands::set_top_level(false);
+        });
+
+        // Create a .git directory to establish project root
+        fs::create_dir(".git").unwrap();
+
+        // Set up HOME environment variable to temp directory
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        fs::write("AGENTS.md", "# AGENTS\n\n- root\n").unwrap();
+        fs::create_dir_all("packages/api/src").unwrap();
+        fs::write("packages/api/AGENTS.md", "# AGENTS\n\n- api\n").unwrap();
+        env::set_current_dir(temp_dir.path().join("packages/api/src")).unwrap();
+
+        let project_name = &utils::project_stash_key(temp_dir.path()).unwrap();
+        let nested_key = format!("{}@packages--api", project_name);
+
+        // The nearest AGENTS.md is stashed under its own key
+        commands::handle_stash(None).unwrap();
+        let nested_stash = utils::get_stash_path(&nested_key).unwrap();
+        assert_eq!(fs::read_to_string(&nested_stash).unwrap(), "# AGENTS\n\n- api\n");
+
+        // --top targets the repository root instead
+        commands::set_top_level(true);
+        commands::handle_stash(None).unwrap();
+        let root_stash = utils::get_stash_path(project_name).unwrap();
+        assert_eq!(fs::read_to_string(&root_stash).unwrap(), "# AGENTS\n\n- root\n");
+        commands::set_top_level(false);
+
+        // The nested stash is still found once the nested file is gone
+        fs::remove_file(temp_dir.path().join("packages/api/AGENTS.md")).unwrap();
+        commands::handle_apply(&commands::ApplyOptions {
+            force: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let restored = fs::read_to_string(temp_dir.path().join("packages/api/AGENTS.md")).unwrap();
+        assert_eq!(restored, "# AGENTS\n\n- api\n");
+        assert!(commands::handle_status().is_ok());
+    }
+
+    #[test]
+    #[serial]
+    fn test_handle_apply_backup() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(|| {
+            let _ = env::set_current_dir(&original_dir);
+        });
+
+        // Create a .git directory to establish project root
+        fs::create_dir(".git").unwrap();
+
+        // Set up HOME environment variable to temp directory
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        fs::write("AGENTS.md", "# AGENTS\n\n- stashed\n").unwrap();
+        commands::handle_stash(None).unwrap();
+
+        let options = commands::ApplyOptions {
+            force: true,
+            backup: true,
+            ..Default::default()
+        };
+
+        // The first backup goes to AGENTS.md.bak
+        fs::write("AGENTS.md", "# AGENTS\n\n- local one\n").unwrap();
+        commands::handle_apply(&options).unwrap();
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- stashed\n");
+        assert_eq!(fs::read_to_string("AGENTS.md.bak").unwrap(), "# AGENTS\n\n- local one\n");
+
+        // Later backups never overwrite an existing one
+        fs::write("AGENTS.md", "# AGENTS\n\n- local two\n").unwrap();
+        commands::handle_apply(&options).unwrap();
+        assert_eq!(fs::read_to_string("AGENTS.md.bak").unwrap(), "# AGENTS\n\n- local one\n");
+        let backups = fs::read_dir(".")
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().ends_with(".bak"))
+            .count();
+        assert_eq!(backups, 2);
+    }
+
+    #[test]
+    #[serial]
+    fn test_handle_apply_up_to_date() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(|| {
+            let _ = env::set_current_dir(&original_dir);
+        });
+
+        // Create a .git directory to establish project root
+        fs::create_dir(".git").unwrap();
+
+        // Set up HOME environment variable to temp directory
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        fs::write("AGENTS.md", "# AGENTS\n\n- same\n").unwrap();
+        commands::handle_stash(None).unwrap();
+
+        // Nothing is rewritten, so there is nothing to undo either, even without force
+        commands::handle_apply(&commands::ApplyOptions::default()).unwrap();
+        let project_name = &utils::project_stash_key(temp_dir.path()).unwrap();
+        assert!(crate::undo::load_backup(project_name).unwrap().is_none());
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- same\n");
+    }
+
+    #[test]
+    #[serial]
+    fn test_handle_apply_only_on() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(|| {
+            let _ = env::set_current_dir(&original_dir);
+        });
+
+        // Create a .git directory on a feature branch to establish project root
+        fs::create_dir(".git").unwrap();
+        fs::write(".git/HEAD", "ref: refs/heads/feature\n").unwrap();
+        fs::write(".agstash.toml", "apply_only_on = [\"main\", \"release/*\"]\n").unwrap();
+
+        // Set up HOME environment variable to temp directory
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        fs::write("AGENTS.md", "# AGENTS\n\n- stashed\n").unwrap();
+        commands::handle_stash(None).unwrap();
+        fs::write("AGENTS.md", "# AGENTS\n\n- local\n").unwrap();
+        let forced = commands::ApplyOptions {
+            force: true,
+            ..Default::default()
+        };
+
+        // Refused on a branch outside the list
+        let refused = commands::handle_apply(&commands::ApplyOptions::default()).unwrap_err();
+        assert_eq!(crate::error::classify(refused.as_ref()).code(), "E033");
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- local\n");
+
+        // Refused mid-rebase, even on an allowed branch
+        fs::write(".git/HEAD", "ref: refs/heads/release/2.0\n").unwrap();
+        fs::create_dir(".git/rebase-merge").unwrap();
+        assert!(commands::handle_apply(&commands::ApplyOptions::default()).is_err());
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- local\n");
+
+        // Force applies anyway
+        commands::handle_apply(&forced).unwrap();
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- stashed\n");
+
+        // Allowed branches apply normally
+        fs::remove_dir(".git/rebase-merge").unwrap();
+        fs::remove_file("AGENTS.md").unwrap();
+        commands::handle_apply(&commands::ApplyOptions::default()).unwrap();
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- stashed\n");
+    }
+
+    #[test]
+    #[serial]
+    fn test_stash_apply_roundtrip_property() {
+        use proptest::prelude::*;
+        use proptest::test_runner::{Config, TestRunner};
+
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(|| {
+            let _ = env::set_current_dir(&original_dir);
+        });
+
+        // Create a .git directory to establish project root
+        fs::create_dir(".git").unwrap();
+
+        // Set up HOME environment variable to temp directory
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        // Any text after the header, with any mix of line endings and an optional byte order mark
+        let line_ending = prop_oneof![Just(""), Just("\n"), Just("\r\n"), Just("\r")];
+        let lines = prop::collection::vec(("\\PC{0,40}", line_ending), 0..20);
+        let strategy = (any::<bool>(), "[ \t\r\n]{0,3}", lines).prop_map(|(bom, leading, lines)| {
+            let mut content = if bom { "\u{feff}".to_string() } else { String::new() };
+            content.push_str(&leading);
+            content.push_str("# AGENTS");
+            for (text, ending) in lines {
+                content.push_str(ending);
+                content.push_str(&text);
+            }
+            content
+        });
+
+        let mut runner = TestRunner::new(Config { cases: 64, ..Config::default() });
+        runner
+            .run(&strategy, |content| {
+                fs::write("AGENTS.md", &content).unwrap();
+                commands::handle_stash(None).unwrap();
+                fs::remove_file("AGENTS.md").unwrap();
+                commands::handle_apply(&commands::ApplyOptions::default()).unwrap();
+                prop_assert_eq!(fs::read("AGENTS.md").unwrap(), content.into_bytes());
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn test_handle_apply_fallback_global() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(|| {
+            let _ = env::set_current_dir(&original_dir);
+        });
+
+        // Create a .git directory to establish project root
+        fs::create_dir(".git").unwrap();
+
+        // Set up HOME environment variable to temp directory
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        let fallback = commands::ApplyOptions {
+            fallback_global: true,
+            ..Default::default()
+        };
+
+        // Nothing to write until a fallback is configured
+        commands::handle_apply(&fallback).unwrap();
+        assert!(!utils::file_exists("AGENTS.md"));
+
+        fs::write(temp_dir.path().join("global.md"), "# AGENTS\n\n- global\n").unwrap();
+        let config_path = crate::config::get_user_config_path().unwrap();
+        fs::create_dir_all(config_path.parent().unwrap()).unwrap();
+        fs::write(&config_path, "global_fallback = \"~/global.md\"\n").unwrap();
+        commands::handle_apply(&fallback).unwrap();
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- global\n");
+        assert!(commands::handle_status().is_ok());
+
+        // Replacing a file keeps an undo backup of it, as any apply does
+        fs::write("AGENTS.md", "# AGENTS\n\n- local\n").unwrap();
+        let forced = commands::ApplyOptions {
+            force: true,
+            ..fallback.clone()
+        };
+        commands::handle_apply(&forced).unwrap();
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- global\n");
+        commands::handle_undo(true).unwrap();
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- local\n");
+
+        // --file picks the file the fallback is written to
+        commands::set_target_file(ManagedFile::new("CLAUDE.md").unwrap());
+        let _reset_file = defer::defer(|| commands::set_target_file(ManagedFile::agents()));
+        commands::handle_apply(&fallback).unwrap();
+        assert_eq!(fs::read_to_string("CLAUDE.md").unwrap(), "# AGENTS\n\n- global\n");
+        commands::set_target_file(ManagedFile::agents());
+
+        // A stash always wins over the fallback
+        commands::handle_stash(None).unwrap();
+        fs::remove_file("AGENTS.md").unwrap();
+        commands::handle_apply(&fallback).unwrap();
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- local\n");
+    }
+
+    #[test]
+    fn test_append_rules() {
+        let existing = "# AGENTS\n\n- Use tabs\n- Run cargo test\n";
+        let stash = "# AGENTS\n\n- Run cargo test\n- Prefer small commits\n\n## Style\n\n- Use tabs\n";
+
+        let merged = super::append_rules(existing, stash);
+        assert_eq!(
+            merged,
+            "# AGENTS\n\n- Use tabs\n- Run cargo test\n\n- Prefer small commits\n\n## Style\n"
+        );
+
+        // Appending the same rules again changes nothing
+        assert_eq!(super::append_rules(existing, existing), existing);
+    }
+
     #[test]
     #[serial]
     fn test_handle_clean() {
@@ -323,6 +1609,9 @@ mod tests {
         // Create a .git directory to establish project root
         fs::create_dir(".git").unwrap();
 
+        // Set up HOME environment variable to temp directory
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
         // Create an AGENTS.md file
         let agents_file = "AGENTS.md";
         let agents_content = "# AGENTS\n\nTest content";
@@ -332,14 +1621,26 @@ mod tests {
         assert!(Path::new(agents_file).exists());
 
         // Run clean command
-        let result = commands::handle_clean();
+        let result = commands::handle_clean(true, false);
         assert!(result.is_ok());
 
         // Check if AGENTS.md was removed
         assert!(!Path::new(agents_file).exists());
 
+        // Check the content was kept in the pre-clean slot
+        let project_name = &utils::project_stash_key(temp_dir.path()).unwrap();
+        let slot = utils::slot_stash_key(project_name, "pre-clean");
+        let snapshot = utils::get_stash_path(&slot).unwrap();
+        assert_eq!(fs::read_to_string(&snapshot).unwrap(), agents_content);
+
+        // Cleaning without auto-stash leaves the slot alone
+        fs::write(agents_file, "# AGENTS\n\nOther content").unwrap();
+        assert!(commands::handle_clean(false, false).is_ok());
+        assert!(!Path::new(agents_file).exists());
+        assert_eq!(fs::read_to_string(&snapshot).unwrap(), agents_content);
+
         // Try to clean again - should not error
-        let result = commands::handle_clean();
+        let result = commands::handle_clean(true, false);
         assert!(result.is_ok());
     }
 
@@ -360,15 +1661,7 @@ mod tests {
         fs::create_dir(".git").unwrap();
 
         // Set up HOME environment variable to temp directory
-        let original_home = env::var("HOME").unwrap_or_default();
-        env::set_var("HOME", temp_dir.path());
-        
-        // Ensure cleanup happens
-        let _cleanup_home = defer::defer(move || {
-            if !original_home.is_empty() {
-                env::set_var("HOME", original_home);
-            }
-        });
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
 
         // Create an AGENTS.md file with valid content
         let agents_file = "AGENTS.md";
@@ -376,16 +1669,12 @@ mod tests {
         fs::write(agents_file, agents_content).unwrap();
 
         // Run stash command
-        let result = commands::handle_stash();
+        let result = commands::handle_stash(None);
         assert!(result.is_ok());
 
         // Check if the file was stashed
-        let project_name = temp_dir.path().file_name().unwrap().to_str().unwrap();
-        let stash_path = dirs::home_dir()
-            .unwrap()
-            .join(".agstash")
-            .join("stashes")
-            .join(format!("stash-{}.md", project_name));
+        let project_name = &utils::project_stash_key(temp_dir.path()).unwrap();
+        let stash_path = utils::get_stash_path(project_name).unwrap();
             
         assert!(stash_path.exists());
 
@@ -411,15 +1700,7 @@ mod tests {
         fs::create_dir(".git").unwrap();
 
         // Set up HOME environment variable to temp directory
-        let original_home = env::var("HOME").unwrap_or_default();
-        env::set_var("HOME", temp_dir.path());
-        
-        // Ensure cleanup happens
-        let _cleanup_home = defer::defer(move || {
-            if !original_home.is_empty() {
-                env::set_var("HOME", original_home);
-            }
-        });
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
 
         // Create an AGENTS.md file with invalid content (missing header)
         let agents_file = "AGENTS.md";
@@ -427,39 +1708,112 @@ mod tests {
         fs::write(agents_file, agents_content).unwrap();
 
         // Run stash command - should not error but should not stash
-        let result = commands::handle_stash();
+        let result = commands::handle_stash(None);
         assert!(result.is_ok());
 
         // Check that no stash was created
-        let project_name = temp_dir.path().file_name().unwrap().to_str().unwrap();
-        let stash_path = dirs::home_dir()
-            .unwrap()
-            .join(".agstash")
-            .join("stashes")
-            .join(format!("stash-{}.md", project_name));
+        let project_name = &utils::project_stash_key(temp_dir.path()).unwrap();
+        let stash_path = utils::get_stash_path(project_name).unwrap();
             
         // The stash directory might still be created even if no file is stashed
         // So we check if the specific stash file exists
         assert!(!stash_path.exists());
     }
 
+    #[test]
+    #[serial]
+    fn test_strict() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir(".git").unwrap();
+
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+
+        // Ensure cleanup happens, leaving strict mode off for the other tests
+        let _cleanup = defer::defer(move || {
+            commands::set_strict(false);
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        // Soft failures succeed until strict mode is on
+        assert!(commands::handle_stash(None).is_ok());
+        assert!(commands::handle_apply(&commands::ApplyOptions::default()).is_ok());
+        commands::set_strict(true);
+        let code = |result: Result<(), Box<dyn std::error::Error>>| error::classify(result.unwrap_err().as_ref()).code();
+        assert_eq!(code(commands::handle_stash(None)), "E012");
+        assert_eq!(code(commands::handle_apply(&commands::ApplyOptions::default())), "E010");
+        fs::write("AGENTS.md", "No header").unwrap();
+        assert_eq!(code(commands::handle_stash(None)), "E013");
+    }
+
+    #[test]
+    #[serial]
+    fn test_target_file() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir(".git").unwrap();
+
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+
+        // Ensure cleanup happens, AGENTS.md becoming the target again
+        let _cleanup = defer::defer(move || {
+            commands::set_target_file(ManagedFile::agents());
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        commands::set_target_file(ManagedFile::new("CLAUDE.md").unwrap());
+        commands::handle_init(false).unwrap();
+        assert_eq!(fs::read_to_string("CLAUDE.md").unwrap(), "# CLAUDE.md\n\n\n");
+        assert!(!Path::new("AGENTS.md").exists());
+
+        // CLAUDE.md has a slot of its own next to the project's AGENTS.md stash
+        let project_name = utils::project_stash_key(temp_dir.path()).unwrap();
+        fs::write("CLAUDE.md", "Prefer small commits.\n").unwrap();
+        fs::write("AGENTS.md", "# AGENTS\n\n- untouched\n").unwrap();
+        commands::handle_stash(None).unwrap();
+        let slot = utils::get_stash_path(&utils::slot_stash_key(&project_name, "CLAUDE.md")).unwrap();
+        assert_eq!(fs::read_to_string(&slot).unwrap(), "Prefer small commits.\n");
+        assert!(!utils::get_stash_path(&project_name).unwrap().exists());
+
+        commands::handle_clean(false, false).unwrap();
+        assert!(!Path::new("CLAUDE.md").exists());
+        assert!(Path::new("AGENTS.md").exists());
+
+        commands::handle_apply(&commands::ApplyOptions { force: true, ..Default::default() }).unwrap();
+        assert_eq!(fs::read_to_string("CLAUDE.md").unwrap(), "Prefer small commits.\n");
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- untouched\n");
+
+        // Copilot's instructions come back along with the .github directory they live in
+        commands::set_target_file(ManagedFile::new(".github/copilot-instructions.md").unwrap());
+        fs::create_dir(".github").unwrap();
+        fs::write(".github/copilot-instructions.md", "---\napplyTo: \"**\"\n---\nUse tabs.\n").unwrap();
+        commands::handle_stash(None).unwrap();
+        let slot = utils::slot_stash_key(&project_name, ".github--copilot-instructions.md");
+        assert!(!utils::get_stash_path(&slot).unwrap().exists());
+        fs::write(".github/copilot-instructions.md", "Use tabs.\n").unwrap();
+        commands::handle_stash(None).unwrap();
+        fs::remove_dir_all(".github").unwrap();
+        commands::handle_apply(&commands::ApplyOptions::default()).unwrap();
+        assert_eq!(fs::read_to_string(".github/copilot-instructions.md").unwrap(), "Use tabs.\n");
+    }
+
     #[test]
     #[serial]
     fn test_handle_uninstall() {
         // Create a temporary directory to use as HOME
         let temp_dir = TempDir::new().unwrap();
-        let original_home = env::var("HOME").unwrap_or_default();
-        env::set_var("HOME", temp_dir.path());
-        
-        // Ensure cleanup happens
-        let _cleanup_home = defer::defer(move || {
-            if !original_home.is_empty() {
-                env::set_var("HOME", original_home);
-            }
-        });
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
 
         // Create the .agstash directory with some content
-        let agstash_dir = dirs::home_dir().unwrap().join(".agstash");
+        let agstash_dir = utils::get_legacy_dir().unwrap();
         fs::create_dir_all(&agstash_dir).unwrap();
 
         // Create a test file inside .agstash
@@ -469,15 +1823,33 @@ mod tests {
         // Verify the directory exists
         assert!(agstash_dir.exists());
 
+        // Backups kept inside the store survive it
+        let backup_dir = agstash_dir.join("backups");
+        fs::create_dir_all(&backup_dir).unwrap();
+        fs::write(backup_dir.join("agstash-20261017T120000Z.tar.gz"), "").unwrap();
+        let configured = backup_dir.display().to_string();
+        commands::handle_uninstall(false, Some(&configured)).unwrap();
+        assert!(!test_file.exists());
+        assert!(backup_dir.join("agstash-20261017T120000Z.tar.gz").exists());
+
         // Run uninstall command
-        let result = commands::handle_uninstall();
+        let result = commands::handle_uninstall(false, None);
         assert!(result.is_ok());
 
         // Check if .agstash directory was removed
         assert!(!agstash_dir.exists());
 
         // Try to uninstall again - should not error
-        let result = commands::handle_uninstall();
+        let result = commands::handle_uninstall(false, None);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_annotation() {
+        assert_eq!(
+            commands::annotation("error", "docs/AGENTS.md", Some(3), "50% done\nsee stash"),
+            "::error file=docs/AGENTS.md,line=3::50%25 done%0Asee stash"
+        );
+        assert_eq!(commands::annotation("warning", "a,b:c.md", None, "x"), "::warning file=a%2Cb%3Ac.md::x");
+    }
 }
\ No newline at end of file
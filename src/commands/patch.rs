@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use similar::{ChangeTag, DiffTag, TextDiff};
+
+use super::{announce, color_string, get_user_confirmation, project_context, BOLD, CYAN, GREEN, RED, YELLOW};
+use crate::history;
+use crate::journal;
+use crate::metadata;
+use crate::store;
+use crate::utils;
+
+// HandleStashPatch walks the hunks between the stash and AGENTS.md and only stashes the ones the user accepts
+pub fn handle_stash_patch(message: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let (root, project_name) = project_context()?;
+    let agents_path = root.join("AGENTS.md");
+    let stash_path = utils::get_stash_path(&project_name)?;
+
+    if !utils::file_exists(&agents_path) {
+        utils::log_info(&format!("AGENTS.md does not exist in project root: {}", agents_path.display()));
+        println!(
+            "{} {}",
+            color_string("AGENTS.md", BOLD),
+            color_string("does not exist in project root.", YELLOW)
+        );
+        return Ok(());
+    }
+
+    let (err, agents_content) = utils::read_file(&agents_path);
+    if let Some(error) = err {
+        return Err(error);
+    }
+    let stash_content = if utils::file_exists(&stash_path) {
+        let (err, content) = utils::read_file(&stash_path);
+        if let Some(error) = err {
+            return Err(error);
+        }
+        content
+    } else {
+        String::new()
+    };
+
+    let patched = select_hunks(&stash_content, &agents_content, |hunk| {
+        print!("{}", hunk);
+        print!("Stash this hunk? [y/N]: ");
+        io::stdout().flush()?; // Ensure the prompt is displayed
+        get_user_confirmation()
+    })?;
+
+    if patched == stash_content {
+        utils::log_info("No hunks selected, stash unchanged");
+        println!("No changes stashed for {}", color_string(&project_name, BOLD));
+        return Ok(());
+    }
+
+    if !utils::is_valid_agents(&patched) {
+        utils::log_warn("Selected hunks produce invalid content, stash aborted");
+        println!(
+            "{} {}",
+            color_string("The selected hunks leave the stash without a '# AGENTS' header.", YELLOW),
+            color_string("Stash aborted.", YELLOW)
+        );
+        return Ok(());
+    }
+
+    history::record_snapshot(&project_name, &stash_path, "stash")?;
+    if let Some(error) = store::write_stash(&stash_path, &patched) {
+        return Err(error);
+    }
+    store::share_stash(&stash_path)?;
+    let stash_metadata = metadata::StashMetadata::new(message, Some(agents_path.display().to_string()));
+    metadata::save_metadata(&project_name, &stash_metadata)?;
+    history::record_version(&project_name, &stash_path, message)?;
+    journal::record_event(&project_name, "stash", message)?;
+    utils::log_info(&format!("Selected hunks stashed for project: {}", project_name));
+    announce(&format!(
+        "{} selected changes for {}",
+        color_string("Stashed", GREEN),
+        color_string(&project_name, BOLD)
+    ));
+
+    Ok(())
+}
+
+// select_hunks asks decide about every hunk turning old into new and returns old with only the accepted hunks applied
+fn select_hunks<F>(old: &str, new: &str, mut decide: F) -> Result<String, Box<dyn std::error::Error>>
+where
+    F: FnMut(&str) -> Result<bool, Box<dyn std::error::Error>>,
+{
+    let diff = TextDiff::from_lines(old, new);
+
+    // Changes are identified by where they start in both files
+    let mut accepted = HashSet::new();
+    for group in diff.grouped_ops(3) {
+        let mut hunk = String::new();
+        if let (Some(first), Some(last)) = (group.first(), group.last()) {
+            hunk.push_str(&color_string(
+                &format!(
+                    "@@ -{},{} +{},{} @@",
+                    first.old_range().start + 1,
+                    last.old_range().end - first.old_range().start,
+                    first.new_range().start + 1,
+                    last.new_range().end - first.new_range().start
+                ),
+                CYAN,
+            ));
+            hunk.push('\n');
+        }
+        for op in &group {
+            for change in diff.iter_changes(op) {
+                let line = format!("{}{}", change.tag(), change.value().trim_end_matches('\n'));
+                let line = match change.tag() {
+                    ChangeTag::Delete => color_string(&line, RED),
+                    ChangeTag::Insert => color_string(&line, GREEN),
+                    ChangeTag::Equal => line,
+                };
+                hunk.push_str(&line);
+                hunk.push('\n');
+            }
+        }
+
+        if decide(&hunk)? {
+            for op in group.iter().filter(|op| op.tag() != DiffTag::Equal) {
+                accepted.insert((op.old_range().start, op.new_range().start));
+            }
+        }
+    }
+
+    let old_lines = diff.old_slices();
+    let new_lines = diff.new_slices();
+    let mut patched = String::new();
+    for op in diff.ops() {
+        let take_new = op.tag() != DiffTag::Equal
+            && accepted.contains(&(op.old_range().start, op.new_range().start));
+        if take_new {
+            new_lines[op.new_range()].iter().for_each(|line| patched.push_str(line));
+        } else {
+            old_lines[op.old_range()].iter().for_each(|line| patched.push_str(line));
+        }
+    }
+
+    Ok(patched)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_select_hunks() {
+        let old = "# AGENTS\n\n- one\n- two\n- three\n- four\n- five\n- six\n- seven\n- eight\n";
+        let new = "# AGENTS\n\n- one (edited)\n- two\n- three\n- four\n- five\n- six\n- seven\n- eight\n- nine\n";
+
+        // Accepting everything reproduces the new content
+        let all = super::select_hunks(old, new, |_| Ok(true)).unwrap();
+        assert_eq!(all, new);
+
+        // Rejecting everything keeps the old content
+        let none = super::select_hunks(old, new, |_| Ok(false)).unwrap();
+        assert_eq!(none, old);
+
+        // Accepting only the second hunk keeps the first edit out of the stash
+        let mut seen = 0;
+        let partial = super::select_hunks(old, new, |_| {
+            seen += 1;
+            Ok(seen == 2)
+        })
+        .unwrap();
+        assert_eq!(seen, 2);
+        assert_eq!(partial, format!("{}- nine\n", old));
+    }
+}
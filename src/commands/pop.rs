@@ -0,0 +1,204 @@
+use std::io::{self, Write};
+
+use super::{announce, apply_stash, color_string, fail_if_strict, get_user_confirmation, project_context, ApplyOptions, BOLD, RED, YELLOW};
+use crate::error::AgStashError;
+use crate::journal;
+use crate::metadata;
+use crate::undo;
+use crate::utils;
+
+// HandlePop applies the project's stash and then removes it from the global store
+pub fn handle_pop(force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if !apply_stash(&ApplyOptions {
+        force,
+        ..Default::default()
+    })? {
+        utils::log_info("Stash was not applied, keeping it in the store");
+        return Ok(());
+    }
+
+    let (root, project_name) = project_context()?;
+    let stash_path = utils::get_stash_path(&project_name)?;
+    let agents_path = root.join("AGENTS.md");
+
+    // Only drop the stash once the applied file is known to match it byte for byte
+    let (err, stash_content) = utils::read_file(&stash_path);
+    if let Some(error) = err {
+        return Err(error);
+    }
+    let (err, agents_content) = utils::read_file(&agents_path);
+    if let Some(error) = err {
+        return Err(error);
+    }
+    if stash_content != agents_content {
+        utils::log_warn("Applied AGENTS.md does not match the stash, keeping stash");
+        return Err("Applied AGENTS.md does not match the stash; the stash was kept".into());
+    }
+
+    utils::remove_file(&stash_path)?;
+    metadata::remove_metadata(&project_name)?;
+    journal::record_event(&project_name, "drop", None)?;
+    utils::log_info(&format!("Removed stash: {}", stash_path.display()));
+    announce(&format!(
+        "{} stash for {}",
+        color_string("Dropped", RED),
+        color_string(&project_name, BOLD)
+    ));
+
+    Ok(())
+}
+
+// HandleDrop removes the project's stash from the global store
+pub fn handle_drop(force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let (_, project_name) = project_context()?;
+    let stash_path = utils::get_stash_path(&project_name)?;
+
+    if !utils::file_exists(&stash_path) {
+        fail_if_strict(AgStashError::StashNotFound(format!("No stash found for project {}", project_name)))?;
+        utils::log_info(&format!("No stash found for project: {}", project_name));
+        println!("No stash found for project {}", color_string(&project_name, BOLD));
+        return Ok(());
+    }
+
+    if !force {
+        println!(
+            "\n{} This will delete the stash for {}.",
+            color_string("WARNING:", &format!("{}{}", YELLOW, BOLD)),
+            color_string(&project_name, BOLD)
+        );
+        print!("Type 'yes' to confirm or 'no' to cancel [y/N]: ");
+        io::stdout().flush()?; // Ensure the prompt is displayed
+
+        if !get_user_confirmation()? {
+            utils::log_info("User declined to drop the stash");
+            println!("\nOperation cancelled. The stash was not modified.");
+            return Ok(());
+        }
+    }
+
+    undo::save_backup(&project_name, "drop", &stash_path)?;
+    utils::remove_file(&stash_path)?;
+    metadata::remove_metadata(&project_name)?;
+    journal::record_event(&project_name, "drop", None)?;
+    utils::log_info(&format!("Removed stash: {}", stash_path.display()));
+    announce(&format!(
+        "{} stash for {}",
+        color_string("Dropped", RED),
+        color_string(&project_name, BOLD)
+    ));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::store;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_pop() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(|| {
+            let _ = env::set_current_dir(&original_dir);
+        });
+
+        // Create a .git directory to establish project root
+        fs::create_dir(".git").unwrap();
+
+        // Set up HOME environment variable to temp directory
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        let agents_content = "# AGENTS\n\n- popped\n";
+        fs::write("AGENTS.md", agents_content).unwrap();
+        commands::handle_stash(None).unwrap();
+        fs::remove_file("AGENTS.md").unwrap();
+
+        let project_name = &utils::project_stash_key(temp_dir.path()).unwrap();
+        let stash_path = utils::get_stash_path(project_name).unwrap();
+
+        // Pop restores the file and removes the stash
+        commands::handle_pop(true).unwrap();
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), agents_content);
+        assert!(!stash_path.exists());
+
+        // Popping again finds no stash and leaves AGENTS.md untouched
+        commands::handle_pop(true).unwrap();
+        assert!(temp_dir.path().join("AGENTS.md").exists());
+    }
+
+    #[test]
+    #[serial]
+    fn test_handle_pop_invalid_stash_is_kept() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(|| {
+            let _ = env::set_current_dir(&original_dir);
+        });
+
+        // Create a .git directory to establish project root
+        fs::create_dir(".git").unwrap();
+
+        // Set up HOME environment variable to temp directory
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+        store::create_store().unwrap();
+
+        // An invalid stash cannot be applied, so it must survive the pop
+        let project_name = &utils::project_stash_key(temp_dir.path()).unwrap();
+        let stash_path = utils::get_stash_path(project_name).unwrap();
+        fs::write(&stash_path, "not an agents file").unwrap();
+
+        commands::handle_pop(true).unwrap();
+        assert!(stash_path.exists());
+        assert!(!temp_dir.path().join("AGENTS.md").exists());
+    }
+
+    #[test]
+    #[serial]
+    fn test_handle_drop() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(|| {
+            let _ = env::set_current_dir(&original_dir);
+        });
+
+        // Create a .git directory to establish project root
+        fs::create_dir(".git").unwrap();
+
+        // Set up HOME environment variable to temp directory
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        fs::write("AGENTS.md", "# AGENTS\n").unwrap();
+        commands::handle_stash(None).unwrap();
+
+        let project_name = &utils::project_stash_key(temp_dir.path()).unwrap();
+        let stash_path = utils::get_stash_path(project_name).unwrap();
+        assert!(stash_path.exists());
+
+        commands::handle_drop(true).unwrap();
+        assert!(!stash_path.exists());
+
+        // Dropping a missing stash should not error
+        assert!(commands::handle_drop(true).is_ok());
+    }
+}
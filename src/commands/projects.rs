@@ -0,0 +1,106 @@
+use std::path::Path;
+
+use serde_json::json;
+
+use super::{color_string, indicator, json_output, print_json, Indicator, BOLD, CYAN, YELLOW};
+use crate::registry;
+use crate::utils;
+
+// HandleProjects lists every project stashed on this machine with its path, whether the path is still there and
+// whether it has a stash
+pub fn handle_projects() -> Result<(), Box<dyn std::error::Error>> {
+    let projects = registry::load_projects()?;
+    if json_output() {
+        let mut entries = Vec::new();
+        for (root, project) in &projects {
+            let stash_key = registry::project_key(Path::new(root))?;
+            entries.push(json!({
+                "project": stash_key,
+                "root": root,
+                "alias": project.alias,
+                "added_at": project.added_at,
+                "path_exists": Path::new(root).is_dir(),
+                "stashed": utils::file_exists(utils::get_stash_path(&stash_key)?),
+            }));
+        }
+        return print_json(&json!(entries));
+    }
+    if projects.is_empty() {
+        println!("{}", color_string("No known projects.", YELLOW));
+        println!("Projects are recorded the first time they are stashed");
+        return Ok(());
+    }
+
+    for (root, project) in projects {
+        let root = Path::new(&root);
+        let stash_key = registry::project_key(root)?;
+        println!(
+            "{} {}",
+            color_string(&stash_key, BOLD),
+            color_string(&format!("({})", project.added_at), CYAN)
+        );
+        println!("  {}", root.display());
+
+        let path_state = if root.is_dir() {
+            indicator(Indicator::Ok, "path exists")
+        } else {
+            indicator(Indicator::Error, "path missing")
+        };
+        let stash_state = if utils::file_exists(utils::get_stash_path(&stash_key)?) {
+            indicator(Indicator::Ok, "stashed")
+        } else {
+            indicator(Indicator::Warning, "no stash")
+        };
+        println!("  {}  {}", path_state, stash_state);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::registry;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_projects_are_registered_on_stash() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir(".git").unwrap();
+
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(move || {
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        commands::handle_projects().unwrap();
+        assert!(registry::load_projects().unwrap().is_empty());
+
+        fs::write("AGENTS.md", "# AGENTS\n").unwrap();
+        commands::handle_stash(None).unwrap();
+        let projects = registry::load_projects().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        assert_eq!(projects.keys().collect::<Vec<_>>(), vec![&root.to_string_lossy().into_owned()]);
+        let added_at = projects.values().next().unwrap().added_at.clone();
+
+        // Stashing again keeps the first record
+        commands::handle_stash(Some("again")).unwrap();
+        assert_eq!(registry::load_projects().unwrap().values().next().unwrap().added_at, added_at);
+        assert_eq!(registry::project_key(&root).unwrap(), utils::project_stash_key(&root).unwrap());
+        commands::handle_projects().unwrap();
+    }
+}
@@ -0,0 +1,95 @@
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+
+use super::{nested_context, symbol, target_file, Indicator, TOP_LEVEL};
+use crate::registry;
+use crate::utils;
+
+// HandlePromptStatus prints a compact, uncolored token for a shell prompt, e.g. "agents✓" when AGENTS.md matches its
+// stash, "agents~" when it drifted, "agents✗" when it is missing or invalid and "agents!" when it has no stash.
+// Prints nothing outside a project or where there is neither a file nor a stash. Only the two files are read and
+// nothing is logged, so it stays fast however large the store is
+pub fn handle_prompt_status() -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(token) = prompt_token()? {
+        println!("{}", token);
+    }
+    Ok(())
+}
+
+// prompt_token returns the token for the working directory, if there is one to show
+fn prompt_token() -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let file = target_file();
+    let Ok(root) = utils::get_project_root() else {
+        return Ok(None);
+    };
+    if file.is_rules_dir() {
+        return Ok(None);
+    }
+    let project_name = registry::project_key(&root)?;
+    let nested = if TOP_LEVEL.load(Ordering::Relaxed) {
+        None
+    } else {
+        nested_context(&root, &project_name)?
+    };
+    let (dir, stash_key) = nested.unwrap_or((root, project_name));
+
+    let content = fs::read(dir.join(&file.name)).ok();
+    let stash_content = fs::read(utils::get_stash_path(&file.stash_key(&stash_key))?).ok();
+    let state = match (&content, &stash_content) {
+        (None, None) => return Ok(None),
+        (None, Some(_)) => Indicator::Error,
+        (Some(content), _) if !file.is_valid(&String::from_utf8_lossy(content)) => Indicator::Error,
+        (Some(_), None) => Indicator::Warning,
+        (Some(content), Some(stash_content)) if content == stash_content => Indicator::Ok,
+        (Some(_), Some(_)) => Indicator::Drift,
+    };
+    Ok(Some(format!("{}{}", token_name(&file.name), symbol(state))))
+}
+
+// token_name shortens a file name for the prompt, e.g. "agents" for AGENTS.md and "cursorrules" for .cursorrules
+fn token_name(name: &str) -> String {
+    let stem = Path::new(name).file_stem().and_then(|stem| stem.to_str()).unwrap_or(name);
+    stem.trim_start_matches('.').to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_prompt_token() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir(".git").unwrap();
+
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(move || {
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        assert_eq!(super::prompt_token().unwrap(), None);
+        fs::write("AGENTS.md", "# AGENTS\n\n- prompt\n").unwrap();
+        assert_eq!(super::prompt_token().unwrap().as_deref(), Some("agents!"));
+        commands::handle_stash(None).unwrap();
+        assert_eq!(super::prompt_token().unwrap().as_deref(), Some("agents✓"));
+        fs::write("AGENTS.md", "# AGENTS\n\n- edited\n").unwrap();
+        assert_eq!(super::prompt_token().unwrap().as_deref(), Some("agents~"));
+        fs::remove_file("AGENTS.md").unwrap();
+        assert_eq!(super::prompt_token().unwrap().as_deref(), Some("agents✗"));
+        assert_eq!(super::token_name(".github/copilot-instructions.md"), "copilot-instructions");
+    }
+}
@@ -0,0 +1,211 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use super::{announce, apply_allowed, apply_stash_to, color_string, fail_if_strict, root_context, stash_agents, ApplyOptions, BOLD, GREEN, YELLOW};
+use crate::error::AgStashError;
+use crate::managed::ManagedFile;
+use crate::registry;
+use crate::utils;
+
+// File in the project root naming directories that stash --recursive skips, one name or root-relative path per line
+const IGNORE_FILE: &str = ".agstashignore";
+
+// Directories that are never searched for AGENTS.md files
+const ALWAYS_IGNORED: &[&str] = &[".git", "node_modules"];
+
+// HandleStashRecursive stashes every AGENTS.md under the project root, the root's as the project's stash and the rest
+// as nested stashes keyed by their relative path
+pub fn handle_stash_recursive(message: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let (root, project_name) = root_context()?;
+    let ignored = load_ignore(&root)?;
+    let dirs = find_agents_dirs(&root, &ignored);
+    if dirs.is_empty() {
+        println!("{} under {}", color_string("No AGENTS.md found", YELLOW), root.display());
+        return Ok(());
+    }
+
+    let mut stashed = 0;
+    for dir in dirs {
+        let relative = dir.strip_prefix(&root).unwrap_or(&dir);
+        let stash_key = if relative.as_os_str().is_empty() {
+            project_name.clone()
+        } else {
+            utils::nested_stash_key(&project_name, relative)
+        };
+        let agents_path = dir.join("AGENTS.md");
+        let display_path = relative.join("AGENTS.md").display().to_string();
+
+        let (err, content) = utils::read_file(&agents_path);
+        if let Some(error) = err {
+            return Err(error);
+        }
+        if !utils::is_valid_agents(&content) {
+            println!("{} {} (missing the '# AGENTS' header)", color_string("Skipped", YELLOW), display_path);
+            continue;
+        }
+        stash_agents(&agents_path, &stash_key, message)?;
+        announce(&format!("{} {} as {}", color_string("Stashed", GREEN), display_path, color_string(&stash_key, BOLD)));
+        stashed += 1;
+    }
+    registry::register_project(&root)?;
+
+    utils::log_info(&format!("Stashed {} AGENTS.md file(s) for project: {}", stashed, project_name));
+    println!("\n{} AGENTS.md file(s) stashed for {}", stashed, color_string(&project_name, BOLD));
+    Ok(())
+}
+
+// HandleApplyRecursive applies the project's stash and every nested stash it has, recreating the directories they
+// belong in
+pub fn handle_apply_recursive(options: &ApplyOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let (root, project_name) = root_context()?;
+
+    let mut targets = Vec::new();
+    for (stash_key, _) in utils::list_stashes(utils::get_stashes_dir()?)? {
+        if stash_key == project_name {
+            targets.push((root.clone(), stash_key));
+        } else if let Some(relative) = utils::nested_relative_path(&stash_key, &project_name) {
+            // A key naming a path outside the project is never written
+            if relative.components().all(|component| matches!(component, Component::Normal(_))) {
+                targets.push((root.join(relative), stash_key));
+            }
+        }
+    }
+    if targets.is_empty() {
+        fail_if_strict(AgStashError::StashNotFound(format!("No stash found for project {}", project_name)))?;
+        utils::log_info(&format!("No stash found for project: {}", project_name));
+        println!("No stash found for project {}", color_string(&project_name, BOLD));
+        return Ok(());
+    }
+
+    // The branch guard is checked once for the whole tree, so nothing is applied if the root is refused
+    apply_allowed(&root, options.force)?;
+
+    let total = targets.len();
+    let mut applied = 0;
+    for (dir, stash_key) in targets {
+        fs::create_dir_all(&dir)?;
+        if apply_stash_to(&dir, &stash_key, &ManagedFile::agents(), options)? {
+            applied += 1;
+        }
+    }
+
+    println!("\n{} of {} AGENTS.md file(s) applied for {}", applied, total, color_string(&project_name, BOLD));
+    Ok(())
+}
+
+// load_ignore reads the project's ignore file, returning no patterns when it has none
+fn load_ignore(root: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let path = root.join(IGNORE_FILE);
+    if !utils::file_exists(&path) {
+        return Ok(Vec::new());
+    }
+    let (err, content) = utils::read_file(&path);
+    if let Some(error) = err {
+        return Err(error);
+    }
+    Ok(content
+        .lines()
+        .map(|line| line.trim().trim_matches('/'))
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+// is_ignored matches a directory against the ignore patterns: a pattern with a '/' is a path from the root, anything
+// else a directory name at any depth
+fn is_ignored(relative: &Path, patterns: &[String]) -> bool {
+    patterns.iter().any(|pattern| {
+        if pattern.contains('/') {
+            relative.starts_with(pattern)
+        } else {
+            relative.components().any(|component| component.as_os_str() == pattern.as_str())
+        }
+    })
+}
+
+// find_agents_dirs lists the directories under root holding an AGENTS.md, root first, skipping ignored directories,
+// symlinks and nested repositories, which are projects of their own
+fn find_agents_dirs(root: &Path, ignored: &[String]) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        if dir.join("AGENTS.md").is_file() {
+            found.push(dir.clone());
+        }
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if !entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                continue;
+            }
+            if ALWAYS_IGNORED.iter().any(|name| entry.file_name() == *name) {
+                continue;
+            }
+            let path = entry.path();
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            if is_ignored(relative, ignored) || path.join(".git").exists() {
+                continue;
+            }
+            pending.push(path);
+        }
+    }
+    found.sort();
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_recursive_stash_and_apply() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir(".git").unwrap();
+
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(move || {
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        let project_name = utils::project_stash_key(temp_dir.path()).unwrap();
+        fs::create_dir_all("packages/api").unwrap();
+        fs::create_dir_all("packages/web").unwrap();
+        fs::create_dir_all("vendor/lib").unwrap();
+        fs::write("AGENTS.md", "# AGENTS\n\n- root\n").unwrap();
+        fs::write("packages/api/AGENTS.md", "# AGENTS\n\n- api\n").unwrap();
+        fs::write("packages/web/AGENTS.md", "# AGENTS\n\n- web\n").unwrap();
+        fs::write("vendor/lib/AGENTS.md", "# AGENTS\n\n- vendored\n").unwrap();
+        fs::write(".agstashignore", "# third-party code\nvendor/\n").unwrap();
+
+        commands::handle_stash_recursive(Some("monorepo")).unwrap();
+        assert!(utils::get_stash_path(&project_name).unwrap().exists());
+        let api_key = utils::nested_stash_key(&project_name, std::path::Path::new("packages/api"));
+        assert!(utils::get_stash_path(&api_key).unwrap().exists());
+        let vendor_key = utils::nested_stash_key(&project_name, std::path::Path::new("vendor/lib"));
+        assert!(!utils::get_stash_path(&vendor_key).unwrap().exists());
+
+        // A fresh checkout gets every file back, directories included
+        fs::remove_dir_all("packages").unwrap();
+        fs::remove_file("AGENTS.md").unwrap();
+        commands::handle_apply_recursive(&commands::ApplyOptions { force: true, ..Default::default() }).unwrap();
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- root\n");
+        assert_eq!(fs::read_to_string("packages/api/AGENTS.md").unwrap(), "# AGENTS\n\n- api\n");
+        assert_eq!(fs::read_to_string("packages/web/AGENTS.md").unwrap(), "# AGENTS\n\n- web\n");
+    }
+}
@@ -0,0 +1,87 @@
+use std::fs;
+
+use super::{color_string, fail_if_strict, BOLD, GREEN, YELLOW};
+use crate::error::AgStashError;
+use crate::history;
+use crate::journal;
+use crate::metadata;
+use crate::utils;
+
+// HandleRename moves the stash keyed by old_name so it is keyed by new_name instead
+pub fn handle_rename(old_name: &str, new_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    utils::validate_project_name(old_name)?;
+    utils::validate_project_name(new_name)?;
+
+    let old_path = utils::get_stash_path(old_name)?;
+    let new_path = utils::get_stash_path(new_name)?;
+
+    if !utils::file_exists(&old_path) {
+        fail_if_strict(AgStashError::StashNotFound(format!("No stash found for project {}", old_name)))?;
+        utils::log_info(&format!("No stash found for project: {}", old_name));
+        println!("No stash found for project {}", color_string(old_name, BOLD));
+        return Ok(());
+    }
+
+    if utils::file_exists(&new_path) {
+        utils::log_warn(&format!("A stash already exists for project: {}", new_name));
+        println!(
+            "{} {}",
+            color_string(&format!("A stash already exists for {}.", new_name), YELLOW),
+            color_string("Rename aborted.", YELLOW)
+        );
+        return Ok(());
+    }
+
+    fs::rename(&old_path, &new_path)?;
+    metadata::rename_metadata(old_name, new_name)?;
+    history::rename_history(old_name, new_name)?;
+    journal::record_event(new_name, "rename", Some(&format!("renamed from {}", old_name)))?;
+    utils::log_info(&format!("Moved stash {} to {}", old_path.display(), new_path.display()));
+    println!(
+        "{} stash {} to {}",
+        color_string("Renamed", GREEN),
+        color_string(old_name, BOLD),
+        color_string(new_name, BOLD)
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::store;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_rename() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+        store::create_store().unwrap();
+
+        let old_path = utils::get_stash_path("old-name").unwrap();
+        fs::write(&old_path, "# AGENTS\n").unwrap();
+
+        commands::handle_rename("old-name", "new-name").unwrap();
+
+        let new_path = utils::get_stash_path("new-name").unwrap();
+        assert!(!old_path.exists());
+        assert_eq!(fs::read_to_string(&new_path).unwrap(), "# AGENTS\n");
+
+        // Renaming onto an existing stash leaves both untouched
+        fs::write(&old_path, "# AGENTS\n\n- other\n").unwrap();
+        commands::handle_rename("old-name", "new-name").unwrap();
+        assert!(old_path.exists());
+        assert_eq!(fs::read_to_string(&new_path).unwrap(), "# AGENTS\n");
+
+        // Names that would escape the stash directory are rejected
+        assert!(commands::handle_rename("old-name", "../escape").is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::{color_string, BOLD, GREEN, YELLOW};
+use crate::history::{self, Version, VersionKind};
+use crate::journal;
+use crate::managed::ManagedFile;
+use crate::metadata::{self, StashMetadata};
+use crate::store;
+use crate::utils;
+
+// HandleRepair fixes what doctor can only report: corrupt files are moved to the quarantine, missing metadata and
+// history records are rebuilt from file times, and identical stashes are linked to their content objects again
+pub fn handle_repair() -> Result<(), Box<dyn std::error::Error>> {
+    let agstash_dir = utils::get_agstash_dir()?;
+    if !agstash_dir.is_dir() {
+        println!("{}", color_string("Nothing to repair.", YELLOW));
+        return Ok(());
+    }
+
+    let mut repairs = Vec::new();
+    repair_stashes(&mut repairs)?;
+    repair_history(&mut repairs)?;
+    repair_journal(&mut repairs)?;
+
+    if repairs.is_empty() {
+        println!("{}", color_string("Nothing to repair.", YELLOW));
+        return Ok(());
+    }
+    for repair in &repairs {
+        println!("{} {}", color_string("Repaired", GREEN), repair);
+    }
+    println!("\n{} repair(s) made", color_string(&repairs.len().to_string(), BOLD));
+    Ok(())
+}
+
+// GetQuarantineDir returns where repair moves files it cannot use
+pub fn get_quarantine_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(utils::get_agstash_dir()?.join("quarantine"))
+}
+
+// quarantine moves a file into the quarantine under name, keeping anything already there with that name
+fn quarantine(path: &Path, name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let quarantine_dir = get_quarantine_dir()?;
+    fs::create_dir_all(&quarantine_dir)?;
+    let mut target = quarantine_dir.join(name);
+    if utils::file_exists(&target) {
+        target = utils::backup_path_for(&target);
+    }
+    fs::rename(path, &target)?;
+    utils::log_info(&format!("Quarantined {} as {}", path.display(), target.display()));
+    Ok(target)
+}
+
+// repair_stashes quarantines unreadable stashes, rebuilds their metadata and relinks them to content objects
+fn repair_stashes(repairs: &mut Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    for (stash_key, path) in utils::list_stashes(utils::get_stashes_dir()?)? {
+        let (err, content) = utils::read_file(&path);
+        if err.is_some() || !ManagedFile::for_stash_key(&stash_key).is_valid(&content) {
+            let target = quarantine(&path, &format!("stash-{}.md", stash_key))?;
+            let sidecar = path.with_extension("json");
+            if utils::file_exists(&sidecar) {
+                quarantine(&sidecar, &format!("stash-{}.json", stash_key))?;
+            }
+            repairs.push(format!("stash {}: unreadable or invalid, moved to {}", stash_key, target.display()));
+            continue;
+        }
+
+        let rebuild = match metadata::load_metadata(&stash_key) {
+            Ok(Some(_)) => false,
+            Ok(None) => true,
+            Err(_) => {
+                let target = quarantine(&metadata::get_metadata_path(&stash_key)?, &format!("stash-{}.json", stash_key))?;
+                repairs.push(format!("stash {}: corrupt metadata moved to {}", stash_key, target.display()));
+                true
+            }
+        };
+        if rebuild {
+            metadata::save_metadata(&stash_key, &StashMetadata::from_file(&path, "metadata rebuilt by repair")?)?;
+            repairs.push(format!("stash {}: metadata rebuilt from the file time", stash_key));
+        }
+
+        store::share_stash(&path)?;
+    }
+    Ok(())
+}
+
+// repair_history quarantines corrupt version records and rebuilds the records of versions that lost theirs
+fn repair_history(repairs: &mut Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    for stash_key in history::list_histories()? {
+        let history_dir = history::get_history_dir(&stash_key)?;
+        let mut contents = Vec::new();
+        for entry in fs::read_dir(&history_dir)? {
+            let path = entry?.path();
+            let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default().to_string();
+            let Some((stem, extension)) = file_name.split_once('.') else {
+                continue;
+            };
+            let Ok(id) = stem.parse::<u64>() else {
+                continue;
+            };
+
+            if extension != "json" {
+                contents.push((id, path));
+                continue;
+            }
+            let (err, record) = utils::read_file(&path);
+            if err.is_some() || serde_json::from_str::<Version>(&record).is_err() {
+                let target = quarantine(&path, &format!("history-{}-{}", stash_key, file_name))?;
+                repairs.push(format!("history {} version {}: corrupt record moved to {}", stash_key, id, target.display()));
+            }
+        }
+
+        for (id, path) in contents {
+            let record_path = history_dir.join(format!("{}.json", id));
+            if utils::file_exists(&record_path) {
+                continue;
+            }
+            let version = Version {
+                id,
+                created_at: modified_at(&path)?,
+                message: Some("record rebuilt by repair".to_string()),
+                sha256: None,
+                kind: VersionKind::Stash,
+            };
+            if let Some(error) = utils::write_file(&record_path, &serde_json::to_string_pretty(&version)?) {
+                return Err(error);
+            }
+            repairs.push(format!("history {} version {}: record rebuilt from the file time", stash_key, id));
+        }
+    }
+    Ok(())
+}
+
+// repair_journal moves journal lines that cannot be parsed into the quarantine
+fn repair_journal(repairs: &mut Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let journal_path = journal::get_journal_path()?;
+    if !utils::file_exists(&journal_path) {
+        return Ok(());
+    }
+    let (err, content) = utils::read_file(&journal_path);
+    if err.is_some() {
+        let target = quarantine(&journal_path, "journal.jsonl")?;
+        repairs.push(format!("journal: unreadable, moved to {}", target.display()));
+        return Ok(());
+    }
+
+    let (kept, malformed): (Vec<&str>, Vec<&str>) = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .partition(|line| serde_json::from_str::<journal::JournalEntry>(line).is_ok());
+    if malformed.is_empty() {
+        return Ok(());
+    }
+
+    let quarantine_dir = get_quarantine_dir()?;
+    fs::create_dir_all(&quarantine_dir)?;
+    let target = quarantine_dir.join("journal-malformed.jsonl");
+    let (_, previous) = utils::read_file(&target);
+    if let Some(error) = utils::write_file(&target, &format!("{}{}\n", previous, malformed.join("\n"))) {
+        return Err(error);
+    }
+    let rewritten: String = kept.iter().map(|line| format!("{}\n", line)).collect();
+    if let Some(error) = utils::write_file(&journal_path, &rewritten) {
+        return Err(error);
+    }
+    repairs.push(format!("journal: {} malformed line(s) moved to {}", malformed.len(), target.display()));
+    Ok(())
+}
+
+// modified_at renders a file's modification time as a timestamp, falling back to now
+fn modified_at(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let modified = fs::metadata(path)?.modified().unwrap_or_else(|_| SystemTime::now());
+    Ok(utils::format_timestamp(modified))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::history;
+    use crate::journal;
+    use crate::metadata;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_repair() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        // An empty store needs nothing
+        commands::handle_repair().unwrap();
+
+        fs::create_dir_all(utils::get_stashes_dir().unwrap()).unwrap();
+        fs::write(utils::get_stash_path("healthy").unwrap(), "# AGENTS\n").unwrap();
+        fs::write(utils::get_stash_path("broken").unwrap(), "no header\n").unwrap();
+        fs::write(utils::get_stash_path("healthy+CLAUDE.md").unwrap(), "Prefer small commits.\n").unwrap();
+        fs::write(utils::get_stash_path("scrambled").unwrap(), "# AGENTS\n").unwrap();
+        fs::write(metadata::get_metadata_path("scrambled").unwrap(), "{not json").unwrap();
+
+        let source = temp_dir.path().join("AGENTS.md");
+        fs::write(&source, "# AGENTS\n\n- kept\n").unwrap();
+        history::record_version("healthy", &source, None).unwrap();
+        fs::remove_file(history::get_history_dir("healthy").unwrap().join("1.json")).unwrap();
+
+        journal::record_event("healthy", "stash", None).unwrap();
+        let journal_path = journal::get_journal_path().unwrap();
+        let mut journal_content = fs::read_to_string(&journal_path).unwrap();
+        journal_content.push_str("garbage\n");
+        fs::write(&journal_path, journal_content).unwrap();
+
+        commands::handle_repair().unwrap();
+
+        let quarantine_dir = super::get_quarantine_dir().unwrap();
+        assert!(!utils::get_stash_path("broken").unwrap().exists());
+        assert!(utils::get_stash_path("healthy+CLAUDE.md").unwrap().exists());
+        assert_eq!(fs::read_to_string(quarantine_dir.join("stash-broken.md")).unwrap(), "no header\n");
+        assert!(quarantine_dir.join("stash-scrambled.json").exists());
+        assert!(metadata::load_metadata("scrambled").unwrap().is_some());
+        assert!(metadata::load_metadata("healthy").unwrap().is_some());
+        assert_eq!(history::list_versions("healthy").unwrap().len(), 1);
+        assert_eq!(journal::read_events().unwrap().len(), 1);
+        assert_eq!(fs::read_to_string(quarantine_dir.join("journal-malformed.jsonl")).unwrap(), "garbage\n");
+    }
+}
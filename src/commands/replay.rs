@@ -0,0 +1,133 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{color_string, BOLD, GREEN, YELLOW};
+use crate::error::AgStashError;
+use crate::history;
+use crate::journal;
+use crate::utils;
+
+// Content-changing journal actions; a stash none of these touched after the bound still holds what it held then
+const WRITE_ACTIONS: [&str; 8] = ["stash", "edit", "merge", "copy", "import", "rename", "restore", "undo"];
+
+// HandleReplay rebuilds the stashes as they stood at until into a separate directory, from the journal and the history,
+// returning the directory written
+pub fn handle_replay(until: &str, into: Option<&Path>) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let bound = history::parse_time_bound(until)?;
+    let target = match into {
+        Some(dir) => dir.to_path_buf(),
+        None => env::temp_dir().join(format!("agstash-replay-{}", bound.replace([':', '-'], ""))),
+    };
+    if target.is_dir() && fs::read_dir(&target)?.next().is_some() {
+        return Err(AgStashError::FileExists(format!("{} is not empty; choose another directory with --into", target.display())).into());
+    }
+    let stashes_dir = target.join("stashes");
+    fs::create_dir_all(&stashes_dir)?;
+
+    let entries = journal::read_events()?;
+    let keys = journal::stashes_at(&entries, &bound);
+    utils::log_info(&format!("Replaying {} journal entries up to {}", entries.len(), bound));
+
+    println!("Store as of {} in {}", color_string(&bound, BOLD), target.display());
+    let mut missing = 0;
+    for stash_key in &keys {
+        // History moves with a renamed stash, so look it up under the name it has now
+        let current_key = journal::renamed_to(&entries, stash_key);
+        let content = match history::stash_version_at(&current_key, &bound)? {
+            Some(version) => Some((history::read_version(&current_key, version.id)?, format!("version {}", version.id))),
+            None => unchanged_since(&entries, &current_key, &bound)?.map(|content| (content, "current stash".to_string())),
+        };
+
+        match content {
+            Some((content, origin)) => {
+                if let Some(error) = utils::write_file(stashes_dir.join(format!("stash-{}.md", stash_key)), &content) {
+                    return Err(error);
+                }
+                println!("  {} {} ({})", color_string("Restored", GREEN), color_string(stash_key, BOLD), origin);
+            }
+            None => {
+                missing += 1;
+                println!("  {} {} (no version recorded by then)", color_string("Missing", YELLOW), color_string(stash_key, BOLD));
+            }
+        }
+    }
+
+    if keys.is_empty() {
+        println!("{}", color_string("No stashes existed at that time.", YELLOW));
+    } else if missing > 0 {
+        println!("\n{} stash(es) existed but their content at that time was not recorded", missing);
+    }
+    Ok(target)
+}
+
+// unchanged_since returns the current content of a stash when nothing has written to it after bound
+fn unchanged_since(entries: &[journal::JournalEntry], stash_key: &str, bound: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let written_later = entries.iter().any(|entry| {
+        entry.project == stash_key && entry.timestamp.as_str() > bound && WRITE_ACTIONS.contains(&entry.action.as_str())
+    });
+    let stash_path = utils::get_stash_path(stash_key)?;
+    if written_later || !utils::file_exists(&stash_path) {
+        return Ok(None);
+    }
+
+    let (err, content) = utils::read_file(&stash_path);
+    match err {
+        Some(error) => Err(error),
+        None => Ok(Some(content)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::history;
+    use crate::journal;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_replay() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        let source = temp_dir.path().join("AGENTS.md");
+        fs::write(&source, "# AGENTS\n\n- old rule\n").unwrap();
+        history::record_version("api", &source, None).unwrap();
+        journal::record_event("api", "stash", None).unwrap();
+
+        // Pretend the first stash happened long ago, then stash again today
+        let history_dir = history::get_history_dir("api").unwrap();
+        let record = history_dir.join("1.json");
+        let mut version: history::Version = serde_json::from_str(&fs::read_to_string(&record).unwrap()).unwrap();
+        version.created_at = "2026-01-01T00:00:00Z".to_string();
+        fs::write(&record, serde_json::to_string(&version).unwrap()).unwrap();
+        let journal_path = journal::get_journal_path().unwrap();
+        let mut entry: journal::JournalEntry = serde_json::from_str(fs::read_to_string(&journal_path).unwrap().trim()).unwrap();
+        entry.timestamp = "2026-01-01T00:00:00Z".to_string();
+        fs::write(&journal_path, format!("{}\n", serde_json::to_string(&entry).unwrap())).unwrap();
+
+        fs::write(&source, "# AGENTS\n\n- new rule\n").unwrap();
+        history::record_version("api", &source, None).unwrap();
+        journal::record_event("api", "stash", None).unwrap();
+
+        let into = temp_dir.path().join("replay");
+        let target = commands::handle_replay("2026-01-02", Some(&into)).unwrap();
+        assert_eq!(
+            fs::read_to_string(target.join("stashes").join("stash-api.md")).unwrap(),
+            "# AGENTS\n\n- old rule\n"
+        );
+
+        // Nothing existed before the first stash, and a used directory is never overwritten
+        let before = temp_dir.path().join("before");
+        commands::handle_replay("2025-12-31", Some(&before)).unwrap();
+        assert_eq!(fs::read_dir(before.join("stashes")).unwrap().count(), 0);
+        assert!(commands::handle_replay("2026-01-02", Some(&into)).is_err());
+        assert!(commands::handle_replay("last tuesday", None).is_err());
+    }
+}
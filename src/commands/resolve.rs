@@ -0,0 +1,216 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use similar::TextDiff;
+
+use super::{color_string, BOLD, CYAN, GREEN, RED, YELLOW};
+use crate::history;
+use crate::journal;
+use crate::metadata::{self, StashMetadata};
+use crate::store;
+use crate::utils;
+
+// ConflictResolution is what resolve does with a conflict copy a sync client left beside a stash
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConflictResolution {
+    // Show both sides and prompt for each copy
+    Ask,
+    KeepStash,
+    KeepCopy,
+    // Keep the copy as its own stash, e.g. "api+conflict"
+    KeepBoth,
+    Skip,
+}
+
+// HandleResolve settles the conflict copies that file sync clients left in the store, one at a time
+pub fn handle_resolve(resolution: ConflictResolution, to_trash: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let conflicts = utils::list_sync_conflicts(utils::get_stashes_dir()?)?;
+    if conflicts.is_empty() {
+        println!("{}", color_string("No sync conflicts found.", GREEN));
+        return Ok(());
+    }
+
+    let mut resolved = 0;
+    for (stash_key, copy_path) in conflicts {
+        let file_name = copy_path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let stash_path = utils::get_stash_path(&stash_key)?;
+        let (err, copy) = utils::read_file(&copy_path);
+        if let Some(error) = err {
+            return Err(error);
+        }
+
+        // A copy whose stash is gone, or that matches it, needs no decision
+        let choice = if !utils::file_exists(&stash_path) {
+            ConflictResolution::KeepCopy
+        } else {
+            let (err, stash) = utils::read_file(&stash_path);
+            if let Some(error) = err {
+                return Err(error);
+            }
+            if stash == copy {
+                ConflictResolution::KeepStash
+            } else if resolution == ConflictResolution::Ask {
+                print_conflict(&stash_key, &file_name, &stash, &copy);
+                ask_resolution()?
+            } else {
+                resolution
+            }
+        };
+
+        let outcome = match choice {
+            ConflictResolution::KeepStash => {
+                utils::discard(&copy_path, to_trash)?;
+                format!("kept the stash for {}", stash_key)
+            }
+            ConflictResolution::KeepCopy => {
+                if !utils::is_valid_agents(&copy) {
+                    println!("{} {} (missing the '# AGENTS' header)", color_string("Skipped", YELLOW), file_name);
+                    continue;
+                }
+                replace_stash(&stash_key, &stash_path, &copy, &file_name)?;
+                utils::remove_file(&copy_path)?;
+                format!("replaced the stash for {} with the copy", stash_key)
+            }
+            ConflictResolution::KeepBoth => {
+                let slot_key = free_slot(&stash_key)?;
+                let slot_path = utils::get_stash_path(&slot_key)?;
+                fs::rename(&copy_path, &slot_path)?;
+                store::share_stash(&slot_path)?;
+                let message = format!("sync conflict copy of {}", stash_key);
+                metadata::save_metadata(&slot_key, &StashMetadata::new(Some(&message), None))?;
+                history::record_version(&slot_key, &slot_path, Some(&message))?;
+                journal::record_event(&slot_key, "copy", Some(&message))?;
+                format!("kept the copy as {}", color_string(&slot_key, BOLD))
+            }
+            ConflictResolution::Ask | ConflictResolution::Skip => {
+                println!("{} {}", color_string("Skipped", YELLOW), file_name);
+                continue;
+            }
+        };
+        utils::log_info(&format!("Resolved {}: {}", copy_path.display(), outcome));
+        println!("{} {}: {}", color_string("Resolved", GREEN), file_name, outcome);
+        resolved += 1;
+    }
+
+    println!("\n{} conflict(s) resolved", resolved);
+    Ok(())
+}
+
+// print_conflict shows how a conflict copy differs from the stash it belongs to
+fn print_conflict(stash_key: &str, file_name: &str, stash: &str, copy: &str) {
+    println!("\n{} {}", color_string("Sync conflict for", YELLOW), color_string(stash_key, BOLD));
+    let stash_header = format!("stash/{}", stash_key);
+    let diff = TextDiff::from_lines(stash, copy);
+    let unified = diff.unified_diff().context_radius(3).header(&stash_header, file_name).to_string();
+    for line in unified.lines() {
+        if line.starts_with("---") || line.starts_with("+++") {
+            println!("{}", color_string(line, BOLD));
+        } else if line.starts_with("@@") {
+            println!("{}", color_string(line, CYAN));
+        } else if line.starts_with('+') {
+            println!("{}", color_string(line, GREEN));
+        } else if line.starts_with('-') {
+            println!("{}", color_string(line, RED));
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+// ask_resolution prompts for what to do with one conflict copy, skipping it on anything unrecognised
+fn ask_resolution() -> Result<ConflictResolution, Box<dyn std::error::Error>> {
+    print!("Keep the [s]tash, the [c]opy, [b]oth, or leave it for [l]ater? [s/c/b/L]: ");
+    io::stdout().flush()?; // Ensure the prompt is displayed
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let resolution = match input.trim().to_lowercase().as_str() {
+        "s" | "stash" => ConflictResolution::KeepStash,
+        "c" | "copy" => ConflictResolution::KeepCopy,
+        "b" | "both" => ConflictResolution::KeepBoth,
+        _ => ConflictResolution::Skip,
+    };
+    Ok(resolution)
+}
+
+// replace_stash writes a conflict copy over its stash, keeping the stash it replaces in the history
+fn replace_stash(stash_key: &str, stash_path: &Path, copy: &str, file_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    history::record_snapshot(stash_key, stash_path, "resolve")?;
+    if let Some(error) = store::write_stash(stash_path, copy) {
+        return Err(error);
+    }
+    store::share_stash(stash_path)?;
+    let message = format!("resolved sync conflict with {}", file_name);
+    if metadata::load_metadata(stash_key)?.is_some() {
+        metadata::refresh_checksum(stash_key)?;
+    } else {
+        metadata::save_metadata(stash_key, &StashMetadata::new(Some(&message), None))?;
+    }
+    history::record_version(stash_key, stash_path, Some(&message))?;
+    journal::record_event(stash_key, "edit", Some(&message))?;
+    Ok(())
+}
+
+// free_slot finds an unused slot key for a kept conflict copy, e.g. "api+conflict" or "api+conflict-2"
+fn free_slot(stash_key: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let mut candidate = utils::slot_stash_key(stash_key, "conflict");
+    let mut counter = 2;
+    while utils::file_exists(utils::get_stash_path(&candidate)?) {
+        candidate = utils::slot_stash_key(stash_key, &format!("conflict-{}", counter));
+        counter += 1;
+    }
+    Ok(candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use super::ConflictResolution;
+    use crate::commands;
+    use crate::history;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_resolve() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        let stashes_dir = utils::get_stashes_dir().unwrap();
+        fs::create_dir_all(&stashes_dir).unwrap();
+        let api_path = utils::get_stash_path("api").unwrap();
+        fs::write(&api_path, "# AGENTS\n\n- laptop\n").unwrap();
+        let api_copy = stashes_dir.join("stash-api (conflicted copy).md");
+        fs::write(&api_copy, "# AGENTS\n\n- desktop\n").unwrap();
+
+        // Leaving it for later changes nothing
+        commands::handle_resolve(ConflictResolution::Skip, false).unwrap();
+        assert!(api_copy.exists());
+
+        commands::handle_resolve(ConflictResolution::KeepBoth, false).unwrap();
+        assert!(!api_copy.exists());
+        assert_eq!(fs::read_to_string(&api_path).unwrap(), "# AGENTS\n\n- laptop\n");
+        assert_eq!(
+            fs::read_to_string(utils::get_stash_path("api+conflict").unwrap()).unwrap(),
+            "# AGENTS\n\n- desktop\n"
+        );
+
+        // Taking the copy keeps the replaced stash in the history
+        fs::write(stashes_dir.join("stash-api.sync-conflict-20260101-120000-ABCDEFG.md"), "# AGENTS\n\n- phone\n").unwrap();
+        commands::handle_resolve(ConflictResolution::KeepCopy, false).unwrap();
+        assert_eq!(fs::read_to_string(&api_path).unwrap(), "# AGENTS\n\n- phone\n");
+        let versions = history::list_versions("api").unwrap();
+        assert_eq!(history::read_version("api", versions[0].id).unwrap(), "# AGENTS\n\n- laptop\n");
+
+        fs::write(stashes_dir.join("stash-api 2.md"), "# AGENTS\n\n- tablet\n").unwrap();
+        commands::handle_resolve(ConflictResolution::KeepStash, false).unwrap();
+        assert_eq!(fs::read_to_string(&api_path).unwrap(), "# AGENTS\n\n- phone\n");
+        assert!(utils::list_sync_conflicts(&stashes_dir).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::{announce, apply_allowed, color_string, fail_if_strict, get_user_confirmation, ApplyOptions, BOLD, GREEN, RED, YELLOW};
+use crate::bundle;
+use crate::error::AgStashError;
+use crate::journal;
+use crate::managed::ManagedFile;
+use crate::utils;
+
+// Rule a new rules directory starts with, applied to every request like AGENTS.md
+const STARTER_RULE: &str = "---\ndescription: Project conventions\nalwaysApply: true\n---\n\n\n";
+
+// Name of the rule init creates in an empty rules directory
+const STARTER_RULE_NAME: &str = "project.mdc";
+
+// InitRules creates a starter rule in a rules directory that has none
+pub fn init_rules(root: &Path, rules: &ManagedFile) -> Result<(), Box<dyn std::error::Error>> {
+    if !rules.rule_files(root)?.is_empty() {
+        println!("{} {}", color_string(&rules.name, BOLD), color_string("already has rules.", YELLOW));
+        return Ok(());
+    }
+    let dir = root.join(&rules.name);
+    fs::create_dir_all(&dir)?;
+    if let Some(error) = utils::write_file(dir.join(STARTER_RULE_NAME), STARTER_RULE) {
+        return Err(error);
+    }
+    utils::log_info(&format!("Created {}/{}", rules.name, STARTER_RULE_NAME));
+    announce(&format!("{} {}/{}", color_string("Created", GREEN), rules.name, STARTER_RULE_NAME));
+    Ok(())
+}
+
+// CleanRules removes a rules directory, first keeping its rules in the pre-clean slot when auto_stash is set
+pub fn clean_rules(
+    root: &Path,
+    rules: &ManagedFile,
+    slot: &str,
+    auto_stash: bool,
+    to_trash: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = root.join(&rules.name);
+    if !dir.is_dir() {
+        println!("{} {}", color_string(&rules.name, BOLD), color_string("does not exist.", YELLOW));
+        return Ok(());
+    }
+    if auto_stash {
+        let captured = bundle::save_bundle(root, slot, &rules.rule_files(root)?)?;
+        if !captured.is_empty() {
+            journal::record_event(slot, "stash", Some("automatic snapshot before clean"))?;
+            announce(&format!("{} {} to {}", color_string("Stashed", GREEN), rules.name, color_string(slot, BOLD)));
+        }
+    }
+    utils::discard(&dir, to_trash)?;
+    let verb = if to_trash { "Trashed" } else { "Removed" };
+    utils::log_info(&format!("{} {}", verb, rules.name));
+    println!("{} {}", color_string(verb, RED), rules.name);
+    Ok(())
+}
+
+// StashRules stashes every rule file in a rules directory as one unit, replacing the directory's previous stash
+pub fn stash_rules(
+    root: &Path,
+    rules: &ManagedFile,
+    stash_key: &str,
+    message: Option<&str>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let files = rules.rule_files(root)?;
+    for relative in &files {
+        let (err, content) = utils::read_file(root.join(relative));
+        if let Some(error) = err {
+            return Err(error);
+        }
+        if content.trim().is_empty() {
+            fail_if_strict(AgStashError::InvalidFile(format!("{} is empty", relative)))?;
+            println!("{} {}", color_string(&format!("{} is empty.", relative), YELLOW), color_string("Stash aborted.", YELLOW));
+            return Ok(false);
+        }
+    }
+
+    let captured = bundle::save_bundle(root, stash_key, &files)?;
+    if captured.is_empty() {
+        fail_if_strict(AgStashError::FileNotFound(format!("{} has no rule files in the project root", rules.name)))?;
+        println!("{} {}", color_string(&rules.name, BOLD), color_string("has no rule files in the project root.", YELLOW));
+        return Ok(false);
+    }
+    journal::record_event(stash_key, "stash", message)?;
+    utils::log_info(&format!("Stashed {} rule file(s) as {}", captured.len(), stash_key));
+    announce(&format!(
+        "{} {} rule file(s) from {} for {}",
+        color_string("Stashed", GREEN),
+        captured.len(),
+        rules.name,
+        color_string(stash_key, BOLD)
+    ));
+    Ok(true)
+}
+
+// ApplyRules writes a stashed rules directory back into root in one step, asking before it replaces rules that differ
+// unless forced; rules the stash doesn't have are left alone
+pub fn apply_rules(
+    root: &Path,
+    rules: &ManagedFile,
+    stash_key: &str,
+    options: &ApplyOptions,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    if options.version.is_some() || options.append || options.backup {
+        return Err(AgStashError::Usage(format!("--version, --append and --backup work on single files, not on {}", rules.name)).into());
+    }
+    let files = bundle::read_bundle(stash_key)?;
+    if files.is_empty() {
+        fail_if_strict(AgStashError::StashNotFound(format!("No stash found for project {}", stash_key)))?;
+        utils::log_info(&format!("No stash found for project: {}", stash_key));
+        println!("No stash found for project {}", color_string(stash_key, BOLD));
+        return Ok(false);
+    }
+    apply_allowed(root, options.force)?;
+
+    // Rules that already match are left alone so their mtimes survive
+    let changed: bundle::BundleFiles = files
+        .into_iter()
+        .filter(|(relative, content)| fs::read(root.join(relative)).ok().as_ref() != Some(content))
+        .collect();
+    if changed.is_empty() {
+        println!("{} is already up to date for {}", color_string(&rules.name, BOLD), color_string(stash_key, BOLD));
+        return Ok(true);
+    }
+
+    let replaced: Vec<&str> = changed
+        .iter()
+        .filter(|(relative, _)| root.join(relative).exists())
+        .map(|(relative, _)| relative.as_str())
+        .collect();
+    if !replaced.is_empty() && !options.force {
+        println!(
+            "\n{} applying the stash replaces {}",
+            color_string("WARNING:", &format!("{}{}", YELLOW, BOLD)),
+            replaced.join(", ")
+        );
+        print!("Type 'yes' to confirm or 'no' to cancel [y/N]: ");
+        io::stdout().flush()?; // Ensure the prompt is displayed
+        if !get_user_confirmation()? {
+            println!("\nOperation cancelled. {} was not modified.", color_string(&rules.name, BOLD));
+            return Ok(false);
+        }
+    }
+
+    bundle::apply_bundle(root, &changed)?;
+    journal::record_event(stash_key, "apply", None)?;
+    utils::log_info(&format!("Applied {} rule file(s) from {}", changed.len(), stash_key));
+    announce(&format!(
+        "{} {} rule file(s) to {} for {}",
+        color_string("Applied", GREEN),
+        changed.len(),
+        rules.name,
+        color_string(stash_key, BOLD)
+    ));
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::bundle;
+    use crate::commands;
+    use crate::managed::ManagedFile;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_cursor_rules_dir() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir(".git").unwrap();
+
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+
+        // Ensure cleanup happens, AGENTS.md becoming the target again
+        let _cleanup = defer::defer(move || {
+            commands::set_target_file(ManagedFile::agents());
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        commands::set_target_file(ManagedFile::new(".cursor/rules").unwrap());
+        commands::handle_init(false).unwrap();
+        assert!(Path::new(".cursor/rules/project.mdc").exists());
+
+        fs::create_dir_all(".cursor/rules/frontend").unwrap();
+        fs::write(".cursor/rules/project.mdc", "Use tabs.\n").unwrap();
+        fs::write(".cursor/rules/frontend/react.mdc", "Use hooks.\n").unwrap();
+        commands::handle_stash(None).unwrap();
+        let stash_key = format!("{}+.cursor--rules", utils::project_stash_key(temp_dir.path()).unwrap());
+        assert_eq!(
+            bundle::list_bundle(&stash_key).unwrap(),
+            vec![".cursor/rules/frontend/react.mdc", ".cursor/rules/project.mdc"]
+        );
+
+        // Clean keeps the directory in the pre-clean slot before removing it
+        commands::handle_clean(true, false).unwrap();
+        assert!(!Path::new(".cursor/rules").exists());
+        assert_eq!(bundle::list_bundle(&utils::slot_stash_key(&stash_key, "pre-clean")).unwrap().len(), 2);
+
+        commands::handle_apply(&commands::ApplyOptions::default()).unwrap();
+        assert_eq!(fs::read_to_string(".cursor/rules/frontend/react.mdc").unwrap(), "Use hooks.\n");
+        assert!(commands::handle_apply(&commands::ApplyOptions { append: true, ..Default::default() }).is_err());
+    }
+}
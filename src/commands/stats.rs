@@ -0,0 +1,219 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::json;
+
+use super::{color_string, json_output, print_json, BOLD, CYAN, YELLOW};
+use crate::archive;
+use crate::history;
+use crate::metadata;
+use crate::store;
+use crate::utils;
+
+// StoreStats summarizes what the stash store holds and how much space it takes
+#[derive(Debug, Default)]
+struct StoreStats {
+    stashes: usize,
+    versions: usize,
+    total_bytes: u64,
+    dedup_saved_bytes: u64,
+    oldest: Option<(String, String)>,
+    newest: Option<(String, String)>,
+    projects: BTreeMap<String, ProjectUsage>,
+}
+
+// ProjectUsage is one project's share of the store
+#[derive(Debug, Default, PartialEq)]
+struct ProjectUsage {
+    stashes: usize,
+    versions: usize,
+    bytes: u64,
+}
+
+// HandleStats reports how many projects, stashes and history versions the store holds and the disk space they use
+pub fn handle_stats() -> Result<(), Box<dyn std::error::Error>> {
+    let stats = collect_stats()?;
+    if json_output() {
+        return stats_json(&stats);
+    }
+
+    if stats.stashes == 0 && stats.versions == 0 {
+        println!("{}", color_string("The stash store is empty.", YELLOW));
+        return Ok(());
+    }
+
+    println!("Projects:  {}", stats.projects.len());
+    println!("Stashes:   {}", stats.stashes);
+    println!("Versions:  {}", stats.versions);
+    println!("Disk used: {}", color_string(&utils::format_size(stats.total_bytes), BOLD));
+    if stats.dedup_saved_bytes > 0 {
+        println!("Saved by sharing identical stashes: {}", utils::format_size(stats.dedup_saved_bytes));
+    }
+    if let Some((stash_key, created_at)) = &stats.oldest {
+        println!("Oldest:    {} {}", color_string(stash_key, BOLD), color_string(created_at, CYAN));
+    }
+    if let Some((stash_key, created_at)) = &stats.newest {
+        println!("Newest:    {} {}", color_string(stash_key, BOLD), color_string(created_at, CYAN));
+    }
+
+    println!();
+    for (project_name, usage) in &stats.projects {
+        println!(
+            "{:<24} {:>10}  {} stash(es), {} version(s)",
+            color_string(project_name, BOLD),
+            utils::format_size(usage.bytes),
+            usage.stashes,
+            usage.versions
+        );
+    }
+
+    Ok(())
+}
+
+// stats_json prints every figure stats gathered as JSON
+fn stats_json(stats: &StoreStats) -> Result<(), Box<dyn std::error::Error>> {
+    let dated = |entry: &Option<(String, String)>| {
+        entry
+            .as_ref()
+            .map(|(stash_key, created_at)| json!({ "project": stash_key, "created_at": created_at }))
+    };
+    let projects: serde_json::Map<String, serde_json::Value> = stats
+        .projects
+        .iter()
+        .map(|(project_name, usage)| {
+            let usage = json!({ "stashes": usage.stashes, "versions": usage.versions, "bytes": usage.bytes });
+            (project_name.clone(), usage)
+        })
+        .collect();
+    print_json(&json!({
+        "stashes": stats.stashes,
+        "versions": stats.versions,
+        "total_bytes": stats.total_bytes,
+        "dedup_saved_bytes": stats.dedup_saved_bytes,
+        "oldest": dated(&stats.oldest),
+        "newest": dated(&stats.newest),
+        "projects": projects,
+    }))
+}
+
+// collect_stats walks the store once and gathers every figure stats prints
+fn collect_stats() -> Result<StoreStats, Box<dyn std::error::Error>> {
+    let agstash_dir = utils::get_agstash_dir()?;
+    let mut stats = StoreStats::default();
+
+    let mut stored_hashes = HashSet::new();
+    let mut dated = Vec::new();
+    for (stash_key, path) in utils::list_stashes(utils::get_stashes_dir()?)? {
+        stats.stashes += 1;
+        stats.projects.entry(utils::project_of(&stash_key).to_string()).or_default().stashes += 1;
+
+        // Every identical copy after the first is shared rather than stored
+        let content = fs::read(&path)?;
+        if !stored_hashes.insert(store::content_hash(&content)) {
+            stats.dedup_saved_bytes += content.len() as u64;
+        }
+
+        let created_at = match metadata::load_metadata(&stash_key)? {
+            Some(stash_metadata) => stash_metadata.created_at,
+            None => utils::format_timestamp(fs::metadata(&path)?.modified()?),
+        };
+        dated.push((created_at, stash_key));
+    }
+    dated.sort();
+    stats.oldest = dated.first().map(|(created_at, key)| (key.clone(), created_at.clone()));
+    stats.newest = dated.last().map(|(created_at, key)| (key.clone(), created_at.clone()));
+
+    for stash_key in history::list_histories()? {
+        let count = history::list_versions(&stash_key)?.len();
+        stats.versions += count;
+        stats.projects.entry(utils::project_of(&stash_key).to_string()).or_default().versions += count;
+    }
+
+    for (project_name, usage) in stats.projects.iter_mut() {
+        for relative in archive::project_files(project_name)? {
+            usage.bytes += fs::metadata(agstash_dir.join(relative))?.len();
+        }
+    }
+
+    if agstash_dir.is_dir() {
+        stats.total_bytes = disk_usage(&agstash_dir)?;
+    }
+    Ok(stats)
+}
+
+// disk_usage adds up the size of every file under dir, counting hard-linked files once
+fn disk_usage(dir: &Path) -> Result<u64, Box<dyn std::error::Error>> {
+    let mut seen = HashSet::new();
+    let mut total = 0;
+    let mut pending: Vec<PathBuf> = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else if metadata.is_file() && seen.insert(file_identity(&entry.path(), &metadata)) {
+                total += metadata.len();
+            }
+        }
+    }
+    Ok(total)
+}
+
+// file_identity tells hard links to the same file apart from distinct files
+#[cfg(unix)]
+fn file_identity(_path: &Path, metadata: &fs::Metadata) -> String {
+    use std::os::unix::fs::MetadataExt;
+    format!("{}:{}", metadata.dev(), metadata.ino())
+}
+
+// file_identity falls back to the path where the store never links files
+#[cfg(not(unix))]
+fn file_identity(path: &Path, _metadata: &fs::Metadata) -> String {
+    path.display().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::history;
+    use crate::store;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_collect_stats() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        // An empty store reports nothing
+        assert!(commands::handle_stats().is_ok());
+        assert_eq!(super::collect_stats().unwrap().stashes, 0);
+
+        let shared = "# AGENTS\n\n- shared\n";
+        for stash_key in ["api", "api+pre-clean", "web"] {
+            let path = utils::get_stash_path(stash_key).unwrap();
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, shared).unwrap();
+            store::share_stash(&path).unwrap();
+            history::record_version(stash_key, &path, None).unwrap();
+        }
+
+        let stats = super::collect_stats().unwrap();
+        assert_eq!(stats.stashes, 3);
+        assert_eq!(stats.versions, 3);
+        assert_eq!(stats.projects.len(), 2);
+        assert_eq!(stats.projects["api"].stashes, 2);
+        assert_eq!(stats.dedup_saved_bytes, 2 * shared.len() as u64);
+        assert!(stats.total_bytes > 0);
+        assert!(stats.oldest.is_some() && stats.newest.is_some());
+        assert!(commands::handle_stats().is_ok());
+    }
+}
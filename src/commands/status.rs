@@ -0,0 +1,280 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+
+use serde_json::{json, Value};
+
+use super::{color_string, global_fallback, indicator, json_output, nested_context, print_json, root_context, target_file, Indicator, BOLD, TOP_LEVEL};
+use crate::daemon::{self, State};
+use crate::error::AgStashError;
+use crate::managed::ManagedFile;
+use crate::utils;
+
+// HandleStatus reports whether AGENTS.md, or the file chosen with --file, and its stash exist, whether they match, and
+// when each was last modified; a nested AGENTS.md is reported separately
+pub fn handle_status() -> Result<(), Box<dyn std::error::Error>> {
+    let file = target_file();
+    if file.is_rules_dir() {
+        return Err(AgStashError::Usage(format!("status works on single files, not on {}", file.name)).into());
+    }
+    let (root, project_name) = match root_context() {
+        Ok(context) => context,
+        Err(error) => {
+            // Outside any project the only instructions an agent can get are the global fallback's
+            let Some((_, fallback_content)) = global_fallback()? else {
+                return Err(error);
+            };
+            let dir = env::current_dir()?;
+            let agents_path = dir.join(&file.name);
+            if json_output() {
+                let level = level_json(&file, &dir, &agents_path, None, Some(&fallback_content));
+                return print_json(&json!({ "project": null, "root": null, "levels": [level] }));
+            }
+            println!("Project: {}", color_string("none", BOLD));
+            println!("Dir:     {}", dir.display());
+            println!("{}: {}", file.name, describe_file(&agents_path));
+            if !report_fallback(&agents_path, &fallback_content)? {
+                println!("Source:    {}", indicator(Indicator::Warning, "no project; apply --fallback-global writes the global fallback"));
+            }
+            return Ok(());
+        }
+    };
+    let fallback_content = global_fallback().ok().flatten().map(|(_, content)| content);
+    let nested = if TOP_LEVEL.load(Ordering::Relaxed) {
+        None
+    } else {
+        nested_context(&root, &project_name)?
+    };
+
+    if json_output() {
+        let fallback_content = fallback_content.as_deref();
+        let stash_path = utils::get_stash_path(&file.stash_key(&project_name))?;
+        let mut levels = vec![level_json(&file, &root, &root.join(&file.name), Some(&stash_path), fallback_content)];
+        if let Some((dir, stash_key)) = &nested {
+            let stash_path = utils::get_stash_path(&file.stash_key(stash_key))?;
+            levels.push(level_json(&file, dir, &dir.join(&file.name), Some(&stash_path), fallback_content));
+        }
+        return print_json(&json!({
+            "project": project_name,
+            "root": root.display().to_string(),
+            "root_marker": utils::root_marker(&root),
+            "levels": levels,
+        }));
+    }
+
+    println!("Project: {}", color_string(&project_name, BOLD));
+    match utils::root_marker(&root) {
+        Some(marker) => println!("Root:    {} (found by {})", root.display(), marker),
+        None => println!("Root:    {}", root.display()),
+    }
+
+    // A running daemon already knows the answer for a project with no nested AGENTS.md; it only watches AGENTS.md
+    if nested.is_none() && file.is_agents() {
+        if let Some(project_state) = daemon::query(&root) {
+            let marker = super::daemon::state_indicator(project_state.state);
+            println!("AGENTS.md: {}", indicator(marker, project_state.state.label()));
+            if let Some(stashed_at) = &project_state.stashed_at {
+                println!("Stashed:   {}", stashed_at);
+            }
+            println!("Daemon:    checked at {}", project_state.checked_at);
+            return Ok(());
+        }
+    }
+    let stash_path = utils::get_stash_path(&file.stash_key(&project_name))?;
+    report_level(&file, &root.join(&file.name), &stash_path, fallback_content.as_deref())?;
+
+    if let Some((dir, stash_key)) = nested {
+        let relative = dir.strip_prefix(&root).unwrap_or(&dir);
+        println!();
+        println!("Nested:  {}", color_string(&relative.display().to_string(), BOLD));
+        let stash_path = utils::get_stash_path(&file.stash_key(&stash_key))?;
+        report_level(&file, &dir.join(&file.name), &stash_path, fallback_content.as_deref())?;
+    }
+
+    Ok(())
+}
+
+// report_level prints existence, validity and drift for one agent file and its stash, noting when a file without a
+// stash is the global fallback
+fn report_level(
+    file: &ManagedFile,
+    agents_path: &Path,
+    stash_path: &Path,
+    fallback_content: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("{}: {}", file.name, describe_file(agents_path));
+    println!("Stash:     {}", describe_file(stash_path));
+    match fs::read_link(agents_path) {
+        Ok(target) if target == stash_path => println!("Mode:      {}", indicator(Indicator::Ok, "linked to stash")),
+        Ok(target) => println!("Mode:      {}", indicator(Indicator::Warning, &format!("symlink to {}", target.display()))),
+        Err(_) if utils::file_exists(agents_path) => println!("Mode:      copy"),
+        Err(_) => {}
+    }
+
+    if !utils::file_exists(agents_path) {
+        return Ok(());
+    }
+
+    let (err, agents_content) = utils::read_file(agents_path);
+    if let Some(error) = err {
+        return Err(error);
+    }
+    if !file.is_valid(&agents_content) {
+        println!("Content:   {}", indicator(Indicator::Warning, &format!("{} is not valid ({})", file.name, file.requirement())));
+    }
+
+    if !utils::file_exists(stash_path) {
+        if let Some(fallback_content) = fallback_content {
+            report_fallback(agents_path, fallback_content)?;
+        }
+        return Ok(());
+    }
+
+    let (err, stash_content) = utils::read_file(stash_path);
+    if let Some(error) = err {
+        return Err(error);
+    }
+
+    if agents_content == stash_content {
+        println!("Drift:     {}", indicator(Indicator::Ok, "in sync"));
+    } else {
+        println!("Drift:     {}", indicator(Indicator::Drift, &format!("{} differs from stash", file.name)));
+    }
+
+    Ok(())
+}
+
+// level_json describes one agent file and its stash for --output json; state is one of missing, invalid, unstashed,
+// in_sync and drift
+fn level_json(
+    file: &ManagedFile,
+    dir: &Path,
+    agents_path: &Path,
+    stash_path: Option<&Path>,
+    fallback_content: Option<&str>,
+) -> Value {
+    let content = fs::read(agents_path).ok();
+    let stash_content = stash_path.and_then(|stash_path| fs::read(stash_path).ok());
+    let state = match (&content, &stash_content) {
+        (None, _) => State::Missing,
+        (Some(content), _) if !file.is_valid(&String::from_utf8_lossy(content)) => State::Invalid,
+        (Some(_), None) => State::Unstashed,
+        (Some(content), Some(stash_content)) if content == stash_content => State::InSync,
+        (Some(_), Some(_)) => State::Drift,
+    };
+    let mode = match fs::read_link(agents_path) {
+        Ok(target) if Some(target.as_path()) == stash_path => Some("linked"),
+        Ok(_) => Some("symlink"),
+        Err(_) => content.is_some().then_some("copy"),
+    };
+    json!({
+        "dir": dir.display().to_string(),
+        "agents": file_json(agents_path),
+        "stash": stash_path.map(file_json),
+        "mode": mode,
+        "state": state,
+        "global_fallback": content.is_some() && fallback_content.map(str::as_bytes) == content.as_deref(),
+    })
+}
+
+// file_json describes whether a file exists and when it was last modified
+fn file_json(path: &Path) -> Value {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok().map(utils::format_timestamp);
+    json!({
+        "path": path.display().to_string(),
+        "exists": utils::file_exists(path),
+        "modified": modified,
+    })
+}
+
+// report_fallback prints that the file is the global fallback when it is, returning whether it was
+fn report_fallback(agents_path: &Path, fallback_content: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    if !utils::file_exists(agents_path) || fs::read(agents_path)? != fallback_content.as_bytes() {
+        return Ok(false);
+    }
+    println!("Source:    {}", indicator(Indicator::Ok, "using global fallback"));
+    Ok(true)
+}
+
+// describe_file summarises whether a file exists and when it was last modified
+fn describe_file(path: &Path) -> String {
+    if !utils::file_exists(path) {
+        return indicator(Indicator::Error, "missing");
+    }
+
+    let modified = fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map(utils::format_timestamp)
+        .unwrap_or_else(|_| "unknown".to_string());
+    format!("{} (modified {})", indicator(Indicator::Ok, "present"), modified)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::managed::ManagedFile;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_status() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(|| {
+            let _ = env::set_current_dir(&original_dir);
+        });
+
+        // Create a .git directory to establish project root
+        fs::create_dir(".git").unwrap();
+
+        // Set up HOME environment variable to temp directory
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        // Neither file exists yet
+        assert!(commands::handle_status().is_ok());
+
+        // Both files exist
+        fs::write("AGENTS.md", "# AGENTS\n").unwrap();
+        commands::handle_stash(None).unwrap();
+        assert!(commands::handle_status().is_ok());
+    }
+
+    #[test]
+    fn test_describe_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("AGENTS.md");
+        assert!(super::describe_file(&path).contains("missing"));
+
+        fs::write(&path, "# AGENTS\n").unwrap();
+        assert!(super::describe_file(&path).contains("(modified "));
+    }
+
+    #[test]
+    fn test_level_json() {
+        let temp_dir = TempDir::new().unwrap();
+        let agents_path = temp_dir.path().join("AGENTS.md");
+        let stash_path = temp_dir.path().join("stash.md");
+        assert_eq!(super::level_json(&ManagedFile::agents(), temp_dir.path(), &agents_path, Some(&stash_path), None)["state"], "missing");
+
+        fs::write(&agents_path, "# AGENTS\n").unwrap();
+        let level = super::level_json(&ManagedFile::agents(), temp_dir.path(), &agents_path, Some(&stash_path), Some("# AGENTS\n"));
+        assert_eq!(level["state"], "unstashed");
+        assert_eq!(level["mode"], "copy");
+        assert_eq!(level["global_fallback"], true);
+
+        fs::write(&stash_path, "# AGENTS\n\n- other\n").unwrap();
+        let level = super::level_json(&ManagedFile::agents(), temp_dir.path(), &agents_path, Some(&stash_path), None);
+        assert_eq!(level["state"], "drift");
+        assert_eq!(level["stash"]["exists"], true);
+    }
+}
@@ -0,0 +1,110 @@
+use std::fs;
+use std::io::{self, Write};
+
+use super::{color_string, get_user_confirmation, indicator, project_context, Indicator, BOLD, YELLOW};
+use crate::config;
+use crate::managed::{ManagedFile, AGENTS_FILE};
+use crate::utils;
+
+// HandleSync rewrites every configured mirror, e.g. CLAUDE.md and .cursorrules, with the content of AGENTS.md, naming
+// the mirrors that were edited on their own since the last sync. With check set nothing is written. Returns whether any
+// mirror had diverged
+pub fn handle_sync(check: bool, force: bool) -> Result<bool, Box<dyn std::error::Error>> {
+    let (dir, _) = project_context()?;
+    let agents_path = dir.join(AGENTS_FILE);
+    if !utils::file_exists(&agents_path) {
+        println!("{} {}", color_string(AGENTS_FILE, BOLD), color_string("does not exist, nothing to sync from.", YELLOW));
+        return Ok(false);
+    }
+    let source = fs::read(&agents_path)?;
+
+    let mut missing = Vec::new();
+    let mut diverged = Vec::new();
+    for name in config::load_settings()?.mirrors.unwrap_or_default() {
+        let mirror = ManagedFile::new(&name)?;
+        if mirror.is_agents() {
+            continue;
+        }
+        match fs::read(dir.join(&mirror.name)) {
+            Ok(content) if content == source => println!("{}", indicator(Indicator::Ok, &mirror.name)),
+            Ok(_) => {
+                println!("{} has diverged from {}", indicator(Indicator::Drift, &mirror.name), AGENTS_FILE);
+                diverged.push(mirror);
+            }
+            Err(_) => {
+                println!("{} is missing", indicator(Indicator::Error, &mirror.name));
+                missing.push(mirror);
+            }
+        }
+    }
+    if check || (missing.is_empty() && diverged.is_empty()) {
+        return Ok(!diverged.is_empty());
+    }
+
+    // A diverged mirror may hold edits meant for AGENTS.md, so it is only replaced once confirmed
+    if !diverged.is_empty() && !force {
+        print!("\nReplace the diverged mirrors with {}? [y/N]: ", AGENTS_FILE);
+        io::stdout().flush()?; // Ensure the prompt is displayed
+        if !get_user_confirmation()? {
+            println!("\nOperation cancelled. No mirrors were modified.");
+            return Ok(true);
+        }
+    }
+    for mirror in missing.iter().chain(&diverged) {
+        let path = dir.join(&mirror.name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, &source)?;
+        utils::log_info(&format!("Synced {} from {}", path.display(), AGENTS_FILE));
+    }
+    println!(
+        "\n{} mirror(s) regenerated from {}",
+        missing.len() + diverged.len(),
+        color_string(AGENTS_FILE, BOLD)
+    );
+    Ok(!diverged.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_sync() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir(".git").unwrap();
+
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(move || {
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        fs::write("AGENTS.md", "# AGENTS\n\n- Use tabs\n").unwrap();
+        fs::write("CLAUDE.md", "# AGENTS\n\n- Use spaces\n").unwrap();
+
+        // Checking reports the diverged mirror without touching it
+        assert!(commands::handle_sync(true, false).unwrap());
+        assert_eq!(fs::read_to_string("CLAUDE.md").unwrap(), "# AGENTS\n\n- Use spaces\n");
+        assert!(!std::path::Path::new(".cursorrules").exists());
+
+        assert!(commands::handle_sync(false, true).unwrap());
+        assert_eq!(fs::read_to_string("CLAUDE.md").unwrap(), "# AGENTS\n\n- Use tabs\n");
+        assert_eq!(fs::read_to_string(".cursorrules").unwrap(), "# AGENTS\n\n- Use tabs\n");
+        assert!(!commands::handle_sync(true, false).unwrap());
+    }
+}
@@ -0,0 +1,187 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use super::{color_string, get_user_confirmation, project_context, BOLD, GREEN, YELLOW};
+use crate::journal;
+use crate::metadata;
+use crate::store;
+use crate::utils;
+
+// HandleTidy imports ad-hoc AGENTS.md copies left in the project as named stashes and removes them from the working tree
+pub fn handle_tidy(force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let (dir, project_name) = project_context()?;
+    let backups = find_adhoc_backups(&dir)?;
+
+    if backups.is_empty() {
+        utils::log_info(&format!("No ad-hoc AGENTS.md copies found in: {}", dir.display()));
+        println!("Nothing to tidy in {}", color_string(&project_name, BOLD));
+        return Ok(());
+    }
+
+    let mut imported = 0;
+    for (path, slot) in backups {
+        let stash_key = utils::slot_stash_key(&project_name, &slot);
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let stash_path = utils::get_stash_path(&stash_key)?;
+
+        if utils::file_exists(&stash_path) {
+            utils::log_warn(&format!("A stash already exists for: {}", stash_key));
+            println!(
+                "{} {}",
+                color_string(&format!("A stash already exists for {}.", stash_key), YELLOW),
+                color_string(&format!("Skipping {}.", file_name), YELLOW)
+            );
+            continue;
+        }
+
+        if !force {
+            print!(
+                "Import {} as stash {} and remove it? [y/N]: ",
+                color_string(&file_name, BOLD),
+                color_string(&stash_key, BOLD)
+            );
+            io::stdout().flush()?; // Ensure the prompt is displayed
+            if !get_user_confirmation()? {
+                utils::log_info(&format!("User kept {}", path.display()));
+                continue;
+            }
+        }
+
+        if let Some(error) = store::copy_to_stash(&path, &stash_path) {
+            return Err(error);
+        }
+        store::share_stash(&stash_path)?;
+        let message = format!("imported from {}", file_name);
+        let stash_metadata = metadata::StashMetadata::new(Some(&message), Some(path.display().to_string()));
+        metadata::save_metadata(&stash_key, &stash_metadata)?;
+        journal::record_event(&stash_key, "stash", Some(&message))?;
+        fs::remove_file(&path)?;
+        utils::log_info(&format!("Imported {} to: {}", path.display(), stash_path.display()));
+        println!(
+            "{} {} as {}",
+            color_string("Imported", GREEN),
+            file_name,
+            color_string(&stash_key, BOLD)
+        );
+        imported += 1;
+    }
+
+    println!("\n{} file(s) tidied", imported);
+    Ok(())
+}
+
+// find_adhoc_backups lists copies of AGENTS.md in dir, such as AGENTS.md.bak or AGENTS-old.md, with the slot each imports into
+fn find_adhoc_backups(dir: &Path) -> Result<Vec<(PathBuf, String)>, Box<dyn std::error::Error>> {
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let slot = path.file_name().and_then(|name| name.to_str()).and_then(backup_slot);
+        if let Some(slot) = slot {
+            backups.push((path, slot));
+        }
+    }
+    backups.sort();
+    Ok(backups)
+}
+
+// backup_slot derives a stash slot from a file name that looks like a copy of AGENTS.md, e.g. "AGENTS-old.md" becomes "old"
+fn backup_slot(file_name: &str) -> Option<String> {
+    if file_name == "AGENTS.md" {
+        return None;
+    }
+    let rest = file_name.strip_prefix("AGENTS")?;
+    if !rest.starts_with(['.', '-', '_', ' ']) {
+        return None;
+    }
+
+    // Drop the markdown extension wherever it sits, e.g. "AGENTS.md.bak" or "AGENTS-old.md"
+    let rest = rest.replacen(".md", "", 1);
+    let slot: Vec<&str> = rest
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect();
+
+    // A bare copy like "AGENTS.md~" or "AGENTS.md.orig" still counts
+    if slot.is_empty() {
+        if file_name.ends_with('~') {
+            return Some("backup".to_string());
+        }
+        return None;
+    }
+    Some(slot.join("-").to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    fn test_backup_slot() {
+        assert_eq!(super::backup_slot("AGENTS.md.bak").as_deref(), Some("bak"));
+        assert_eq!(super::backup_slot("AGENTS-old.md").as_deref(), Some("old"));
+        assert_eq!(super::backup_slot("AGENTS.md.orig").as_deref(), Some("orig"));
+        assert_eq!(super::backup_slot("AGENTS.md~").as_deref(), Some("backup"));
+        assert_eq!(
+            super::backup_slot("AGENTS.md.20261017T101500Z.bak").as_deref(),
+            Some("20261017t101500z-bak")
+        );
+        assert_eq!(super::backup_slot("AGENTS.md"), None);
+        assert_eq!(super::backup_slot("AGENTSFILE.md"), None);
+        assert_eq!(super::backup_slot("README.md"), None);
+    }
+
+    #[test]
+    #[serial]
+    fn test_handle_tidy() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(|| {
+            let _ = env::set_current_dir(&original_dir);
+        });
+
+        // Create a .git directory to establish project root
+        fs::create_dir(".git").unwrap();
+
+        // Set up HOME environment variable to temp directory
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        fs::write("AGENTS.md", "# AGENTS\n\n- current\n").unwrap();
+        fs::write("AGENTS.md.bak", "# AGENTS\n\n- backup\n").unwrap();
+        fs::write("AGENTS-old.md", "# AGENTS\n\n- old\n").unwrap();
+
+        commands::handle_tidy(true).unwrap();
+
+        // The copies are imported as named stashes and removed
+        let project_name = &utils::project_stash_key(temp_dir.path()).unwrap();
+        let bak = utils::get_stash_path(&utils::slot_stash_key(project_name, "bak")).unwrap();
+        let old = utils::get_stash_path(&utils::slot_stash_key(project_name, "old")).unwrap();
+        assert_eq!(fs::read_to_string(bak).unwrap(), "# AGENTS\n\n- backup\n");
+        assert_eq!(fs::read_to_string(old).unwrap(), "# AGENTS\n\n- old\n");
+        assert!(!Path::new("AGENTS.md.bak").exists());
+        assert!(!Path::new("AGENTS-old.md").exists());
+
+        // The working AGENTS.md is left alone
+        assert!(Path::new("AGENTS.md").exists());
+
+        // An occupied slot is never overwritten
+        fs::write("AGENTS.md.bak", "# AGENTS\n\n- newer\n").unwrap();
+        commands::handle_tidy(true).unwrap();
+        assert!(Path::new("AGENTS.md.bak").exists());
+    }
+}
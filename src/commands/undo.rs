@@ -0,0 +1,114 @@
+use std::io::{self, Write};
+
+use super::{color_string, current_project_name, get_user_confirmation, BOLD, GREEN, YELLOW};
+use crate::journal;
+use crate::undo;
+use crate::utils;
+
+// HandleUndo restores the file removed or overwritten by the project's last clean, apply or drop
+pub fn handle_undo(force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let project_name = current_project_name()?;
+
+    let record = match undo::load_backup(&project_name)? {
+        Some(record) => record,
+        None => {
+            utils::log_info(&format!("No undo backup for project: {}", project_name));
+            println!("Nothing to undo for project {}", color_string(&project_name, BOLD));
+            return Ok(());
+        }
+    };
+
+    // The file may have been recreated since; don't clobber it silently
+    if utils::file_exists(&record.target) && !force {
+        println!(
+            "\n{} {} exists and will be replaced by the version saved before '{}'.",
+            color_string("WARNING:", &format!("{}{}", YELLOW, BOLD)),
+            color_string(&record.target.display().to_string(), BOLD),
+            record.action
+        );
+        print!("Type 'yes' to confirm or 'no' to cancel [y/N]: ");
+        io::stdout().flush()?; // Ensure the prompt is displayed
+
+        if !get_user_confirmation()? {
+            utils::log_info("User declined to undo");
+            println!("\nOperation cancelled. Nothing was restored.");
+            return Ok(());
+        }
+    }
+
+    undo::restore_backup(&project_name)?;
+    journal::record_event(&project_name, "undo", Some(&format!("undid {}", record.action)))?;
+    utils::log_info(&format!("Restored {} from undo backup", record.target.display()));
+    println!(
+        "{} {} (undid {} from {})",
+        color_string("Restored", GREEN),
+        record.target.display(),
+        record.action,
+        record.created_at
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::metadata;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_handle_undo() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(|| {
+            let _ = env::set_current_dir(&original_dir);
+        });
+
+        // Create a .git directory to establish project root
+        fs::create_dir(".git").unwrap();
+
+        // Set up HOME environment variable to temp directory
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        // Nothing to undo yet
+        assert!(commands::handle_undo(true).is_ok());
+
+        // Undo a clean
+        fs::write("AGENTS.md", "# AGENTS\n\n- local\n").unwrap();
+        commands::handle_clean(false, false).unwrap();
+        commands::handle_undo(true).unwrap();
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- local\n");
+
+        // Undo an apply that overwrote local edits
+        commands::handle_stash(Some("kept")).unwrap();
+        fs::write("AGENTS.md", "# AGENTS\n\n- edited\n").unwrap();
+        commands::handle_apply(&commands::ApplyOptions {
+            force: true,
+            ..Default::default()
+        })
+        .unwrap();
+        commands::handle_undo(true).unwrap();
+        assert_eq!(fs::read_to_string("AGENTS.md").unwrap(), "# AGENTS\n\n- edited\n");
+
+        // Undo a drop, including its metadata
+        let project_name = &utils::project_stash_key(temp_dir.path()).unwrap();
+        let stash_path = utils::get_stash_path(project_name).unwrap();
+        commands::handle_drop(true).unwrap();
+        assert!(!stash_path.exists());
+        commands::handle_undo(true).unwrap();
+        assert_eq!(fs::read_to_string(&stash_path).unwrap(), "# AGENTS\n\n- local\n");
+        let stash_metadata = metadata::load_metadata(project_name).unwrap().unwrap();
+        assert_eq!(stash_metadata.message.as_deref(), Some("kept"));
+    }
+}
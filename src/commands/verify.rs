@@ -0,0 +1,173 @@
+use std::fs;
+
+use serde_json::json;
+
+use super::{color_string, indicator, json_output, print_json, Indicator, BOLD};
+use crate::history;
+use crate::metadata;
+use crate::store;
+use crate::utils;
+
+// Verification is what verify found after re-hashing the store
+#[derive(Debug, Default)]
+struct Verification {
+    checked: usize,
+    unrecorded: usize,
+    problems: Vec<String>,
+}
+
+impl Verification {
+    // check compares content with the checksum recorded for it, if any
+    fn check(&mut self, label: &str, content: &[u8], recorded: Option<&str>) {
+        let Some(recorded) = recorded else {
+            self.unrecorded += 1;
+            return;
+        };
+        self.checked += 1;
+        if store::content_hash(content) == recorded {
+            return;
+        }
+        if content.is_empty() {
+            self.problems.push(format!("{} was truncated to 0 bytes", label));
+        } else {
+            self.problems.push(format!("{} changed outside agstash (now {})", label, utils::format_size(content.len() as u64)));
+        }
+    }
+}
+
+// HandleVerify re-hashes every stash, history version and content object and reports any that no longer match the
+// checksum recorded when agstash wrote them, returning whether any were found
+pub fn handle_verify() -> Result<bool, Box<dyn std::error::Error>> {
+    let verification = verify_store()?;
+    if json_output() {
+        print_json(&json!({
+            "checked": verification.checked,
+            "unrecorded": verification.unrecorded,
+            "problems": verification.problems,
+        }))?;
+        return Ok(!verification.problems.is_empty());
+    }
+
+    for problem in &verification.problems {
+        println!("{}", indicator(Indicator::Error, problem));
+    }
+    if verification.unrecorded > 0 {
+        println!(
+            "{} {} file(s) have no checksum yet; they were written before checksums were recorded",
+            indicator(Indicator::Warning, "unverified"),
+            verification.unrecorded
+        );
+    }
+    if verification.problems.is_empty() {
+        println!("{} {} file(s) match their checksums", indicator(Indicator::Ok, "ok"), verification.checked);
+        return Ok(false);
+    }
+    println!(
+        "\n{} of {} file(s) failed verification",
+        color_string(&verification.problems.len().to_string(), BOLD),
+        verification.checked
+    );
+    Ok(true)
+}
+
+// verify_store checks the stashes against their metadata, versions against their records and objects against their names
+fn verify_store() -> Result<Verification, Box<dyn std::error::Error>> {
+    let mut verification = Verification::default();
+
+    let stashes_dir = utils::get_stashes_dir()?;
+    if stashes_dir.is_dir() {
+        for (stash_key, path) in utils::list_stashes(&stashes_dir)? {
+            let label = format!("stash {}", stash_key);
+            let content = match fs::read(&path) {
+                Ok(content) => content,
+                Err(error) => {
+                    verification.problems.push(format!("{} cannot be read: {}", label, error));
+                    continue;
+                }
+            };
+            match metadata::load_metadata(&stash_key) {
+                // A stash applied with --link is edited through the link, so its checksum is expected to go stale
+                Ok(Some(stash_metadata)) if stash_metadata.linked_to.is_some() => {}
+                Ok(stash_metadata) => {
+                    let recorded = stash_metadata.and_then(|stash_metadata| stash_metadata.sha256);
+                    verification.check(&label, &content, recorded.as_deref());
+                }
+                Err(error) => verification.problems.push(format!("{} cannot be checked: {}", label, error)),
+            }
+        }
+    }
+
+    for stash_key in history::list_histories()? {
+        let versions = match history::list_versions(&stash_key) {
+            Ok(versions) => versions,
+            Err(error) => {
+                verification.problems.push(format!("history {} cannot be checked: {}", stash_key, error));
+                continue;
+            }
+        };
+        for version in versions {
+            let label = format!("history {} version {}", stash_key, version.id);
+            match history::read_version(&stash_key, version.id) {
+                Ok(content) => verification.check(&label, content.as_bytes(), version.sha256.as_deref()),
+                Err(error) => verification.problems.push(format!("{} cannot be read: {}", label, error)),
+            }
+        }
+    }
+
+    // Objects are named by their hash, so each one carries its own checksum
+    let objects_dir = store::get_objects_dir()?;
+    if objects_dir.is_dir() {
+        for entry in fs::read_dir(&objects_dir)? {
+            let path = entry?.path();
+            let hash = path.file_name().and_then(|name| name.to_str()).unwrap_or_default().to_string();
+            let content = fs::read(&path)?;
+            verification.check(&format!("object {}", hash), &content, Some(&hash));
+        }
+    }
+    Ok(verification)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::history;
+    use crate::metadata;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_verify_store() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        fs::create_dir_all(utils::get_stashes_dir().unwrap()).unwrap();
+        for key in ["kept", "edited", "emptied", "legacy"] {
+            let stash_path = utils::get_stash_path(key).unwrap();
+            fs::write(&stash_path, format!("# AGENTS\n\n- {}\n", key)).unwrap();
+            if key != "legacy" {
+                metadata::save_metadata(key, &metadata::StashMetadata::new(None, None)).unwrap();
+            }
+            history::record_version(key, &stash_path, None).unwrap();
+        }
+        assert!(!commands::handle_verify().unwrap());
+
+        fs::write(utils::get_stash_path("edited").unwrap(), "# AGENTS\n\n- changed\n").unwrap();
+        fs::write(utils::get_stash_path("emptied").unwrap(), "").unwrap();
+        fs::write(history::get_version_path("kept", 1).unwrap(), "# AGENTS\n").unwrap();
+
+        let verification = super::verify_store().unwrap();
+        assert_eq!(verification.checked, 7);
+        assert_eq!(verification.unrecorded, 1);
+        assert_eq!(verification.problems.len(), 3, "{:?}", verification.problems);
+        assert!(verification.problems.iter().any(|problem| problem == "stash emptied was truncated to 0 bytes"));
+        assert!(verification.problems.iter().any(|problem| problem.starts_with("stash edited changed")));
+        assert!(verification.problems.iter().any(|problem| problem.starts_with("history kept version 1 changed")));
+        assert!(commands::handle_verify().unwrap());
+    }
+}
@@ -0,0 +1,763 @@
+use std::env;
+use std::path::{Component, Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error::AgStashError;
+use crate::utils;
+
+// Location of the administrator-managed configuration file on Unix
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/agstash/config.toml";
+
+// Environment variable that switches every mutating command off, e.g. on CI images
+pub const READONLY_ENV: &str = "AGSTASH_READONLY";
+
+// Environment variable naming a configuration file to read and write instead of the user's
+pub const CONFIG_ENV: &str = "AGSTASH_CONFIG";
+
+// Name of the per-project configuration file, read from the project root
+pub const PROJECT_CONFIG_FILE: &str = ".agstash.toml";
+
+// SystemConfig holds settings enforced by the administrator of the machine
+#[derive(Debug, Default, Deserialize)]
+pub struct SystemConfig {
+    // Commands the CLI refuses to run, optionally narrowed by flag, e.g. "uninstall" or "drop --force"
+    #[serde(default)]
+    pub disabled_commands: Vec<String>,
+    // Machine-wide defaults for the regular settings
+    #[serde(flatten)]
+    pub settings: Settings,
+}
+
+impl SystemConfig {
+    // is_command_disabled checks whether a command invoked with the given argument ids is disabled
+    pub fn is_command_disabled(&self, command: &str, args: &[String]) -> bool {
+        self.disabled_commands.iter().any(|entry| {
+            let mut parts = entry.split_whitespace();
+            if parts.next() != Some(command) {
+                return false;
+            }
+            // Every flag named in the entry must be present for it to apply
+            parts.all(|flag| {
+                let id = flag.trim_start_matches('-').replace('-', "_");
+                args.contains(&id)
+            })
+        })
+    }
+}
+
+// Settings holds the preferences any configuration layer may set; unset fields fall through to the layer below
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Settings {
+    // Whether status indicators use glyphs (✓ ✗ ~ !) rather than plain ASCII
+    #[serde(default)]
+    pub icons: Option<bool>,
+    // Whether clean snapshots AGENTS.md into the pre-clean slot before removing it
+    #[serde(default)]
+    pub auto_stash: Option<bool>,
+    // Whether clean and uninstall move files to the system trash instead of deleting them
+    #[serde(default)]
+    pub trash: Option<bool>,
+    // Whether mutating commands are refused so only queries can run
+    #[serde(default)]
+    pub readonly: Option<bool>,
+    // How many versions of each stash the history keeps; unlimited when unset
+    #[serde(default, alias = "history_limit")]
+    pub keep_last: Option<usize>,
+    // How many days history versions are kept; gc falls back to its own window when unset
+    #[serde(default)]
+    pub keep_days: Option<u64>,
+    // Whether history versions are stored gzipped; existing versions are read either way. Stashes stay plain markdown
+    #[serde(default)]
+    pub compress: Option<bool>,
+    // Branches apply is allowed on, e.g. ["main", "release/*"]; apply runs anywhere when unset
+    #[serde(default)]
+    pub apply_only_on: Option<Vec<String>>,
+    // Machine-wide AGENTS.md that apply --fallback-global writes where there is no project or stash
+    #[serde(default)]
+    pub global_fallback: Option<String>,
+    // Whether uninstall, gc, migrate and repair first write a backup of the whole store
+    #[serde(default)]
+    pub auto_backup: Option<bool>,
+    // Where backup writes its archives; backups in the data directory when unset
+    #[serde(default)]
+    pub backup_dir: Option<String>,
+    // How many backups are kept, oldest removed first; backup falls back to its own default when unset
+    #[serde(default)]
+    pub keep_backups: Option<usize>,
+    // Files stash --bundle and apply --bundle move together, relative to the project root
+    #[serde(default)]
+    pub bundle: Option<Vec<String>>,
+    // File init, clean, stash and apply work on instead of AGENTS.md, e.g. CLAUDE.md
+    #[serde(default)]
+    pub target_file: Option<String>,
+    // Agent files --file and target_file accept besides the ones agstash knows, e.g. [".windsurfrules"]
+    #[serde(default)]
+    pub agent_files: Option<Vec<String>>,
+    // Files sync keeps identical to AGENTS.md and mirror links to it, relative to the project root
+    #[serde(default)]
+    pub mirrors: Option<Vec<String>>,
+    // Whether init and apply add the files they write to the project's .gitignore
+    #[serde(default)]
+    pub gitignore: Option<bool>,
+    // Whether those files go in .git/info/exclude, which only this machine reads, instead of .gitignore
+    #[serde(default)]
+    pub exclude_local: Option<bool>,
+    // Whether stash with no AGENTS.md, apply with no stash and similar soft failures exit non-zero
+    #[serde(default)]
+    pub strict: Option<bool>,
+    // Whether output on a terminal is colored
+    #[serde(default)]
+    pub color: Option<bool>,
+    // Whether agstash asks before overwriting or removing files; when off every question is answered yes
+    #[serde(default)]
+    pub confirm: Option<bool>,
+    // File init writes instead of the built-in scaffold, filled in with the same project facts; in a project's
+    // .agstash.toml it is a path inside the project, read from the project root
+    #[serde(default)]
+    pub template: Option<String>,
+    // Whether stash and apply work on the nested AGENTS.md files too, as with --recursive
+    #[serde(default)]
+    pub recursive: Option<bool>,
+    // Subjects lint accepts both asked for and forbidden, e.g. "rebase before merging"
+    #[serde(default)]
+    pub lint_ignore: Option<Vec<String>>,
+    // Directory holding the stashes, their history and everything else agstash keeps, e.g. a synced folder or a
+    // network share; the platform's data directory when unset
+    #[serde(default)]
+    pub store: Option<String>,
+}
+
+impl Settings {
+    // defaults returns the built-in values every other layer overrides
+    pub fn defaults() -> Settings {
+        Settings {
+            icons: Some(true),
+            auto_stash: Some(true),
+            trash: Some(false),
+            readonly: Some(false),
+            keep_last: None,
+            keep_days: None,
+            compress: Some(false),
+            apply_only_on: None,
+            global_fallback: None,
+            auto_backup: Some(false),
+            backup_dir: None,
+            keep_backups: None,
+            bundle: Some(crate::bundle::DEFAULT_BUNDLE.iter().map(|file| file.to_string()).collect()),
+            target_file: None,
+            agent_files: None,
+            mirrors: Some(vec!["CLAUDE.md".to_string(), ".cursorrules".to_string()]),
+            gitignore: Some(false),
+            exclude_local: Some(false),
+            strict: Some(false),
+            color: Some(true),
+            confirm: Some(true),
+            template: None,
+            recursive: Some(false),
+            lint_ignore: None,
+            store: None,
+        }
+    }
+
+    // from_env reads AGSTASH_<KEY> variables, e.g. AGSTASH_READONLY=1 or AGSTASH_HISTORY_LIMIT=20
+    pub fn from_env() -> Result<Settings, Box<dyn std::error::Error>> {
+        let flag = |key: &str| env::var(env_var_name(key)).ok().map(|value| parse_flag(&value));
+        Ok(Settings {
+            icons: flag("icons"),
+            auto_stash: flag("auto_stash"),
+            trash: flag("trash"),
+            readonly: flag("readonly"),
+            keep_last: env_number("keep_last")?,
+            keep_days: env_number("keep_days")?,
+            compress: flag("compress"),
+            apply_only_on: env_list("apply_only_on"),
+            global_fallback: env::var(env_var_name("global_fallback")).ok(),
+            auto_backup: flag("auto_backup"),
+            backup_dir: env::var(env_var_name("backup_dir")).ok(),
+            keep_backups: env_number("keep_backups")?,
+            bundle: env_list("bundle"),
+            target_file: env::var(env_var_name("target_file")).ok(),
+            agent_files: env_list("agent_files"),
+            mirrors: env_list("mirrors"),
+            gitignore: flag("gitignore"),
+            exclude_local: flag("exclude_local"),
+            strict: flag("strict"),
+            color: flag("color"),
+            confirm: flag("confirm"),
+            template: env::var(env_var_name("template")).ok(),
+            recursive: flag("recursive"),
+            lint_ignore: env_list("lint_ignore"),
+            store: env::var(env_var_name("store")).ok(),
+        })
+    }
+
+    // merge overrides these settings with every field over sets
+    pub fn merge(&mut self, over: &Settings) {
+        self.icons = over.icons.or(self.icons);
+        self.auto_stash = over.auto_stash.or(self.auto_stash);
+        self.trash = over.trash.or(self.trash);
+        self.readonly = over.readonly.or(self.readonly);
+        self.keep_last = over.keep_last.or(self.keep_last);
+        self.keep_days = over.keep_days.or(self.keep_days);
+        self.compress = over.compress.or(self.compress);
+        self.apply_only_on = over.apply_only_on.clone().or(self.apply_only_on.take());
+        self.global_fallback = over.global_fallback.clone().or(self.global_fallback.take());
+        self.auto_backup = over.auto_backup.or(self.auto_backup);
+        self.backup_dir = over.backup_dir.clone().or(self.backup_dir.take());
+        self.keep_backups = over.keep_backups.or(self.keep_backups);
+        self.bundle = over.bundle.clone().or(self.bundle.take());
+        self.target_file = over.target_file.clone().or(self.target_file.take());
+        self.agent_files = over.agent_files.clone().or(self.agent_files.take());
+        self.mirrors = over.mirrors.clone().or(self.mirrors.take());
+        self.gitignore = over.gitignore.or(self.gitignore);
+        self.exclude_local = over.exclude_local.or(self.exclude_local);
+        self.strict = over.strict.or(self.strict);
+        self.color = over.color.or(self.color);
+        self.confirm = over.confirm.or(self.confirm);
+        self.template = over.template.clone().or(self.template.take());
+        self.recursive = over.recursive.or(self.recursive);
+        self.lint_ignore = over.lint_ignore.clone().or(self.lint_ignore.take());
+        self.store = over.store.clone().or(self.store.take());
+    }
+
+    // strip_machine_settings clears the settings only a machine's own configuration may set, returning their names
+    fn strip_machine_settings(&mut self) -> Vec<&'static str> {
+        let mut stripped = Vec::new();
+        if self.readonly.take().is_some() {
+            stripped.push("readonly");
+        }
+        if self.global_fallback.take().is_some() {
+            stripped.push("global_fallback");
+        }
+        if self.auto_backup.take().is_some() {
+            stripped.push("auto_backup");
+        }
+        if self.backup_dir.take().is_some() {
+            stripped.push("backup_dir");
+        }
+        if self.store.take().is_some() {
+            stripped.push("store");
+        }
+        stripped
+    }
+
+    // entries lists every setting by key with its value, if set
+    pub fn entries(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![
+            ("icons", self.icons.map(|v| v.to_string())),
+            ("auto_stash", self.auto_stash.map(|v| v.to_string())),
+            ("trash", self.trash.map(|v| v.to_string())),
+            ("readonly", self.readonly.map(|v| v.to_string())),
+            ("keep_last", self.keep_last.map(|v| v.to_string())),
+            ("keep_days", self.keep_days.map(|v| v.to_string())),
+            ("compress", self.compress.map(|v| v.to_string())),
+            ("apply_only_on", self.apply_only_on.as_ref().map(|v| v.join(", "))),
+            ("global_fallback", self.global_fallback.clone()),
+            ("auto_backup", self.auto_backup.map(|v| v.to_string())),
+            ("backup_dir", self.backup_dir.clone()),
+            ("keep_backups", self.keep_backups.map(|v| v.to_string())),
+            ("bundle", self.bundle.as_ref().map(|v| v.join(", "))),
+            ("target_file", self.target_file.clone()),
+            ("agent_files", self.agent_files.as_ref().map(|v| v.join(", "))),
+            ("mirrors", self.mirrors.as_ref().map(|v| v.join(", "))),
+            ("gitignore", self.gitignore.map(|v| v.to_string())),
+            ("exclude_local", self.exclude_local.map(|v| v.to_string())),
+            ("strict", self.strict.map(|v| v.to_string())),
+            ("color", self.color.map(|v| v.to_string())),
+            ("confirm", self.confirm.map(|v| v.to_string())),
+            ("template", self.template.clone()),
+            ("recursive", self.recursive.map(|v| v.to_string())),
+            ("lint_ignore", self.lint_ignore.as_ref().map(|v| v.join(", "))),
+            ("store", self.store.clone()),
+        ]
+    }
+}
+
+// Origin is where a configuration layer comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Origin {
+    Default,
+    System(PathBuf),
+    User(PathBuf),
+    Project(PathBuf),
+    Env,
+    Flag,
+}
+
+impl Origin {
+    // describe names the origin of key for humans, e.g. "user (/home/me/.agstash/config.toml)"
+    pub fn describe(&self, key: &str) -> String {
+        match self {
+            Origin::Default => "default".to_string(),
+            Origin::System(path) => format!("system ({})", path.display()),
+            Origin::User(path) => format!("user ({})", path.display()),
+            Origin::Project(path) => format!("project ({})", path.display()),
+            Origin::Env => format!("environment ({})", env_var_name(key)),
+            Origin::Flag => "command-line flag".to_string(),
+        }
+    }
+}
+
+// ConfigLayers holds every configuration source in precedence order, lowest first:
+// defaults < system < user < project < environment < command-line flags
+pub struct ConfigLayers {
+    pub layers: Vec<(Origin, Settings)>,
+}
+
+impl ConfigLayers {
+    // load reads every layer except command-line flags, skipping the project layer outside a project
+    pub fn load() -> Result<ConfigLayers, Box<dyn std::error::Error>> {
+        let mut layers = vec![(Origin::Default, Settings::defaults())];
+
+        let system_path = system_config_path();
+        layers.push((Origin::System(system_path.clone()), load_system_config_from(&system_path)?.settings));
+
+        let user_path = get_user_config_path()?;
+        layers.push((Origin::User(user_path.clone()), load_toml_file(&user_path)?));
+
+        if let Ok(root) = utils::get_project_root() {
+            let project_path = root.join(PROJECT_CONFIG_FILE);
+            let mut project: Settings = load_toml_file(&project_path)?;
+            project.template = project.template.map(|template| project_template(&root, &project_path, &template)).transpose()?;
+            // What the machine does with its store, backups and fallback is up to each machine, not to a project it
+            // checks out
+            for key in project.strip_machine_settings() {
+                utils::log_warn(&format!("Ignoring {} in {}; set it in the user config instead", key, project_path.display()));
+            }
+            layers.push((Origin::Project(project_path.clone()), project));
+        }
+
+        layers.push((Origin::Env, Settings::from_env()?));
+        Ok(ConfigLayers { layers })
+    }
+
+    // with_flags adds the settings given on the command line as the highest layer
+    pub fn with_flags(mut self, flags: Settings) -> ConfigLayers {
+        self.layers.push((Origin::Flag, flags));
+        self
+    }
+
+    // effective merges every layer into the settings in force
+    pub fn effective(&self) -> Settings {
+        let mut settings = Settings::default();
+        for (_, layer) in &self.layers {
+            settings.merge(layer);
+        }
+        settings
+    }
+
+    // origin_of returns the highest layer that sets key, if any does
+    pub fn origin_of(&self, key: &str) -> Option<&Origin> {
+        self.layers
+            .iter()
+            .rev()
+            .find(|(_, layer)| layer.entries().iter().any(|(name, value)| *name == key && value.is_some()))
+            .map(|(origin, _)| origin)
+    }
+}
+
+// project_template resolves the template a project's config names against the project root, refusing a path that
+// leads out of the project
+fn project_template(root: &Path, project_path: &Path, template: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let relative = Path::new(template);
+    let escapes = utils::home_relative(template).is_some()
+        || relative.has_root()
+        || relative.components().any(|component| !matches!(component, Component::Normal(_) | Component::CurDir));
+    if escapes {
+        return Err(AgStashError::InvalidConfig(format!(
+            "Invalid configuration in {}: template {} must be a path inside the project",
+            project_path.display(),
+            template
+        ))
+        .into());
+    }
+    Ok(root.join(relative).display().to_string())
+}
+
+// GetUserConfigPath returns the location of the user's configuration file, or the file AGSTASH_CONFIG names
+pub fn get_user_config_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if let Some(path) = env::var_os(CONFIG_ENV).filter(|path| !path.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+    Ok(utils::get_config_dir()?.join("config.toml"))
+}
+
+// LoadUserConfig reads the user's configuration, treating a missing file as empty
+pub fn load_user_config() -> Result<Settings, Box<dyn std::error::Error>> {
+    load_toml_file(get_user_config_path()?)
+}
+
+// ConfiguredStore returns the store directory the store setting or AGSTASH_STORE names, if any. A store whose parent
+// directory is missing, such as a network share that isn't mounted, is refused rather than created on the local disk
+pub fn configured_store() -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let Some(store) = load_settings()?.store.filter(|store| !store.trim().is_empty()) else {
+        return Ok(None);
+    };
+    let store_dir = utils::expand_home(&store)?;
+    if !store_dir.is_dir() && !store_dir.parent().is_some_and(Path::is_dir) {
+        return Err(AgStashError::InvalidConfig(format!(
+            "The store {} can't be reached; mount or create {} or change the store setting",
+            store_dir.display(),
+            store_dir.parent().unwrap_or(&store_dir).display()
+        ))
+        .into());
+    }
+    Ok(Some(store_dir))
+}
+
+// LoadSettings returns the settings in force from every layer below the command line
+pub fn load_settings() -> Result<Settings, Box<dyn std::error::Error>> {
+    Ok(ConfigLayers::load()?.effective())
+}
+
+// SetUserValue writes key = value into the user's configuration file, keeping its comments and other settings. The
+// value is stored as the type the setting takes: a boolean, a number, a comma-separated list or a string
+pub fn set_user_value(key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if !Settings::default().entries().iter().any(|(name, _)| *name == key) {
+        return Err(AgStashError::Usage(format!("Unknown setting '{}'; run `agstash config list` to see them", key)).into());
+    }
+    let typed = typed_value(key, value)
+        .ok_or_else(|| AgStashError::InvalidConfig(format!("'{}' is not a valid value for {}", value, key)))?;
+
+    let path = get_user_config_path()?;
+    let content = if utils::file_exists(&path) {
+        let (err, content) = utils::read_file(&path);
+        if let Some(error) = err {
+            return Err(error);
+        }
+        content
+    } else {
+        String::new()
+    };
+    let mut document: toml_edit::DocumentMut = content
+        .parse()
+        .map_err(|e| AgStashError::InvalidConfig(format!("Invalid configuration in {}: {}", path.display(), e)))?;
+    document[key] = toml_edit::Item::Value(typed);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if let Some(error) = utils::write_file(&path, &document.to_string()) {
+        return Err(error);
+    }
+    Ok(())
+}
+
+// typed_value reads value as the first type key accepts: a boolean, a number, a list split on commas or a string
+fn typed_value(key: &str, value: &str) -> Option<toml_edit::Value> {
+    let value = value.trim();
+    let mut candidates: Vec<toml_edit::Value> = Vec::new();
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => candidates.push(true.into()),
+        "false" | "no" | "off" | "0" => candidates.push(false.into()),
+        _ => {}
+    }
+    if let Ok(number) = value.parse::<i64>() {
+        candidates.push(number.into());
+    }
+    let items: toml_edit::Array = value.split(',').map(str::trim).filter(|item| !item.is_empty()).collect();
+    candidates.push(items.into());
+    candidates.push(value.into());
+    candidates
+        .into_iter()
+        .find(|candidate| toml::from_str::<Settings>(&format!("{} = {}", key, candidate)).is_ok())
+}
+
+// env_var_name returns the environment variable that overrides a setting, e.g. AGSTASH_HISTORY_LIMIT
+fn env_var_name(key: &str) -> String {
+    format!("AGSTASH_{}", key.to_uppercase())
+}
+
+// env_number reads a numeric setting from its AGSTASH_<KEY> variable
+fn env_number<T: std::str::FromStr>(key: &str) -> Result<Option<T>, Box<dyn std::error::Error>> {
+    match env::var(env_var_name(key)) {
+        Ok(value) => match value.trim().parse() {
+            Ok(number) => Ok(Some(number)),
+            Err(_) => Err(AgStashError::InvalidConfig(format!("{} must be a number, got '{}'", env_var_name(key), value)).into()),
+        },
+        Err(_) => Ok(None),
+    }
+}
+
+// env_list reads a comma-separated list setting from its AGSTASH_<KEY> variable, e.g. AGSTASH_APPLY_ONLY_ON=main,develop
+fn env_list(key: &str) -> Option<Vec<String>> {
+    env::var(env_var_name(key)).ok().map(|value| {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(String::from)
+            .collect()
+    })
+}
+
+// parse_flag interprets an environment variable value as a boolean, treating anything but an explicit "off" as on
+fn parse_flag(value: &str) -> bool {
+    !matches!(value.trim().to_lowercase().as_str(), "" | "0" | "false" | "no" | "off")
+}
+
+// SystemConfigPath returns where the administrator configuration lives: /etc/agstash/config.toml, or
+// %ProgramData%\agstash\config.toml on Windows
+pub fn system_config_path() -> PathBuf {
+    if !cfg!(windows) {
+        return PathBuf::from(SYSTEM_CONFIG_PATH);
+    }
+    let program_data = env::var_os("ProgramData").filter(|dir| !dir.is_empty());
+    PathBuf::from(program_data.unwrap_or_else(|| r"C:\ProgramData".into())).join("agstash").join("config.toml")
+}
+
+// LoadSystemConfig reads the administrator configuration, treating a missing file as empty
+pub fn load_system_config() -> Result<SystemConfig, Box<dyn std::error::Error>> {
+    load_system_config_from(system_config_path())
+}
+
+// LoadSystemConfigFrom reads an administrator configuration file from path
+pub fn load_system_config_from<P: AsRef<Path>>(path: P) -> Result<SystemConfig, Box<dyn std::error::Error>> {
+    load_toml_file(path)
+}
+
+// load_toml_file parses a TOML configuration file, returning the defaults when it does not exist
+fn load_toml_file<T, P>(path: P) -> Result<T, Box<dyn std::error::Error>>
+where
+    T: Default + serde::de::DeserializeOwned,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    if !utils::file_exists(path) {
+        return Ok(T::default());
+    }
+
+    let (err, content) = utils::read_file(path);
+    if let Some(error) = err {
+        return Err(error);
+    }
+    let config = toml::from_str(&content)
+        .map_err(|e| AgStashError::InvalidConfig(format!("Invalid configuration in {}: {}", path.display(), e)))?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::config;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    fn test_load_system_config_from() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+
+        // A missing file disables nothing
+        let system_config = config::load_system_config_from(&config_path).unwrap();
+        assert!(system_config.disabled_commands.is_empty());
+
+        fs::write(&config_path, "disabled_commands = [\"uninstall\", \"drop --force\"]\n").unwrap();
+        let system_config = config::load_system_config_from(&config_path).unwrap();
+        assert_eq!(system_config.disabled_commands, vec!["uninstall", "drop --force"]);
+
+        // Regular settings can be given machine-wide too
+        fs::write(&config_path, "disabled_commands = []\nkeep_last = 5\nkeep_days = 30\n").unwrap();
+        let system_config = config::load_system_config_from(&config_path).unwrap();
+        assert_eq!(system_config.settings.keep_last, Some(5));
+        assert_eq!(system_config.settings.keep_days, Some(30));
+
+        // The original name of keep_last is still understood
+        fs::write(&config_path, "history_limit = 7\n").unwrap();
+        let system_config = config::load_system_config_from(&config_path).unwrap();
+        assert_eq!(system_config.settings.keep_last, Some(7));
+
+        // Malformed files are reported rather than ignored
+        fs::write(&config_path, "disabled_commands = \"uninstall\"\n").unwrap();
+        assert!(config::load_system_config_from(&config_path).is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_user_config() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        assert_eq!(config::load_user_config().unwrap().icons, None);
+
+        let config_path = config::get_user_config_path().unwrap();
+        fs::create_dir_all(config_path.parent().unwrap()).unwrap();
+        fs::write(&config_path, "icons = false\n").unwrap();
+        assert_eq!(config::load_user_config().unwrap().icons, Some(false));
+
+        // AGSTASH_CONFIG swaps in another file
+        let other_path = temp_dir.path().join("other.toml");
+        fs::write(&other_path, "icons = true\n").unwrap();
+        env::set_var(config::CONFIG_ENV, &other_path);
+        let settings = config::load_user_config();
+        env::remove_var(config::CONFIG_ENV);
+        assert_eq!(settings.unwrap().icons, Some(true));
+    }
+
+    #[test]
+    fn test_config_layers() {
+        let layers = config::ConfigLayers {
+            layers: vec![
+                (config::Origin::Default, config::Settings::defaults()),
+                (
+                    config::Origin::User("config.toml".into()),
+                    config::Settings {
+                        icons: Some(false),
+                        keep_last: Some(10),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    config::Origin::Env,
+                    config::Settings {
+                        keep_last: Some(3),
+                        ..Default::default()
+                    },
+                ),
+            ],
+        }
+        .with_flags(config::Settings {
+            icons: Some(true),
+            ..Default::default()
+        });
+
+        // Higher layers win field by field
+        let settings = layers.effective();
+        assert_eq!(settings.icons, Some(true));
+        assert_eq!(settings.keep_last, Some(3));
+        assert_eq!(settings.trash, Some(false));
+
+        assert_eq!(layers.origin_of("icons"), Some(&config::Origin::Flag));
+        assert_eq!(layers.origin_of("keep_last").unwrap().describe("keep_last"), "environment (AGSTASH_KEEP_LAST)");
+        assert_eq!(layers.origin_of("trash"), Some(&config::Origin::Default));
+    }
+
+    #[test]
+    #[serial]
+    fn test_project_config() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir(".git").unwrap();
+
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+
+        // Ensure cleanup happens
+        let _cleanup = defer::defer(move || {
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        fs::create_dir_all(temp_home.path().join(".agstash")).unwrap();
+        fs::write(config::get_user_config_path().unwrap(), "recursive = false\ntemplate = \"~/mine.md\"\n").unwrap();
+        fs::write(
+            ".agstash.toml",
+            "target_file = \"CLAUDE.md\"\nrecursive = true\ntemplate = \"docs/AGENTS.template.md\"\nlint_ignore = [\"use emojis\"]\n",
+        )
+        .unwrap();
+
+        // The committed conventions override the user's, and the template is found in the project
+        let settings = config::load_settings().unwrap();
+        assert_eq!(settings.target_file.as_deref(), Some("CLAUDE.md"));
+        assert_eq!(settings.recursive, Some(true));
+        assert_eq!(settings.template, Some(temp_dir.path().join("docs/AGENTS.template.md").display().to_string()));
+        assert_eq!(settings.lint_ignore, Some(vec!["use emojis".to_string()]));
+
+        // Machine settings stay with the user's config
+        fs::write(
+            ".agstash.toml",
+            "readonly = true
+global_fallback = \"/tmp/AGENTS.md\"
+auto_backup = true
+backup_dir = \"/tmp\"
+",
+        )
+        .unwrap();
+        let settings = config::load_settings().unwrap();
+        assert_eq!(settings.readonly, Some(false));
+        assert_eq!(settings.global_fallback, None);
+        assert_eq!(settings.auto_backup, Some(false));
+        assert_eq!(settings.backup_dir, None);
+
+        // A template outside the project is refused
+        for template in ["/etc/passwd", "../shared/AGENTS.md", "docs/../../AGENTS.md", "~/AGENTS.md"] {
+            fs::write(".agstash.toml", format!("template = \"{}\"\n", template)).unwrap();
+            let error = config::load_settings().unwrap_err();
+            assert_eq!(crate::error::classify(error.as_ref()).code(), "E030");
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_configured_store() {
+        // Create a temporary directory and change to it
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(&temp_dir).unwrap();
+        fs::create_dir(".git").unwrap();
+
+        // Create a temporary HOME directory
+        let temp_home = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_home.path()).unwrap();
+
+        // Ensure cleanup happens, the store going back to its default location
+        let _cleanup = defer::defer(move || {
+            utils::set_store_dir(None);
+            env::remove_var("AGSTASH_STORE");
+            env::set_current_dir(original_dir).unwrap();
+        });
+
+        assert_eq!(config::configured_store().unwrap(), None);
+
+        // A store in a synced folder holds the stashes, while the config stays where it was
+        let config_path = config::get_user_config_path().unwrap();
+        fs::create_dir_all(config_path.parent().unwrap()).unwrap();
+        fs::create_dir(temp_home.path().join("Sync")).unwrap();
+        fs::write(&config_path, "store = \"~/Sync/agstash\"\n").unwrap();
+        let store_dir = temp_home.path().join("Sync").join("agstash");
+        assert_eq!(config::configured_store().unwrap(), Some(store_dir.clone()));
+        utils::set_store_dir(Some(store_dir.clone()));
+        assert!(utils::get_stash_path("api").unwrap().starts_with(&store_dir));
+        assert_eq!(config::get_user_config_path().unwrap(), config_path);
+
+        // A project can't move the store
+        fs::write(".agstash.toml", "store = \"elsewhere\"\n").unwrap();
+        assert_eq!(config::configured_store().unwrap(), Some(store_dir));
+
+        // AGSTASH_STORE wins, and a store on a share that isn't mounted is refused
+        env::set_var("AGSTASH_STORE", temp_home.path().join("share").join("agstash"));
+        let error = config::configured_store().unwrap_err();
+        assert_eq!(crate::error::classify(error.as_ref()).code(), "E030");
+    }
+
+    #[test]
+    fn test_parse_flag() {
+        assert!(super::parse_flag("1"));
+        assert!(super::parse_flag("true"));
+        assert!(super::parse_flag("YES"));
+        assert!(!super::parse_flag("0"));
+        assert!(!super::parse_flag("false"));
+        assert!(!super::parse_flag("Off"));
+        assert!(!super::parse_flag(""));
+    }
+
+    #[test]
+    fn test_is_command_disabled() {
+        let system_config = config::SystemConfig {
+            disabled_commands: vec!["uninstall".to_string(), "drop --force".to_string()],
+            ..Default::default()
+        };
+
+        assert!(system_config.is_command_disabled("uninstall", &[]));
+        assert!(system_config.is_command_disabled("drop", &["force".to_string()]));
+        assert!(!system_config.is_command_disabled("drop", &[]));
+        assert!(!system_config.is_command_disabled("stash", &[]));
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils;
+
+// A running daemon keeps the state of every registered project up to date, and status asks it over a local socket
+// rather than reading the files itself. The protocol is one line each way: the project root, then its state as JSON,
+// or null for a project the daemon doesn't watch.
+
+// State is what the daemon last found for a project's AGENTS.md
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum State {
+    InSync,
+    Drift,
+    Unstashed,
+    Invalid,
+    Missing,
+}
+
+impl State {
+    // label describes the state the way status does
+    pub fn label(&self) -> &'static str {
+        match self {
+            State::InSync => "in sync",
+            State::Drift => "AGENTS.md differs from stash",
+            State::Unstashed => "AGENTS.md has no stash",
+            State::Invalid => "AGENTS.md is missing the '# AGENTS' header",
+            State::Missing => "no AGENTS.md",
+        }
+    }
+}
+
+// ProjectState is the daemon's answer for one project
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectState {
+    pub stash_key: String,
+    pub state: State,
+    pub checked_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stashed_at: Option<String>,
+}
+
+// States maps each watched project root to what the daemon last found there
+pub type States = Arc<Mutex<BTreeMap<String, ProjectState>>>;
+
+// GetSocketPath returns the socket a running daemon listens on
+pub fn get_socket_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(utils::get_agstash_dir()?.join("daemon.sock"))
+}
+
+// root_entry names a project root the way the daemon keys it, so a root reached through a symlink still matches
+fn root_entry(root: &Path) -> String {
+    root.canonicalize().unwrap_or_else(|_| root.to_path_buf()).display().to_string()
+}
+
+// Record stores what the daemon found for a project root
+pub fn record(states: &States, root: &Path, state: ProjectState) {
+    if let Ok(mut states) = states.lock() {
+        states.insert(root_entry(root), state);
+    }
+}
+
+#[cfg(unix)]
+mod socket {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::Path;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{get_socket_path, root_entry, ProjectState, States};
+
+    // How long status waits for the daemon before reading the files itself
+    const QUERY_TIMEOUT: Duration = Duration::from_millis(200);
+
+    // Query asks a running daemon for a project's state, returning None when no daemon answers or it doesn't watch
+    // the project
+    pub fn query(root: &Path) -> Option<ProjectState> {
+        let mut stream = UnixStream::connect(get_socket_path().ok()?).ok()?;
+        stream.set_read_timeout(Some(QUERY_TIMEOUT)).ok()?;
+        stream.set_write_timeout(Some(QUERY_TIMEOUT)).ok()?;
+        writeln!(stream, "{}", root_entry(root)).ok()?;
+        let mut answer = String::new();
+        BufReader::new(stream).read_line(&mut answer).ok()?;
+        serde_json::from_str::<Option<ProjectState>>(&answer).ok().flatten()
+    }
+
+    // IsRunning reports whether a daemon is listening on the socket
+    pub fn is_running() -> bool {
+        get_socket_path().is_ok_and(|path| UnixStream::connect(path).is_ok())
+    }
+
+    // Listen binds the daemon's socket, replacing one a daemon that has stopped left behind, and answers queries
+    // from states on a background thread
+    pub fn listen(states: States) -> Result<(), Box<dyn std::error::Error>> {
+        let socket_path = get_socket_path()?;
+        if is_running() {
+            return Err(format!("A daemon is already listening on {}", socket_path.display()).into());
+        }
+        if socket_path.exists() {
+            std::fs::remove_file(&socket_path)?;
+        }
+        let listener = UnixListener::bind(&socket_path)?;
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = answer(stream, &states);
+            }
+        });
+        Ok(())
+    }
+
+    // answer replies to one query
+    fn answer(mut stream: UnixStream, states: &States) -> std::io::Result<()> {
+        stream.set_read_timeout(Some(QUERY_TIMEOUT))?;
+        let mut root = String::new();
+        BufReader::new(&stream).read_line(&mut root)?;
+        let state = states.lock().ok().and_then(|states| states.get(root.trim()).cloned());
+        writeln!(stream, "{}", serde_json::to_string(&state)?)
+    }
+}
+
+#[cfg(not(unix))]
+mod socket {
+    use std::path::Path;
+
+    use super::{ProjectState, States};
+
+    // Query finds no daemon where agstash does not run one
+    pub fn query(_root: &Path) -> Option<ProjectState> {
+        None
+    }
+
+    // IsRunning finds no daemon where agstash does not run one
+    pub fn is_running() -> bool {
+        false
+    }
+
+    // Listen refuses where local sockets aren't available
+    pub fn listen(_states: States) -> Result<(), Box<dyn std::error::Error>> {
+        Err("The daemon needs Unix domain sockets, which this platform lacks".into())
+    }
+}
+
+pub use socket::{is_running, listen, query};
+
+#[cfg(all(test, unix))]
+mod tests {
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::daemon::{self, ProjectState, State, States};
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_query_daemon() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        let project = temp_dir.path().join("api");
+        std::fs::create_dir_all(temp_dir.path().join(".agstash")).unwrap();
+        assert!(!daemon::is_running());
+        assert_eq!(daemon::query(&project), None);
+
+        let states = States::default();
+        let state = ProjectState {
+            stash_key: "api".to_string(),
+            state: State::Drift,
+            checked_at: "2026-10-17 09:00:00".to_string(),
+            stashed_at: None,
+        };
+        daemon::record(&states, &project, state.clone());
+        daemon::listen(states).unwrap();
+        assert!(daemon::is_running());
+        assert!(daemon::listen(States::default()).is_err());
+
+        assert_eq!(daemon::query(&project), Some(state));
+        assert_eq!(daemon::query(&temp_dir.path().join("web")), None);
+    }
+}
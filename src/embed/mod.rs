@@ -0,0 +1,241 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::archive;
+use crate::commands;
+use crate::managed::ManagedFile;
+use crate::metadata::{self, StashMetadata};
+use crate::utils;
+
+// The embedding API is for front ends, such as tray apps, that run agstash operations on their own threads instead of
+// spawning the CLI. Nothing here prints to stdout or needs an async runtime, every value returned can be sent to
+// another thread, and long operations report progress and stop early when cancelled.
+
+// EmbedError is why an embedded operation stopped
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmbedError {
+    Cancelled,
+    Failed(String),
+}
+
+impl fmt::Display for EmbedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmbedError::Cancelled => write!(f, "Operation cancelled"),
+            EmbedError::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for EmbedError {}
+
+impl From<Box<dyn std::error::Error>> for EmbedError {
+    fn from(error: Box<dyn std::error::Error>) -> Self {
+        EmbedError::Failed(error.to_string())
+    }
+}
+
+impl From<std::io::Error> for EmbedError {
+    fn from(error: std::io::Error) -> Self {
+        EmbedError::Failed(error.to_string())
+    }
+}
+
+// EmbedResult is the result of an embedded operation
+pub type EmbedResult<T> = Result<T, EmbedError>;
+
+// CancelToken lets another thread stop a running operation; clones share the same flag
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    // new creates a token that is not cancelled
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    // cancel asks every operation holding a clone of this token to stop at its next step
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    // is_cancelled reports whether cancel has been called
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    // check returns Cancelled once cancel has been called
+    fn check(&self) -> EmbedResult<()> {
+        if self.is_cancelled() {
+            return Err(EmbedError::Cancelled);
+        }
+        Ok(())
+    }
+}
+
+// Progress is how far an operation has got: done of total steps, and the item it just finished
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    pub done: usize,
+    pub total: usize,
+    pub item: String,
+}
+
+// StashInfo describes one stash in the store
+#[derive(Debug, Clone, PartialEq)]
+pub struct StashInfo {
+    pub key: String,
+    pub path: PathBuf,
+    pub metadata: Option<StashMetadata>,
+}
+
+// ListStashes returns every stash in the store with its metadata, sorted by key
+pub fn list_stashes() -> EmbedResult<Vec<StashInfo>> {
+    let mut stashes = Vec::new();
+    for (key, path) in utils::list_stashes(utils::get_stashes_dir()?)? {
+        let metadata = metadata::load_metadata(&key)?;
+        stashes.push(StashInfo { key, path, metadata });
+    }
+    Ok(stashes)
+}
+
+// StashFile stashes the AGENTS.md at agents_path under stash_key, as stash does for the current project
+pub fn stash_file(stash_key: &str, agents_path: &Path, message: Option<&str>) -> EmbedResult<()> {
+    utils::validate_project_name(stash_key)?;
+    let (err, content) = utils::read_file(agents_path);
+    if let Some(error) = err {
+        return Err(EmbedError::Failed(format!("Could not read {}: {}", agents_path.display(), error)));
+    }
+    let file = ManagedFile::for_stash_key(stash_key);
+    if !file.is_valid(&content) {
+        return Err(EmbedError::Failed(format!("{} is not a valid {} ({})", agents_path.display(), file.name, file.requirement())));
+    }
+    commands::stash_agents(agents_path, stash_key, message)?;
+    Ok(())
+}
+
+// ApplyFile writes the stash for stash_key to agents_path, returning false when there is no stash or the file already
+// matches it; whatever it replaces can be restored with undo
+pub fn apply_file(stash_key: &str, agents_path: &Path) -> EmbedResult<bool> {
+    utils::validate_project_name(stash_key)?;
+    let stash_path = utils::get_stash_path(stash_key)?;
+    if !utils::file_exists(&stash_path) {
+        return Ok(false);
+    }
+    let (err, content) = utils::read_file(&stash_path);
+    if let Some(error) = err {
+        return Err(error.into());
+    }
+    let file = ManagedFile::for_stash_key(stash_key);
+    if !file.is_valid(&content) {
+        return Err(EmbedError::Failed(format!("The stash for {} is not a valid {} ({})", stash_key, file.name, file.requirement())));
+    }
+    if fs::read(agents_path).is_ok_and(|existing| existing == content.as_bytes()) {
+        return Ok(false);
+    }
+
+    commands::write_applied(stash_key, agents_path, &content, None)?;
+    Ok(true)
+}
+
+// Export packs the store into an archive at output as the export command does, calling progress after each file;
+// a cancelled export removes the partial archive
+pub fn export<F>(output: &Path, metadata: bool, history: bool, mut progress: F, cancel: &CancelToken) -> EmbedResult<usize>
+where
+    F: FnMut(&Progress),
+{
+    if utils::file_exists(output) {
+        return Err(EmbedError::Failed(format!("{} already exists", output.display())));
+    }
+    let files = archive::export_files(metadata, history)?;
+    if !files.iter().any(|relative| relative.starts_with("stashes")) {
+        return Ok(0);
+    }
+    cancel.check()?;
+
+    let agstash_dir = utils::get_agstash_dir()?;
+    if let Some(parent) = output.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let packed = (|| -> EmbedResult<()> {
+        let encoder = GzEncoder::new(File::create(output)?, Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        for (done, relative) in files.iter().enumerate() {
+            cancel.check()?;
+            builder.append_path_with_name(agstash_dir.join(relative), relative)?;
+            progress(&Progress {
+                done: done + 1,
+                total: files.len(),
+                item: relative.display().to_string(),
+            });
+        }
+        builder.into_inner()?.finish()?;
+        Ok(())
+    })();
+    if let Err(error) = packed {
+        let _ = fs::remove_file(output);
+        return Err(error);
+    }
+    Ok(files.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::thread;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::embed::{self, CancelToken, EmbedError};
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_embed_on_background_thread() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        let project = temp_dir.path().join("api");
+        fs::create_dir_all(&project).unwrap();
+        let agents_path = project.join("AGENTS.md");
+        fs::write(&agents_path, "# AGENTS\n\n- tray\n").unwrap();
+
+        // Everything runs on a worker thread and only Send values come back
+        let worker_agents = agents_path.clone();
+        let stashes = thread::spawn(move || {
+            embed::stash_file("api", &worker_agents, Some("from the tray"))?;
+            embed::list_stashes()
+        })
+        .join()
+        .unwrap()
+        .unwrap();
+        assert_eq!(stashes.len(), 1);
+        assert_eq!(stashes[0].metadata.as_ref().unwrap().message.as_deref(), Some("from the tray"));
+
+        fs::write(&agents_path, "# AGENTS\n").unwrap();
+        assert!(embed::apply_file("api", &agents_path).unwrap());
+        assert!(!embed::apply_file("api", &agents_path).unwrap());
+        assert_eq!(fs::read_to_string(&agents_path).unwrap(), "# AGENTS\n\n- tray\n");
+
+        let output = temp_dir.path().join("export.tar.gz");
+        let mut seen = Vec::new();
+        let count = embed::export(&output, true, true, |progress| seen.push(progress.done), &CancelToken::new()).unwrap();
+        assert_eq!(seen, (1..=count).collect::<Vec<_>>());
+
+        // A cancelled export leaves nothing behind
+        let cancelled = temp_dir.path().join("cancelled.tar.gz");
+        let cancel = CancelToken::new();
+        let result = embed::export(&cancelled, true, true, |_| cancel.cancel(), &cancel);
+        assert_eq!(result, Err(EmbedError::Cancelled));
+        assert!(!cancelled.exists());
+    }
+}
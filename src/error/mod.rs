@@ -0,0 +1,151 @@
+use std::fmt;
+
+// AgStashError is a failure with a stable code, e.g. E010 for StashNotFound, that wrappers can branch on instead of
+// parsing the message. Codes are never reused or renumbered; a new kind of failure gets a new code
+#[derive(Debug, Clone, PartialEq)]
+pub enum AgStashError {
+    // Any failure that has no kind of its own yet
+    Other(String),
+    // Reading or writing a file failed
+    Io(String),
+    // The arguments given don't make sense together or for this command
+    Usage(String),
+    // The project, or the slot or nested directory asked for, has no stash
+    StashNotFound(String),
+    // The stash has no version matching the one asked for
+    VersionNotFound(String),
+    // The agent file to stash or check is not there
+    FileNotFound(String),
+    // The agent file is not valid, e.g. AGENTS.md without its '# AGENTS' header
+    InvalidFile(String),
+    // The file to write already exists
+    FileExists(String),
+    // No project root was found above the working directory
+    ProjectNotFound(String),
+    // No known project has the name given with --project
+    UnknownProject(String),
+    // More than one known project has the name given with --project
+    AmbiguousProject(String),
+    // A configuration file or variable has a value agstash can't use
+    InvalidConfig(String),
+    // The administrator disabled the command or one of its flags
+    CommandDisabled(String),
+    // The command would modify files but agstash is in read-only mode
+    ReadOnly(String),
+    // The project's apply_only_on rule refused to apply on this branch or during this git operation
+    ApplyRefused(String),
+    // The store was written by a newer agstash
+    UnsupportedStoreFormat(String),
+    // An archive or manifest to import is damaged or unsafe
+    InvalidArchive(String),
+}
+
+impl AgStashError {
+    // Code returns the stable code of the error, e.g. "E010"
+    pub fn code(&self) -> &'static str {
+        match self {
+            AgStashError::Other(_) => "E001",
+            AgStashError::Io(_) => "E002",
+            AgStashError::Usage(_) => "E003",
+            AgStashError::StashNotFound(_) => "E010",
+            AgStashError::VersionNotFound(_) => "E011",
+            AgStashError::FileNotFound(_) => "E012",
+            AgStashError::InvalidFile(_) => "E013",
+            AgStashError::FileExists(_) => "E014",
+            AgStashError::ProjectNotFound(_) => "E020",
+            AgStashError::UnknownProject(_) => "E021",
+            AgStashError::AmbiguousProject(_) => "E022",
+            AgStashError::InvalidConfig(_) => "E030",
+            AgStashError::CommandDisabled(_) => "E031",
+            AgStashError::ReadOnly(_) => "E032",
+            AgStashError::ApplyRefused(_) => "E033",
+            AgStashError::UnsupportedStoreFormat(_) => "E040",
+            AgStashError::InvalidArchive(_) => "E041",
+        }
+    }
+
+    // Kind returns the name of the error, e.g. "StashNotFound"
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AgStashError::Other(_) => "Other",
+            AgStashError::Io(_) => "Io",
+            AgStashError::Usage(_) => "Usage",
+            AgStashError::StashNotFound(_) => "StashNotFound",
+            AgStashError::VersionNotFound(_) => "VersionNotFound",
+            AgStashError::FileNotFound(_) => "FileNotFound",
+            AgStashError::InvalidFile(_) => "InvalidFile",
+            AgStashError::FileExists(_) => "FileExists",
+            AgStashError::ProjectNotFound(_) => "ProjectNotFound",
+            AgStashError::UnknownProject(_) => "UnknownProject",
+            AgStashError::AmbiguousProject(_) => "AmbiguousProject",
+            AgStashError::InvalidConfig(_) => "InvalidConfig",
+            AgStashError::CommandDisabled(_) => "CommandDisabled",
+            AgStashError::ReadOnly(_) => "ReadOnly",
+            AgStashError::ApplyRefused(_) => "ApplyRefused",
+            AgStashError::UnsupportedStoreFormat(_) => "UnsupportedStoreFormat",
+            AgStashError::InvalidArchive(_) => "InvalidArchive",
+        }
+    }
+
+    // Message returns the human-readable description of the error
+    pub fn message(&self) -> &str {
+        match self {
+            AgStashError::Other(message)
+            | AgStashError::Io(message)
+            | AgStashError::Usage(message)
+            | AgStashError::StashNotFound(message)
+            | AgStashError::VersionNotFound(message)
+            | AgStashError::FileNotFound(message)
+            | AgStashError::InvalidFile(message)
+            | AgStashError::FileExists(message)
+            | AgStashError::ProjectNotFound(message)
+            | AgStashError::UnknownProject(message)
+            | AgStashError::AmbiguousProject(message)
+            | AgStashError::InvalidConfig(message)
+            | AgStashError::CommandDisabled(message)
+            | AgStashError::ReadOnly(message)
+            | AgStashError::ApplyRefused(message)
+            | AgStashError::UnsupportedStoreFormat(message)
+            | AgStashError::InvalidArchive(message) => message,
+        }
+    }
+}
+
+impl fmt::Display for AgStashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl std::error::Error for AgStashError {}
+
+// Classify returns the AgStashError behind any error a command returned: the error itself when it is one, Io for
+// I/O errors and Other for the rest, keeping the original message
+pub fn classify(error: &(dyn std::error::Error + 'static)) -> AgStashError {
+    if let Some(error) = error.downcast_ref::<AgStashError>() {
+        return error.clone();
+    }
+    if error.is::<std::io::Error>() {
+        return AgStashError::Io(error.to_string());
+    }
+    AgStashError::Other(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AgStashError;
+
+    #[test]
+    fn test_classify() {
+        let error: Box<dyn std::error::Error> = AgStashError::StashNotFound("No stash found for project demo".into()).into();
+        let classified = super::classify(error.as_ref());
+        assert_eq!((classified.code(), classified.kind()), ("E010", "StashNotFound"));
+        assert_eq!(error.to_string(), "No stash found for project demo");
+
+        let error: Box<dyn std::error::Error> = std::io::Error::new(std::io::ErrorKind::NotFound, "gone").into();
+        assert_eq!(super::classify(error.as_ref()), AgStashError::Io("gone".into()));
+
+        let error: Box<dyn std::error::Error> = "Something else".into();
+        assert_eq!(super::classify(error.as_ref()).code(), "E001");
+    }
+}
@@ -0,0 +1,272 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils;
+
+// GitContext records the state of the repository a stash was made in
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GitContext {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    pub commit: String,
+    // Whether tracked files had uncommitted changes; unknown when git itself couldn't be run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dirty: Option<bool>,
+}
+
+impl GitContext {
+    // describe summarizes the context, e.g. "main @ abc1234 (uncommitted changes)"
+    pub fn describe(&self) -> String {
+        let commit = &self.commit[..self.commit.len().min(7)];
+        let mut description = match &self.branch {
+            Some(branch) => format!("{} @ {}", branch, commit),
+            None => format!("detached @ {}", commit),
+        };
+        if self.dirty == Some(true) {
+            description.push_str(" (uncommitted changes)");
+        }
+        description
+    }
+}
+
+// GetGitDir returns the git directory of a work tree, following the "gitdir:" file used by worktrees and submodules
+pub fn get_git_dir(root: &Path) -> Option<PathBuf> {
+    let dot_git = root.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    if !dot_git.is_file() {
+        return None;
+    }
+
+    let (err, content) = utils::read_file(&dot_git);
+    if err.is_some() {
+        return None;
+    }
+    let target = content.trim().strip_prefix("gitdir:")?.trim();
+    Some(root.join(target))
+}
+
+// MainWorktree returns the root of the main working tree when root is a linked worktree made with `git worktree add`,
+// so every worktree of a repository can be treated as one project; a bare repository stands in for its own root
+pub fn main_worktree(root: &Path) -> Option<PathBuf> {
+    if !root.join(".git").is_file() {
+        return None;
+    }
+    let git_dir = get_git_dir(root)?;
+    // Submodules also use a "gitdir:" file, but only worktrees share a common directory with the main repository
+    let (err, common) = utils::read_file(git_dir.join("commondir"));
+    if err.is_some() {
+        return None;
+    }
+    let common_dir = git_dir.join(common.trim()).canonicalize().ok()?;
+    if common_dir.file_name().is_some_and(|name| name == ".git") {
+        return common_dir.parent().map(Path::to_path_buf);
+    }
+    Some(common_dir)
+}
+
+// Context returns the branch, HEAD commit and dirty state of the repository containing path, or None outside a
+// repository or before its first commit
+pub fn context(path: &Path) -> Option<GitContext> {
+    let root = path.ancestors().find(|dir| get_git_dir(dir).is_some())?;
+    Some(GitContext {
+        branch: current_branch(root),
+        commit: head_commit(root)?,
+        dirty: is_dirty(root),
+    })
+}
+
+// HeadCommit returns the commit HEAD points at, following a branch through loose and packed refs
+pub fn head_commit(root: &Path) -> Option<String> {
+    let git_dir = get_git_dir(root)?;
+    let (err, head) = utils::read_file(git_dir.join("HEAD"));
+    if err.is_some() {
+        return None;
+    }
+    let Some(reference) = head.trim().strip_prefix("ref:").map(str::trim) else {
+        return Some(head.trim().to_string()).filter(|commit| !commit.is_empty());
+    };
+
+    // Branches live in the directory every worktree shares
+    let common_dir = common_dir(root)?;
+    for dir in [&git_dir, &common_dir] {
+        let (err, commit) = utils::read_file(dir.join(reference));
+        if err.is_none() && !commit.trim().is_empty() {
+            return Some(commit.trim().to_string());
+        }
+    }
+    let (err, packed) = utils::read_file(common_dir.join("packed-refs"));
+    if err.is_some() {
+        return None;
+    }
+    packed.lines().find_map(|line| {
+        let (commit, name) = line.split_once(' ')?;
+        (name.trim() == reference).then(|| commit.to_string())
+    })
+}
+
+// is_dirty asks git whether tracked files have uncommitted changes, returning None when git can't be run
+fn is_dirty(root: &Path) -> Option<bool> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["status", "--porcelain", "--untracked-files=no"])
+        .output()
+        .ok()?;
+    output.status.success().then_some(!output.stdout.is_empty())
+}
+
+// HooksDir returns the directory git runs hooks from, which every worktree of a repository shares
+pub fn hooks_dir(root: &Path) -> Option<PathBuf> {
+    Some(common_dir(root)?.join("hooks"))
+}
+
+// ExcludePath returns the repository's info/exclude file, which ignores files on this machine only
+pub fn exclude_path(root: &Path) -> Option<PathBuf> {
+    Some(common_dir(root)?.join("info").join("exclude"))
+}
+
+// common_dir returns the git directory every worktree of a repository shares; a plain repository's own
+fn common_dir(root: &Path) -> Option<PathBuf> {
+    let git_dir = get_git_dir(root)?;
+    let (err, common) = utils::read_file(git_dir.join("commondir"));
+    if err.is_some() {
+        return Some(git_dir);
+    }
+    Some(git_dir.join(common.trim()))
+}
+
+// CurrentBranch returns the checked-out branch, or None for a detached HEAD or a directory that isn't a repository
+pub fn current_branch(root: &Path) -> Option<String> {
+    let (err, head) = utils::read_file(get_git_dir(root)?.join("HEAD"));
+    if err.is_some() {
+        return None;
+    }
+    head.trim().strip_prefix("ref: refs/heads/").map(String::from)
+}
+
+// OperationInProgress names a rebase, merge or similar operation that has been started but not finished
+pub fn operation_in_progress(root: &Path) -> Option<&'static str> {
+    let git_dir = get_git_dir(root)?;
+    let markers = [
+        ("rebase-merge", "rebase"),
+        ("rebase-apply", "rebase"),
+        ("MERGE_HEAD", "merge"),
+        ("CHERRY_PICK_HEAD", "cherry-pick"),
+        ("REVERT_HEAD", "revert"),
+        ("BISECT_LOG", "bisect"),
+    ];
+    markers
+        .iter()
+        .find(|(marker, _)| fs::symlink_metadata(git_dir.join(marker)).is_ok())
+        .map(|(_, operation)| *operation)
+}
+
+// BranchMatches checks a branch against a pattern, where a trailing '*' matches any suffix, e.g. "release/*"
+pub fn branch_matches(branch: &str, pattern: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => branch.starts_with(prefix),
+        None => branch == pattern,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+
+    use crate::git;
+
+    #[test]
+    fn test_git_state() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        // Not a repository
+        assert_eq!(git::current_branch(root), None);
+        assert_eq!(git::operation_in_progress(root), None);
+
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::write(root.join(".git").join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        assert_eq!(git::current_branch(root).as_deref(), Some("main"));
+        assert_eq!(git::operation_in_progress(root), None);
+
+        fs::create_dir(root.join(".git").join("rebase-merge")).unwrap();
+        assert_eq!(git::operation_in_progress(root), Some("rebase"));
+
+        // Detached HEAD has no branch
+        fs::write(root.join(".git").join("HEAD"), "3f1c2a9d\n").unwrap();
+        assert_eq!(git::current_branch(root), None);
+    }
+
+    #[test]
+    fn test_worktree_git_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let git_dir = temp_dir.path().join("main-repo.git");
+        let worktree = temp_dir.path().join("worktree");
+        fs::create_dir_all(&git_dir).unwrap();
+        fs::create_dir_all(&worktree).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/feature/x\n").unwrap();
+        fs::write(worktree.join(".git"), format!("gitdir: {}\n", git_dir.display())).unwrap();
+
+        assert_eq!(git::current_branch(&worktree).as_deref(), Some("feature/x"));
+    }
+
+    #[test]
+    fn test_main_worktree() {
+        let temp_dir = TempDir::new().unwrap();
+        let main = temp_dir.path().canonicalize().unwrap().join("api");
+        let linked_git_dir = main.join(".git").join("worktrees").join("api-feature");
+        let worktree = temp_dir.path().join("api-feature");
+        fs::create_dir_all(&linked_git_dir).unwrap();
+        fs::create_dir_all(&worktree).unwrap();
+        fs::write(linked_git_dir.join("commondir"), "../..\n").unwrap();
+        fs::write(worktree.join(".git"), format!("gitdir: {}\n", linked_git_dir.display())).unwrap();
+
+        assert_eq!(git::main_worktree(&worktree), Some(main.clone()));
+        assert_eq!(git::main_worktree(&main), None);
+
+        // A submodule's git directory has no commondir
+        let submodule = temp_dir.path().join("vendor");
+        let module_git_dir = main.join(".git").join("modules").join("vendor");
+        fs::create_dir_all(&submodule).unwrap();
+        fs::create_dir_all(&module_git_dir).unwrap();
+        fs::write(submodule.join(".git"), format!("gitdir: {}\n", module_git_dir.display())).unwrap();
+        assert_eq!(git::main_worktree(&submodule), None);
+    }
+
+    #[test]
+    fn test_head_commit() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let git_dir = root.join(".git");
+        fs::create_dir_all(git_dir.join("refs").join("heads")).unwrap();
+
+        // An unborn branch has no commit yet
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        assert_eq!(git::head_commit(root), None);
+
+        fs::write(git_dir.join("packed-refs"), "# pack-refs with: peeled\n0a1b2c3d4e5f refs/heads/main\n").unwrap();
+        assert_eq!(git::head_commit(root).as_deref(), Some("0a1b2c3d4e5f"));
+        fs::write(git_dir.join("refs").join("heads").join("main"), "abc1234def567\n").unwrap();
+        assert_eq!(git::head_commit(root).as_deref(), Some("abc1234def567"));
+
+        let context = git::GitContext {
+            branch: Some("main".to_string()),
+            commit: "abc1234def567".to_string(),
+            dirty: Some(true),
+        };
+        assert_eq!(context.describe(), "main @ abc1234 (uncommitted changes)");
+    }
+
+    #[test]
+    fn test_branch_matches() {
+        assert!(git::branch_matches("main", "main"));
+        assert!(git::branch_matches("release/1.2", "release/*"));
+        assert!(!git::branch_matches("mainline", "main"));
+    }
+}
@@ -0,0 +1,520 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::store;
+use crate::utils;
+
+// Retention decides how much history each stash keeps; None means no limit
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Retention {
+    pub keep_last: Option<usize>,
+    pub keep_days: Option<u64>,
+}
+
+impl Retention {
+    // from_settings reads the retention rules from the configuration
+    pub fn from_settings(settings: &config::Settings) -> Retention {
+        Retention {
+            keep_last: settings.keep_last,
+            keep_days: settings.keep_days,
+        }
+    }
+}
+
+// VersionKind tells the versions holding stash content from the copies of a project's file kept before apply
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionKind {
+    #[default]
+    Stash,
+    // The file apply replaced, which was never the stash's content
+    ApplySnapshot,
+}
+
+// Message of the apply snapshots recorded before versions had a kind
+const LEGACY_APPLY_SNAPSHOT: &str = "snapshot before apply";
+
+// Version describes one recorded revision of a stash
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Version {
+    pub id: u64,
+    pub created_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub kind: VersionKind,
+}
+
+impl Version {
+    // is_stash reports whether the version holds content the stash itself had
+    pub fn is_stash(&self) -> bool {
+        self.kind == VersionKind::Stash
+    }
+}
+
+// GetHistoryDir returns the directory holding every recorded version of a stash
+pub fn get_history_dir(stash_key: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    utils::validate_project_name(stash_key)?;
+    Ok(utils::get_agstash_dir()?.join("history").join(stash_key))
+}
+
+// GetVersionPath returns where the content of a version is kept uncompressed
+pub fn get_version_path(stash_key: &str, id: u64) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(get_history_dir(stash_key)?.join(format!("{}.md", id)))
+}
+
+// GetCompressedVersionPath returns where the content of a version is kept when compression is on
+pub fn get_compressed_version_path(stash_key: &str, id: u64) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(get_history_dir(stash_key)?.join(format!("{}.md.gz", id)))
+}
+
+// ReadVersion returns the content of a version, whether or not it was stored compressed
+pub fn read_version(stash_key: &str, id: u64) -> Result<String, Box<dyn std::error::Error>> {
+    let compressed_path = get_compressed_version_path(stash_key, id)?;
+    if !utils::file_exists(&compressed_path) {
+        let (err, content) = utils::read_file(get_version_path(stash_key, id)?);
+        return match err {
+            Some(error) => Err(error),
+            None => Ok(content),
+        };
+    }
+
+    let mut content = String::new();
+    GzDecoder::new(fs::File::open(&compressed_path)?)
+        .read_to_string(&mut content)
+        .map_err(|e| format!("Could not decompress {}: {}", compressed_path.display(), e))?;
+    Ok(content)
+}
+
+// write_version stores the content of a version, gzipped when compress is set
+fn write_version(stash_key: &str, id: u64, content: &str, compress: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if !compress {
+        if let Some(error) = utils::write_file(get_version_path(stash_key, id)?, content) {
+            return Err(error);
+        }
+        return Ok(());
+    }
+
+    let mut encoder = GzEncoder::new(fs::File::create(get_compressed_version_path(stash_key, id)?)?, Compression::default());
+    encoder.write_all(content.as_bytes())?;
+    encoder.finish()?;
+    Ok(())
+}
+
+// ListVersions returns a stash's versions, oldest first
+pub fn list_versions(stash_key: &str) -> Result<Vec<Version>, Box<dyn std::error::Error>> {
+    let history_dir = get_history_dir(stash_key)?;
+    if !history_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut versions = Vec::new();
+    for entry in fs::read_dir(&history_dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        let (err, content) = utils::read_file(&path);
+        if let Some(error) = err {
+            return Err(error);
+        }
+        let mut version: Version = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid history entry in {}: {}", path.display(), e))?;
+        if !content.contains("\"kind\"") && version.message.as_deref() == Some(LEGACY_APPLY_SNAPSHOT) {
+            version.kind = VersionKind::ApplySnapshot;
+        }
+        versions.push(version);
+    }
+    versions.sort_by_key(|version| version.id);
+    Ok(versions)
+}
+
+// ListStashVersions returns the versions holding the stash's own content, oldest first, leaving out apply snapshots
+pub fn list_stash_versions(stash_key: &str) -> Result<Vec<Version>, Box<dyn std::error::Error>> {
+    Ok(list_versions(stash_key)?.into_iter().filter(Version::is_stash).collect())
+}
+
+// RecordVersion adds the content at path as the newest version of a stash, unless it matches the newest one already
+pub fn record_version(stash_key: &str, path: &Path, message: Option<&str>) -> Result<Version, Box<dyn std::error::Error>> {
+    record(stash_key, path, message, VersionKind::Stash)
+}
+
+// record adds the content at path as the newest version of a stash, of the given kind
+fn record(stash_key: &str, path: &Path, message: Option<&str>, kind: VersionKind) -> Result<Version, Box<dyn std::error::Error>> {
+    let (err, content) = utils::read_file(path);
+    if let Some(error) = err {
+        return Err(error);
+    }
+
+    let versions = list_versions(stash_key)?;
+    if let Some(latest) = versions.last() {
+        if read_version(stash_key, latest.id).is_ok_and(|latest_content| latest_content == content) {
+            return Ok(latest.clone());
+        }
+    }
+    let settings = config::load_settings()?;
+
+    let version = Version {
+        id: versions.last().map_or(1, |latest| latest.id + 1),
+        created_at: utils::format_timestamp(SystemTime::now()),
+        message: message.map(String::from),
+        sha256: Some(store::content_hash(content.as_bytes())),
+        kind,
+    };
+    let history_dir = get_history_dir(stash_key)?;
+    fs::create_dir_all(&history_dir)?;
+    write_version(stash_key, version.id, &content, settings.compress.unwrap_or(false))?;
+    let record_path = history_dir.join(format!("{}.json", version.id));
+    if let Some(error) = utils::write_file(record_path, &serde_json::to_string_pretty(&version)?) {
+        return Err(error);
+    }
+
+    let retention = Retention::from_settings(&settings);
+    prune_versions(stash_key, &retention)?;
+    Ok(version)
+}
+
+// RecordSnapshot keeps the stash an action is about to replace as a version, so there is always a recovery point
+pub fn record_snapshot(stash_key: &str, path: &Path, action: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if !utils::file_exists(path) {
+        return Ok(());
+    }
+    let version = record_version(stash_key, path, Some(&format!("snapshot before {}", action)))?;
+    utils::log_info(&format!("Snapshotted {} as version {} of {}", path.display(), version.id, stash_key));
+    Ok(())
+}
+
+// RecordApplySnapshot keeps the project file apply is about to replace in the stash's history, as an apply snapshot
+// that history, retention and apply --version tell from the stash's own versions
+pub fn record_apply_snapshot(stash_key: &str, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if !utils::file_exists(path) {
+        return Ok(());
+    }
+    let version = record(stash_key, path, Some(LEGACY_APPLY_SNAPSHOT), VersionKind::ApplySnapshot)?;
+    utils::log_info(&format!("Snapshotted {} as version {} of {}", path.display(), version.id, stash_key));
+    Ok(())
+}
+
+// PruneVersions deletes the versions of a stash that retention no longer keeps, returning how many were removed
+pub fn prune_versions(stash_key: &str, retention: &Retention) -> Result<usize, Box<dyn std::error::Error>> {
+    let expired = expired_versions(stash_key, retention)?;
+    for path in version_files(stash_key, &expired)? {
+        if utils::file_exists(&path) {
+            utils::remove_file(path)?;
+        }
+    }
+    Ok(expired.len())
+}
+
+// ExpiredVersions lists the versions of a stash that fall outside retention; stash versions and apply snapshots are
+// counted apart, so snapshots never push out the stash's own versions, and the newest of each is always kept
+pub fn expired_versions(stash_key: &str, retention: &Retention) -> Result<Vec<Version>, Box<dyn std::error::Error>> {
+    let versions = list_versions(stash_key)?;
+    let keep_last = retention.keep_last.unwrap_or(usize::MAX).max(1);
+    let cutoff = retention
+        .keep_days
+        .map(|days| utils::format_timestamp(SystemTime::now() - Duration::from_secs(days * 86_400)));
+
+    let mut expired = Vec::new();
+    for kind in [VersionKind::Stash, VersionKind::ApplySnapshot] {
+        expired.extend(
+            versions
+                .iter()
+                .rev()
+                .filter(|version| version.kind == kind)
+                .enumerate()
+                .filter(|(index, version)| {
+                    *index > 0 && (*index >= keep_last || cutoff.as_ref().is_some_and(|cutoff| version.created_at < *cutoff))
+                })
+                .map(|(_, version)| version.clone()),
+        );
+    }
+    expired.sort_by_key(|version| version.id);
+    Ok(expired)
+}
+
+// VersionFiles returns the content and record files that may make up the given versions of a stash
+pub fn version_files(stash_key: &str, versions: &[Version]) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let history_dir = get_history_dir(stash_key)?;
+    let mut files = Vec::new();
+    for version in versions {
+        files.push(get_version_path(stash_key, version.id)?);
+        files.push(get_compressed_version_path(stash_key, version.id)?);
+        files.push(history_dir.join(format!("{}.json", version.id)));
+    }
+    Ok(files)
+}
+
+// ListHistories returns every stash key that has a history directory
+pub fn list_histories() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let history_root = utils::get_agstash_dir()?.join("history");
+    if !history_root.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut keys = Vec::new();
+    for entry in fs::read_dir(&history_root)? {
+        let path = entry?.path();
+        if let (true, Some(name)) = (path.is_dir(), path.file_name().and_then(|name| name.to_str())) {
+            keys.push(name.to_string());
+        }
+    }
+    keys.sort();
+    Ok(keys)
+}
+
+// ResolveVersion finds a version of the stash's own content by id, or the newest one recorded on or before a date such
+// as "2026-10-03"; apply snapshots are never picked
+pub fn resolve_version(stash_key: &str, spec: &str) -> Result<Option<Version>, Box<dyn std::error::Error>> {
+    let versions = list_stash_versions(stash_key)?;
+
+    if let Ok(id) = spec.parse::<u64>() {
+        return Ok(versions.into_iter().find(|version| version.id == id));
+    }
+
+    let bound = parse_time_bound(spec).map_err(|_| {
+        format!(
+            "Invalid version '{}': expected an id, a date (YYYY-MM-DD) or a timestamp (YYYY-MM-DDTHH:MM:SSZ)",
+            spec
+        )
+    })?;
+    Ok(versions.into_iter().rev().find(|version| version.created_at <= bound))
+}
+
+// ParseTimeBound turns a date such as "2026-10-03" into the timestamp at the end of that day; full timestamps pass through.
+// Timestamps share one fixed format, so the result compares correctly against recorded ones as a string.
+pub fn parse_time_bound(spec: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let bound = match spec.len() {
+        10 => format!("{}T23:59:59Z", spec),
+        20 => spec.to_string(),
+        _ => String::new(),
+    };
+    let is_date = bound.len() == 20
+        && bound.char_indices().all(|(i, c)| match i {
+            4 | 7 => c == '-',
+            10 => c == 'T',
+            13 | 16 => c == ':',
+            19 => c == 'Z',
+            _ => c.is_ascii_digit(),
+        });
+    if !is_date {
+        return Err(format!("Invalid time '{}': expected a date (YYYY-MM-DD) or a timestamp (YYYY-MM-DDTHH:MM:SSZ)", spec).into());
+    }
+    Ok(bound)
+}
+
+// StashVersionAt returns the newest version recorded by bound that holds stash content, skipping apply snapshots
+pub fn stash_version_at(stash_key: &str, bound: &str) -> Result<Option<Version>, Box<dyn std::error::Error>> {
+    let versions = list_stash_versions(stash_key)?;
+    Ok(versions.into_iter().rev().find(|version| version.created_at.as_str() <= bound))
+}
+
+// RenameHistory moves a stash's history along with a renamed stash. When new_key already has a history the two are
+// merged in the order they were recorded and renumbered, so no version is left behind under old_key
+pub fn rename_history(old_key: &str, new_key: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let old_dir = get_history_dir(old_key)?;
+    let new_dir = get_history_dir(new_key)?;
+    if !old_dir.is_dir() {
+        return Ok(());
+    }
+    if !new_dir.exists() {
+        fs::rename(old_dir, new_dir)?;
+        return Ok(());
+    }
+
+    let mut merged: Vec<(&str, Version)> = list_versions(new_key)?
+        .into_iter()
+        .map(|version| (new_key, version))
+        .chain(list_versions(old_key)?.into_iter().map(|version| (old_key, version)))
+        .collect();
+    merged.sort_by(|(_, a), (_, b)| a.created_at.cmp(&b.created_at));
+
+    // The merged history is built beside the target and swapped in whole, so a failure leaves both histories as they were
+    let staging_dir = new_dir.with_file_name(format!(".{}.merging", new_key));
+    if staging_dir.exists() {
+        fs::remove_dir_all(&staging_dir)?;
+    }
+    fs::create_dir_all(&staging_dir)?;
+    for (index, (stash_key, mut version)) in merged.into_iter().enumerate() {
+        let compressed_path = get_compressed_version_path(stash_key, version.id)?;
+        let (content_path, extension) = if utils::file_exists(&compressed_path) {
+            (compressed_path, "md.gz")
+        } else {
+            (get_version_path(stash_key, version.id)?, "md")
+        };
+        version.id = index as u64 + 1;
+        fs::copy(&content_path, staging_dir.join(format!("{}.{}", version.id, extension)))?;
+        fs::write(staging_dir.join(format!("{}.json", version.id)), serde_json::to_string_pretty(&version)?)?;
+    }
+    fs::remove_dir_all(&new_dir)?;
+    fs::rename(&staging_dir, &new_dir)?;
+    fs::remove_dir_all(&old_dir)?;
+    utils::log_info(&format!("Merged the history of {} into {}", old_key, new_key));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::history;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_record_and_resolve_versions() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        let source = temp_dir.path().join("AGENTS.md");
+        fs::write(&source, "# AGENTS\n\n- first\n").unwrap();
+        let first = history::record_version("demo", &source, Some("start")).unwrap();
+        assert_eq!(first.id, 1);
+
+        // Recording unchanged content doesn't add a version
+        assert_eq!(history::record_version("demo", &source, None).unwrap(), first);
+
+        fs::write(&source, "# AGENTS\n\n- second\n").unwrap();
+        let second = history::record_version("demo", &source, None).unwrap();
+        assert_eq!(second.id, 2);
+        assert_eq!(history::list_versions("demo").unwrap().len(), 2);
+
+        let resolved = history::resolve_version("demo", "1").unwrap().unwrap();
+        assert_eq!(resolved.message.as_deref(), Some("start"));
+        assert_eq!(
+            fs::read_to_string(history::get_version_path("demo", resolved.id).unwrap()).unwrap(),
+            "# AGENTS\n\n- first\n"
+        );
+        assert!(history::resolve_version("demo", "7").unwrap().is_none());
+
+        // Dates pick the newest version recorded by the end of that day
+        assert_eq!(history::resolve_version("demo", "9999-12-31").unwrap().unwrap().id, 2);
+        assert!(history::resolve_version("demo", "2000-01-01").unwrap().is_none());
+        assert!(history::resolve_version("demo", "last tuesday").is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_retention() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        let config_path = crate::config::get_user_config_path().unwrap();
+        fs::create_dir_all(config_path.parent().unwrap()).unwrap();
+        fs::write(&config_path, "keep_last = 2\n").unwrap();
+
+        let source = temp_dir.path().join("AGENTS.md");
+        for rule in ["one", "two", "three"] {
+            fs::write(&source, format!("# AGENTS\n\n- {}\n", rule)).unwrap();
+            history::record_snapshot("demo", &source, "apply").unwrap();
+        }
+
+        // Only the newest versions are kept, and ids are never reused
+        let ids: Vec<u64> = history::list_versions("demo").unwrap().iter().map(|v| v.id).collect();
+        assert_eq!(ids, vec![2, 3]);
+        assert!(!history::get_version_path("demo", 1).unwrap().exists());
+
+        // Snapshots of missing files are skipped
+        history::record_snapshot("demo", &temp_dir.path().join("missing.md"), "apply").unwrap();
+        assert_eq!(history::list_versions("demo").unwrap().len(), 2);
+
+        // Versions older than keep_days expire, except the newest one
+        for id in [2, 3] {
+            let record = history::get_history_dir("demo").unwrap().join(format!("{}.json", id));
+            let mut version: history::Version = serde_json::from_str(&fs::read_to_string(&record).unwrap()).unwrap();
+            version.created_at = "2000-01-01T00:00:00Z".to_string();
+            fs::write(&record, serde_json::to_string(&version).unwrap()).unwrap();
+        }
+        let retention = history::Retention {
+            keep_last: None,
+            keep_days: Some(30),
+        };
+        let expired = history::expired_versions("demo", &retention).unwrap();
+        assert_eq!(expired.iter().map(|v| v.id).collect::<Vec<_>>(), vec![2]);
+
+        // Apply snapshots are counted apart, so they never push out the stash's own versions, and are never resolved
+        for rule in ["four", "five", "six"] {
+            fs::write(&source, format!("# AGENTS\n\n- {}\n", rule)).unwrap();
+            history::record_apply_snapshot("demo", &source).unwrap();
+        }
+        let ids: Vec<u64> = history::list_versions("demo").unwrap().iter().map(|v| v.id).collect();
+        assert_eq!(ids, vec![2, 3, 5, 6]);
+        let ids: Vec<u64> = history::list_stash_versions("demo").unwrap().iter().map(|v| v.id).collect();
+        assert_eq!(ids, vec![2, 3]);
+        assert!(history::resolve_version("demo", "6").unwrap().is_none());
+        assert_eq!(history::resolve_version("demo", "9999-12-31").unwrap().unwrap().id, 3);
+    }
+
+    #[test]
+    #[serial]
+    fn test_compressed_versions() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        let source = temp_dir.path().join("AGENTS.md");
+        fs::write(&source, "# AGENTS\n\n- plain\n").unwrap();
+        history::record_version("demo", &source, None).unwrap();
+
+        let config_path = crate::config::get_user_config_path().unwrap();
+        fs::create_dir_all(config_path.parent().unwrap()).unwrap();
+        fs::write(&config_path, "compress = true\nkeep_last = 2\n").unwrap();
+        for rule in ["packed", "packed again"] {
+            fs::write(&source, format!("# AGENTS\n\n- {}\n", rule)).unwrap();
+            history::record_version("demo", &source, None).unwrap();
+        }
+
+        // New versions are gzipped, and both kinds read back the same way
+        assert!(history::get_compressed_version_path("demo", 2).unwrap().exists());
+        assert!(!history::get_version_path("demo", 2).unwrap().exists());
+        assert_eq!(history::read_version("demo", 3).unwrap(), "# AGENTS\n\n- packed again\n");
+
+        // Recording the same content again is still recognized
+        assert_eq!(history::record_version("demo", &source, None).unwrap().id, 3);
+
+        // Pruning removes the plain version that fell out of retention
+        assert!(!history::get_version_path("demo", 1).unwrap().exists());
+        assert_eq!(history::read_version("demo", 2).unwrap(), "# AGENTS\n\n- packed\n");
+    }
+
+    #[test]
+    #[serial]
+    fn test_rename_history_merges() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        let source = temp_dir.path().join("AGENTS.md");
+        for (stash_key, rule) in [("old", "old first"), ("old", "old second"), ("new", "new first")] {
+            fs::write(&source, format!("# AGENTS\n\n- {}\n", rule)).unwrap();
+            history::record_version(stash_key, &source, None).unwrap();
+        }
+
+        // Both histories end up under the new key, numbered from 1, and nothing is left under the old one
+        history::rename_history("old", "new").unwrap();
+        assert!(!history::get_history_dir("old").unwrap().exists());
+        let versions = history::list_versions("new").unwrap();
+        assert_eq!(versions.iter().map(|version| version.id).collect::<Vec<_>>(), vec![1, 2, 3]);
+        let mut contents: Vec<String> = versions.iter().map(|version| history::read_version("new", version.id).unwrap()).collect();
+        contents.sort();
+        assert_eq!(
+            contents,
+            vec!["# AGENTS\n\n- new first\n", "# AGENTS\n\n- old first\n", "# AGENTS\n\n- old second\n"]
+        );
+        assert_eq!(history::list_histories().unwrap(), vec!["new".to_string()]);
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils;
+
+// JournalEntry is one recorded operation on a project's stash
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub timestamp: String,
+    pub project: String,
+    pub action: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+// GetJournalPath returns the append-only journal of stash operations
+pub fn get_journal_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(utils::get_agstash_dir()?.join("journal.jsonl"))
+}
+
+// RecordEvent appends an operation to the journal
+pub fn record_event(project: &str, action: &str, message: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let entry = JournalEntry {
+        timestamp: utils::format_timestamp(SystemTime::now()),
+        project: project.to_string(),
+        action: action.to_string(),
+        message: message.map(String::from),
+    };
+
+    let journal_path = get_journal_path()?;
+    if let Some(parent) = journal_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(&journal_path)?;
+    writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+    Ok(())
+}
+
+// ReadEvents returns every journal entry in the order it was recorded, skipping lines that cannot be parsed
+pub fn read_events() -> Result<Vec<JournalEntry>, Box<dyn std::error::Error>> {
+    let journal_path = get_journal_path()?;
+    if !utils::file_exists(&journal_path) {
+        return Ok(Vec::new());
+    }
+
+    let (err, content) = utils::read_file(&journal_path);
+    if let Some(error) = err {
+        return Err(error);
+    }
+
+    let mut entries = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => utils::log_warn(&format!("Skipping malformed journal line {}: {}", index + 1, e)),
+        }
+    }
+    Ok(entries)
+}
+
+// StashesAt replays entries recorded up to bound, an RFC 3339 timestamp, and returns the stash keys that existed then
+pub fn stashes_at(entries: &[JournalEntry], bound: &str) -> BTreeSet<String> {
+    let mut present = BTreeSet::new();
+    let mut archived: HashMap<String, Vec<String>> = HashMap::new();
+
+    for entry in entries.iter().filter(|entry| entry.timestamp.as_str() <= bound) {
+        let key = entry.project.clone();
+        match entry.action.as_str() {
+            "stash" | "edit" | "merge" | "copy" | "import" => {
+                present.insert(key);
+            }
+            "drop" => {
+                present.remove(&key);
+            }
+            "rename" => {
+                if let Some(old_key) = entry.message.as_deref().and_then(|m| m.strip_prefix("renamed from ")) {
+                    present.remove(old_key);
+                }
+                present.insert(key);
+            }
+            "undo" if entry.message.as_deref() == Some("undid drop") => {
+                present.insert(key);
+            }
+            // Archiving takes the project's nested and slot stashes along, and restoring brings them back
+            "archive" => {
+                let owned: Vec<String> = present.iter().filter(|k| utils::stash_belongs_to(k, &key)).cloned().collect();
+                present.retain(|k| !utils::stash_belongs_to(k, &key));
+                archived.insert(key, owned);
+            }
+            "restore" => {
+                present.extend(archived.remove(&key).unwrap_or_default());
+            }
+            _ => {}
+        }
+    }
+    present
+}
+
+// RenamedTo follows every rename in entries to find the key a stash is known by now
+pub fn renamed_to(entries: &[JournalEntry], stash_key: &str) -> String {
+    let mut current = stash_key.to_string();
+    for entry in entries.iter().filter(|entry| entry.action == "rename") {
+        if entry.message.as_deref().and_then(|m| m.strip_prefix("renamed from ")) == Some(current.as_str()) {
+            current = entry.project.clone();
+        }
+    }
+    current
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::journal;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_record_and_read_events() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        assert!(journal::read_events().unwrap().is_empty());
+
+        journal::record_event("demo", "stash", Some("first")).unwrap();
+        journal::record_event("demo", "apply", None).unwrap();
+
+        // Malformed lines are skipped rather than failing the whole read
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(journal::get_journal_path().unwrap())
+            .unwrap();
+        writeln!(file, "not json").unwrap();
+
+        let entries = journal::read_events().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].action, "stash");
+        assert_eq!(entries[0].message.as_deref(), Some("first"));
+        assert_eq!(entries[1].action, "apply");
+    }
+
+    #[test]
+    fn test_stashes_at() {
+        let entry = |timestamp: &str, project: &str, action: &str, message: Option<&str>| journal::JournalEntry {
+            timestamp: timestamp.to_string(),
+            project: project.to_string(),
+            action: action.to_string(),
+            message: message.map(String::from),
+        };
+        let entries = vec![
+            entry("2026-01-01T00:00:00Z", "api", "stash", None),
+            entry("2026-01-02T00:00:00Z", "web", "stash", None),
+            entry("2026-01-03T00:00:00Z", "web", "drop", None),
+            entry("2026-01-04T00:00:00Z", "service", "rename", Some("renamed from api")),
+            entry("2026-01-05T00:00:00Z", "web", "undo", Some("undid drop")),
+        ];
+
+        let keys = |bound: &str| journal::stashes_at(&entries, bound).into_iter().collect::<Vec<_>>();
+        assert!(keys("2025-12-31T00:00:00Z").is_empty());
+        assert_eq!(keys("2026-01-02T12:00:00Z"), vec!["api", "web"]);
+        assert_eq!(keys("2026-01-03T12:00:00Z"), vec!["api"]);
+        assert_eq!(keys("2026-01-05T12:00:00Z"), vec!["service", "web"]);
+
+        assert_eq!(journal::renamed_to(&entries, "api"), "service");
+        assert_eq!(journal::renamed_to(&entries, "web"), "web");
+    }
+}
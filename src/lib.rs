@@ -1,2 +1,22 @@
+pub mod adapters;
+pub mod archive;
+pub mod bundle;
 pub mod commands;
+pub mod config;
+pub mod daemon;
+pub mod embed;
+pub mod error;
+pub mod git;
+pub mod history;
+pub mod journal;
+pub mod lint;
+pub mod managed;
+pub mod manifest;
+pub mod markdown;
+pub mod metadata;
+pub mod registry;
+pub mod store;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod undo;
 pub mod utils;
\ No newline at end of file
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+
+// Rule is one bullet of an AGENTS.md, remembered with where it was written
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub source: String,
+    pub line: usize,
+    pub text: String,
+}
+
+// Contradiction is a pair of rules that ask for opposite things
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contradiction {
+    pub first: Rule,
+    pub second: Rule,
+}
+
+// Phrases that turn a rule into a prohibition; they are tried before the positive ones so "must not" isn't read as "must"
+const NEGATIVE_PREFIXES: [&str; 8] = ["never ", "do not ", "don't ", "dont ", "avoid ", "no ", "stop ", "must not "];
+
+// Phrases that only stress a rule without changing what it asks for
+const POSITIVE_PREFIXES: [&str; 4] = ["always ", "prefer ", "must ", "please "];
+
+// ExtractRules lists the bullets of content outside fenced code blocks, with their 1-based line numbers
+pub fn extract_rules(source: &str, content: &str) -> Vec<Rule> {
+    let mut rules = Vec::new();
+    let mut in_fence = false;
+    for (index, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        if let Some(text) = ["- ", "* ", "+ "].iter().find_map(|marker| trimmed.strip_prefix(marker)) {
+            rules.push(Rule {
+                source: source.to_string(),
+                line: index + 1,
+                text: text.trim().to_string(),
+            });
+        }
+    }
+    rules
+}
+
+// FindContradictions pairs up rules that say "do X" and "never X" about the same X
+pub fn find_contradictions(rules: &[Rule]) -> Vec<Contradiction> {
+    let mut by_subject: HashMap<String, (Vec<&Rule>, Vec<&Rule>)> = HashMap::new();
+    for rule in rules {
+        let (negative, subject) = polarity(&rule.text);
+        if subject.is_empty() {
+            continue;
+        }
+        let entry = by_subject.entry(subject).or_default();
+        if negative {
+            entry.1.push(rule);
+        } else {
+            entry.0.push(rule);
+        }
+    }
+
+    let mut contradictions = Vec::new();
+    for (positives, negatives) in by_subject.into_values() {
+        for first in &positives {
+            for second in &negatives {
+                contradictions.push(Contradiction {
+                    first: (*first).clone(),
+                    second: (*second).clone(),
+                });
+            }
+        }
+    }
+    contradictions.sort_by(|a, b| (&a.first.source, a.first.line).cmp(&(&b.first.source, b.first.line)));
+    contradictions
+}
+
+// IsIgnored reports whether rule is about one of the subjects in ignored, which lint accepts both asked for and
+// forbidden; "always use emojis" and "never use emojis" are both about "use emojis"
+pub fn is_ignored(rule: &Rule, ignored: &[String]) -> bool {
+    let subject = polarity(&rule.text).1;
+    ignored.iter().any(|entry| polarity(entry).1 == subject)
+}
+
+// polarity splits a rule into whether it forbids something and the normalized thing it is about
+fn polarity(text: &str) -> (bool, String) {
+    let mut subject: String = text
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '\'' || c == '-' { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    subject.push(' ');
+
+    let mut negative = false;
+    loop {
+        if let Some(rest) = NEGATIVE_PREFIXES.iter().find_map(|prefix| subject.strip_prefix(prefix)) {
+            negative = !negative;
+            subject = rest.to_string();
+        } else if let Some(rest) = POSITIVE_PREFIXES.iter().find_map(|prefix| subject.strip_prefix(prefix)) {
+            subject = rest.to_string();
+        } else {
+            break;
+        }
+    }
+    (negative, subject.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lint;
+
+    #[test]
+    fn test_extract_rules() {
+        let rules = lint::extract_rules("demo", "# AGENTS\n\n- Use tabs\n\n```\n- not a rule\n```\n* Run tests\n");
+        let texts: Vec<(usize, &str)> = rules.iter().map(|rule| (rule.line, rule.text.as_str())).collect();
+        assert_eq!(texts, vec![(3, "Use tabs"), (8, "Run tests")]);
+    }
+
+    #[test]
+    fn test_find_contradictions() {
+        let mut rules = lint::extract_rules("repo", "# AGENTS\n\n- Always rebase before merging\n- Use tabs\n");
+        rules.extend(lint::extract_rules(
+            "repo@docs",
+            "# AGENTS\n\n- Never rebase before merging.\n- Don't use spaces\n",
+        ));
+
+        let contradictions = lint::find_contradictions(&rules);
+        assert_eq!(contradictions.len(), 1);
+        assert_eq!(contradictions[0].first.source, "repo");
+        assert_eq!(contradictions[0].first.line, 3);
+        assert_eq!(contradictions[0].second.source, "repo@docs");
+        assert_eq!(contradictions[0].second.text, "Never rebase before merging.");
+
+        // Rules that agree are fine, however they are phrased
+        let rules = lint::extract_rules("repo", "# AGENTS\n\n- Never force-push\n- Do not force-push\n- Always test\n- Test\n");
+        assert!(lint::find_contradictions(&rules).is_empty());
+    }
+
+    #[test]
+    fn test_is_ignored() {
+        let rules = lint::extract_rules("repo", "# AGENTS\n\n- Always rebase before merging\n- Use tabs\n");
+        let ignored = vec!["Rebase before merging".to_string()];
+        assert!(lint::is_ignored(&rules[0], &ignored));
+        assert!(!lint::is_ignored(&rules[1], &ignored));
+    }
+}
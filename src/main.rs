@@ -1,14 +1,50 @@
-use clap::Parser;
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
 
-mod commands;
-mod utils;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
+
+use agstash::error::AgStashError;
+use agstash::managed::{self, ManagedFile};
+use agstash::{commands, config, history, registry, store, utils};
 
 #[derive(Parser)]
 #[command(name = "agstash")]
 #[command(about = "A tool for stashing and managing AGENTS.md files", long_about = None)]
 struct Args {
-    #[arg(short, long, help = "Enable verbose output")]
+    #[arg(short, long, global = true, help = "Enable verbose output, logging what each step does")]
     verbose: bool,
+
+    #[arg(short, long, global = true, conflicts_with = "verbose", help = "Print only errors, prompts and requested data, leaving out confirmations and warnings")]
+    quiet: bool,
+
+    #[arg(long, global = true, help = "Print without ANSI colors; also turned off by NO_COLOR or when output is not a terminal")]
+    no_color: bool,
+
+    #[arg(long, global = true, help = "Exit non-zero on soft failures, such as stash with no AGENTS.md or apply with no stash")]
+    strict: bool,
+
+    #[arg(short = 'C', global = true, value_name = "DIR", help = "Run as if agstash was started in DIR instead of the current directory")]
+    directory: Option<PathBuf>,
+
+    #[arg(long, global = true, help = "Use plain ASCII status indicators instead of symbols")]
+    no_icons: bool,
+
+    #[arg(long, global = true, help = "Target the repository root AGENTS.md instead of the nearest nested one")]
+    top: bool,
+
+    #[arg(long = "project", global = true, value_name = "NAME", help = "Work on a project listed by `agstash projects` instead of the one in the current directory")]
+    target_project: Option<String>,
+
+    #[arg(long = "file", global = true, value_name = "NAME", help = "Work on another agent file instead of AGENTS.md, e.g. --file CLAUDE.md")]
+    target_file: Option<String>,
+
+    #[arg(long = "output", id = "output_format", global = true, value_name = "FORMAT", value_parser = ["text", "json"], default_value = "text", help = "Print text, or JSON for scripts, from list, projects, status, diff, check and audit")]
+    output: String,
+
+    #[arg(long, global = true, value_name = "COMMAND", help = "Run another agstash command afterwards on the same project, e.g. --then \"apply -f\"")]
+    then: Vec<String>,
     
     #[command(subcommand)]
     command: Option<Commands>,
@@ -20,40 +56,918 @@ enum Commands {
     Init {
         #[arg(short = 'f', long, help = "Overwrite existing AGENTS.md file without prompting for confirmation")]
         force: bool,
+        #[arg(long, help = "Add AGENTS.md to the project's .gitignore")]
+        gitignore: bool,
+        #[arg(long, conflicts_with = "gitignore", help = "Add AGENTS.md to .git/info/exclude, which only this machine reads")]
+        exclude_local: bool,
+        #[arg(long, conflicts_with_all = ["gitignore", "exclude_local"], help = "Leave .gitignore alone even when the gitignore setting is on")]
+        no_gitignore: bool,
     },
     /// Remove the AGENTS.md file from the current directory
-    Clean,
+    Clean {
+        #[arg(long, help = "Remove AGENTS.md without snapshotting it into the pre-clean slot first")]
+        no_stash: bool,
+        #[arg(long, help = "Move AGENTS.md to the system trash instead of deleting it")]
+        trash: bool,
+    },
     /// Stash the AGENTS.md file to a global location for later retrieval
-    Stash,
+    Stash {
+        #[arg(short = 'm', long, help = "Message describing the stash")]
+        message: Option<String>,
+        #[arg(short = 'p', long, help = "Interactively choose which changed hunks to stash")]
+        patch: bool,
+        #[arg(short = 'r', long, conflicts_with = "patch", help = "Stash every AGENTS.md under the project root, skipping directories listed in .agstashignore")]
+        recursive: bool,
+        #[arg(long, conflicts_with_all = ["patch", "recursive"], help = "Stash every agent file in the bundle setting (AGENTS.md, CLAUDE.md, ...) together")]
+        bundle: bool,
+    },
     /// Apply a previously stashed AGENTS.md file to the current directory
     Apply {
         #[arg(short = 'f', long, help = "Overwrite existing AGENTS.md file without prompting for confirmation")]
         force: bool,
+        #[arg(short = 'b', long, help = "Copy an existing AGENTS.md to AGENTS.md.bak before overwriting it")]
+        backup: bool,
+        #[arg(short = 'a', long, help = "Add the stashed rules below the existing AGENTS.md instead of replacing it")]
+        append: bool,
+        #[arg(long, value_name = "ID|DATE", help = "Apply a version from the stash history instead of the current stash")]
+        version: Option<String>,
+        #[arg(long, help = "Write the configured global_fallback AGENTS.md when there is no project or no stash")]
+        fallback_global: bool,
+        #[arg(short = 'r', long, conflicts_with_all = ["version", "fallback_global"], help = "Apply the project's stash and every nested stash to the directories they came from")]
+        recursive: bool,
+        #[arg(long, conflicts_with_all = ["backup", "append", "version", "fallback_global", "recursive"], help = "Apply every file in the project's bundle together")]
+        bundle: bool,
+        #[arg(long, conflicts_with_all = ["backup", "append", "version", "fallback_global", "recursive", "bundle"], help = "Symlink AGENTS.md to the stash instead of copying it, so edits in either place stay in sync")]
+        link: bool,
+        #[arg(long, help = "Add the applied files to the project's .gitignore")]
+        gitignore: bool,
+        #[arg(long, conflicts_with = "gitignore", help = "Add the applied files to .git/info/exclude, which only this machine reads")]
+        exclude_local: bool,
+        #[arg(long, conflicts_with_all = ["gitignore", "exclude_local"], help = "Leave .gitignore alone even when the gitignore setting is on")]
+        no_gitignore: bool,
+    },
+    /// Apply the stashed AGENTS.md to the current directory and remove it from the global location
+    Pop {
+        #[arg(short = 'f', long, help = "Overwrite existing AGENTS.md file without prompting for confirmation")]
+        force: bool,
+    },
+    /// Delete the stashed AGENTS.md for the current project
+    Drop {
+        #[arg(short = 'f', long, help = "Delete the stash without prompting for confirmation")]
+        force: bool,
+    },
+    /// Rename a stash after its project directory has been renamed
+    Rename {
+        #[arg(help = "Current project name of the stash")]
+        old: String,
+        #[arg(help = "New project name for the stash")]
+        new: String,
+    },
+    /// Duplicate a project's stash so another project can start from it
+    Copy {
+        #[arg(help = "Project whose stash is copied")]
+        src: String,
+        #[arg(help = "Project that receives the copy")]
+        dst: String,
+        #[arg(short = 'f', long, help = "Overwrite an existing stash for the destination project")]
+        force: bool,
+    },
+    /// Rewrite one tool's instruction file in another tool's format, e.g. convert --from agents --to cursorrules
+    Convert {
+        #[arg(long, value_name = "FORMAT", help = "Format to read: agents, claude, gemini, copilot, cursorrules or cursor")]
+        from: String,
+        #[arg(long, value_name = "FORMAT", help = "Format to write, in the location that tool reads it from")]
+        to: String,
+        #[arg(long, help = "Print the converted file instead of writing it")]
+        stdout: bool,
+        #[arg(short = 'f', long, help = "Overwrite the target file if it already exists")]
+        force: bool,
+    },
+    /// Combine two projects' stashes, merging sections with the same heading
+    Merge {
+        #[arg(help = "First project; its sections keep their order")]
+        first: String,
+        #[arg(help = "Second project; its new sections and bullets are added")]
+        second: String,
+        #[arg(long, value_name = "PROJECT", help = "Store the result as this project's stash instead of printing it")]
+        into: Option<String>,
+        #[arg(short = 'f', long, help = "Overwrite an existing stash for the --into project")]
+        force: bool,
+    },
+    /// Open a stashed AGENTS.md in $VISUAL or $EDITOR
+    Edit {
+        #[arg(help = "Project whose stash is edited (defaults to the current project)")]
+        project: Option<String>,
+    },
+    /// Add a warning to a stash that apply shows before writing it, e.g. annotate --warning "experimental rules"
+    Annotate {
+        #[arg(help = "Project whose stash is annotated (defaults to the current project)")]
+        project: Option<String>,
+        #[arg(short = 'w', long, conflicts_with = "clear", help = "Warning to show whenever the stash is applied")]
+        warning: Option<String>,
+        #[arg(long, help = "Remove every warning from the stash")]
+        clear: bool,
+    },
+    /// Key the current project's stashes by a friendly name instead of its directory name, e.g. alias api
+    Alias {
+        #[arg(conflicts_with = "remove", help = "Alias to register for the current project")]
+        name: Option<String>,
+        #[arg(long, help = "Unregister the alias and key the stashes by directory name again")]
+        remove: bool,
+    },
+    /// Restore the file removed or overwritten by the last clean, apply or drop
+    Undo {
+        #[arg(short = 'f', long, help = "Overwrite the current file without prompting for confirmation")]
+        force: bool,
+    },
+    /// Show a diff between the stashed AGENTS.md and the one in the project root, exiting 1 if they differ and 2 on an error
+    Diff,
+    /// List every stashed project with its stash message
+    List,
+    /// List every project stashed on this machine, with its path and whether it still has a stash
+    Projects,
+    /// Print a stash and its metadata
+    Show {
+        #[arg(help = "Project whose stash is shown (defaults to the current project)")]
+        project: Option<String>,
+        #[arg(long, value_name = "ID|DATE", help = "Show a version from the stash history instead of the current stash")]
+        version: Option<String>,
+        #[arg(long, help = "Print the sections and rules as JSON, with where the stash came from")]
+        ast: bool,
+    },
+    /// List the recorded versions of a project's stash
+    History {
+        #[arg(help = "Project whose history is listed (defaults to the current project)")]
+        project: Option<String>,
+    },
+    /// Show the history of stash operations
+    Log {
+        #[arg(short = 'a', long, help = "Show history for every project instead of the current one")]
+        all: bool,
+    },
+    /// Search every stash for a pattern
+    Grep {
+        #[arg(help = "Text to search for")]
+        pattern: String,
+        #[arg(short = 'E', long, help = "Interpret the pattern as a regular expression")]
+        regex: bool,
+        #[arg(short = 'i', long, help = "Match case-insensitively")]
+        ignore_case: bool,
+    },
+    /// Report whether AGENTS.md and its stash exist and whether they are in sync
+    Status,
+    /// Print a short uncolored token for a shell prompt, e.g. agents✓, agents~ for drift or agents✗ when missing
+    PromptStatus,
+    /// Symlink CLAUDE.md, .cursorrules and the other configured mirrors to AGENTS.md so every assistant reads one file
+    Mirror {
+        #[arg(long, help = "Remove the mirrors, leaving AGENTS.md")]
+        remove: bool,
+        #[arg(short = 'f', long, conflicts_with = "remove", help = "Replace mirrors that have content of their own")]
+        force: bool,
+    },
+    /// Regenerate CLAUDE.md, .cursorrules and the other configured mirrors from AGENTS.md, reporting any that diverged
+    Sync {
+        #[arg(long, help = "Only report mirrors that are missing or diverged, exiting non-zero if any diverged")]
+        check: bool,
+        #[arg(short = 'f', long, help = "Replace diverged mirrors without prompting for confirmation")]
+        force: bool,
+    },
+    /// Print the shell snippet that offers to apply a project's stash on entering it, e.g. eval "$(agstash hook bash)"
+    Hook {
+        #[arg(value_parser = ["bash", "zsh", "fish"], help = "Shell to print the snippet for")]
+        shell: String,
+    },
+    /// Offer to apply the stash of a project entered without its AGENTS.md; run by the shell hook
+    #[command(hide = true)]
+    HookApply {
+        #[arg(long, value_name = "DIR", help = "Directory the shell came from")]
+        from: Option<PathBuf>,
+    },
+    /// Install git hooks that warn when AGENTS.md drifted from its stash, or stash it on every commit
+    InstallHooks {
+        #[arg(long, help = "Remove the agstash hooks, restoring the hooks they replaced")]
+        uninstall: bool,
+        #[arg(long, conflicts_with = "uninstall", help = "Stash a changed AGENTS.md on commit instead of warning")]
+        auto_stash: bool,
+    },
+    /// Check AGENTS.md against its stash from a git hook; run by the hooks install-hooks writes
+    #[command(hide = true)]
+    GitHook {
+        #[arg(help = "Hook being run, e.g. pre-commit")]
+        hook: String,
+        #[arg(long, help = "Stash a changed AGENTS.md instead of warning")]
+        auto_stash: bool,
+    },
+    /// Watch every registered project, stashing AGENTS.md edits and reporting drift, and answer status over a local socket
+    Daemon {
+        #[arg(long, value_name = "SECS", default_value_t = 2, help = "Seconds between checks of the watched projects")]
+        interval: u64,
+        #[arg(long, help = "Only report changes and drift, without stashing edits")]
+        no_auto_stash: bool,
+    },
+    /// Check for CI that AGENTS.md exists, is valid and matches its stash, exiting 2 if missing, 3 if invalid, 4 if drifted
+    Check,
+    /// Report validation and drift for stashed projects without modifying anything
+    Audit {
+        #[arg(short = 'a', long, help = "Audit every project in the stash store instead of the current one, checking registered projects for drift")]
+        all: bool,
+        #[arg(long, value_name = "DIR", help = "Audit a shared or exported stashes directory instead of the local store")]
+        store: Option<std::path::PathBuf>,
+    },
+    /// Check the installation for problems and suggest how to fix each one
+    Doctor {
+        #[arg(long, help = "Also check that stash then apply reproduces files byte for byte on this machine")]
+        roundtrip: bool,
+    },
+    /// Fix what doctor finds: rebuild missing metadata and history records, quarantine corrupt files
+    Repair,
+    /// Settle the conflict copies a file sync client left next to stashes, asking which copy to keep
+    Resolve {
+        #[arg(long, conflicts_with_all = ["keep_copy", "keep_both"], help = "Keep every stash and discard the conflict copies")]
+        keep_stash: bool,
+        #[arg(long, conflicts_with = "keep_both", help = "Replace every stash with its conflict copy")]
+        keep_copy: bool,
+        #[arg(long, help = "Keep each conflict copy as its own stash, e.g. api+conflict")]
+        keep_both: bool,
+    },
+    /// Upgrade the store to the on-disk layout this version of agstash uses
+    Migrate,
+    /// Re-hash every stored file and report any that changed or were truncated outside agstash
+    Verify,
+    /// Flag rules that contradict each other in a project's stash or between it and its nested stashes
+    Lint {
+        #[arg(help = "Project whose stashes are checked (defaults to the current project)")]
+        project: Option<String>,
+    },
+    /// Report how many projects, stashes and versions the store holds and the space they use
+    Stats,
+    /// Prune old history versions, orphaned metadata and empty directories from the store
+    Gc {
+        #[arg(long, value_name = "DAYS", help = "Remove history versions older than this many days (defaults to keep_days, or 90)")]
+        days: Option<u64>,
+        #[arg(long, value_name = "COUNT", help = "Keep only this many versions of each stash (defaults to keep_last)")]
+        keep: Option<usize>,
+        #[arg(short = 'n', long, help = "Report what would be removed without removing anything")]
+        dry_run: bool,
+    },
+    /// Bundle a retired project's stashes, metadata and backups into one archive file
+    ArchiveProject {
+        #[arg(help = "Project to archive")]
+        project: String,
+    },
+    /// Pack every stash into one portable .tar.gz archive, e.g. to move to a new machine
+    Export {
+        #[arg(short = 'o', long = "out", value_name = "FILE", help = "Archive to write, e.g. stashes.tar.gz, or - for a json manifest on stdout")]
+        output: std::path::PathBuf,
+        #[arg(long, value_name = "tar|json", default_value = "tar", help = "Write a .tar.gz archive or a JSON manifest")]
+        format: String,
+        #[arg(long, help = "Include each stash's metadata")]
+        metadata: bool,
+        #[arg(long, help = "Include the history of every stash")]
+        history: bool,
+    },
+    /// Bring stashes from an export archive or a directory of markdown files into the store
+    Import {
+        #[arg(help = "Archive written by export, or a directory of markdown files")]
+        path: std::path::PathBuf,
+        #[arg(long, conflicts_with_all = ["skip", "rename"], help = "Replace stashes that are already in the store")]
+        overwrite: bool,
+        #[arg(long, conflicts_with = "rename", help = "Leave stashes that are already in the store alone")]
+        skip: bool,
+        #[arg(long, help = "Import clashing stashes under a new name such as api-imported")]
+        rename: bool,
+    },
+    /// Write a timestamped archive of the whole store to the backup directory, keeping the newest few
+    Backup,
+    /// Put the store back as it was in a backup archive, previewing every change first
+    Restore {
+        #[arg(help = "Backup archive to restore, e.g. ~/.local/share/agstash/backups/agstash-20261017T120000Z.tar.gz")]
+        archive: std::path::PathBuf,
+        #[arg(long, help = "Keep files the backup does not have instead of removing them")]
+        merge: bool,
+        #[arg(short = 'f', long, help = "Restore without asking for confirmation")]
+        force: bool,
+    },
+    /// Move an archived project back into the active store
+    RestoreProject {
+        #[arg(help = "Project to restore")]
+        project: String,
+        #[arg(short = 'f', long, help = "Overwrite files already in the active store")]
+        force: bool,
+    },
+    /// Import stray AGENTS.md copies such as AGENTS.md.bak as named stashes and remove them
+    Tidy {
+        #[arg(short = 'f', long, help = "Import every detected copy without prompting for confirmation")]
+        force: bool,
+    },
+    /// Work with the journal of stash operations
+    Journal {
+        #[command(subcommand)]
+        action: JournalAction,
+    },
+    /// Run several agstash commands in turn on the same project, stopping at the first that fails
+    Run {
+        #[arg(help = "Commands separated by ';' or '&&', e.g. \"stash -m wip; apply -f\"")]
+        script: String,
+    },
+    /// Inspect and change agstash configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
     },
     /// Remove the global .agstash directory and all stashed files
-    Uninstall,
+    Uninstall {
+        #[arg(long, help = "Move the .agstash directory to the system trash instead of deleting it")]
+        trash: bool,
+    },
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
-    
-    utils::setup_logging(args.verbose);
+#[derive(clap::Subcommand)]
+enum ConfigAction {
+    /// Print the effective value of every setting
+    #[command(visible_alias = "show")]
+    List {
+        #[arg(long, help = "Show which layer each value comes from")]
+        origin: bool,
+    },
+    /// Print the effective value of one setting
+    Get {
+        #[arg(help = "Setting to print, e.g. trash")]
+        key: String,
+        #[arg(long, help = "Show which layer the value comes from")]
+        origin: bool,
+    },
+    /// Store a setting in the user configuration file, ~/.agstash/config.toml
+    Set {
+        #[arg(help = "Setting to store, e.g. trash")]
+        key: String,
+        #[arg(help = "Value to store; lists are separated by commas, e.g. CLAUDE.md,.cursorrules")]
+        value: String,
+    },
+}
+
+#[derive(clap::Subcommand)]
+enum JournalAction {
+    /// Rebuild the stashes as they stood at a past time into a separate directory
+    Replay {
+        #[arg(long, value_name = "DATE|TIMESTAMP", help = "Point in time to rebuild, e.g. 2026-10-03 or 2026-10-03T14:00:00Z")]
+        until: String,
+        #[arg(long, value_name = "DIR", help = "Directory to write the rebuilt store to (defaults to a new temporary directory)")]
+        into: Option<std::path::PathBuf>,
+    },
+}
+
+// Commands that can print their report as JSON, named in the error the others give for --output json
+const JSON_COMMANDS: &[&str] = &[
+    "list", "projects", "status", "diff", "check", "audit", "show", "log", "history", "stats", "verify", "doctor", "lint",
+];
+
+impl Commands {
+    // is_destructive reports whether the command rewrites or removes the store as a whole, so auto_backup runs first
+    fn is_destructive(&self) -> bool {
+        match self {
+            Commands::Gc { dry_run, .. } => !dry_run,
+            Commands::Uninstall { .. } | Commands::Migrate | Commands::Repair => true,
+            _ => false,
+        }
+    }
+
+    // supports_json reports whether the command can print its report as JSON with --output json
+    fn supports_json(&self) -> bool {
+        matches!(
+            self,
+            Commands::List
+                | Commands::Projects
+                | Commands::Status
+                | Commands::Diff
+                | Commands::Check
+                | Commands::Audit { .. }
+                | Commands::Show { .. }
+                | Commands::Log { .. }
+                | Commands::History { .. }
+                | Commands::Stats
+                | Commands::Verify
+                | Commands::Doctor { .. }
+                | Commands::Lint { .. }
+        )
+    }
+
+    // is_mutating reports whether the command writes to the project or the stash store
+    fn is_mutating(&self) -> bool {
+        match self {
+            Commands::Init { .. }
+            | Commands::Clean { .. }
+            | Commands::Stash { .. }
+            | Commands::Apply { .. }
+            | Commands::Pop { .. }
+            | Commands::Drop { .. }
+            | Commands::Rename { .. }
+            | Commands::Copy { .. }
+            | Commands::Edit { .. }
+            | Commands::Undo { .. }
+            | Commands::ArchiveProject { .. }
+            | Commands::RestoreProject { .. }
+            | Commands::Import { .. }
+            | Commands::Restore { .. }
+            | Commands::Tidy { .. }
+            | Commands::Repair
+            | Commands::Resolve { .. }
+            | Commands::Migrate
+            | Commands::Mirror { .. }
+            | Commands::Daemon { .. }
+            | Commands::HookApply { .. }
+            | Commands::InstallHooks { .. }
+            | Commands::Uninstall { .. } => true,
+            Commands::Merge { into, .. } => into.is_some(),
+            Commands::Convert { stdout, .. } => !stdout,
+            Commands::Sync { check, .. } => !check,
+            Commands::GitHook { auto_stash, .. } => *auto_stash,
+            Commands::Gc { dry_run, .. } => !dry_run,
+            Commands::Annotate { warning, clear, .. } => warning.is_some() || *clear,
+            Commands::Alias { name, remove } => name.is_some() || *remove,
+            Commands::Diff
+            | Commands::List
+            | Commands::Projects
+            | Commands::Show { .. }
+            | Commands::History { .. }
+            | Commands::Log { .. }
+            | Commands::Grep { .. }
+            | Commands::Status
+            | Commands::Check
+            | Commands::PromptStatus
+            | Commands::Hook { .. }
+            | Commands::Audit { .. }
+            | Commands::Doctor { .. }
+            | Commands::Lint { .. }
+            | Commands::Stats
+            | Commands::Export { .. }
+            | Commands::Backup
+            | Commands::Verify
+            | Commands::Journal { .. }
+            | Commands::Run { .. }
+            | Commands::Config { .. } => false,
+        }
+    }
+}
+
+fn main() {
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+    if let Err(error) = run(&matches, &args) {
+        report_error(error.as_ref(), args.output == "json");
+        std::process::exit(1);
+    }
+}
+
+// report_error prints the error with its stable code, as "Error [E010]: ..." on stderr or, with --output json, as a
+// JSON object on stdout where scripts read the rest of the output
+fn report_error(error: &(dyn std::error::Error + 'static), json: bool) {
+    let error = agstash::error::classify(error);
+    if json {
+        let report = serde_json::json!({
+            "error": {
+                "code": error.code(),
+                "kind": error.kind(),
+                "message": error.message(),
+            }
+        });
+        println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+    } else {
+        eprintln!("Error [{}]: {}", error.code(), error.message());
+    }
+}
+
+// run runs the command and any commands chained after it
+fn run(matches: &ArgMatches, args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    utils::setup_logging(args.verbose, args.quiet);
+    if let Some(dir) = &args.directory {
+        env::set_current_dir(dir).map_err(|e| format!("Cannot change to {}: {}", dir.display(), e))?;
+    }
+    // The store is resolved once for the whole chain; doctor reports a config that can't be read instead of failing
+    match config::configured_store() {
+        Ok(store_dir) => utils::set_store_dir(store_dir),
+        Err(error) if !matches!(args.command, Some(Commands::Doctor { .. })) => return Err(error),
+        Err(_) => {}
+    }
+
+    let mut chain = match &args.command {
+        Some(Commands::Run { script }) => utils::split_chain(script)?,
+        _ => Vec::new(),
+    };
+    for step in &args.then {
+        chain.extend(utils::split_chain(step)?);
+    }
+    if chain.is_empty() {
+        return execute(matches, args);
+    }
+
+    // Every command in the chain works on the project found before the first one ran
+    utils::pin_project_root();
+    if !matches!(args.command, Some(Commands::Run { .. })) {
+        execute(matches, args)?;
+    }
+    for words in chain {
+        let step_matches = Args::command().try_get_matches_from(std::iter::once("agstash".to_string()).chain(words))?;
+        let mut step_args = Args::from_arg_matches(&step_matches)?;
+        if matches!(step_args.command, Some(Commands::Run { .. })) || !step_args.then.is_empty() {
+            return Err(AgStashError::Usage("Chained commands cannot start another chain".into()).into());
+        }
+        if step_args.directory.is_some() {
+            return Err(AgStashError::Usage("Chained commands run in the first command's directory and cannot use -C".into()).into());
+        }
+        step_args.no_icons |= args.no_icons;
+        step_args.top |= args.top;
+        step_args.quiet |= args.quiet;
+        step_args.no_color |= args.no_color;
+        step_args.strict |= args.strict;
+        execute(&step_matches, &step_args)?;
+    }
+    Ok(())
+}
+
+// execute checks and runs one command with the configuration it sees
+fn execute(matches: &ArgMatches, args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    // The project named with --project is pinned first so its .agstash.toml is the project layer
+    if let Some(name) = &args.target_project {
+        let root = registry::find_project(name)?;
+        if !root.is_dir() {
+            utils::log_warn(&format!("The path of project {} no longer exists: {}", name, root.display()));
+        }
+        utils::set_project_root(root);
+    }
+
+    // Flags are the highest configuration layer
+    let mut flags = config::Settings {
+        icons: args.no_icons.then_some(false),
+        target_file: args.target_file.clone(),
+        strict: args.strict.then_some(true),
+        color: args.no_color.then_some(false),
+        ..Default::default()
+    };
+    match &args.command {
+        Some(Commands::Clean { no_stash, trash }) => {
+            flags.auto_stash = no_stash.then_some(false);
+            flags.trash = trash.then_some(true);
+        }
+        Some(Commands::Uninstall { trash }) => {
+            flags.trash = trash.then_some(true);
+        }
+        Some(Commands::Init { gitignore, exclude_local, no_gitignore, .. })
+        | Some(Commands::Apply { gitignore, exclude_local, no_gitignore, .. }) => {
+            flags.gitignore = if *gitignore || *exclude_local { Some(true) } else { no_gitignore.then_some(false) };
+            // Naming either file explicitly overrides the exclude_local setting
+            flags.exclude_local = (*gitignore || *exclude_local).then_some(*exclude_local);
+        }
+        _ => {}
+    }
+    if let Some(Commands::Stash { recursive, .. } | Commands::Apply { recursive, .. }) = &args.command {
+        flags.recursive = recursive.then_some(true);
+    }
+    let layers = match config::ConfigLayers::load() {
+        Ok(layers) => layers,
+        // Doctor reports a configuration that can't be read, so it runs on the defaults and the flags instead
+        Err(_) if matches!(args.command, Some(Commands::Doctor { .. })) => config::ConfigLayers {
+            layers: vec![(config::Origin::Default, config::Settings::defaults())],
+        },
+        Err(error) => return Err(error),
+    }
+    .with_flags(flags);
+    let settings = layers.effective();
+
+    commands::set_icons(settings.icons.unwrap_or(true));
+    // Colors are left out for --no-color or color = false, a non-empty NO_COLOR (https://no-color.org) and output
+    // that is piped
+    let no_color_env = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    commands::set_color(settings.color.unwrap_or(true) && !no_color_env && io::stdout().is_terminal());
+    commands::set_confirm(settings.confirm.unwrap_or(true));
+    commands::set_quiet(args.quiet);
+    commands::set_strict(settings.strict.unwrap_or(false));
+    let json = args.output == "json";
+    commands::set_json_output(json);
+
+    // Refuse commands the administrator has disabled before doing any work
+    if let Some((name, sub_matches)) = matches.subcommand() {
+        let system_config = match config::load_system_config() {
+            Ok(system_config) => system_config,
+            // Doctor reports an administrator configuration that can't be read, so nothing is disabled for it
+            Err(_) if matches!(args.command, Some(Commands::Doctor { .. })) => config::SystemConfig::default(),
+            Err(error) => return Err(error),
+        };
+        let given_args: Vec<String> = sub_matches
+            .ids()
+            .filter(|id| sub_matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+            .map(|id| id.as_str().to_string())
+            .collect();
+        if system_config.is_command_disabled(name, &given_args) {
+            return Err(AgStashError::CommandDisabled(format!("'{}' is disabled by administrator", name)).into());
+        }
+    }
+
+    // Fail fast on writes in read-only mode; queries keep working
+    let readonly = settings.readonly.unwrap_or(false);
+    if let (true, Some(command)) = (readonly, &args.command) {
+        if command.is_mutating() {
+            let name = matches.subcommand_name().unwrap_or_default();
+            return Err(AgStashError::ReadOnly(format!(
+                "'{}' would modify files but agstash is in read-only mode (unset {} or the 'readonly' config key)",
+                name,
+                config::READONLY_ENV
+            ))
+            .into());
+        }
+    }
+
+    // Doctor runs once the administrator and read-only guards pass, but before the rest of the configuration is checked
+    // so it can report what is wrong with it
+    if let Some(Commands::Doctor { roundtrip }) = &args.command {
+        if commands::handle_doctor(*roundtrip)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    commands::set_top_level(args.top);
+    let target_file = settings.target_file.as_deref().unwrap_or(managed::AGENTS_FILE);
+    commands::set_target_file(ManagedFile::recognize(target_file, settings.agent_files.as_deref().unwrap_or_default())?);
+
+    if let (true, Some(command)) = (json, &args.command) {
+        if !command.supports_json() {
+            let name = matches.subcommand_name().unwrap_or_default();
+            return Err(AgStashError::Usage(format!(
+                "'{}' has no JSON output; --output json works with {}",
+                name,
+                JSON_COMMANDS.join(", ")
+            ))
+            .into());
+        }
+    }
+
+    // A ~/.agstash left from before the XDG base directories moves only once a command is allowed to write; migrate
+    // moves it itself, and uninstall removes it where it is
+    if let Some(command) = &args.command {
+        if command.is_mutating() && !matches!(command, Commands::Migrate | Commands::Uninstall { .. }) {
+            commands::move_legacy_store()?;
+        }
+    }
+
+    // A store written by a newer agstash may use a layout this one would damage
+    if !matches!(args.command, None | Some(Commands::Migrate) | Some(Commands::Uninstall { .. })) {
+        store::check_format()?;
+    }
+
+    // Back up the whole store before a command that could lose it, when configured
+    if let (true, Some(command)) = (settings.auto_backup.unwrap_or(false), &args.command) {
+        if command.is_destructive() {
+            commands::handle_backup(
+                settings.backup_dir.as_deref(),
+                settings.keep_backups.unwrap_or(commands::DEFAULT_KEEP_BACKUPS),
+            )?;
+        }
+    }
     
     match &args.command {
-        Some(Commands::Init { force }) => {
+        Some(Commands::Init { force, .. }) => {
             commands::handle_init(*force)?;
+            if settings.gitignore.unwrap_or(false) {
+                commands::ignore_agent_files(false, settings.exclude_local.unwrap_or(false))?;
+            }
+        }
+        Some(Commands::Clean { .. }) => {
+            commands::handle_clean(settings.auto_stash.unwrap_or(true), settings.trash.unwrap_or(false))?;
+        }
+        Some(Commands::Stash { message, patch, bundle, .. }) => {
+            if *bundle {
+                commands::handle_stash_bundle(message.as_deref())?;
+            } else if *patch {
+                commands::handle_stash_patch(message.as_deref())?;
+            } else if settings.recursive.unwrap_or(false) {
+                commands::handle_stash_recursive(message.as_deref())?;
+            } else {
+                commands::handle_stash(message.as_deref())?;
+            }
+        }
+        Some(Commands::Apply { force, backup, append, version, fallback_global, bundle, link, .. }) => {
+            let options = commands::ApplyOptions {
+                force: *force,
+                backup: *backup,
+                append: *append,
+                version: version.clone(),
+                fallback_global: *fallback_global,
+            };
+            if *link {
+                commands::handle_apply_link(*force)?;
+            } else if *bundle {
+                commands::handle_apply_bundle(*force)?;
+            } else if settings.recursive.unwrap_or(false) && options.version.is_none() && !options.fallback_global {
+                commands::handle_apply_recursive(&options)?;
+            } else {
+                commands::handle_apply(&options)?;
+            }
+            if settings.gitignore.unwrap_or(false) {
+                commands::ignore_agent_files(*bundle, settings.exclude_local.unwrap_or(false))?;
+            }
+        }
+        Some(Commands::Pop { force }) => {
+            commands::handle_pop(*force)?;
+        }
+        Some(Commands::Drop { force }) => {
+            commands::handle_drop(*force)?;
+        }
+        Some(Commands::Rename { old, new }) => {
+            commands::handle_rename(old, new)?;
+        }
+        Some(Commands::Copy { src, dst, force }) => {
+            commands::handle_copy(src, dst, *force)?;
+        }
+        Some(Commands::Convert { from, to, stdout, force }) => {
+            commands::handle_convert(from, to, *stdout, *force)?;
         }
-        Some(Commands::Clean) => {
-            commands::handle_clean()?;
+        Some(Commands::Merge { first, second, into, force }) => {
+            commands::handle_merge(first, second, into.as_deref(), *force)?;
         }
-        Some(Commands::Stash) => {
-            commands::handle_stash()?;
+        Some(Commands::Edit { project }) => {
+            commands::handle_edit(project.as_deref())?;
         }
-        Some(Commands::Apply { force }) => {
-            commands::handle_apply(*force)?;
+        Some(Commands::Annotate { project, warning, clear }) => {
+            commands::handle_annotate(project.as_deref(), warning.as_deref(), *clear)?;
         }
-        Some(Commands::Uninstall) => {
-            commands::handle_uninstall()?;
+        Some(Commands::Alias { name, remove }) => {
+            commands::handle_alias(name.as_deref(), *remove)?;
+        }
+        Some(Commands::Undo { force }) => {
+            commands::handle_undo(*force)?;
+        }
+        Some(Commands::Diff) => {
+            // Exit 1 when the files differ so scripts can react to drift, and 2 on an error, like diff(1)
+            match commands::handle_diff() {
+                Ok(false) => {}
+                Ok(true) => std::process::exit(1),
+                Err(error) => {
+                    report_error(error.as_ref(), args.output == "json");
+                    std::process::exit(2);
+                }
+            }
+        }
+        Some(Commands::List) => {
+            commands::handle_list()?;
+        }
+        Some(Commands::Projects) => {
+            commands::handle_projects()?;
+        }
+        Some(Commands::Show { project, version, ast }) => {
+            commands::handle_show(project.as_deref(), version.as_deref(), *ast)?;
+        }
+        Some(Commands::History { project }) => {
+            commands::handle_history(project.as_deref())?;
+        }
+        Some(Commands::Log { all }) => {
+            commands::handle_log(*all)?;
+        }
+        Some(Commands::Grep { pattern, regex, ignore_case }) => {
+            // Exit non-zero when nothing matched, like grep
+            if !commands::handle_grep(pattern, *regex, *ignore_case)? {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Status) => {
+            commands::handle_status()?;
+        }
+        Some(Commands::PromptStatus) => {
+            commands::handle_prompt_status()?;
+        }
+        Some(Commands::Mirror { remove, force }) => {
+            commands::handle_mirror(*remove, *force)?;
+        }
+        Some(Commands::Sync { check, force }) => {
+            // Exit non-zero when a mirror had diverged so CI can catch edits made to the wrong file
+            if commands::handle_sync(*check, *force)? {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Hook { shell }) => {
+            commands::handle_hook(shell)?;
+        }
+        Some(Commands::HookApply { from }) => {
+            // An empty --from is a shell that has not changed directory yet
+            commands::handle_hook_apply(from.as_deref().filter(|dir| !dir.as_os_str().is_empty()))?;
+        }
+        Some(Commands::InstallHooks { uninstall, auto_stash }) => {
+            commands::handle_install_hooks(*uninstall, *auto_stash)?;
+        }
+        Some(Commands::GitHook { hook, auto_stash }) => {
+            commands::handle_git_hook(hook, *auto_stash)?;
+        }
+        Some(Commands::Daemon { interval, no_auto_stash }) => {
+            commands::handle_daemon(*interval, !*no_auto_stash)?;
+        }
+        Some(Commands::Check) => {
+            let code = commands::handle_check()?;
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
+        Some(Commands::Audit { all, store }) => {
+            if commands::handle_audit(*all, args.output == "json", store.as_deref())? {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Doctor { .. }) => {
+            // Handled before the configuration is loaded
+        }
+        Some(Commands::Resolve { keep_stash, keep_copy, keep_both }) => {
+            let resolution = match (keep_stash, keep_copy, keep_both) {
+                (true, _, _) => commands::ConflictResolution::KeepStash,
+                (_, true, _) => commands::ConflictResolution::KeepCopy,
+                (_, _, true) => commands::ConflictResolution::KeepBoth,
+                _ => commands::ConflictResolution::Ask,
+            };
+            commands::handle_resolve(resolution, settings.trash.unwrap_or(false))?;
+        }
+        Some(Commands::Repair) => {
+            commands::handle_repair()?;
+        }
+        Some(Commands::Migrate) => {
+            commands::handle_migrate()?;
+        }
+        Some(Commands::Verify) => {
+            // Exit non-zero when a file no longer matches its checksum
+            if commands::handle_verify()? {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Lint { project }) => {
+            // Exit non-zero when contradictions were found, like audit
+            if commands::handle_lint(project.as_deref())? {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Stats) => {
+            commands::handle_stats()?;
+        }
+        Some(Commands::Gc { days, keep, dry_run }) => {
+            let retention = history::Retention {
+                keep_last: keep.or(settings.keep_last),
+                keep_days: days.or(settings.keep_days).or(Some(commands::DEFAULT_RETENTION_DAYS)),
+            };
+            commands::handle_gc(&retention, *dry_run)?;
+        }
+        Some(Commands::ArchiveProject { project }) => {
+            commands::handle_archive_project(project)?;
+        }
+        Some(Commands::Export { output, format, metadata, history }) => {
+            commands::handle_export(output, format, *metadata, *history)?;
+        }
+        Some(Commands::Import { path, overwrite, skip, rename }) => {
+            let conflict = match (overwrite, skip, rename) {
+                (true, _, _) => commands::ImportConflict::Overwrite,
+                (_, true, _) => commands::ImportConflict::Skip,
+                (_, _, true) => commands::ImportConflict::Rename,
+                _ => commands::ImportConflict::Refuse,
+            };
+            commands::handle_import(path, conflict)?;
+        }
+        Some(Commands::Backup) => {
+            commands::handle_backup(
+                settings.backup_dir.as_deref(),
+                settings.keep_backups.unwrap_or(commands::DEFAULT_KEEP_BACKUPS),
+            )?;
+        }
+        Some(Commands::Restore { archive, merge, force }) => {
+            commands::handle_restore(
+                archive,
+                *merge,
+                *force,
+                settings.backup_dir.as_deref(),
+                settings.keep_backups.unwrap_or(commands::DEFAULT_KEEP_BACKUPS),
+            )?;
+        }
+        Some(Commands::RestoreProject { project, force }) => {
+            commands::handle_restore_project(project, *force)?;
+        }
+        Some(Commands::Tidy { force }) => {
+            commands::handle_tidy(*force)?;
+        }
+        Some(Commands::Journal { action }) => match action {
+            JournalAction::Replay { until, into } => {
+                commands::handle_replay(until, into.as_deref())?;
+            }
+        },
+        Some(Commands::Run { .. }) => {
+            // The chain is run by main, one command at a time
+        }
+        Some(Commands::Config { action }) => match action {
+            ConfigAction::List { origin } => {
+                commands::handle_config_list(&layers, *origin)?;
+            }
+            ConfigAction::Get { key, origin } => {
+                commands::handle_config_get(&layers, key, *origin)?;
+            }
+            ConfigAction::Set { key, value } => {
+                commands::handle_config_set(key, value)?;
+            }
+        },
+        Some(Commands::Uninstall { .. }) => {
+            commands::handle_uninstall(settings.trash.unwrap_or(false), settings.backup_dir.as_deref())?;
         }
         None => {
             // Print usage when no command is provided
@@ -73,8 +987,83 @@ Available Commands:
   clean       Remove the AGENTS.md file from the current directory
   stash       Stash the AGENTS.md file to a global location for later retrieval
   apply       Apply a previously stashed AGENTS.md file to the current directory
+  pop         Apply the stashed AGENTS.md and remove it from the global location
+  drop        Delete the stashed AGENTS.md for the current project
+  rename      Rename a stash after its project directory has been renamed
+  copy        Duplicate a project's stash so another project can start from it
+  convert     Rewrite one tool's instruction file in another tool's format, e.g. AGENTS.md as .cursorrules
+  merge       Combine two projects' stashes, merging sections with the same heading
+  edit        Open a stashed AGENTS.md in $VISUAL or $EDITOR
+  annotate    Add a warning to a stash that apply shows before writing it
+  alias       Key the current project's stashes by a friendly name
+  undo        Restore the file removed or overwritten by the last clean, apply or drop
+  diff        Show a diff between the stashed AGENTS.md and the one in the project root
+  list        List every stashed project with its stash message
+  projects    List every project stashed on this machine and whether its path still exists
+  show        Print a stash and its metadata
+  history     List the recorded versions of a project's stash
+  log         Show the history of stash operations
+  grep        Search every stash for a pattern
+  status      Report whether AGENTS.md and its stash exist and whether they are in sync
+  prompt-status  Print a short uncolored token for a shell prompt, e.g. agents✓ or agents~ for drift
+  mirror      Symlink CLAUDE.md, .cursorrules and the other configured mirrors to AGENTS.md
+  sync        Regenerate CLAUDE.md, .cursorrules and the other configured mirrors from AGENTS.md, reporting any that diverged
+  hook        Print a shell snippet that offers to apply a project's stash on entering it
+  install-hooks  Install git hooks that warn about AGENTS.md drift or stash it on commit
+  daemon      Watch every registered project, stashing AGENTS.md edits and reporting drift
+  check       Check for CI that AGENTS.md exists, is valid and matches its stash, with an exit code per failure
+  audit       Report validation and drift for stashed projects without modifying anything
+  doctor      Check the installation for problems and suggest how to fix each one
+  repair      Fix what doctor finds: rebuild missing metadata and history records, quarantine corrupt files
+  resolve     Settle the conflict copies a file sync client left next to stashes, asking which copy to keep
+  migrate     Upgrade the store to the on-disk layout this version of agstash uses
+  verify      Re-hash every stored file and report any that changed or were truncated outside agstash
+  lint        Flag rules that contradict each other in a project's stash or between it and its nested stashes
+  stats       Report how many projects, stashes and versions the store holds and the space they use
+  gc          Prune old history versions, orphaned metadata and empty directories from the store
+  archive-project  Bundle a retired project's stashes, metadata and backups into one archive file
+  restore-project  Move an archived project back into the active store
+  export      Pack every stash into one portable .tar.gz archive, e.g. export -o stashes.tar.gz
+  backup      Write a timestamped archive of the whole store to the backup directory, keeping the newest few
+  restore     Put the store back as it was in a backup archive, previewing every change first
+  import      Bring stashes from an export archive or a directory of markdown files into the store
+  tidy        Import stray AGENTS.md copies such as AGENTS.md.bak as named stashes and remove them
+  journal     Work with the journal of stash operations, e.g. journal replay --until 2026-10-03
+  run         Run several commands in turn on the same project, e.g. run "stash -m wip; apply -f"
+  config      Inspect and change agstash configuration
   uninstall   Remove the global .agstash directory and all stashed files
   help        Show this help message
 "#;
     println!("{}", usage);
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use clap::{CommandFactory, FromArgMatches};
+
+    use super::{Args, Commands};
+
+    // parse reads a command line the way main does
+    fn parse(argv: &[&str]) -> Args {
+        let matches = Args::command().try_get_matches_from(argv).unwrap();
+        Args::from_arg_matches(&matches).unwrap()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        Args::command().debug_assert();
+
+        // export's archive and the global output format are separate arguments
+        let args = parse(&["agstash", "export", "-o", "stashes.tar.gz"]);
+        assert_eq!(args.output, "text");
+        assert!(matches!(&args.command, Some(Commands::Export { output, .. }) if output == Path::new("stashes.tar.gz")));
+
+        let args = parse(&["agstash", "--output", "json", "export", "--out", "-", "--format", "json"]);
+        assert_eq!(args.output, "json");
+        assert!(matches!(&args.command, Some(Commands::Export { output, .. }) if output == Path::new("-")));
+
+        let args = parse(&["agstash", "list", "--output", "json"]);
+        assert_eq!(args.output, "json");
+    }
+}
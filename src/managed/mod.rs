@@ -0,0 +1,221 @@
+use std::fs;
+use std::path::{Component, Path};
+
+use crate::error::AgStashError;
+use crate::utils;
+
+// The file init, clean, stash and apply work on when no other is chosen
+pub const AGENTS_FILE: &str = "AGENTS.md";
+
+// GitHub Copilot's repository-wide instructions, which live in a directory a fresh checkout may not have
+pub const COPILOT_FILE: &str = ".github/copilot-instructions.md";
+
+// Agent instruction files agstash knows; the agent_files setting adds others without a new release
+pub const KNOWN_FILES: &[&str] = &[
+    AGENTS_FILE,
+    "CLAUDE.md",
+    "GEMINI.md",
+    ".cursorrules",
+    COPILOT_FILE,
+    ".cursor/rules",
+];
+
+// Directories of rule files managed as one unit, such as Cursor's newer layout of one .mdc file per rule
+pub const RULES_DIRS: &[&str] = &[".cursor/rules"];
+
+// Extension of the rule files in a rules directory
+const RULE_EXTENSION: &str = "mdc";
+
+// ManagedFile is an agent instruction file agstash manages, named by its path from the project root, e.g. AGENTS.md,
+// CLAUDE.md or .cursorrules, or a rules directory such as .cursor/rules; each one a project has is stashed under its
+// own key
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManagedFile {
+    pub name: String,
+}
+
+impl ManagedFile {
+    // new checks that name is a path inside the project
+    pub fn new(name: &str) -> Result<ManagedFile, Box<dyn std::error::Error>> {
+        let name = name.trim_end_matches('/');
+        let path = Path::new(name);
+        if name.is_empty() || !path.components().all(|component| matches!(component, Component::Normal(_))) {
+            return Err(AgStashError::Usage(format!("Invalid file '{}': use a path inside the project, e.g. CLAUDE.md", name)).into());
+        }
+        Ok(ManagedFile { name: name.to_string() })
+    }
+
+    // recognize returns the managed file for name if it is one agstash knows or one of the custom agent files
+    pub fn recognize(name: &str, custom: &[String]) -> Result<ManagedFile, Box<dyn std::error::Error>> {
+        let file = ManagedFile::new(name)?;
+        let custom_match = custom.iter().any(|custom_name| custom_name.trim_end_matches('/') == file.name);
+        if !KNOWN_FILES.contains(&file.name.as_str()) && !custom_match {
+            return Err(AgStashError::Usage(format!(
+                "Unrecognized agent file '{}': use one of {}, or add it to agent_files in the config",
+                file.name,
+                KNOWN_FILES.join(", ")
+            ))
+            .into());
+        }
+        Ok(file)
+    }
+
+    // for_stash_key returns the file a stash holds: the one its slot is named after, e.g. CLAUDE.md for "api+CLAUDE.md"
+    // and its "api+CLAUDE.md+pre-clean" snapshot, and AGENTS.md for the project's own stash and plain slots such as
+    // pre-clean. File slots are the ones with a dot, which no slot agstash names itself has
+    pub fn for_stash_key(stash_key: &str) -> ManagedFile {
+        utils::split_stash_key(stash_key)
+            .slots
+            .into_iter()
+            .find(|slot| slot.contains('.'))
+            .and_then(|slot| ManagedFile::new(&slot.replace("--", "/")).ok())
+            .unwrap_or_else(ManagedFile::agents)
+    }
+
+    // agents returns AGENTS.md, the file managed by default
+    pub fn agents() -> ManagedFile {
+        ManagedFile {
+            name: AGENTS_FILE.to_string(),
+        }
+    }
+
+    // is_agents reports whether this is AGENTS.md, whose stash is the project's own
+    pub fn is_agents(&self) -> bool {
+        self.name == AGENTS_FILE
+    }
+
+    // is_rules_dir reports whether this is a directory of rule files, stashed and applied as a whole
+    pub fn is_rules_dir(&self) -> bool {
+        RULES_DIRS.contains(&self.name.as_str())
+    }
+
+    // rule_files lists the rule files under this rules directory in root as paths relative to root, sorted
+    pub fn rule_files(&self, root: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut files = Vec::new();
+        let mut pending = vec![root.join(&self.name)];
+        while let Some(dir) = pending.pop() {
+            if !dir.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    pending.push(path);
+                } else if path.extension().is_some_and(|extension| extension == RULE_EXTENSION) {
+                    if let Ok(relative) = path.strip_prefix(root) {
+                        let components: Vec<String> = relative
+                            .components()
+                            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+                            .collect();
+                        files.push(components.join("/"));
+                    }
+                }
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    // stash_key returns the key this file is stashed under for a project: the project's key for AGENTS.md, and a slot
+    // named after the file for the rest, e.g. "api+CLAUDE.md" or "api+.github--copilot-instructions.md"
+    pub fn stash_key(&self, project_name: &str) -> String {
+        if self.is_agents() {
+            return project_name.to_string();
+        }
+        utils::slot_stash_key(project_name, &self.name.replace('/', "--"))
+    }
+
+    // header returns the heading a new file starts with, e.g. "# AGENTS" or "# CLAUDE.md"
+    pub fn header(&self) -> String {
+        if self.is_agents() {
+            return "# AGENTS".to_string();
+        }
+        format!("# {}", self.name)
+    }
+
+    // is_valid checks content before it is stashed or applied: nothing of MAX_STASH_SIZE or more is, AGENTS.md needs
+    // its '# AGENTS' header, Copilot's instructions are plain markdown without the front matter only path-specific
+    // instruction files take, and other files only need to be non-empty since their tools require no header
+    pub fn is_valid(&self, content: &str) -> bool {
+        if content.len() >= utils::MAX_STASH_SIZE {
+            return false;
+        }
+        if self.is_agents() {
+            return utils::is_valid_agents(content);
+        }
+        if self.name == COPILOT_FILE && content.trim_start().starts_with("---") {
+            return false;
+        }
+        !content.trim().is_empty()
+    }
+
+    // requirement describes what is_valid found missing
+    pub fn requirement(&self) -> &'static str {
+        if self.is_agents() {
+            return "missing '# AGENTS' header";
+        }
+        if self.name == COPILOT_FILE {
+            return "it must be non-empty markdown without front matter";
+        }
+        "the file is empty"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+
+    use crate::managed::ManagedFile;
+
+    #[test]
+    fn test_managed_file() {
+        let agents = ManagedFile::agents();
+        assert_eq!(agents.stash_key("api-3f2a91c0"), "api-3f2a91c0");
+        assert!(agents.is_valid("# AGENTS\n"));
+        assert!(!agents.is_valid("# CLAUDE.md\n"));
+
+        let claude = ManagedFile::new("CLAUDE.md").unwrap();
+        assert_eq!(claude.stash_key("api-3f2a91c0"), "api-3f2a91c0+CLAUDE.md");
+        assert_eq!(claude.header(), "# CLAUDE.md");
+        assert!(claude.is_valid("Prefer small commits.\n"));
+        assert!(!claude.is_valid("\n\n"));
+
+        let copilot = ManagedFile::new(".github/copilot-instructions.md").unwrap();
+        assert_eq!(copilot.stash_key("api"), "api+.github--copilot-instructions.md");
+        assert!(copilot.is_valid("Use tabs.\n"));
+        assert!(!copilot.is_valid("---\napplyTo: \"**/*.ts\"\n---\nUse tabs.\n"));
+
+        let cursorrules = ManagedFile::new(".cursorrules").unwrap();
+        assert!(!cursorrules.is_rules_dir());
+        assert_eq!(cursorrules.stash_key("api"), "api+.cursorrules");
+
+        assert_eq!(ManagedFile::for_stash_key("api"), agents);
+        assert_eq!(ManagedFile::for_stash_key("api@packages--web+pre-clean"), agents);
+        assert_eq!(ManagedFile::for_stash_key("api+CLAUDE.md+pre-clean"), claude);
+        assert_eq!(ManagedFile::for_stash_key("api+.github--copilot-instructions.md"), copilot);
+
+        let rules = ManagedFile::new(".cursor/rules/").unwrap();
+        assert!(rules.is_rules_dir());
+        assert_eq!(rules.stash_key("api"), "api+.cursor--rules");
+
+        let root = TempDir::new().unwrap();
+        fs::create_dir_all(root.path().join(".cursor/rules/frontend")).unwrap();
+        fs::write(root.path().join(".cursor/rules/style.mdc"), "Use tabs.\n").unwrap();
+        fs::write(root.path().join(".cursor/rules/frontend/react.mdc"), "Use hooks.\n").unwrap();
+        fs::write(root.path().join(".cursor/rules/notes.txt"), "Not a rule.\n").unwrap();
+        assert_eq!(
+            rules.rule_files(root.path()).unwrap(),
+            vec![".cursor/rules/frontend/react.mdc", ".cursor/rules/style.mdc"]
+        );
+
+        assert!(ManagedFile::recognize("GEMINI.md", &[]).is_ok());
+        assert!(ManagedFile::recognize("CLAUDE.MD", &[]).is_err());
+        assert!(ManagedFile::recognize(".windsurfrules", &[]).is_err());
+        assert!(ManagedFile::recognize(".windsurfrules", &[".windsurfrules".to_string()]).is_ok());
+
+        assert!(ManagedFile::new("../CLAUDE.md").is_err());
+        assert!(ManagedFile::new("/etc/CLAUDE.md").is_err());
+        assert!(ManagedFile::new("").is_err());
+    }
+}
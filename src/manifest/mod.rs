@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::utils;
+
+// ProjectFacts holds the values extracted from a project manifest
+#[derive(Debug, Default, PartialEq)]
+pub struct ProjectFacts {
+    pub package_name: Option<String>,
+    pub test_command: Option<String>,
+    pub toolchain: Option<String>,
+}
+
+impl ProjectFacts {
+    // is_empty reports whether no facts could be extracted
+    pub fn is_empty(&self) -> bool {
+        self.package_name.is_none() && self.test_command.is_none() && self.toolchain.is_none()
+    }
+
+    // to_vars exposes the facts as template variables
+    pub fn to_vars(&self) -> HashMap<String, String> {
+        let mut vars = HashMap::new();
+        if let Some(name) = &self.package_name {
+            vars.insert("package_name".to_string(), name.clone());
+        }
+        if let Some(command) = &self.test_command {
+            vars.insert("test_command".to_string(), command.clone());
+        }
+        if let Some(toolchain) = &self.toolchain {
+            vars.insert("toolchain".to_string(), toolchain.clone());
+        }
+        vars
+    }
+}
+
+// DetectFacts looks for Cargo.toml, package.json or pyproject.toml in dir and extracts the first one found
+pub fn detect_facts(dir: &Path) -> ProjectFacts {
+    let cargo_toml = dir.join("Cargo.toml");
+    if utils::file_exists(&cargo_toml) {
+        let (err, content) = utils::read_file(&cargo_toml);
+        if err.is_none() {
+            return parse_cargo_toml(&content);
+        }
+    }
+
+    let package_json = dir.join("package.json");
+    if utils::file_exists(&package_json) {
+        let (err, content) = utils::read_file(&package_json);
+        if err.is_none() {
+            return parse_package_json(&content);
+        }
+    }
+
+    let pyproject_toml = dir.join("pyproject.toml");
+    if utils::file_exists(&pyproject_toml) {
+        let (err, content) = utils::read_file(&pyproject_toml);
+        if err.is_none() {
+            return parse_pyproject_toml(&content);
+        }
+    }
+
+    ProjectFacts::default()
+}
+
+fn parse_cargo_toml(content: &str) -> ProjectFacts {
+    let value: toml::Value = match content.parse() {
+        Ok(value) => value,
+        Err(_) => return ProjectFacts::default(),
+    };
+
+    let package = value.get("package");
+    let package_name = package
+        .and_then(|p| p.get("name"))
+        .and_then(|n| n.as_str())
+        .map(String::from);
+    let toolchain = package
+        .and_then(|p| p.get("rust-version"))
+        .and_then(|v| v.as_str())
+        .map(|v| format!("Rust {}", v));
+
+    ProjectFacts {
+        package_name,
+        test_command: Some("cargo test".to_string()),
+        toolchain,
+    }
+}
+
+fn parse_package_json(content: &str) -> ProjectFacts {
+    let value: serde_json::Value = match serde_json::from_str(content) {
+        Ok(value) => value,
+        Err(_) => return ProjectFacts::default(),
+    };
+
+    let package_name = value.get("name").and_then(|n| n.as_str()).map(String::from);
+    let test_command = value
+        .get("scripts")
+        .and_then(|s| s.get("test"))
+        .map(|_| "npm test".to_string());
+    let toolchain = value
+        .get("engines")
+        .and_then(|e| e.get("node"))
+        .and_then(|v| v.as_str())
+        .map(|v| format!("Node {}", v));
+
+    ProjectFacts {
+        package_name,
+        test_command,
+        toolchain,
+    }
+}
+
+fn parse_pyproject_toml(content: &str) -> ProjectFacts {
+    let value: toml::Value = match content.parse() {
+        Ok(value) => value,
+        Err(_) => return ProjectFacts::default(),
+    };
+
+    let project = value.get("project");
+    let poetry = value.get("tool").and_then(|t| t.get("poetry"));
+
+    let package_name = project
+        .and_then(|p| p.get("name"))
+        .or_else(|| poetry.and_then(|p| p.get("name")))
+        .and_then(|n| n.as_str())
+        .map(String::from);
+    let test_command = value
+        .get("tool")
+        .and_then(|t| t.get("pytest"))
+        .map(|_| "pytest".to_string());
+    let toolchain = project
+        .and_then(|p| p.get("requires-python"))
+        .and_then(|v| v.as_str())
+        .map(|v| format!("Python {}", v));
+
+    ProjectFacts {
+        package_name,
+        test_command,
+        toolchain,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+
+    use crate::manifest;
+
+    #[test]
+    fn test_detect_facts_cargo() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("Cargo.toml"),
+            "[package]\nname = \"demo\"\nrust-version = \"1.70\"\n",
+        )
+        .unwrap();
+
+        let facts = manifest::detect_facts(temp_dir.path());
+        assert_eq!(facts.package_name.as_deref(), Some("demo"));
+        assert_eq!(facts.test_command.as_deref(), Some("cargo test"));
+        assert_eq!(facts.toolchain.as_deref(), Some("Rust 1.70"));
+    }
+
+    #[test]
+    fn test_detect_facts_package_json() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("package.json"),
+            r#"{"name": "web", "scripts": {"test": "jest"}, "engines": {"node": ">=18"}}"#,
+        )
+        .unwrap();
+
+        let facts = manifest::detect_facts(temp_dir.path());
+        assert_eq!(facts.package_name.as_deref(), Some("web"));
+        assert_eq!(facts.test_command.as_deref(), Some("npm test"));
+        assert_eq!(facts.toolchain.as_deref(), Some("Node >=18"));
+    }
+
+    #[test]
+    fn test_detect_facts_pyproject() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("pyproject.toml"),
+            "[project]\nname = \"tool\"\nrequires-python = \">=3.10\"\n\n[tool.pytest.ini_options]\n",
+        )
+        .unwrap();
+
+        let facts = manifest::detect_facts(temp_dir.path());
+        assert_eq!(facts.package_name.as_deref(), Some("tool"));
+        assert_eq!(facts.test_command.as_deref(), Some("pytest"));
+        assert_eq!(facts.toolchain.as_deref(), Some("Python >=3.10"));
+    }
+
+    #[test]
+    fn test_detect_facts_no_manifest() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(manifest::detect_facts(temp_dir.path()).is_empty());
+    }
+}
@@ -0,0 +1,321 @@
+use std::collections::HashSet;
+
+// Document is an AGENTS.md split into sections, enough structure for merging without a full markdown parser
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Document {
+    pub sections: Vec<Section>,
+}
+
+// Section is a heading with the blocks beneath it up to the next heading; the preamble before any heading has none
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub heading: Option<Heading>,
+    pub blocks: Vec<Block>,
+}
+
+// Heading is an ATX heading such as "## Style"
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heading {
+    pub level: usize,
+    pub text: String,
+}
+
+// Block is a run of lines between blank lines
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    // List holds bullet items, each being its marker line plus any indented continuation lines
+    List(Vec<Vec<String>>),
+    // Text holds any other lines, including whole fenced code blocks
+    Text(Vec<String>),
+}
+
+impl Heading {
+    // parse reads a heading line, returning None for anything else such as "#hashtag"
+    pub fn parse(line: &str) -> Option<Heading> {
+        let level = line.chars().take_while(|c| *c == '#').count();
+        if level == 0 || level > 6 {
+            return None;
+        }
+        let rest = &line[level..];
+        if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+            return None;
+        }
+        Some(Heading {
+            level,
+            text: rest.trim().to_string(),
+        })
+    }
+
+    // key normalizes the heading so "## Style" and "##  style" name the same section
+    pub fn key(&self) -> String {
+        let text = self.text.split_whitespace().collect::<Vec<_>>().join(" ");
+        format!("{} {}", self.level, text.to_lowercase())
+    }
+
+    // render writes the heading back as a markdown line
+    pub fn render(&self) -> String {
+        if self.text.is_empty() {
+            return "#".repeat(self.level);
+        }
+        format!("{} {}", "#".repeat(self.level), self.text)
+    }
+}
+
+impl Section {
+    // has_heading_key checks whether the section's heading normalizes to key; None matches the preamble
+    fn has_heading_key(&self, key: Option<&str>) -> bool {
+        self.heading.as_ref().map(Heading::key).as_deref() == key
+    }
+
+    // items returns the normalized text of every bullet item in the section
+    fn items(&self) -> HashSet<String> {
+        self.blocks
+            .iter()
+            .filter_map(|block| match block {
+                Block::List(items) => Some(items),
+                Block::Text(_) => None,
+            })
+            .flatten()
+            .map(|item| item_text(item))
+            .collect()
+    }
+
+    // merge adds the items and text of other that this section doesn't already have
+    fn merge(&mut self, other: Section) {
+        let mut seen = self.items();
+        for block in other.blocks {
+            match block {
+                Block::List(items) => {
+                    let items: Vec<_> = items.into_iter().filter(|item| seen.insert(item_text(item))).collect();
+                    if items.is_empty() {
+                        continue;
+                    }
+                    let last_list = self.blocks.iter_mut().rev().find_map(|block| match block {
+                        Block::List(existing) => Some(existing),
+                        Block::Text(_) => None,
+                    });
+                    match last_list {
+                        Some(existing) => existing.extend(items),
+                        None => self.blocks.push(Block::List(items)),
+                    }
+                }
+                Block::Text(lines) => {
+                    if !self.blocks.contains(&Block::Text(lines.clone())) {
+                        self.blocks.push(Block::Text(lines));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Document {
+    // parse splits content into sections and blocks
+    pub fn parse(content: &str) -> Document {
+        let mut sections = vec![Section {
+            heading: None,
+            blocks: Vec::new(),
+        }];
+        let mut lines = content.lines().peekable();
+
+        while let Some(line) = lines.next() {
+            let section = sections.last_mut().expect("there is always a section");
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            if let Some(heading) = Heading::parse(line) {
+                sections.push(Section {
+                    heading: Some(heading),
+                    blocks: Vec::new(),
+                });
+                continue;
+            }
+
+            if is_fence(line) {
+                // Keep fenced code intact, blank lines and '#' comments included
+                let mut block = vec![line.to_string()];
+                for inner in lines.by_ref() {
+                    block.push(inner.to_string());
+                    if is_fence(inner) {
+                        break;
+                    }
+                }
+                section.blocks.push(Block::Text(block));
+                continue;
+            }
+
+            if is_bullet(line) {
+                let mut items = vec![vec![line.to_string()]];
+                while let Some(next) = lines.peek() {
+                    if next.trim().is_empty() || Heading::parse(next).is_some() || is_fence(next) {
+                        break;
+                    }
+                    if is_bullet(next) {
+                        items.push(vec![next.to_string()]);
+                    } else if next.starts_with([' ', '\t']) {
+                        items.last_mut().expect("list has an item").push(next.to_string());
+                    } else {
+                        break;
+                    }
+                    lines.next();
+                }
+                section.blocks.push(Block::List(items));
+                continue;
+            }
+
+            let mut block = vec![line.to_string()];
+            while let Some(next) = lines.peek() {
+                if next.trim().is_empty() || Heading::parse(next).is_some() || is_fence(next) || is_bullet(next) {
+                    break;
+                }
+                block.push(next.to_string());
+                lines.next();
+            }
+            section.blocks.push(Block::Text(block));
+        }
+
+        // Drop an empty preamble so documents starting with a heading don't gain a leading blank line
+        if sections[0].blocks.is_empty() {
+            sections.remove(0);
+        }
+        Document { sections }
+    }
+
+    // render writes the document back out with a blank line between every heading and block
+    pub fn render(&self) -> String {
+        let mut chunks = Vec::new();
+        for section in &self.sections {
+            if let Some(heading) = &section.heading {
+                chunks.push(heading.render());
+            }
+            for block in &section.blocks {
+                let lines: Vec<&str> = match block {
+                    Block::List(items) => items.iter().flatten().map(String::as_str).collect(),
+                    Block::Text(lines) => lines.iter().map(String::as_str).collect(),
+                };
+                chunks.push(lines.join("\n"));
+            }
+        }
+        if chunks.is_empty() {
+            return String::new();
+        }
+        format!("{}\n", chunks.join("\n\n"))
+    }
+
+    // merge folds other into this document: sections with the same heading are combined and only unseen content is added
+    pub fn merge(&mut self, other: Document) {
+        for section in other.sections {
+            let key = section.heading.as_ref().map(Heading::key);
+            match self.sections.iter_mut().find(|existing| existing.has_heading_key(key.as_deref())) {
+                Some(existing) => existing.merge(section),
+                None => self.sections.push(section),
+            }
+        }
+    }
+
+    // append adds other below this document, dropping its '# AGENTS' title and any bullet items already present
+    pub fn append(&mut self, other: Document) {
+        let mut seen: HashSet<String> = self.sections.iter().flat_map(Section::items).collect();
+
+        for (index, mut section) in other.sections.into_iter().enumerate() {
+            if index == 0 && section.heading.as_ref().is_some_and(is_title) {
+                section.heading = None;
+            }
+            section.blocks = section
+                .blocks
+                .into_iter()
+                .filter_map(|block| match block {
+                    Block::List(items) => {
+                        let items: Vec<_> = items.into_iter().filter(|item| seen.insert(item_text(item))).collect();
+                        (!items.is_empty()).then_some(Block::List(items))
+                    }
+                    text => Some(text),
+                })
+                .collect();
+            if section.heading.is_some() || !section.blocks.is_empty() {
+                self.sections.push(section);
+            }
+        }
+    }
+}
+
+// is_title checks whether a heading is the top-level '# AGENTS' title every AGENTS.md starts with
+fn is_title(heading: &Heading) -> bool {
+    heading.level == 1 && heading.text.starts_with("AGENTS")
+}
+
+// is_bullet checks whether a line starts a markdown list item
+fn is_bullet(line: &str) -> bool {
+    ["- ", "* ", "+ "].iter().any(|marker| line.starts_with(marker))
+}
+
+// is_fence checks whether a line opens or closes a fenced code block
+fn is_fence(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.starts_with("```") || trimmed.starts_with("~~~")
+}
+
+// ItemText returns a list item without its marker or indentation, so the same rule is recognized regardless of either
+pub fn item_text(item: &[String]) -> String {
+    item.iter()
+        .map(|line| line.trim())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_start_matches(['-', '*', '+'])
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::markdown::{Block, Document, Heading};
+
+    #[test]
+    fn test_parse_and_render() {
+        let content = "# AGENTS\n\nIntro text\nspanning lines\n\n- one\n  continued\n- two\n\n## Build\n\n```sh\n# not a heading\n\ncargo build\n```\n";
+        let document = Document::parse(content);
+
+        assert_eq!(document.sections.len(), 2);
+        assert_eq!(document.sections[0].blocks.len(), 2);
+        assert_eq!(
+            document.sections[0].blocks[1],
+            Block::List(vec![
+                vec!["- one".to_string(), "  continued".to_string()],
+                vec!["- two".to_string()]
+            ])
+        );
+        assert_eq!(document.sections[1].heading.as_ref().unwrap().text, "Build");
+
+        // Well-formed documents survive a round trip unchanged
+        assert_eq!(document.render(), content);
+    }
+
+    #[test]
+    fn test_heading_parse() {
+        assert_eq!(Heading::parse("## Style").unwrap().level, 2);
+        assert!(Heading::parse("#hashtag").is_none());
+        assert!(Heading::parse("####### too deep").is_none());
+        assert_eq!(Heading::parse("## Style").unwrap().key(), Heading::parse("##  style ").unwrap().key());
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut document = Document::parse("# AGENTS\n\n- Run cargo test\n\n## Style\n\n- Use tabs\n");
+        document.merge(Document::parse("# AGENTS\n\n* Run cargo test\n- Keep commits small\n\n## Release\n\n- Tag releases\n"));
+
+        assert_eq!(
+            document.render(),
+            "# AGENTS\n\n- Run cargo test\n- Keep commits small\n\n## Style\n\n- Use tabs\n\n## Release\n\n- Tag releases\n"
+        );
+    }
+
+    #[test]
+    fn test_append() {
+        let mut document = Document::parse("# AGENTS\n\n- Use tabs\n");
+        document.append(Document::parse("# AGENTS\n\n- Use tabs\n- Small commits\n"));
+
+        assert_eq!(document.render(), "# AGENTS\n\n- Use tabs\n\n- Small commits\n");
+    }
+}
@@ -0,0 +1,172 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::git::{self, GitContext};
+use crate::store;
+use crate::utils;
+
+// StashMetadata describes where and why a stash was made; it is kept in a JSON sidecar next to the stash
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StashMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub created_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    // Cautions added with annotate, shown whenever the stash is applied
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    // Where apply --link symlinked the stash; a linked stash keeps its own file rather than sharing an object, so
+    // edits made through the link reach no other stash
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub linked_to: Option<String>,
+    // Branch, commit and dirty state of the repository the stash was made in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<GitContext>,
+}
+
+impl StashMetadata {
+    // new creates metadata for a stash made now, noting the git state of the repository source_path is in
+    pub fn new(message: Option<&str>, source_path: Option<String>) -> Self {
+        StashMetadata {
+            message: message.map(String::from),
+            created_at: utils::format_timestamp(SystemTime::now()),
+            git: source_path.as_deref().and_then(|path| git::context(Path::new(path))),
+            source_path,
+            sha256: None,
+            warnings: Vec::new(),
+            linked_to: None,
+        }
+    }
+
+    // from_file rebuilds metadata for a stash that has none, dating it by the file's modification time
+    pub fn from_file(path: &Path, message: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let modified = fs::metadata(path)?.modified().unwrap_or_else(|_| SystemTime::now());
+        Ok(StashMetadata {
+            message: Some(message.to_string()),
+            created_at: utils::format_timestamp(modified),
+            source_path: None,
+            sha256: None,
+            warnings: Vec::new(),
+            linked_to: None,
+            git: None,
+        })
+    }
+}
+
+// GetMetadataPath returns the sidecar path holding a project's stash metadata
+pub fn get_metadata_path(project_name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let stash_path = utils::get_stash_path(project_name)?;
+    Ok(stash_path.with_extension("json"))
+}
+
+// LoadMetadata reads a project's stash metadata, returning None when the stash has none
+pub fn load_metadata(project_name: &str) -> Result<Option<StashMetadata>, Box<dyn std::error::Error>> {
+    let path = get_metadata_path(project_name)?;
+    if !utils::file_exists(&path) {
+        return Ok(None);
+    }
+
+    let (err, content) = utils::read_file(&path);
+    if let Some(error) = err {
+        return Err(error);
+    }
+    let metadata = serde_json::from_str(&content)
+        .map_err(|e| format!("Invalid stash metadata in {}: {}", path.display(), e))?;
+    Ok(Some(metadata))
+}
+
+// SaveMetadata writes a project's stash metadata, stamped with the checksum of the stash as it is now
+pub fn save_metadata(project_name: &str, metadata: &StashMetadata) -> Result<(), Box<dyn std::error::Error>> {
+    store::create_store()?;
+    let path = get_metadata_path(project_name)?;
+    let mut metadata = metadata.clone();
+    if let Ok(stash_content) = fs::read(utils::get_stash_path(project_name)?) {
+        metadata.sha256 = Some(store::content_hash(&stash_content));
+    }
+    let content = serde_json::to_string_pretty(&metadata)?;
+    if let Some(error) = utils::write_file(&path, &content) {
+        return Err(error);
+    }
+    Ok(())
+}
+
+// RefreshChecksum re-stamps a stash's metadata after its content was changed in place
+pub fn refresh_checksum(project_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(metadata) = load_metadata(project_name)? {
+        save_metadata(project_name, &metadata)?;
+    }
+    Ok(())
+}
+
+// RemoveMetadata deletes a project's stash metadata if present
+pub fn remove_metadata(project_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let path = get_metadata_path(project_name)?;
+    if utils::file_exists(&path) {
+        utils::remove_file(&path)?;
+    }
+    Ok(())
+}
+
+// RenameMetadata moves a stash's metadata along with a renamed stash
+pub fn rename_metadata(old_name: &str, new_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let old_path = get_metadata_path(old_name)?;
+    if utils::file_exists(&old_path) {
+        fs::rename(&old_path, get_metadata_path(new_name)?)?;
+    } else {
+        remove_metadata(new_name)?;
+    }
+    Ok(())
+}
+
+// CopyMetadata duplicates a stash's metadata for a copied stash
+pub fn copy_metadata(src_name: &str, dst_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let src_path = get_metadata_path(src_name)?;
+    if utils::file_exists(&src_path) {
+        if let Some(error) = utils::copy_file(&src_path, get_metadata_path(dst_name)?) {
+            return Err(error);
+        }
+    } else {
+        remove_metadata(dst_name)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::metadata;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_metadata_lifecycle() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        assert!(metadata::load_metadata("demo").unwrap().is_none());
+
+        let stash_metadata = metadata::StashMetadata::new(Some("sprint rules"), Some("/src/demo".to_string()));
+        metadata::save_metadata("demo", &stash_metadata).unwrap();
+        assert_eq!(metadata::load_metadata("demo").unwrap(), Some(stash_metadata.clone()));
+
+        metadata::copy_metadata("demo", "copy").unwrap();
+        assert_eq!(metadata::load_metadata("copy").unwrap(), Some(stash_metadata.clone()));
+
+        metadata::rename_metadata("demo", "renamed").unwrap();
+        assert!(metadata::load_metadata("demo").unwrap().is_none());
+        assert_eq!(metadata::load_metadata("renamed").unwrap(), Some(stash_metadata));
+
+        metadata::remove_metadata("renamed").unwrap();
+        assert!(metadata::load_metadata("renamed").unwrap().is_none());
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::AgStashError;
+use crate::git;
+use crate::utils;
+
+// The project registry records every project root on this machine that has been stashed, and the alias its stashes are
+// keyed by when one was chosen, so the same project can share one key on every machine whatever its directory is
+// called or wherever it lives
+
+// Project is what the registry knows about one project root
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Project {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    #[serde(default)]
+    pub added_at: String,
+}
+
+// GetRegistryPath returns the file holding the project registry
+pub fn get_registry_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(utils::get_agstash_dir()?.join("projects.json"))
+}
+
+// LoadProjects reads every registered project keyed by its root, returning an empty registry when there is none yet
+pub fn load_projects() -> Result<BTreeMap<String, Project>, Box<dyn std::error::Error>> {
+    let path = get_registry_path()?;
+    if !utils::file_exists(&path) {
+        return Ok(BTreeMap::new());
+    }
+
+    let (err, content) = utils::read_file(&path);
+    if let Some(error) = err {
+        return Err(error);
+    }
+    let projects = serde_json::from_str(&content)
+        .map_err(|e| format!("Invalid project registry in {}: {}", path.display(), e))?;
+    Ok(projects)
+}
+
+// save_projects writes the registry
+fn save_projects(projects: &BTreeMap<String, Project>) -> Result<(), Box<dyn std::error::Error>> {
+    fs::create_dir_all(utils::get_agstash_dir()?)?;
+    let content = serde_json::to_string_pretty(projects)?;
+    if let Some(error) = utils::write_file(get_registry_path()?, &content) {
+        return Err(error);
+    }
+    Ok(())
+}
+
+// ProjectIdentity returns the directory a project is known by: its root, or the main working tree when the root is a
+// linked git worktree, so all worktrees of a repository share one stash
+pub fn project_identity(root: &Path) -> PathBuf {
+    git::main_worktree(root).unwrap_or_else(|| root.canonicalize().unwrap_or_else(|_| root.to_path_buf()))
+}
+
+// root_entry is how a project root is written in the registry
+fn root_entry(root: &Path) -> String {
+    project_identity(root).to_string_lossy().into_owned()
+}
+
+// RegisterProject records a project root the first time it is stashed
+pub fn register_project(root: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut projects = load_projects()?;
+    let entry = root_entry(root);
+    if projects.contains_key(&entry) {
+        return Ok(());
+    }
+    projects.insert(
+        entry,
+        Project {
+            alias: None,
+            added_at: utils::format_timestamp(SystemTime::now()),
+        },
+    );
+    save_projects(&projects)
+}
+
+// IsRegistered reports whether a project root has been stashed on this machine
+pub fn is_registered(root: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+    Ok(load_projects()?.contains_key(&root_entry(root)))
+}
+
+// ProjectKey returns the key a project root's stashes are stored under: its alias, or its directory name and path hash
+pub fn project_key(root: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    match alias_for(root)? {
+        Some(alias) => Ok(alias),
+        None => utils::project_stash_key(&project_identity(root)),
+    }
+}
+
+// FindProject returns the registered project root a name refers to: its alias, its stash key, or a directory name only
+// one registered project has
+pub fn find_project(name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let mut by_dir_name = Vec::new();
+    for (root, project) in load_projects()? {
+        let root = PathBuf::from(root);
+        let stash_key = match project.alias {
+            Some(alias) => alias,
+            None => utils::project_stash_key(&root)?,
+        };
+        if stash_key == name {
+            return Ok(root);
+        }
+        if root.file_name().is_some_and(|dir_name| dir_name == name) {
+            by_dir_name.push(root);
+        }
+    }
+    match by_dir_name.len() {
+        0 => Err(AgStashError::UnknownProject(format!("No known project named '{}'; run `agstash projects` to list them", name)).into()),
+        1 => Ok(by_dir_name.remove(0)),
+        _ => {
+            let roots: Vec<String> = by_dir_name.iter().map(|root| root.display().to_string()).collect();
+            Err(AgStashError::AmbiguousProject(format!("'{}' could be any of {}; use its alias or stash key", name, roots.join(", "))).into())
+        }
+    }
+}
+
+// AliasFor returns the alias registered for a project root, if any
+pub fn alias_for(root: &Path) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    Ok(load_projects()?.remove(&root_entry(root)).and_then(|project| project.alias))
+}
+
+// RootFor returns the project root on this machine registered under an alias, if any
+pub fn root_for(alias: &str) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let projects = load_projects()?;
+    Ok(projects
+        .into_iter()
+        .find(|(_, project)| project.alias.as_deref() == Some(alias))
+        .map(|(root, _)| PathBuf::from(root)))
+}
+
+// SetAlias registers alias for a project root, replacing any alias it had
+pub fn set_alias(root: &Path, alias: &str) -> Result<(), Box<dyn std::error::Error>> {
+    register_project(root)?;
+    let mut projects = load_projects()?;
+    if let Some(project) = projects.get_mut(&root_entry(root)) {
+        project.alias = Some(alias.to_string());
+    }
+    save_projects(&projects)
+}
+
+// RemoveAlias drops a project root's alias, keeping the project registered, and returns the alias it had
+pub fn remove_alias(root: &Path) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let mut projects = load_projects()?;
+    let removed = projects.get_mut(&root_entry(root)).and_then(|project| project.alias.take());
+    if removed.is_some() {
+        save_projects(&projects)?;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::registry;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_find_project() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        let work_api = temp_dir.path().join("work").join("api");
+        let home_api = temp_dir.path().join("home").join("api");
+        let web = temp_dir.path().join("web");
+        for root in [&work_api, &home_api, &web] {
+            fs::create_dir_all(root).unwrap();
+            registry::register_project(root).unwrap();
+        }
+        registry::set_alias(&home_api, "side-api").unwrap();
+
+        assert_eq!(registry::find_project("web").unwrap(), web.canonicalize().unwrap());
+        assert_eq!(registry::find_project("side-api").unwrap(), home_api.canonicalize().unwrap());
+        let work_key = utils::project_stash_key(&work_api).unwrap();
+        assert_eq!(registry::find_project(&work_key).unwrap(), work_api.canonicalize().unwrap());
+        assert!(registry::find_project("missing").is_err());
+
+        // Two checkouts called "api" need a key to tell them apart
+        registry::remove_alias(&home_api).unwrap();
+        assert!(registry::find_project("api").is_err());
+    }
+}
@@ -0,0 +1,396 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::AgStashError;
+use crate::utils;
+
+// Layout version this agstash reads and writes:
+// 1 = stashes only, 2 = metadata sidecars for every stash, 3 = content objects and checksums, 4 = project keys with a
+// path hash, e.g. "api-3f2a91c0" instead of "api"
+pub const STORE_FORMAT: u32 = 4;
+
+// MigrateLegacyDir moves ~/.agstash to the XDG base directories on Linux: the store to $XDG_DATA_HOME/agstash and
+// config.toml to $XDG_CONFIG_HOME/agstash. Returns where the store went, or None when there was nothing to move. A
+// store that can't be moved, e.g. to another file system, is left in ~/.agstash, which keeps working
+pub fn migrate_legacy_dir() -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let relocated = std::env::var_os(utils::HOME_ENV).is_some_and(|dir| !dir.is_empty());
+    let legacy_dir = utils::get_legacy_dir()?;
+    if !cfg!(target_os = "linux") || relocated || !legacy_dir.is_dir() {
+        return Ok(None);
+    }
+    let data_dir = utils::get_platform_data_dir()?;
+    if data_dir.exists() {
+        utils::log_warn(&format!(
+            "Both {} and {} exist; agstash keeps using {} until one is removed",
+            legacy_dir.display(),
+            data_dir.display(),
+            legacy_dir.display()
+        ));
+        return Ok(None);
+    }
+    if let Some(parent) = data_dir.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if let Err(error) = fs::rename(&legacy_dir, &data_dir) {
+        utils::log_warn(&format!("Could not move {} to {}: {}", legacy_dir.display(), data_dir.display(), error));
+        return Ok(None);
+    }
+
+    let config_path = data_dir.join("config.toml");
+    let xdg_config_path = utils::get_platform_config_dir()?.join("config.toml");
+    if config_path.exists() {
+        if xdg_config_path.exists() {
+            utils::log_warn(&format!(
+                "{} already exists, so the config moved with the store to {} is not read",
+                xdg_config_path.display(),
+                config_path.display()
+            ));
+        } else {
+            fs::create_dir_all(utils::get_platform_config_dir()?)?;
+            fs::rename(&config_path, &xdg_config_path)?;
+        }
+    }
+    Ok(Some(data_dir))
+}
+
+// GetFormatPath returns the file recording which layout version the store uses
+pub fn get_format_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(utils::get_agstash_dir()?.join("format"))
+}
+
+// ReadFormat returns the store's layout version; a store that predates versioning is format 1, and None means there
+// is no store yet
+pub fn read_format() -> Result<Option<u32>, Box<dyn std::error::Error>> {
+    let format_path = get_format_path()?;
+    if !utils::file_exists(&format_path) {
+        return Ok(utils::get_stashes_dir()?.is_dir().then_some(1));
+    }
+    let (err, content) = utils::read_file(&format_path);
+    if let Some(error) = err {
+        return Err(error);
+    }
+    let format = content
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid store format '{}' in {}", content.trim(), format_path.display()))?;
+    Ok(Some(format))
+}
+
+// WriteFormat records the store's layout version
+pub fn write_format(format: u32) -> Result<(), Box<dyn std::error::Error>> {
+    fs::create_dir_all(utils::get_agstash_dir()?)?;
+    if let Some(error) = utils::write_file(get_format_path()?, &format!("{}\n", format)) {
+        return Err(error);
+    }
+    Ok(())
+}
+
+// CheckFormat refuses a store written by a newer agstash, whose layout this one could damage, and points an older
+// store at migrate
+pub fn check_format() -> Result<(), Box<dyn std::error::Error>> {
+    match read_format()? {
+        Some(format) if format > STORE_FORMAT => Err(AgStashError::UnsupportedStoreFormat(format!(
+            "The store at {} uses format {}, but this agstash only understands up to format {}; upgrade agstash to use it",
+            utils::get_agstash_dir()?.display(),
+            format,
+            STORE_FORMAT
+        ))
+        .into()),
+        Some(format) if format < STORE_FORMAT => {
+            utils::log_warn(&format!(
+                "The store uses format {} (current is {}); run `agstash migrate` to upgrade it",
+                format, STORE_FORMAT
+            ));
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+// GetObjectsDir returns the directory holding stash content by hash
+pub fn get_objects_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(utils::get_agstash_dir()?.join("objects"))
+}
+
+// GetObjectPath returns where the content with the given hash is kept
+pub fn get_object_path(hash: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(get_objects_dir()?.join(hash))
+}
+
+// ContentHash returns the hex SHA-256 of content
+pub fn content_hash(content: &[u8]) -> String {
+    Sha256::digest(content).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// ShareStash makes a stash file point at the object for its content, so every stash with the same rules is stored once.
+// Writes through WriteStash and CopyToStash unlink the stash first, leaving the other stashes untouched.
+// A stash applied with --link keeps its own file, since editors write through the link into it.
+pub fn share_stash(stash_path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let hash = content_hash(&fs::read(stash_path)?);
+    if is_linked(stash_path) {
+        return Ok(hash);
+    }
+    link_object(stash_path, &get_object_path(&hash)?)?;
+    Ok(hash)
+}
+
+// CreateStore makes the stashes directory before the first write to a new store, recording the current layout; read-only
+// queries never call it, so they leave no store behind
+pub fn create_store() -> Result<(), Box<dyn std::error::Error>> {
+    let stashes_dir = utils::get_stashes_dir()?;
+    if !stashes_dir.is_dir() {
+        fs::create_dir_all(&stashes_dir)?;
+        write_format(STORE_FORMAT)?;
+    }
+    Ok(())
+}
+
+// WriteStash writes content to a stash like utils::write_file, first creating the store if needed and detaching the
+// stash from the content it shares
+pub fn write_stash<P: AsRef<Path>>(stash_path: P, content: &str) -> Option<Box<dyn std::error::Error>> {
+    if let Err(error) = create_store().and_then(|_| detach_stash(stash_path.as_ref())) {
+        return Some(error);
+    }
+    utils::write_file(stash_path, content)
+}
+
+// CopyToStash copies a file over a stash like utils::copy_file, first creating the store if needed and detaching the
+// stash from the content it shares
+pub fn copy_to_stash<S: AsRef<Path>, D: AsRef<Path>>(src: S, stash_path: D) -> Option<Box<dyn std::error::Error>> {
+    if let Err(error) = create_store().and_then(|_| detach_stash(stash_path.as_ref())) {
+        return Some(error);
+    }
+    utils::copy_file(src, stash_path)
+}
+
+// DetachStash unlinks a stash that shares its content object with other stashes, so writing it in place leaves them
+// untouched. Paths outside the stashes directory are left alone, so a project's own hard links keep working
+#[cfg(unix)]
+pub fn detach_stash(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::MetadataExt;
+
+    if !path.starts_with(utils::get_stashes_dir()?) {
+        return Ok(());
+    }
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_file() && metadata.nlink() > 1 => Ok(fs::remove_file(path)?),
+        _ => Ok(()),
+    }
+}
+
+// DetachStash has nothing to do where stashes are never linked
+#[cfg(not(unix))]
+pub fn detach_stash(_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    Ok(())
+}
+
+// is_linked checks the metadata sidecar beside a stash for a project that apply --link symlinked to it
+fn is_linked(stash_path: &Path) -> bool {
+    fs::read_to_string(stash_path.with_extension("json"))
+        .ok()
+        .and_then(|content| serde_json::from_str::<crate::metadata::StashMetadata>(&content).ok())
+        .is_some_and(|stash_metadata| stash_metadata.linked_to.is_some())
+}
+
+// link_object hard-links path and the object, adopting path as the object when it is the first with this content
+#[cfg(unix)]
+fn link_object(path: &Path, object_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::MetadataExt;
+
+    let (file, object) = (fs::metadata(path)?, fs::metadata(object_path).ok());
+    if let Some(object) = &object {
+        if (file.dev(), file.ino()) == (object.dev(), object.ino()) {
+            return Ok(());
+        }
+    }
+
+    // An object edited in place outside agstash no longer matches its name, so path takes its place
+    let intact = match object {
+        Some(_) => object_path.file_name().and_then(|name| name.to_str()) == Some(content_hash(&fs::read(object_path)?).as_str()),
+        None => false,
+    };
+    if !intact {
+        if object.is_some() {
+            utils::remove_file(object_path)?;
+        }
+        fs::create_dir_all(get_objects_dir()?)?;
+        fs::hard_link(path, object_path)?;
+        return Ok(());
+    }
+
+    // Link beside the stash and rename over it, so the stash is never missing
+    let staging = path.with_extension("md.link");
+    if utils::file_exists(&staging) {
+        utils::remove_file(&staging)?;
+    }
+    fs::hard_link(object_path, &staging)?;
+    fs::rename(&staging, path)?;
+    Ok(())
+}
+
+// link_object leaves stashes as plain copies where hard links aren't relied upon
+#[cfg(not(unix))]
+fn link_object(_path: &Path, _object_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    Ok(())
+}
+
+// OrphanedObjects lists objects no stash points at any more
+#[cfg(unix)]
+pub fn orphaned_objects() -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    use std::os::unix::fs::MetadataExt;
+
+    let objects_dir = get_objects_dir()?;
+    if !objects_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut orphans = Vec::new();
+    for entry in fs::read_dir(&objects_dir)? {
+        let path = entry?.path();
+        if path.is_file() && fs::metadata(&path)?.nlink() == 1 {
+            orphans.push(path);
+        }
+    }
+    orphans.sort();
+    Ok(orphans)
+}
+
+// OrphanedObjects lists nothing where no objects are written
+#[cfg(not(unix))]
+pub fn orphaned_objects() -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    Ok(Vec::new())
+}
+
+// SharedStashes groups stash keys by content hash, keeping only the groups of two or more
+pub fn shared_stashes() -> Result<Vec<Vec<String>>, Box<dyn std::error::Error>> {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (stash_key, path) in utils::list_stashes(utils::get_stashes_dir()?)? {
+        groups.entry(content_hash(&fs::read(&path)?)).or_default().push(stash_key);
+    }
+    Ok(groups.into_values().filter(|keys| keys.len() > 1).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::store;
+    use crate::utils;
+    use crate::testing::TestEnv;
+
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(
+            store::content_hash(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_store_format() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        // No store yet, then one that predates versioning
+        assert_eq!(store::read_format().unwrap(), None);
+        fs::create_dir_all(utils::get_stashes_dir().unwrap()).unwrap();
+        assert_eq!(store::read_format().unwrap(), Some(1));
+        assert!(store::check_format().is_ok());
+
+        store::write_format(store::STORE_FORMAT + 1).unwrap();
+        assert!(store::check_format().is_err());
+        fs::write(store::get_format_path().unwrap(), "three\n").unwrap();
+        assert!(store::read_format().is_err());
+
+        // Looking up a stash leaves no store behind, and one created from scratch starts at the current format
+        fs::remove_dir_all(utils::get_agstash_dir().unwrap()).unwrap();
+        utils::get_stash_path("fresh").unwrap();
+        assert_eq!(store::read_format().unwrap(), None);
+        store::create_store().unwrap();
+        assert_eq!(store::read_format().unwrap(), Some(store::STORE_FORMAT));
+    }
+
+    #[test]
+    #[serial]
+    #[cfg(target_os = "linux")]
+    fn test_migrate_legacy_dir() {
+        // Create a temporary directory to use as HOME, holding the XDG base directories; without AGSTASH_HOME the
+        // store is found the way a real install finds it
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+        std::env::remove_var(utils::HOME_ENV);
+
+        // Nothing to move without a ~/.agstash
+        assert_eq!(store::migrate_legacy_dir().unwrap(), None);
+
+        let legacy_dir = temp_dir.path().join(".agstash");
+        fs::create_dir_all(legacy_dir.join("stashes")).unwrap();
+        fs::write(legacy_dir.join("stashes").join("stash-demo.md"), "# AGENTS\n").unwrap();
+        fs::write(legacy_dir.join("config.toml"), "keep_last = 3\n").unwrap();
+
+        let data_dir = temp_dir.path().join(".local").join("share").join("agstash");
+        assert_eq!(store::migrate_legacy_dir().unwrap(), Some(data_dir.clone()));
+        assert!(!legacy_dir.exists());
+        assert_eq!(utils::get_agstash_dir().unwrap(), data_dir);
+        assert_eq!(fs::read_to_string(utils::get_stash_path("demo").unwrap()).unwrap(), "# AGENTS\n");
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join(".config").join("agstash").join("config.toml")).unwrap(),
+            "keep_last = 3\n"
+        );
+        assert!(!data_dir.join("config.toml").exists());
+
+        // A ~/.agstash that reappears next to the XDG store is left alone
+        fs::create_dir(&legacy_dir).unwrap();
+        assert_eq!(store::migrate_legacy_dir().unwrap(), None);
+        assert!(legacy_dir.exists());
+    }
+
+    #[test]
+    #[serial]
+    fn test_share_stash() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        let first = utils::get_stash_path("first").unwrap();
+        let second = utils::get_stash_path("second").unwrap();
+        let other = utils::get_stash_path("other").unwrap();
+        fs::create_dir_all(first.parent().unwrap()).unwrap();
+        fs::write(&first, "# AGENTS\n\n- shared\n").unwrap();
+        fs::write(&second, "# AGENTS\n\n- shared\n").unwrap();
+        fs::write(&other, "# AGENTS\n\n- other\n").unwrap();
+
+        let hash = store::share_stash(&first).unwrap();
+        assert_eq!(store::share_stash(&second).unwrap(), hash);
+        store::share_stash(&other).unwrap();
+        assert_eq!(store::shared_stashes().unwrap(), vec![vec!["first".to_string(), "second".to_string()]]);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            // Identical stashes are one file on disk
+            let object = store::get_object_path(&hash).unwrap();
+            assert_eq!(fs::metadata(&first).unwrap().ino(), fs::metadata(&second).unwrap().ino());
+            assert_eq!(fs::metadata(&object).unwrap().nlink(), 3);
+
+            // Writing one stash leaves the other and the object alone
+            assert!(store::write_stash(&first, "# AGENTS\n\n- changed\n").is_none());
+            assert_eq!(fs::read_to_string(&second).unwrap(), "# AGENTS\n\n- shared\n");
+            assert_eq!(fs::read_to_string(&object).unwrap(), "# AGENTS\n\n- shared\n");
+
+            // An object is orphaned once no stash points at it
+            utils::remove_file(&other).unwrap();
+            let orphans = store::orphaned_objects().unwrap();
+            assert_eq!(orphans.len(), 1);
+            assert_eq!(fs::read_to_string(&orphans[0]).unwrap(), "# AGENTS\n\n- other\n");
+        }
+    }
+}
@@ -0,0 +1,174 @@
+// Helpers for integration-testing tools built on agstash, enabled with the `testing` feature.
+//
+// TestEnv points HOME, AGSTASH_HOME, the XDG base directories and the working directory at a throwaway directory
+// tree, so the tests using it must not run in parallel with anything else that reads any of them.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tempfile::TempDir;
+
+use crate::config;
+use crate::metadata::{self, StashMetadata};
+use crate::store;
+use crate::utils;
+
+// Variables TestEnv takes over while it lives; AGSTASH_CONFIG is cleared so a developer's own config isn't read
+const ISOLATED_VARS: &[&str] = &["HOME", utils::HOME_ENV, "XDG_DATA_HOME", "XDG_CONFIG_HOME", config::CONFIG_ENV];
+
+// TestEnv is an isolated home directory with a store and fake projects, restored on drop
+pub struct TestEnv {
+    root: Option<TempDir>,
+    home: PathBuf,
+    original_dir: Option<PathBuf>,
+    original_vars: Vec<(&'static str, Option<OsString>)>,
+}
+
+impl TestEnv {
+    // new creates an empty home directory, points HOME at it and AGSTASH_HOME at its store, and enters a fake project
+    // named "project"
+    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
+        let root = TempDir::new()?;
+        let home = root.path().join("home");
+        fs::create_dir_all(&home)?;
+
+        let mut test_env = TestEnv::isolate(home);
+        test_env.original_dir = env::current_dir().ok();
+        test_env.root = Some(root);
+
+        let project_dir = test_env.create_project("project")?;
+        test_env.enter(&project_dir)?;
+        Ok(test_env)
+    }
+
+    // at_home isolates agstash inside home, a directory the caller owns, without changing the working directory
+    pub fn at_home<P: AsRef<Path>>(home: P) -> Result<Self, Box<dyn std::error::Error>> {
+        fs::create_dir_all(home.as_ref())?;
+        Ok(TestEnv::isolate(home.as_ref().to_path_buf()))
+    }
+
+    // isolate points every variable agstash reads its locations from into home
+    fn isolate(home: PathBuf) -> TestEnv {
+        let original_vars = ISOLATED_VARS.iter().map(|name| (*name, env::var_os(name))).collect();
+        env::set_var("HOME", &home);
+        env::set_var(utils::HOME_ENV, home.join(".agstash"));
+        env::set_var("XDG_DATA_HOME", home.join(".local").join("share"));
+        env::set_var("XDG_CONFIG_HOME", home.join(".config"));
+        env::remove_var(config::CONFIG_ENV);
+        utils::set_store_dir(None);
+        TestEnv { root: None, home, original_dir: None, original_vars }
+    }
+
+    // home returns the directory HOME points at
+    pub fn home(&self) -> PathBuf {
+        self.home.clone()
+    }
+
+    // store returns the agstash directory inside the test home
+    pub fn store(&self) -> PathBuf {
+        self.home().join(".agstash")
+    }
+
+    // projects_dir returns where create_project puts fake projects: beside the home of new, inside the one given to
+    // at_home
+    fn projects_dir(&self) -> PathBuf {
+        match &self.root {
+            Some(root) => root.path().join("projects"),
+            None => self.home.join("projects"),
+        }
+    }
+
+    // create_project makes a fake git project with the given directory name
+    pub fn create_project(&self, name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let project_dir = self.projects_dir().join(name);
+        fs::create_dir_all(project_dir.join(".git"))?;
+        Ok(project_dir)
+    }
+
+    // project_key returns the stash key of the fake project with the given directory name
+    pub fn project_key(&self, name: &str) -> Result<String, Box<dyn std::error::Error>> {
+        utils::project_stash_key(&self.projects_dir().join(name))
+    }
+
+    // enter changes the working directory, as if agstash were run from dir
+    pub fn enter<P: AsRef<Path>>(&self, dir: P) -> Result<(), Box<dyn std::error::Error>> {
+        env::set_current_dir(dir)?;
+        Ok(())
+    }
+
+    // write_agents writes AGENTS.md into the current working directory
+    pub fn write_agents(&self, content: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let path = env::current_dir()?.join("AGENTS.md");
+        if let Some(error) = utils::write_file(&path, content) {
+            return Err(error);
+        }
+        Ok(path)
+    }
+
+    // add_stash puts a stash with metadata straight into the store
+    pub fn add_stash(
+        &self,
+        project_name: &str,
+        content: &str,
+        message: Option<&str>,
+    ) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let stash_path = utils::get_stash_path(project_name)?;
+        if let Some(error) = store::write_stash(&stash_path, content) {
+            return Err(error);
+        }
+        metadata::save_metadata(project_name, &StashMetadata::new(message, None))?;
+        Ok(stash_path)
+    }
+}
+
+impl Drop for TestEnv {
+    fn drop(&mut self) {
+        if let Some(original_dir) = &self.original_dir {
+            let _ = env::set_current_dir(original_dir);
+        }
+        for (name, value) in &self.original_vars {
+            match value {
+                Some(value) => env::set_var(name, value),
+                None => env::remove_var(name),
+            }
+        }
+        utils::set_store_dir(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use serial_test::serial;
+
+    use crate::commands;
+    use crate::testing::TestEnv;
+    use crate::utils;
+
+    #[test]
+    #[serial]
+    fn test_test_env() {
+        let original_home = env::var("HOME").ok();
+        let original_store = env::var(utils::HOME_ENV).ok();
+        {
+            let test_env = TestEnv::new().unwrap();
+            assert_eq!(utils::home_dir().unwrap(), test_env.home());
+            assert!(utils::get_project_root().unwrap().ends_with("project"));
+
+            test_env.write_agents("# AGENTS\n").unwrap();
+            commands::handle_stash(None).unwrap();
+            let project_key = test_env.project_key("project").unwrap();
+            assert!(test_env.store().join("stashes").join(format!("stash-{}.md", project_key)).exists());
+
+            let stash_path = test_env.add_stash("other", "# AGENTS\n\n- seeded\n", Some("seed")).unwrap();
+            assert!(stash_path.starts_with(test_env.store()));
+            if cfg!(target_os = "linux") {
+                assert!(utils::get_platform_data_dir().unwrap().starts_with(test_env.home()));
+            }
+        }
+        assert_eq!(env::var("HOME").ok(), original_home);
+        assert_eq!(env::var(utils::HOME_ENV).ok(), original_store);
+    }
+}
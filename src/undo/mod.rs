@@ -0,0 +1,134 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::store;
+use crate::utils;
+
+// UndoRecord describes the file saved before the last destructive operation on a project
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UndoRecord {
+    pub action: String,
+    pub target: PathBuf,
+    pub created_at: String,
+}
+
+// GetUndoDir returns the directory holding the undo backup for a project
+pub fn get_undo_dir(project_name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    utils::validate_project_name(project_name)?;
+    Ok(utils::get_agstash_dir()?.join("undo").join(project_name))
+}
+
+// SaveBackup keeps a copy of target so the destructive action about to run on it can be undone, replacing any older backup
+pub fn save_backup(project_name: &str, action: &str, target: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if !utils::file_exists(target) {
+        return Ok(());
+    }
+
+    let undo_dir = get_undo_dir(project_name)?;
+    fs::create_dir_all(&undo_dir)?;
+
+    if let Some(error) = utils::copy_file(target, undo_dir.join("content")) {
+        return Err(error);
+    }
+
+    // Keep the stash's metadata sidecar alongside a dropped stash
+    let sidecar = target.with_extension("json");
+    let saved_sidecar = undo_dir.join("metadata.json");
+    if action == "drop" && utils::file_exists(&sidecar) {
+        if let Some(error) = utils::copy_file(&sidecar, &saved_sidecar) {
+            return Err(error);
+        }
+    } else if utils::file_exists(&saved_sidecar) {
+        utils::remove_file(&saved_sidecar)?;
+    }
+
+    let record = UndoRecord {
+        action: action.to_string(),
+        target: target.to_path_buf(),
+        created_at: utils::format_timestamp(SystemTime::now()),
+    };
+    if let Some(error) = utils::write_file(undo_dir.join("undo.json"), &serde_json::to_string_pretty(&record)?) {
+        return Err(error);
+    }
+    utils::log_info(&format!("Saved undo backup of {} before {}", target.display(), action));
+    Ok(())
+}
+
+// LoadBackup returns the undo record for a project, if one exists
+pub fn load_backup(project_name: &str) -> Result<Option<UndoRecord>, Box<dyn std::error::Error>> {
+    let record_path = get_undo_dir(project_name)?.join("undo.json");
+    if !utils::file_exists(&record_path) {
+        return Ok(None);
+    }
+
+    let (err, content) = utils::read_file(&record_path);
+    if let Some(error) = err {
+        return Err(error);
+    }
+    let record = serde_json::from_str(&content)
+        .map_err(|e| format!("Invalid undo record in {}: {}", record_path.display(), e))?;
+    Ok(Some(record))
+}
+
+// RestoreBackup writes the saved file back to where it came from and forgets the backup
+pub fn restore_backup(project_name: &str) -> Result<Option<UndoRecord>, Box<dyn std::error::Error>> {
+    let record = match load_backup(project_name)? {
+        Some(record) => record,
+        None => return Ok(None),
+    };
+    let undo_dir = get_undo_dir(project_name)?;
+
+    if let Some(parent) = record.target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if let Some(error) = store::copy_to_stash(undo_dir.join("content"), &record.target) {
+        return Err(error);
+    }
+    let saved_sidecar = undo_dir.join("metadata.json");
+    if utils::file_exists(&saved_sidecar) {
+        if let Some(error) = utils::copy_file(&saved_sidecar, record.target.with_extension("json")) {
+            return Err(error);
+        }
+    }
+
+    fs::remove_dir_all(&undo_dir)?;
+    Ok(Some(record))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use serial_test::serial;
+
+    use crate::undo;
+    use crate::testing::TestEnv;
+
+    #[test]
+    #[serial]
+    fn test_save_and_restore_backup() {
+        // Create a temporary directory to use as HOME
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
+        let target = temp_dir.path().join("AGENTS.md");
+
+        // Missing files have nothing to back up
+        undo::save_backup("demo", "clean", &target).unwrap();
+        assert!(undo::load_backup("demo").unwrap().is_none());
+
+        fs::write(&target, "# AGENTS\n\n- first\n").unwrap();
+        undo::save_backup("demo", "clean", &target).unwrap();
+        fs::remove_file(&target).unwrap();
+
+        let record = undo::restore_backup("demo").unwrap().unwrap();
+        assert_eq!(record.action, "clean");
+        assert_eq!(fs::read_to_string(&target).unwrap(), "# AGENTS\n\n- first\n");
+
+        // A backup can only be restored once
+        assert!(undo::restore_backup("demo").unwrap().is_none());
+    }
+}
@@ -1,26 +1,59 @@
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
-
-// SetupLogging configures the logging based on the verbose flag
-pub fn setup_logging(verbose: bool) {
-    // In Rust, we could use the env_logger or similar crate for more sophisticated logging
-    // For now, we'll just note that verbose mode is enabled/disabled
-    if verbose {
-        eprintln!("Verbose logging enabled");
-    }
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::AgStashError;
+
+// Environment variable that relocates the whole store, e.g. into a sandbox or a test's temporary directory
+pub const HOME_ENV: &str = "AGSTASH_HOME";
+
+// Project root shared by every command of a chain once it has been pinned
+static PINNED_ROOT: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+// Store directory chosen with the store setting or AGSTASH_STORE, resolved once before a command runs
+static STORE_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+// Log levels; only messages at or below the level set by setup_logging are printed
+const LEVEL_ERROR: u8 = 0;
+const LEVEL_WARN: u8 = 1;
+const LEVEL_INFO: u8 = 2;
+
+// Level messages are logged at; warnings unless --verbose or --quiet changed it
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LEVEL_WARN);
+
+// SetupLogging sets the log level from the flags: --verbose adds info messages and --quiet leaves only errors
+pub fn setup_logging(verbose: bool, quiet: bool) {
+    let level = if quiet {
+        LEVEL_ERROR
+    } else if verbose {
+        LEVEL_INFO
+    } else {
+        LEVEL_WARN
+    };
+    LOG_LEVEL.store(level, Ordering::Relaxed);
 }
 
 // LogInfo logs an info message
 pub fn log_info(message: &str) {
-    eprintln!("INFO: {}", message);
+    if LOG_LEVEL.load(Ordering::Relaxed) >= LEVEL_INFO {
+        eprintln!("INFO: {}", message);
+    }
 }
 
 // LogWarn logs a warning message
 pub fn log_warn(message: &str) {
-    eprintln!("WARN: {}", message);
+    if LOG_LEVEL.load(Ordering::Relaxed) >= LEVEL_WARN {
+        eprintln!("WARN: {}", message);
+    }
 }
 
+// Size from which content is refused as a stash instead of being processed
+pub const MAX_STASH_SIZE: usize = 10_000_000;
+
 // IsValidAgents validates that the content starts with "# AGENTS"
 pub fn is_valid_agents(content: &str) -> bool {
     // For empty content, return false rather than panicking
@@ -28,45 +61,251 @@ pub fn is_valid_agents(content: &str) -> bool {
         return false;
     }
 
-    // Check if content is too large to process safely
-    if content.len() >= 10_000_000 {
-        panic!("Content too large to process safely");
+    // Content too large to process safely is not a valid AGENTS.md either
+    if content.len() >= MAX_STASH_SIZE {
+        return false;
     }
 
     basic_validation(content)
 }
 
 fn basic_validation(content: &str) -> bool {
-    let trimmed_start = content.trim_start_matches(|c: char| c == ' ' || c == '\t' || c == '\n' || c == '\r');
+    // Editors on Windows may save a byte order mark, which belongs to the file rather than its text
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let trimmed_start = content.trim_start_matches([' ', '\t', '\n', '\r']);
     trimmed_start.starts_with("# AGENTS")
 }
 
-// GetProjectRoot finds the project root by looking for .git or .gitignore
-pub fn get_project_root() -> Result<PathBuf, Box<dyn std::error::Error>> {
-    let mut current_path = env::current_dir()?;
-
-    loop {
-        // Check if .git directory or .gitignore file exists
-        let git_dir = current_path.join(".git");
-        let git_ignore_file = current_path.join(".gitignore");
-
-        if git_dir.is_dir() || git_ignore_file.is_file() {
-            return Ok(current_path);
+// RenderTemplate substitutes {{name}} placeholders with values from vars, dropping lines that have a placeholder vars
+// can't fill. Each line is read once, so a value that itself contains "{{" is written as it is
+pub fn render_template(template: &str, vars: &HashMap<String, String>) -> String {
+    let mut rendered = String::new();
+    'lines: for line in template.split_inclusive('\n') {
+        let mut output = String::new();
+        let mut rest = line;
+        while let Some(start) = rest.find("{{") {
+            let Some(length) = rest[start + 2..].find("}}") else {
+                continue 'lines;
+            };
+            let Some(value) = vars.get(&rest[start + 2..start + 2 + length]) else {
+                continue 'lines;
+            };
+            output.push_str(&rest[..start]);
+            output.push_str(value);
+            rest = &rest[start + 2 + length + 2..];
         }
+        rendered.push_str(&output);
+        rendered.push_str(rest);
+    }
+    rendered
+}
 
-        // Move up to parent directory
-        match current_path.parent() {
-            Some(parent) => {
-                current_path = parent.to_path_buf();
+// SplitChain splits a command chain into its commands and each command into words, honouring quotes;
+// commands are separated by ';' or '&&'
+pub fn split_chain(script: &str) -> Result<Vec<Vec<String>>, Box<dyn std::error::Error>> {
+    let mut commands = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = script.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some('"'), '\\') if matches!(chars.peek(), Some('"') | Some('\\')) => {
+                word.get_or_insert_with(String::new).push(chars.next().unwrap_or(c));
+            }
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
             }
-            None => {
-                // We've reached the root directory
-                break;
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, ';') | (None, '&') => {
+                if c == '&' && chars.next_if_eq(&'&').is_none() {
+                    return Err("A single '&' is not supported; separate commands with ';' or '&&'".into());
+                }
+                words.extend(word.take());
+                if !words.is_empty() {
+                    commands.push(std::mem::take(&mut words));
+                }
             }
+            (None, c) => word.get_or_insert_with(String::new).push(c),
         }
     }
+    if quote.is_some() {
+        return Err(format!("Unterminated quote in: {}", script).into());
+    }
+    words.extend(word.take());
+    if !words.is_empty() {
+        commands.push(words);
+    }
+    Ok(commands)
+}
+
+// PinProjectRoot resolves the project root once so the rest of a command chain reuses it, even if an earlier command
+// changes what would be found
+pub fn pin_project_root() {
+    if let Ok(root) = get_project_root() {
+        set_project_root(root);
+    }
+}
+
+// SetProjectRoot makes commands work on the project at root instead of the one around the working directory
+pub fn set_project_root(root: PathBuf) {
+    log_info(&format!("Pinned project root: {}", root.display()));
+    if let Ok(mut pinned) = PINNED_ROOT.lock() {
+        *pinned = Some(root);
+    }
+}
+
+// Files and directories that mark a project root: version control first, then the manifests of common build tools for
+// checkouts without it, such as unpacked tarballs and freshly generated templates
+pub const VCS_MARKERS: &[&str] = &[".git", ".gitignore"];
+pub const MANIFEST_MARKERS: &[&str] = &["Cargo.toml", "package.json", "go.mod", "pyproject.toml", "pom.xml"];
+
+// GetProjectRoot finds the project root: the nearest directory with .git or .gitignore, or failing that the nearest
+// one with a build manifest
+pub fn get_project_root() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if let Some(root) = PINNED_ROOT.lock().ok().and_then(|pinned| pinned.clone()) {
+        return Ok(root);
+    }
+    let current_path = env::current_dir()?;
+
+    // A manifest only wins when no ancestor is under version control, so packages inside a repository stay part of it
+    for markers in [VCS_MARKERS, MANIFEST_MARKERS] {
+        if let Some(root) = current_path.ancestors().find(|dir| marker_in(dir, markers).is_some()) {
+            return Ok(root.to_path_buf());
+        }
+    }
+
+    Err(AgStashError::ProjectNotFound("Project root not found".into()).into())
+}
+
+// RootMarker names the marker that makes dir a project root, if any
+pub fn root_marker(dir: &Path) -> Option<&'static str> {
+    marker_in(dir, VCS_MARKERS).or_else(|| marker_in(dir, MANIFEST_MARKERS))
+}
+
+// marker_in returns the first of markers present in dir; .git may also be the file a worktree or submodule has
+fn marker_in(dir: &Path, markers: &[&'static str]) -> Option<&'static str> {
+    markers.iter().copied().find(|marker| {
+        let path = dir.join(marker);
+        path.is_file() || (*marker == ".git" && path.is_dir())
+    })
+}
+
+// ValidateProjectName rejects names that cannot be used as a stash key
+pub fn validate_project_name(project_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if project_name.is_empty() {
+        return Err("Project name should not be empty".into());
+    }
+    if project_name.contains(['/', '\\']) || project_name == "." || project_name == ".." {
+        return Err(format!("Invalid project name: {}", project_name).into());
+    }
+    Ok(())
+}
+
+// NestedStashKey builds the stash key for an AGENTS.md in a subdirectory of a project, e.g. "repo@packages--api"
+pub fn nested_stash_key(project_name: &str, relative: &Path) -> String {
+    let components: Vec<String> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    format!("{}@{}", project_name, components.join("--"))
+}
+
+// NestedRelativePath recovers the subdirectory a nested stash key was made for, e.g. "packages/api" from
+// "repo@packages--api"; None for keys that are not nested stashes of the project, including their slots
+pub fn nested_relative_path(stash_key: &str, project_name: &str) -> Option<PathBuf> {
+    match stash_key.strip_prefix(project_name).and_then(split_key_suffix)? {
+        (Some(nested), slots) if slots.is_empty() => Some(nested.split("--").collect()),
+        _ => None,
+    }
+}
+
+// ProjectStashKey builds the stash key for the project rooted at root from its directory name, for people to read, and
+// a hash of its canonical path, so two projects with the same name never share a stash, e.g. "api-3f2a91c0"
+pub fn project_stash_key(root: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let name = root
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or("Could not extract project name")?;
+    let canonical = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let hash = crate::store::content_hash(canonical.to_string_lossy().as_bytes());
+    Ok(format!("{}-{}", name, &hash[..8]))
+}
+
+// SlotStashKey builds the stash key for a named slot kept alongside a project's stash, e.g. "repo+pre-clean"
+pub fn slot_stash_key(project_name: &str, slot: &str) -> String {
+    format!("{}+{}", project_name, slot)
+}
+
+// StashKey is a stash key taken apart: the project it belongs to, the subdirectory of a nested stash and its slots,
+// e.g. "api-3f2a91c0", Some("packages--web") and ["CLAUDE.md", "pre-clean"] for
+// "api-3f2a91c0@packages--web+CLAUDE.md+pre-clean"
+#[derive(Debug, Clone, PartialEq)]
+pub struct StashKey<'a> {
+    pub project: &'a str,
+    pub nested: Option<&'a str>,
+    pub slots: Vec<&'a str>,
+}
+
+// SplitStashKey takes a stash key apart. A project key ends at its path hash, so directory names such as "c++" or
+// "me@work" stay whole; a legacy key, which has no hash, ends at the first '@' or '+' that starts a well-formed suffix
+pub fn split_stash_key(stash_key: &str) -> StashKey<'_> {
+    let ends = || {
+        stash_key
+            .char_indices()
+            .filter(|(_, c)| matches!(c, '@' | '+'))
+            .map(|(index, _)| index)
+            .chain([stash_key.len()])
+    };
+    let split = ends()
+        .find(|end| has_path_hash(&stash_key[..*end]) && split_key_suffix(&stash_key[*end..]).is_some())
+        .or_else(|| ends().find(|end| *end > 0 && split_key_suffix(&stash_key[*end..]).is_some()))
+        .unwrap_or(stash_key.len());
+    let (nested, slots) = split_key_suffix(&stash_key[split..]).unwrap_or_default();
+    StashKey {
+        project: &stash_key[..split],
+        nested,
+        slots,
+    }
+}
 
-    Err("Project root not found".into())
+// has_path_hash reports whether a project key ends in the path hash project_stash_key adds, e.g. "api-3f2a91c0"
+fn has_path_hash(project: &str) -> bool {
+    project.rsplit_once('-').is_some_and(|(name, hash)| {
+        !name.is_empty() && hash.len() == 8 && hash.chars().all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+    })
+}
+
+// split_key_suffix parses what follows the project in a stash key: an optional "@dir" and then any number of "+slot",
+// none of them empty; None when the text is not such a suffix
+fn split_key_suffix(suffix: &str) -> Option<(Option<&str>, Vec<&str>)> {
+    let (nested, slots) = match suffix.strip_prefix('@') {
+        Some(rest) => match rest.split_once('+') {
+            Some((nested, slots)) => (Some(nested), Some(slots)),
+            None => (Some(rest), None),
+        },
+        None if suffix.is_empty() => (None, None),
+        None => (None, Some(suffix.strip_prefix('+')?)),
+    };
+    let slots: Vec<&str> = slots.map(|slots| slots.split('+').collect()).unwrap_or_default();
+    if nested == Some("") || slots.iter().any(|slot| slot.is_empty()) {
+        return None;
+    }
+    Some((nested, slots))
+}
+
+// StashBelongsTo checks whether a stash key is the project's own stash or one of its nested or slot stashes
+pub fn stash_belongs_to(stash_key: &str, project_name: &str) -> bool {
+    stash_key.strip_prefix(project_name).and_then(split_key_suffix).is_some()
+}
+
+// ProjectOf returns the project a stash key belongs to, dropping any nested directory or slot suffix
+pub fn project_of(stash_key: &str) -> &str {
+    split_stash_key(stash_key).project
 }
 
 // GetStashPath returns the path where the project's AGENTS.md should be stashed
@@ -75,21 +314,262 @@ pub fn get_stash_path(project_name: &str) -> Result<PathBuf, Box<dyn std::error:
         panic!("Project name should not be empty");
     }
 
-    let home_dir = dirs::home_dir().ok_or("Could not find home directory")?;
-    let stash_dir = home_dir.join(".agstash").join("stashes");
+    let stash_path = get_stashes_dir()?.join(format!("stash-{}.md", project_name));
+    Ok(stash_path)
+}
+
+// ExpandHome resolves a configured path, where a leading "~/" (or "~\\" on Windows) stands for the home directory
+pub fn expand_home(path: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    match home_relative(path) {
+        Some(relative) => Ok(home_dir()?.join(relative)),
+        None => Ok(PathBuf::from(path)),
+    }
+}
 
-    // Create the stash directory if it doesn't exist
-    fs::create_dir_all(&stash_dir)?;
+// HomeRelative returns the rest of a path that starts at the home directory, or None when it doesn't
+pub fn home_relative(path: &str) -> Option<&str> {
+    path.strip_prefix("~/").or_else(|| path.strip_prefix("~\\").filter(|_| cfg!(windows)))
+}
 
-    let stash_path = stash_dir.join(format!("stash-{}.md", project_name));
-    Ok(stash_path)
+// ShellPath returns path as a POSIX shell or fish script can use it; on Windows, where hooks run under Git Bash or
+// MSYS, backslashes become forward slashes
+pub fn shell_path(path: &Path) -> String {
+    let path = path.display().to_string();
+    match cfg!(windows) {
+        true => path.replace('\\', "/"),
+        false => path,
+    }
+}
+
+// HomeDir returns the user's home directory as the platform reports it: $HOME on Unix and the profile folder
+// (USERPROFILE) on Windows, where a HOME set by Git Bash or MSYS wins as it does for git
+pub fn home_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if let Some(home) = unix_like_home() {
+        return Ok(home);
+    }
+    Ok(dirs::home_dir().ok_or("Could not find home directory")?)
+}
+
+// unix_like_home returns HOME on Windows when a Unix-like shell or a test set it
+fn unix_like_home() -> Option<PathBuf> {
+    env::var_os("HOME").filter(|home| cfg!(windows) && !home.is_empty()).map(PathBuf::from)
 }
 
-// GetAgstashDir returns the path to the global .agstash directory
+// uses_platform_dirs reports whether a new store goes in the platform's own directories rather than ~/.agstash: the
+// XDG base directories on Linux and the roaming AppData folder on Windows, unless HOME is set there. macOS and the
+// rest keep ~/.agstash
+fn uses_platform_dirs() -> bool {
+    cfg!(target_os = "linux") || (cfg!(windows) && unix_like_home().is_none())
+}
+
+// SetStoreDir makes every command keep its stashes in dir instead of the default store, or in the default store again
+// when dir is None. The user config stays where it is, so it can be what names dir
+pub fn set_store_dir(dir: Option<PathBuf>) {
+    if let Some(dir) = &dir {
+        log_info(&format!("Using the store at {}", dir.display()));
+    }
+    if let Ok(mut store_dir) = STORE_DIR.lock() {
+        *store_dir = dir;
+    }
+}
+
+// GetAgstashDir returns the directory holding the store: the one set with SetStoreDir, then the one AGSTASH_HOME
+// names, ~/.agstash when it is still there, or else the platform's data directory where agstash follows it
 pub fn get_agstash_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
-    let home_dir = dirs::home_dir().ok_or("Could not find home directory")?;
-    let agstash_dir = home_dir.join(".agstash");
-    Ok(agstash_dir)
+    if let Some(dir) = STORE_DIR.lock().ok().and_then(|store_dir| store_dir.clone()) {
+        return Ok(dir);
+    }
+    get_default_agstash_dir()
+}
+
+// get_default_agstash_dir returns where the store lives when no store is configured
+fn get_default_agstash_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if let Some(dir) = env::var_os(HOME_ENV).filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+    let legacy_dir = get_legacy_dir()?;
+    if legacy_dir.exists() || !uses_platform_dirs() {
+        return Ok(legacy_dir);
+    }
+    get_platform_data_dir()
+}
+
+// GetConfigDir returns the directory holding the user's config.toml: the default store itself when AGSTASH_HOME is
+// set or ~/.agstash is still there, or else the platform's config directory where agstash follows it
+pub fn get_config_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let store_holds_config = env::var_os(HOME_ENV).is_some_and(|dir| !dir.is_empty()) || get_legacy_dir()?.exists();
+    if store_holds_config || !uses_platform_dirs() {
+        return get_default_agstash_dir();
+    }
+    get_platform_config_dir()
+}
+
+// GetPlatformDataDir returns $XDG_DATA_HOME/agstash (~/.local/share/agstash when unset) on Linux and
+// %APPDATA%\agstash on Windows
+pub fn get_platform_data_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(dirs::data_dir().ok_or("Could not find the data directory")?.join("agstash"))
+}
+
+// GetPlatformConfigDir returns $XDG_CONFIG_HOME/agstash (~/.config/agstash when unset) on Linux and
+// %APPDATA%\agstash, the store itself, on Windows
+pub fn get_platform_config_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(dirs::config_dir().ok_or("Could not find the config directory")?.join("agstash"))
+}
+
+// GetLegacyDir returns ~/.agstash, where agstash kept everything before it followed the XDG base directories
+pub fn get_legacy_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(home_dir()?.join(".agstash"))
+}
+
+// GetStashesDir returns the directory holding every project's stash
+pub fn get_stashes_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(get_agstash_dir()?.join("stashes"))
+}
+
+// ListStashes returns the project name and path of every stash in stashes_dir, sorted by project name
+pub fn list_stashes<P: AsRef<Path>>(stashes_dir: P) -> Result<Vec<(String, PathBuf)>, Box<dyn std::error::Error>> {
+    let stashes_dir = stashes_dir.as_ref();
+    if !stashes_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut stashes = Vec::new();
+    for entry in fs::read_dir(stashes_dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        if sync_conflict_of(stashes_dir, file_name).is_some() {
+            continue;
+        }
+        let project_name = file_name.strip_prefix("stash-").and_then(|name| name.strip_suffix(".md"));
+        if let Some(project_name) = project_name {
+            if !project_name.is_empty() {
+                stashes.push((project_name.to_string(), path.clone()));
+            }
+        }
+    }
+    stashes.sort();
+    Ok(stashes)
+}
+
+// ListSyncConflicts finds the copies a file sync client left next to stashes it could not merge, with the stash each
+// one conflicts with
+pub fn list_sync_conflicts<P: AsRef<Path>>(stashes_dir: P) -> Result<Vec<(String, PathBuf)>, Box<dyn std::error::Error>> {
+    let stashes_dir = stashes_dir.as_ref();
+    if !stashes_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut conflicts = Vec::new();
+    for entry in fs::read_dir(stashes_dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        if let Some(stash_key) = sync_conflict_of(stashes_dir, file_name) {
+            conflicts.push((stash_key, path.clone()));
+        }
+    }
+    conflicts.sort();
+    Ok(conflicts)
+}
+
+// sync_conflict_of recognises the conflict copies Dropbox, Syncthing, Nextcloud and iCloud make of a stash file and
+// returns the stash key it belongs to, e.g. "foo" for "stash-foo (conflicted copy).md"
+fn sync_conflict_of(stashes_dir: &Path, file_name: &str) -> Option<String> {
+    let stem = file_name.strip_prefix("stash-")?.strip_suffix(".md")?;
+
+    // Syncthing: "stash-foo.sync-conflict-20240101-120000-ABCDEFG.md"
+    if let Some((stash_key, _)) = stem.split_once(".sync-conflict-") {
+        return Some(stash_key.to_string()).filter(|key| !key.is_empty());
+    }
+
+    // Dropbox and Nextcloud: "stash-foo (conflicted copy).md", "stash-foo (Sam's conflicted copy 2024-01-01).md",
+    // "stash-foo (conflicted copy 2024-01-01 120000).md"
+    if let Some((stash_key, suffix)) = stem.rsplit_once(" (") {
+        if suffix.ends_with(')') && suffix.to_lowercase().contains("conflicted copy") && !stash_key.is_empty() {
+            return Some(stash_key.to_string());
+        }
+    }
+
+    // iCloud numbers the second copy, "stash-foo 2.md"; only trust that when the original is still there
+    let (stash_key, number) = stem.rsplit_once(' ')?;
+    if !stash_key.is_empty()
+        && !number.is_empty()
+        && number.chars().all(|c| c.is_ascii_digit())
+        && stashes_dir.join(format!("stash-{}.md", stash_key)).is_file()
+    {
+        return Some(stash_key.to_string());
+    }
+    None
+}
+
+// FormatTimestamp renders a system time as an RFC 3339 UTC timestamp
+pub fn format_timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0) as i64;
+    let days = secs.div_euclid(86_400);
+    let day_secs = secs.rem_euclid(86_400);
+
+    // Convert days since the epoch to a civil date (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        day_secs / 3_600,
+        day_secs % 3_600 / 60,
+        day_secs % 60
+    )
+}
+
+// FormatSize renders a byte count in binary units, e.g. "1.5 KiB"
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+// BackupPathFor picks where to back up path: path.bak, or a timestamped name when that is taken
+pub fn backup_path_for(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let backup_path = path.with_file_name(format!("{}.bak", file_name));
+    if !file_exists(&backup_path) {
+        return backup_path;
+    }
+
+    let stamp = format_timestamp(SystemTime::now()).replace(['-', ':'], "");
+    let mut candidate = path.with_file_name(format!("{}.{}.bak", file_name, stamp));
+    let mut counter = 1;
+    while file_exists(&candidate) {
+        candidate = path.with_file_name(format!("{}.{}-{}.bak", file_name, stamp, counter));
+        counter += 1;
+    }
+    candidate
 }
 
 // ReadFile reads the content of a file - returns (error, content)
@@ -119,6 +599,19 @@ pub fn remove_file<P: AsRef<Path>>(path: P) -> Result<(), Box<dyn std::error::Er
     Ok(())
 }
 
+// Discard removes a file or directory, moving it to the system trash instead when to_trash is set
+pub fn discard<P: AsRef<Path>>(path: P, to_trash: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let path = path.as_ref();
+    if to_trash {
+        trash::delete(path).map_err(|e| format!("Could not move {} to the trash: {}", path.display(), e))?;
+    } else if path.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
 // CopyFile copies a file from source to destination - returns error
 pub fn copy_file<S: AsRef<Path>, D: AsRef<Path>>(src: S, dst: D) -> Option<Box<dyn std::error::Error>> {
     match fs::copy(src, dst) {
@@ -129,12 +622,16 @@ pub fn copy_file<S: AsRef<Path>, D: AsRef<Path>>(src: S, dst: D) -> Option<Box<d
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use std::fs;
     use std::env;
     use std::path::Path;
+    use std::sync::atomic::Ordering;
+    use std::time::{Duration, UNIX_EPOCH};
     use tempfile::TempDir;
     use serial_test::serial;
     use crate::utils;
+    use crate::testing::TestEnv;
 
     #[test]
     fn test_is_valid_agents() {
@@ -143,6 +640,7 @@ mod tests {
         assert!(utils::is_valid_agents("# AGENTS\n"));
         assert!(utils::is_valid_agents("  # AGENTS")); // Leading spaces
         assert!(utils::is_valid_agents("# AGENTS\n\n- content"));
+        assert!(utils::is_valid_agents("\u{feff}# AGENTS\r\n")); // Byte order mark
 
         // Invalid cases
         assert!(!utils::is_valid_agents(""));
@@ -154,19 +652,135 @@ mod tests {
 
     #[test]
     #[serial]
-    fn test_get_stash_path() {
-        // Create a temporary directory to use as home
+    fn test_setup_logging() {
+        // Restore the default level for the other tests
+        let _cleanup = defer::defer(|| utils::setup_logging(false, false));
+
+        utils::setup_logging(false, false);
+        assert_eq!(utils::LOG_LEVEL.load(Ordering::Relaxed), utils::LEVEL_WARN);
+        utils::setup_logging(true, false);
+        assert_eq!(utils::LOG_LEVEL.load(Ordering::Relaxed), utils::LEVEL_INFO);
+        utils::setup_logging(false, true);
+        assert_eq!(utils::LOG_LEVEL.load(Ordering::Relaxed), utils::LEVEL_ERROR);
+    }
+
+    #[test]
+    fn test_render_template() {
+        let mut vars = HashMap::new();
+        vars.insert("package_name".to_string(), "demo".to_string());
+
+        let template = "# AGENTS\n- Package: {{package_name}}\n- Test: {{test_command}}\n";
+        assert_eq!(utils::render_template(template, &vars), "# AGENTS\n- Package: demo\n");
+
+        // Values are not searched for placeholders, so one holding "{{" keeps its line
+        vars.insert("test_command".to_string(), "echo {{ matrix.os }}".to_string());
+        assert_eq!(
+            utils::render_template(template, &vars),
+            "# AGENTS\n- Package: demo\n- Test: echo {{ matrix.os }}\n"
+        );
+    }
+
+    #[test]
+    fn test_split_chain() {
+        let chain = utils::split_chain("stash -m 'before a rebase'; apply --force && status;").unwrap();
+        assert_eq!(
+            chain,
+            vec![
+                vec!["stash", "-m", "before a rebase"],
+                vec!["apply", "--force"],
+                vec!["status"],
+            ]
+        );
+        assert_eq!(utils::split_chain(r#"stash -m "a; \"b\"" ''"#).unwrap(), vec![vec!["stash", "-m", "a; \"b\"", ""]]);
+        assert!(utils::split_chain("").unwrap().is_empty());
+        assert!(utils::split_chain("stash -m 'open").is_err());
+        assert!(utils::split_chain("stash & apply").is_err());
+    }
+
+    #[test]
+    fn test_validate_project_name() {
+        assert!(utils::validate_project_name("my-project").is_ok());
+        assert!(utils::validate_project_name("").is_err());
+        assert!(utils::validate_project_name("..").is_err());
+        assert!(utils::validate_project_name("a/b").is_err());
+        assert!(utils::validate_project_name("a\\b").is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_get_project_root() {
+        // Create a temporary directory to search from
         let temp_dir = TempDir::new().unwrap();
-        let original_home = env::var("HOME").unwrap_or_default();
-        env::set_var("HOME", temp_dir.path());
-        
+        let original_dir = env::current_dir().unwrap();
+
         // Ensure cleanup happens
-        let _cleanup_home = defer::defer(move || {
-            if !original_home.is_empty() {
-                env::set_var("HOME", original_home);
-            }
+        let _cleanup = defer::defer(move || {
+            env::set_current_dir(original_dir).unwrap();
         });
 
+        // A tarball checkout has no .git, so its manifest marks the root
+        let root = temp_dir.path().canonicalize().unwrap().join("api");
+        let src = root.join("crates").join("core").join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(root.join("go.mod"), "module api\n").unwrap();
+        env::set_current_dir(&src).unwrap();
+        assert_eq!(utils::get_project_root().unwrap(), root);
+        assert_eq!(utils::root_marker(&root), Some("go.mod"));
+
+        // The nearest manifest wins among manifests, but never over version control further up
+        let member = root.join("crates").join("core");
+        fs::write(member.join("Cargo.toml"), "[package]\n").unwrap();
+        assert_eq!(utils::get_project_root().unwrap(), member);
+        fs::create_dir(root.join(".git")).unwrap();
+        assert_eq!(utils::get_project_root().unwrap(), root);
+        assert_eq!(utils::root_marker(&root), Some(".git"));
+    }
+
+    #[test]
+    fn test_nested_stash_key() {
+        let key = utils::nested_stash_key("repo", Path::new("packages/api"));
+        assert_eq!(key, "repo@packages--api");
+        assert!(utils::validate_project_name(&key).is_ok());
+        assert_eq!(utils::nested_relative_path(&key, "repo"), Some(Path::new("packages/api").to_path_buf()));
+        assert_eq!(utils::nested_relative_path("repo@packages--api+pre-clean", "repo"), None);
+        assert_eq!(utils::nested_relative_path("repo", "repo"), None);
+    }
+
+    #[test]
+    fn test_stash_belongs_to() {
+        assert!(utils::stash_belongs_to("repo", "repo"));
+        assert!(utils::stash_belongs_to("repo@packages--api", "repo"));
+        assert!(utils::stash_belongs_to("repo+pre-clean", "repo"));
+        assert!(!utils::stash_belongs_to("repository", "repo"));
+        assert!(!utils::stash_belongs_to("other", "repo"));
+
+        assert_eq!(utils::project_of("repo"), "repo");
+        assert_eq!(utils::project_of("repo@packages--api"), "repo");
+        assert_eq!(utils::project_of("repo+pre-clean"), "repo");
+
+        // Names that contain '@' or '+' themselves are not mistaken for another project's slots
+        assert!(!utils::stash_belongs_to("c++-3f2a91c0", "c"));
+        assert!(!utils::stash_belongs_to("c+", "c"));
+        assert!(utils::stash_belongs_to("c++-3f2a91c0+CLAUDE.md", "c++-3f2a91c0"));
+        assert_eq!(utils::project_of("c++-3f2a91c0+CLAUDE.md+pre-clean"), "c++-3f2a91c0");
+        assert_eq!(utils::project_of("me@work-0a1b2c3d@packages--api"), "me@work-0a1b2c3d");
+        assert_eq!(
+            utils::split_stash_key("api-3f2a91c0@packages--web+CLAUDE.md+pre-clean"),
+            utils::StashKey {
+                project: "api-3f2a91c0",
+                nested: Some("packages--web"),
+                slots: vec!["CLAUDE.md", "pre-clean"],
+            }
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_get_stash_path() {
+        // Create a temporary directory to use as home
+        let temp_dir = TempDir::new().unwrap();
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+
         // Test with a sample project name
         let project_name = "test-project";
         let stash_path_result = utils::get_stash_path(project_name);
@@ -174,36 +788,104 @@ mod tests {
         assert!(stash_path_result.is_ok());
         let stash_path = stash_path_result.unwrap();
 
-        let expected_path = temp_dir.path().join(".agstash").join("stashes").join("stash-test-project.md");
-        assert_eq!(stash_path, expected_path);
+        let stash_dir = utils::get_agstash_dir().unwrap().join("stashes");
+        assert_eq!(stash_path, stash_dir.join("stash-test-project.md"));
 
-        // Check if the stash directory was created
-        let stash_dir = temp_dir.path().join(".agstash").join("stashes");
-        assert!(stash_dir.exists());
+        // Only writing a stash creates the stash directory
+        assert!(!stash_dir.exists());
     }
 
     #[test]
     #[serial]
     fn test_get_agstash_dir() {
-        // Create a temporary directory to use as home
+        // Create a temporary directory to use as HOME, holding the XDG base directories; without AGSTASH_HOME the
+        // store is found the way a real install finds it
         let temp_dir = TempDir::new().unwrap();
-        let original_home = env::var("HOME").unwrap_or_default();
-        env::set_var("HOME", temp_dir.path());
-        
-        // Ensure cleanup happens
-        let _cleanup_home = defer::defer(move || {
-            if !original_home.is_empty() {
-                env::set_var("HOME", original_home);
-            }
-        });
+        let _test_env = TestEnv::at_home(temp_dir.path()).unwrap();
+        env::remove_var(utils::HOME_ENV);
+
+        // A new install follows the XDG base directories on Linux
+        let (data_dir, config_dir) = if cfg!(target_os = "linux") {
+            (temp_dir.path().join(".local").join("share").join("agstash"), temp_dir.path().join(".config").join("agstash"))
+        } else {
+            (temp_dir.path().join(".agstash"), temp_dir.path().join(".agstash"))
+        };
+        assert_eq!(utils::get_agstash_dir().unwrap(), data_dir);
+        assert_eq!(utils::get_config_dir().unwrap(), config_dir);
+
+        // An existing ~/.agstash keeps being used for both
+        let legacy_dir = temp_dir.path().join(".agstash");
+        fs::create_dir(&legacy_dir).unwrap();
+        assert_eq!(utils::get_agstash_dir().unwrap(), legacy_dir);
+        assert_eq!(utils::get_config_dir().unwrap(), legacy_dir);
+
+        // AGSTASH_HOME moves the whole store without touching HOME
+        env::set_var(utils::HOME_ENV, temp_dir.path().join("sandbox"));
+        let relocated = (utils::get_agstash_dir(), utils::get_config_dir());
+        env::remove_var(utils::HOME_ENV);
+        assert_eq!(relocated.0.unwrap(), temp_dir.path().join("sandbox"));
+        assert_eq!(relocated.1.unwrap(), temp_dir.path().join("sandbox"));
+    }
+
+    #[test]
+    fn test_list_stashes() {
+        let temp_dir = TempDir::new().unwrap();
+
+        // A missing directory has no stashes
+        let missing = temp_dir.path().join("missing");
+        assert!(utils::list_stashes(&missing).unwrap().is_empty());
 
-        let agstash_dir_result = utils::get_agstash_dir();
+        fs::write(temp_dir.path().join("stash-beta.md"), "# AGENTS").unwrap();
+        fs::write(temp_dir.path().join("stash-alpha.md"), "# AGENTS").unwrap();
+        fs::write(temp_dir.path().join("notes.txt"), "ignored").unwrap();
+
+        let names: Vec<String> = utils::list_stashes(temp_dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, vec!["alpha", "beta"]);
+    }
+
+    #[test]
+    fn test_list_sync_conflicts() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("stash-api.md"), "# AGENTS").unwrap();
+        fs::write(temp_dir.path().join("stash-api (conflicted copy).md"), "# AGENTS").unwrap();
+        fs::write(temp_dir.path().join("stash-api 2.md"), "# AGENTS").unwrap();
+        fs::write(temp_dir.path().join("stash-web.sync-conflict-20260101-120000-ABCDEFG.md"), "# AGENTS").unwrap();
+        fs::write(temp_dir.path().join("stash-cli (Sam's conflicted copy 2026-01-01).md"), "# AGENTS").unwrap();
+        // A project that is really called "release 2" is not a copy of anything
+        fs::write(temp_dir.path().join("stash-release 2.md"), "# AGENTS").unwrap();
+
+        let conflicts: Vec<String> = utils::list_sync_conflicts(temp_dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|(stash_key, _)| stash_key)
+            .collect();
+        assert_eq!(conflicts, vec!["api", "api", "cli", "web"]);
+
+        let names: Vec<String> = utils::list_stashes(temp_dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, vec!["api", "release 2"]);
+    }
 
-        assert!(agstash_dir_result.is_ok());
-        let agstash_dir = agstash_dir_result.unwrap();
+    #[test]
+    fn test_home_relative() {
+        assert_eq!(utils::home_relative("~/notes/AGENTS.md"), Some("notes/AGENTS.md"));
+        assert_eq!(utils::home_relative("notes/AGENTS.md"), None);
+        assert_eq!(utils::home_relative("~\\notes"), cfg!(windows).then_some("notes"));
+        assert_eq!(utils::shell_path(Path::new("bin/agstash")), "bin/agstash");
+    }
 
-        let expected_path = temp_dir.path().join(".agstash");
-        assert_eq!(agstash_dir, expected_path);
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(utils::format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        let time = UNIX_EPOCH + Duration::from_secs(1_709_251_199);
+        assert_eq!(utils::format_timestamp(time), "2024-02-29T23:59:59Z");
     }
 
     #[test]
@@ -247,6 +929,15 @@ mod tests {
         let (read_err, read_content) = utils::read_file(&temp_file);
         assert!(read_err.is_none());
         assert_eq!(read_content, content);
+
+        // A file the user hard-linked themselves is written through the link
+        #[cfg(unix)]
+        {
+            let linked = temp_dir.path().join("linked.txt");
+            fs::hard_link(&temp_file, &linked).unwrap();
+            assert!(utils::write_file(&temp_file, "changed").is_none());
+            assert_eq!(fs::read_to_string(&linked).unwrap(), "changed");
+        }
     }
 
     #[test]
@@ -271,12 +962,37 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Content too large to process safely")]
-    fn test_is_valid_agents_large_content_panics() {
+    fn test_format_size() {
+        assert_eq!(utils::format_size(512), "512 B");
+        assert_eq!(utils::format_size(1536), "1.5 KiB");
+        assert_eq!(utils::format_size(3 * 1024 * 1024), "3.0 MiB");
+    }
+
+    #[test]
+    fn test_discard() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("AGENTS.md");
+        let dir = temp_dir.path().join("store");
+        fs::write(&file, "# AGENTS").unwrap();
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("stash-demo.md"), "# AGENTS").unwrap();
+
+        // Without the trash both files and whole directories are deleted
+        assert!(utils::discard(&file, false).is_ok());
+        assert!(!file.exists());
+        assert!(utils::discard(&dir, false).is_ok());
+        assert!(!dir.exists());
+
+        // Missing paths are an error either way
+        assert!(utils::discard(&file, false).is_err());
+    }
+
+    #[test]
+    fn test_is_valid_agents_large_content() {
         // Create a string larger than 10MB
-        let large_content = "a".repeat(10_000_001); // 10MB + 1 character
+        let large_content = "# AGENTS\n".to_string() + &"a".repeat(10_000_001); // 10MB + 1 character
 
-        utils::is_valid_agents(&large_content);
+        assert!(!utils::is_valid_agents(&large_content));
     }
 
     #[test]